@@ -8,7 +8,16 @@ export enum HttpCommand{
   NONE= 0,
   HttpQuery= 1,
   HttpResultSet= 2,
-  HttpError= 3
+  HttpError= 3,
+  HttpCancel= 4
+};
+
+/**
+ * @enum {number}
+ */
+export enum HttpResultFormat{
+  Rows= 0,
+  ArrowIpc= 1
 };
 
 /**
@@ -196,11 +205,27 @@ traceObj(optionalEncoding?:any):string|Uint8Array|null {
   return offset ? this.bb!.__string(this.bb_pos + offset, optionalEncoding) : null;
 };
 
+/**
+ * @returns HttpResultFormat
+ */
+resultFormat():HttpResultFormat {
+  var offset = this.bb!.__offset(this.bb_pos, 8);
+  return offset ? /**  */ (this.bb!.readUint8(this.bb_pos + offset)) : HttpResultFormat.Rows;
+};
+
+/**
+ * @returns boolean
+ */
+stream():boolean {
+  var offset = this.bb!.__offset(this.bb_pos, 10);
+  return offset ? !!this.bb!.readInt8(this.bb_pos + offset) : false;
+};
+
 /**
  * @param flatbuffers.Builder builder
  */
 static startHttpQuery(builder:flatbuffers.Builder) {
-  builder.startObject(2);
+  builder.startObject(4);
 };
 
 /**
@@ -219,6 +244,22 @@ static addTraceObj(builder:flatbuffers.Builder, traceObjOffset:flatbuffers.Offse
   builder.addFieldOffset(1, traceObjOffset, 0);
 };
 
+/**
+ * @param flatbuffers.Builder builder
+ * @param HttpResultFormat resultFormat
+ */
+static addResultFormat(builder:flatbuffers.Builder, resultFormat:HttpResultFormat) {
+  builder.addFieldInt8(2, resultFormat, HttpResultFormat.Rows);
+};
+
+/**
+ * @param flatbuffers.Builder builder
+ * @param boolean stream
+ */
+static addStream(builder:flatbuffers.Builder, stream:boolean) {
+  builder.addFieldInt8(3, +stream, +false);
+};
+
 /**
  * @param flatbuffers.Builder builder
  * @returns flatbuffers.Offset
@@ -228,13 +269,73 @@ static endHttpQuery(builder:flatbuffers.Builder):flatbuffers.Offset {
   return offset;
 };
 
-static createHttpQuery(builder:flatbuffers.Builder, queryOffset:flatbuffers.Offset, traceObjOffset:flatbuffers.Offset):flatbuffers.Offset {
+static createHttpQuery(builder:flatbuffers.Builder, queryOffset:flatbuffers.Offset, traceObjOffset:flatbuffers.Offset, resultFormat:HttpResultFormat, stream:boolean):flatbuffers.Offset {
   HttpQuery.startHttpQuery(builder);
   HttpQuery.addQuery(builder, queryOffset);
   HttpQuery.addTraceObj(builder, traceObjOffset);
+  HttpQuery.addResultFormat(builder, resultFormat);
+  HttpQuery.addStream(builder, stream);
   return HttpQuery.endHttpQuery(builder);
 }
 }
+/**
+ * @constructor
+ */
+export class HttpCancel {
+  bb: flatbuffers.ByteBuffer|null = null;
+
+  bb_pos:number = 0;
+/**
+ * @param number i
+ * @param flatbuffers.ByteBuffer bb
+ * @returns HttpCancel
+ */
+__init(i:number, bb:flatbuffers.ByteBuffer):HttpCancel {
+  this.bb_pos = i;
+  this.bb = bb;
+  return this;
+};
+
+/**
+ * @param flatbuffers.ByteBuffer bb
+ * @param HttpCancel= obj
+ * @returns HttpCancel
+ */
+static getRootAsHttpCancel(bb:flatbuffers.ByteBuffer, obj?:HttpCancel):HttpCancel {
+  return (obj || new HttpCancel()).__init(bb.readInt32(bb.position()) + bb.position(), bb);
+};
+
+/**
+ * @param flatbuffers.ByteBuffer bb
+ * @param HttpCancel= obj
+ * @returns HttpCancel
+ */
+static getSizePrefixedRootAsHttpCancel(bb:flatbuffers.ByteBuffer, obj?:HttpCancel):HttpCancel {
+  bb.setPosition(bb.position() + flatbuffers.SIZE_PREFIX_LENGTH);
+  return (obj || new HttpCancel()).__init(bb.readInt32(bb.position()) + bb.position(), bb);
+};
+
+/**
+ * @param flatbuffers.Builder builder
+ */
+static startHttpCancel(builder:flatbuffers.Builder) {
+  builder.startObject(0);
+};
+
+/**
+ * @param flatbuffers.Builder builder
+ * @returns flatbuffers.Offset
+ */
+static endHttpCancel(builder:flatbuffers.Builder):flatbuffers.Offset {
+  var offset = builder.endObject();
+  return offset;
+};
+
+static createHttpCancel(builder:flatbuffers.Builder):flatbuffers.Offset {
+  HttpCancel.startHttpCancel(builder);
+  return HttpCancel.endHttpCancel(builder);
+}
+}
 /**
  * @constructor
  */
@@ -388,11 +489,44 @@ rowsLength():number {
   return offset ? this.bb!.__vector_len(this.bb_pos + offset) : 0;
 };
 
+/**
+ * @param number index
+ * @returns number
+ */
+arrowIpc(index: number):number|null {
+  var offset = this.bb!.__offset(this.bb_pos, 8);
+  return offset ? this.bb!.readUint8(this.bb!.__vector(this.bb_pos + offset) + index) : 0;
+};
+
+/**
+ * @returns number
+ */
+arrowIpcLength():number {
+  var offset = this.bb!.__offset(this.bb_pos, 8);
+  return offset ? this.bb!.__vector_len(this.bb_pos + offset) : 0;
+};
+
+/**
+ * @returns Uint8Array
+ */
+arrowIpcArray():Uint8Array|null {
+  var offset = this.bb!.__offset(this.bb_pos, 8);
+  return offset ? new Uint8Array(this.bb!.bytes().buffer, this.bb!.bytes().byteOffset + this.bb!.__vector(this.bb_pos + offset), this.bb!.__vector_len(this.bb_pos + offset)) : null;
+};
+
+/**
+ * @returns boolean
+ */
+hasMore():boolean {
+  var offset = this.bb!.__offset(this.bb_pos, 10);
+  return offset ? !!this.bb!.readInt8(this.bb_pos + offset) : false;
+};
+
 /**
  * @param flatbuffers.Builder builder
  */
 static startHttpResultSet(builder:flatbuffers.Builder) {
-  builder.startObject(2);
+  builder.startObject(4);
 };
 
 /**
@@ -453,6 +587,43 @@ static startRowsVector(builder:flatbuffers.Builder, numElems:number) {
   builder.startVector(4, numElems, 4);
 };
 
+/**
+ * @param flatbuffers.Builder builder
+ * @param flatbuffers.Offset arrowIpcOffset
+ */
+static addArrowIpc(builder:flatbuffers.Builder, arrowIpcOffset:flatbuffers.Offset) {
+  builder.addFieldOffset(2, arrowIpcOffset, 0);
+};
+
+/**
+ * @param flatbuffers.Builder builder
+ * @param Array.<number> data
+ * @returns flatbuffers.Offset
+ */
+static createArrowIpcVector(builder:flatbuffers.Builder, data:number[]|Uint8Array):flatbuffers.Offset {
+  builder.startVector(1, data.length, 1);
+  for (var i = data.length - 1; i >= 0; i--) {
+    builder.addInt8(data[i]);
+  }
+  return builder.endVector();
+};
+
+/**
+ * @param flatbuffers.Builder builder
+ * @param number numElems
+ */
+static startArrowIpcVector(builder:flatbuffers.Builder, numElems:number) {
+  builder.startVector(1, numElems, 1);
+};
+
+/**
+ * @param flatbuffers.Builder builder
+ * @param boolean hasMore
+ */
+static addHasMore(builder:flatbuffers.Builder, hasMore:boolean) {
+  builder.addFieldInt8(3, +hasMore, +false);
+};
+
 /**
  * @param flatbuffers.Builder builder
  * @returns flatbuffers.Offset
@@ -462,10 +633,12 @@ static endHttpResultSet(builder:flatbuffers.Builder):flatbuffers.Offset {
   return offset;
 };
 
-static createHttpResultSet(builder:flatbuffers.Builder, columnsOffset:flatbuffers.Offset, rowsOffset:flatbuffers.Offset):flatbuffers.Offset {
+static createHttpResultSet(builder:flatbuffers.Builder, columnsOffset:flatbuffers.Offset, rowsOffset:flatbuffers.Offset, arrowIpcOffset:flatbuffers.Offset, hasMore:boolean):flatbuffers.Offset {
   HttpResultSet.startHttpResultSet(builder);
   HttpResultSet.addColumns(builder, columnsOffset);
   HttpResultSet.addRows(builder, rowsOffset);
+  HttpResultSet.addArrowIpc(builder, arrowIpcOffset);
+  HttpResultSet.addHasMore(builder, hasMore);
   return HttpResultSet.endHttpResultSet(builder);
 }
 }
@@ -289,6 +289,16 @@ impl HllInstance {
         };
     }
 
+    /// Adds an element with the specified hash to the set. Hashes must be produced by
+    /// `murmur3::hash64` to stay compatible with Airlift.
+    pub fn insert_hash(&mut self, hash: u64) {
+        match self {
+            Sparse(s) => s.insert_hash(hash),
+            Dense(d) => d.insert_hash(hash),
+        }
+        self.make_dense_if_necessary();
+    }
+
     fn ensure_dense(&mut self) -> &mut DenseHll {
         if let Dense(d) = self {
             return d;
@@ -423,6 +433,24 @@ impl SparseHll {
         self.entries = self.merge_entries(o);
     }
 
+    pub fn insert_hash(&mut self, hash: u64) {
+        let bucket = compute_index(hash, SparseHll::EXTENDED_PREFIX_BITS);
+        let value = number_of_leading_zeros(hash, SparseHll::EXTENDED_PREFIX_BITS);
+        match self
+            .entries
+            .binary_search_by_key(&bucket, |e| SparseHll::decode_bucket_index(*e))
+        {
+            Ok(position) => {
+                if SparseHll::decode_bucket_value(self.entries[position]) < value {
+                    self.entries[position] = SparseHll::encode_entry(bucket, value);
+                }
+            }
+            Err(position) => self
+                .entries
+                .insert(position, SparseHll::encode_entry(bucket, value)),
+        }
+    }
+
     pub fn to_dense(&self) -> DenseHll {
         // TODO: this can panic if Sparse HLL had too much precision.
         let mut d = DenseHll::new(self.index_bit_len);
@@ -827,8 +855,7 @@ impl DenseHll {
         }
     }
 
-    #[allow(dead_code)]
-    fn insert_hash(&mut self, hash: u64) {
+    pub fn insert_hash(&mut self, hash: u64) {
         let index = compute_index(hash, self.index_bit_len);
         let value = compute_value(hash, self.index_bit_len);
 
@@ -1127,7 +1154,6 @@ fn index_bit_length(n: u32) -> Result<u8> {
     }
 }
 
-#[allow(dead_code)]
 fn compute_index(hash: u64, index_bit_len: u8) -> u32 {
     return (hash >> (64 - index_bit_len)) as u32;
 }
@@ -1136,7 +1162,6 @@ fn compute_value(hash: u64, index_bit_len: u8) -> u8 {
     return number_of_leading_zeros(hash, index_bit_len) + 1;
 }
 
-#[allow(dead_code)]
 fn number_of_leading_zeros(hash: u64, index_bit_len: u8) -> u8 {
     // place a 1 in the LSB to preserve the original number of leading zeros if the hash happens to be 0.
     let value = (hash << index_bit_len) | (1 << (index_bit_len - 1));
@@ -1561,6 +1586,42 @@ mod tests {
     }
     // TODO: port tests for Sparse HLLs and HLLInstance.
 
+    mod instance {
+        use crate::instance::tests::TestingHll;
+        use crate::instance::HllInstance::{self, Dense, Sparse};
+        use std::hash::Hasher;
+        use twox_hash::XxHash64;
+
+        #[test]
+        fn test_instance_insert() {
+            let mut testing_hll = TestingHll::new(12);
+            let mut hll = HllInstance::new(4096).unwrap();
+            for i in 0..10_000 {
+                let mut hasher = XxHash64::default();
+                hasher.write_i32(i);
+                let h = hasher.finish();
+
+                testing_hll.insert_hash(h);
+                hll.insert_hash(h);
+                if i == 100 {
+                    assert!(matches!(hll, Sparse(_)));
+                    let e = hll.cardinality() as f64;
+                    assert!((e - 101.).abs() < 3., "estimate {}", e);
+                }
+            }
+
+            let d = match &hll {
+                Dense(d) => d,
+                Sparse(_) => panic!("expected dense HLL after 10000 inserts"),
+            };
+            for i in 0..testing_hll.buckets().len() {
+                assert_eq!(d.get_value(i as u32), testing_hll.buckets()[i]);
+            }
+            let e = hll.cardinality() as f64;
+            assert!((e - 10_000.).abs() / 10_000. < 0.05, "estimate {}", e);
+        }
+    }
+
     struct TestingHll {
         index_bit_length: u8,
         buckets: Vec<u32>,
@@ -14,14 +14,14 @@
 
 use crate::error::Result;
 use crate::instance::HllInstance;
+use crate::murmur3::hash64;
 
 /// HyperLogLog sketch estimates a size of a set (i.e. the number of unique elements in it) without
 /// storing all the elements in the set.
 ///
 /// Port of the HyperLogLog from Airlift.
-/// You can deserialize sketches produced by Airlift by using `read()`.
-/// In fact, the code to add new elements has not been ported yet, so reading existing sketches is
-/// the only way to produce non-empty sets at this point.
+/// You can deserialize sketches produced by Airlift by using `read()` or build new ones with
+/// `insert_bytes()`, which hashes elements the same way Airlift does.
 #[derive(Debug, Clone)]
 pub struct HllSketch {
     instance: HllInstance,
@@ -73,6 +73,16 @@ impl HllSketch {
         return self.instance.cardinality();
     }
 
+    /// Adds an element to the set.
+    pub fn insert_bytes(&mut self, data: &[u8]) {
+        self.insert_hash(hash64(data));
+    }
+
+    /// Adds an element with the precomputed hash to the set.
+    pub fn insert_hash(&mut self, hash: u64) {
+        self.instance.insert_hash(hash);
+    }
+
     /// Merges elements from `o` into the current sketch.
     /// Afterwards the current sketch estimates the size of the union.
     ///
@@ -81,3 +91,30 @@ impl HllSketch {
         self.instance.merge_with(&o.instance);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small sets stay sparse and get exact estimates.
+    #[test]
+    fn test_insert_bytes() {
+        let mut s = HllSketch::new(4096).unwrap();
+        assert_eq!(s.cardinality(), 0);
+        for i in 0..100i64 {
+            s.insert_bytes(&i.to_le_bytes());
+            s.insert_bytes(&i.to_le_bytes());
+        }
+        assert_eq!(s.cardinality(), 100);
+
+        let copy = HllSketch::read(&s.write()).unwrap();
+        assert_eq!(copy.cardinality(), 100);
+
+        let mut other = HllSketch::new(4096).unwrap();
+        for i in 50..150i64 {
+            other.insert_bytes(&i.to_le_bytes());
+        }
+        s.merge_with(&other);
+        assert_eq!(s.cardinality(), 150);
+    }
+}
@@ -31,10 +31,20 @@ pub type TestFn = Box<
 pub fn sql_tests() -> Vec<(&'static str, TestFn)> {
     return vec![
         t("insert", insert),
+        t("insert_select", insert_select),
+        t("create_table_as_select", create_table_as_select),
         t("select_test", select_test),
         t("negative_numbers", negative_numbers),
         t("negative_decimal", negative_decimal),
         t("custom_types", custom_types),
+        t("narrow_types", narrow_types),
+        t("alter_table", alter_table),
+        t("swap_and_rename_tables", swap_and_rename_tables),
+        t("delete_from_unique_key_table", delete_from_unique_key_table),
+        t("add_column_after_delete", add_column_after_delete),
+        t("result_source_tables", result_source_tables),
+        t("table_ttl", table_ttl),
+        t("table_parquet_settings", table_parquet_settings),
         t("group_by_boolean", group_by_boolean),
         t("group_by_decimal", group_by_decimal),
         t("group_by_nulls", group_by_nulls),
@@ -60,6 +70,9 @@ pub fn sql_tests() -> Vec<(&'static str, TestFn)> {
         t("inner_column_escaping", inner_column_escaping),
         t("convert_tz", convert_tz),
         t("date_trunc", date_trunc),
+        t("date_trunc_time_zone", date_trunc_time_zone),
+        t("string_functions", string_functions),
+        t("round_greatest_least", round_greatest_least),
         t("coalesce", coalesce),
         t("ilike", ilike),
         t("count_distinct_crash", count_distinct_crash),
@@ -94,6 +107,11 @@ pub fn sql_tests() -> Vec<(&'static str, TestFn)> {
             "create_table_with_csv_no_header",
             create_table_with_csv_no_header,
         ),
+        t(
+            "create_table_with_malformed_parquet",
+            create_table_with_malformed_parquet,
+        ),
+        t("create_table_with_json_lines", create_table_with_json_lines),
         t("create_table_with_url", create_table_with_url),
         t("create_table_fail_and_retry", create_table_fail_and_retry),
         t("empty_crash", empty_crash),
@@ -102,6 +120,11 @@ pub fn sql_tests() -> Vec<(&'static str, TestFn)> {
         t("hyperloglog_empty_inputs", hyperloglog_empty_inputs),
         t("hyperloglog_empty_group_by", hyperloglog_empty_group_by),
         t("hyperloglog_inserts", hyperloglog_inserts),
+        t(
+            "hyperloglog_approx_distinct_merge",
+            hyperloglog_approx_distinct_merge,
+        ),
+        t("hyperloglog_add_agg", hyperloglog_add_agg),
         t("hyperloglog_inplace_group_by", hyperloglog_inplace_group_by),
         t("hyperloglog_postgres", hyperloglog_postgres),
         t("hyperloglog_snowflake", hyperloglog_snowflake),
@@ -156,6 +179,7 @@ pub fn sql_tests() -> Vec<(&'static str, TestFn)> {
         t("unsorted_merge_assertion", unsorted_merge_assertion),
         t("unsorted_data_timestamps", unsorted_data_timestamps),
         // t("ksql_simple", ksql_simple),
+        t("create_kafka_source", create_kafka_source),
         t(
             "dimension_only_queries_for_stream_table",
             dimension_only_queries_for_stream_table,
@@ -222,6 +246,123 @@ async fn insert(service: Box<dyn SqlClient>) {
         ('LastName 1', 23, 'FirstName 1', 'Address 1', 'City 1'), ('LastName 2', 22, 'FirstName 2', 'Address 2', 'City 2');").await.unwrap();
 }
 
+async fn insert_select(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.Orders (city text, amount int, price decimal)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.Orders (city, amount, price) VALUES ('a', 1, 1.5), ('a', 2, 2.5), ('b', 3, 3.5)",
+        )
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE TABLE s.Rollup (city text, amount int, price decimal, total float)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.Rollup (city, amount, price, total) \
+             SELECT city, sum(amount), sum(price), sum(amount) FROM s.Orders GROUP BY 1",
+        )
+        .await
+        .unwrap();
+    // Columns of the table are used when not listed.
+    service
+        .exec_query(
+            "INSERT INTO s.Rollup SELECT city, amount, price, total FROM s.Rollup WHERE city = 'b'",
+        )
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query(
+            "SELECT city, sum(amount), sum(price), sum(total) FROM s.Rollup GROUP BY 1 ORDER BY 1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[("a", 3, dec5(4), 3.), ("b", 6, dec5(7), 6.)])
+    );
+
+    let r = service
+        .exec_query("INSERT INTO s.Rollup (city) SELECT city, amount FROM s.Orders")
+        .await;
+    assert!(r.is_err());
+
+    // Values are converted only when nothing is lost.
+    service
+        .exec_query(
+            "INSERT INTO s.Rollup (city, amount) \
+             SELECT 'c', sum(price) FROM s.Orders WHERE city = 'a'",
+        )
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("INSERT INTO s.Rollup (city, amount) SELECT city, price FROM s.Orders")
+        .await;
+    assert!(r.is_err());
+    let r = service
+        .exec_query(
+            "INSERT INTO s.Rollup (city, amount) \
+             SELECT city, total * 100000000000000000000.0 FROM s.Rollup",
+        )
+        .await;
+    assert!(r.is_err());
+    let r = service
+        .exec_query("SELECT city, amount FROM s.Rollup WHERE city = 'c'")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[("c", 4)]));
+    let r = service
+        .exec_query("SELECT count(*) FROM s.Rollup")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[4]));
+}
+
+async fn create_table_as_select(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.Orders (city text, amount int, price decimal)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.Orders (city, amount, price) VALUES ('a', 1, 1.5), ('a', 2, 2.5), ('b', 3, 3.5)",
+        )
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "CREATE TABLE s.Rollup INDEX by_amount (amount) \
+             AS SELECT city, sum(amount) amount, sum(price) price FROM s.Orders GROUP BY 1",
+        )
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query("SELECT city, amount, price FROM s.Rollup ORDER BY 1")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[("a", 3, dec5(4)), ("b", 3, dec5f1(3, 5))])
+    );
+
+    let r = service
+        .exec_query("CREATE TABLE s.Duplicates AS SELECT city, city FROM s.Orders")
+        .await;
+    assert!(r.is_err());
+    let r = service
+        .exec_query("CREATE TABLE s.WithColumns (city text) AS SELECT city FROM s.Orders")
+        .await;
+    assert!(r.is_err());
+}
+
 async fn select_test(service: Box<dyn SqlClient>) {
     let _ = service.exec_query("CREATE SCHEMA Foo").await.unwrap();
 
@@ -310,14 +451,467 @@ async fn custom_types(service: Box<dyn SqlClient>) {
     service.exec_query("CREATE SCHEMA foo").await.unwrap();
 
     service
-        .exec_query("CREATE TABLE foo.values (int_value mediumint, b1 bytes, b2 varbinary)")
+        .exec_query("CREATE TABLE foo.values (int_value mediumint, b1 bytes, b2 varbinary)")
+        .await
+        .unwrap();
+
+    service
+        .exec_query("INSERT INTO foo.values (int_value, b1, b2) VALUES (-153, X'0a', X'0b')")
+        .await
+        .unwrap();
+
+    // mediumint is stored as a 64-bit integer, as it always was.
+    service
+        .exec_query("INSERT INTO foo.values (int_value) VALUES (3000000000)")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT int_value FROM foo.values ORDER BY int_value")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[-153, 3000000000]));
+}
+
+async fn narrow_types(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.t (d date, i smallint, j int4, f real)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.t (d, i, j, f) VALUES \
+             ('2021-11-01', 1, 100000, 1.5), \
+             ('2021-11-02 10:00:00', -2, -5, 0.25), \
+             (NULL, NULL, NULL, NULL)",
+        )
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query("SELECT d, i, j, f FROM s.t ORDER BY i")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            (
+                Some(timestamp_from_string("2021-11-02T00:00:00Z").unwrap()),
+                Some(-2),
+                Some(-5),
+                Some(0.25),
+            ),
+            (
+                Some(timestamp_from_string("2021-11-01T00:00:00Z").unwrap()),
+                Some(1),
+                Some(100000),
+                Some(1.5),
+            ),
+            (None, None, None, None),
+        ])
+    );
+
+    let r = service
+        .exec_query("SELECT sum(i), max(j) FROM s.t WHERE d IS NOT NULL")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[(-1, 100000)]));
+
+    service
+        .exec_query("INSERT INTO s.t (i) VALUES (40000)")
+        .await
+        .unwrap_err();
+
+    for (i, value) in ["40000", "1.5"].iter().enumerate() {
+        let file = write_tmp_file(&format!("i\n{}\n", value)).unwrap();
+        let path = file.path().to_string_lossy();
+        service
+            .exec_query(&format!(
+                "CREATE TABLE s.csv_{} (i smallint) LOCATION '{}'",
+                i, path
+            ))
+            .await
+            .unwrap_err();
+    }
+}
+
+async fn alter_table(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.t (id int, name text)")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.t (id, name) VALUES (1, 'a'), (2, 'b')")
+        .await
+        .unwrap();
+
+    service
+        .exec_query("ALTER TABLE s.t ADD COLUMN score int DEFAULT 10")
+        .await
+        .unwrap();
+    service
+        .exec_query("ALTER TABLE s.t ADD COLUMN city text")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.t (id, name, score, city) VALUES (3, 'c', 30, 'NY')")
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query("SELECT id, name, score, city FROM s.t ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            (1, "a", 10, None),
+            (2, "b", 10, None),
+            (3, "c", 30, Some("NY")),
+        ])
+    );
+
+    service
+        .exec_query("ALTER TABLE s.t RENAME COLUMN name TO title")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT title FROM s.t WHERE score = 10 ORDER BY title")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[("a"), ("b")]));
+    service
+        .exec_query("SELECT name FROM s.t")
+        .await
+        .unwrap_err();
+
+    service
+        .exec_query("ALTER TABLE s.t RENAME TO t2")
+        .await
+        .unwrap();
+    service.exec_query("SELECT * FROM s.t").await.unwrap_err();
+
+    service
+        .exec_query("ALTER TABLE s.t2 ADD INDEX by_score (score)")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT score, count(*) FROM s.t2 GROUP BY 1 ORDER BY 1")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[(10, 2), (30, 1)]));
+
+    service
+        .exec_query("ALTER TABLE s.t2 ADD COLUMN city text")
+        .await
+        .unwrap_err();
+    service
+        .exec_query("ALTER TABLE s.t2 RENAME COLUMN missing TO other")
+        .await
+        .unwrap_err();
+}
+
+async fn swap_and_rename_tables(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.t (v int)")
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE TABLE s.t_new (v int)")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.t (v) VALUES (1)")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.t_new (v) VALUES (2)")
+        .await
+        .unwrap();
+
+    service
+        .exec_query("ALTER TABLE s.t SWAP WITH t_new")
+        .await
+        .unwrap();
+    let r = service.exec_query("SELECT v FROM s.t").await.unwrap();
+    assert_eq!(to_rows(&r), rows(&[2]));
+    let r = service.exec_query("SELECT v FROM s.t_new").await.unwrap();
+    assert_eq!(to_rows(&r), rows(&[1]));
+
+    // Names freed by earlier renames can be taken by later ones.
+    service
+        .exec_query("RENAME TABLE s.t TO s.t_old, s.t_new TO s.t")
+        .await
+        .unwrap();
+    let r = service.exec_query("SELECT v FROM s.t").await.unwrap();
+    assert_eq!(to_rows(&r), rows(&[1]));
+    let r = service.exec_query("SELECT v FROM s.t_old").await.unwrap();
+    assert_eq!(to_rows(&r), rows(&[2]));
+
+    assert!(service
+        .exec_query("RENAME TABLE s.t TO s.t_old")
+        .await
+        .is_err());
+    assert!(service
+        .exec_query("RENAME TABLE s.missing TO s.other")
+        .await
+        .is_err());
+    assert!(service
+        .exec_query("ALTER TABLE s.t SWAP WITH t")
+        .await
+        .is_err());
+    // Failed statements leave names untouched.
+    let r = service.exec_query("SELECT v FROM s.t").await.unwrap();
+    assert_eq!(to_rows(&r), rows(&[1]));
+}
+
+async fn delete_from_unique_key_table(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.t(id int, v text) unique key (id)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.t(id, v, __seq) VALUES (1, 'a', 1), (2, 'b', 2), (3, 'c', 3), (4, 'd', 4)",
+        )
+        .await
+        .unwrap();
+
+    service
+        .exec_query("DELETE FROM s.t WHERE v = 'b' OR id = 3")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT id, v FROM s.t ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[(1, "a"), (4, "d")]));
+
+    // Tombstones stay hidden.
+    let r = service
+        .exec_query("SELECT * FROM s.t ORDER BY id")
+        .await
+        .unwrap();
+    assert!(r
+        .get_columns()
+        .iter()
+        .all(|c| c.get_name().as_str() != "__deleted"));
+    assert_eq!(r.get_rows().len(), 2);
+    let r = service
+        .exec_query("SELECT columns FROM system.tables WHERE table_name = 't'")
+        .await
+        .unwrap();
+    assert!(!format!("{:?}", r.get_rows()).contains("__deleted"));
+
+    // Tombstones are newer than any row of the table, even with other keys.
+    service
+        .exec_query("INSERT INTO s.t(id, v, __seq) VALUES (3, 'f', 4)")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT id, v FROM s.t ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[(1, "a"), (4, "d")]));
+
+    // Rows with newer sequence values replace tombstones.
+    service
+        .exec_query("INSERT INTO s.t(id, v, __seq) VALUES (2, 'e', 10)")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT id, v FROM s.t ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[(1, "a"), (2, "e"), (4, "d")]));
+
+    service.exec_query("DELETE FROM s.t").await.unwrap();
+    let r = service
+        .exec_query("SELECT count(*) FROM s.t")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[0]));
+
+    service
+        .exec_query("CREATE TABLE s.plain(id int)")
+        .await
+        .unwrap();
+    service
+        .exec_query("DELETE FROM s.plain WHERE id = 1")
+        .await
+        .unwrap_err();
+}
+
+async fn add_column_after_delete(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.t(id int, v text) unique key (id)")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.t(id, v, __seq) VALUES (1, 'a', 1), (2, 'b', 2), (3, 'c', 3)")
+        .await
+        .unwrap();
+    service
+        .exec_query("DELETE FROM s.t WHERE id = 2")
+        .await
+        .unwrap();
+    // The new column is placed after the deleted one.
+    service
+        .exec_query("ALTER TABLE s.t ADD COLUMN score int DEFAULT 10")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.t(id, v, __seq, score) VALUES (4, 'd', 5, 20)")
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query("SELECT id, v, score FROM s.t ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[(1, "a", 10), (3, "c", 10), (4, "d", 20)])
+    );
+    let r = service
+        .exec_query("SELECT score, id FROM s.t WHERE score > 10")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[(20, 4)]));
+    let r = service
+        .exec_query("SELECT * FROM s.t ORDER BY id")
+        .await
+        .unwrap();
+    let names = r
+        .get_columns()
+        .iter()
+        .map(|c| c.get_name().as_str())
+        .collect::<Vec<_>>();
+    assert!(!names.contains(&"__deleted"), "{:?}", names);
+    assert_eq!(names.last(), Some(&"score"));
+    assert_eq!(r.get_rows().len(), 3);
+}
+
+async fn result_source_tables(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.orders (id int, amount int)")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.orders (id, amount) VALUES (1, 10), (2, 20)")
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query("SELECT id, amount * 2 doubled FROM s.orders ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[(1, 20), (2, 40)]));
+    assert_eq!(r.source_table(0), Some("orders"));
+    assert_eq!(r.source_table(1), None);
+
+    let r = service
+        .exec_query("SELECT schema_name FROM information_schema.schemata")
+        .await
+        .unwrap();
+    assert_eq!(r.source_table(0), Some("schemata"));
+}
+
+async fn table_ttl(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.days (id int) WITH (ttl = '7 days')")
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE TABLE s.secs (id int) WITH (ttl = 3600)")
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE TABLE s.forever (id int)")
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query("SELECT table_name, ttl_seconds FROM system.tables ORDER BY table_name")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            ("days", Some(7 * 24 * 3600)),
+            ("forever", None),
+            ("secs", Some(3600)),
+        ])
+    );
+
+    service
+        .exec_query("CREATE TABLE s.bad (id int) WITH (ttl = '7 fortnights')")
+        .await
+        .unwrap_err();
+    service
+        .exec_query("CREATE TABLE s.bad (id int) WITH (ttl = 0)")
+        .await
+        .unwrap_err();
+}
+
+async fn table_parquet_settings(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query(
+            "CREATE TABLE s.zstd (id int) \
+             WITH (parquet_compression = 'zstd', parquet_dictionary = false)",
+        )
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE TABLE s.snappy (id int) WITH (parquet_compression = 'SNAPPY', parquet_statistics = true)")
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE TABLE s.plain (id int)")
+        .await
+        .unwrap();
+
+    service
+        .exec_query("INSERT INTO s.zstd (id) VALUES (1), (2), (3)")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT sum(id) FROM s.zstd")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[6]));
+
+    let r = service
+        .exec_query(
+            "SELECT table_name, parquet_compression, parquet_dictionary, parquet_statistics \
+             FROM system.tables ORDER BY table_name",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            ("plain", None, None, None),
+            ("snappy", Some("snappy"), None, Some(true)),
+            ("zstd", Some("zstd"), Some(false), None),
+        ])
+    );
+
+    service
+        .exec_query("CREATE TABLE s.bad (id int) WITH (parquet_compression = 'brotli')")
         .await
-        .unwrap();
-
+        .unwrap_err();
     service
-        .exec_query("INSERT INTO foo.values (int_value, b1, b2) VALUES (-153, X'0a', X'0b')")
+        .exec_query("CREATE TABLE s.bad (id int) WITH (parquet_dictionary = 'yes')")
         .await
-        .unwrap();
+        .unwrap_err();
 }
 
 async fn group_by_boolean(service: Box<dyn SqlClient>) {
@@ -1246,6 +1840,155 @@ async fn date_trunc(service: Box<dyn SqlClient>) {
     );
 }
 
+async fn date_trunc_time_zone(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.data (t timestamp)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.data (t) VALUES \
+             ('2021-01-01T03:00:00.000Z'), \
+             ('2021-03-17T12:00:00.000Z')",
+        )
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query(
+            "SELECT date_trunc('week', t), \
+                    date_trunc('quarter', t, 'America/Los_Angeles'), \
+                    date_trunc('day', t, '+05:00'), \
+                    CONVERT_TZ(t, 'America/Los_Angeles') \
+             FROM s.data ORDER BY 1",
+        )
+        .await
+        .unwrap();
+    let ts = |s: &str| timestamp_from_string(s).unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            (
+                ts("2020-12-28T00:00:00Z"),
+                ts("2020-10-01T07:00:00Z"),
+                ts("2020-12-31T19:00:00Z"),
+                ts("2020-12-31T19:00:00Z"),
+            ),
+            (
+                ts("2021-03-15T00:00:00Z"),
+                ts("2021-01-01T08:00:00Z"),
+                ts("2021-03-16T19:00:00Z"),
+                ts("2021-03-17T05:00:00Z"),
+            ),
+        ])
+    );
+
+    let r = service
+        .exec_query("SELECT CONVERT_TZ(t, 'Europe/Berlin', '+00:00') FROM s.data ORDER BY 1")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[(ts("2021-01-01T02:00:00Z")), (ts("2021-03-17T11:00:00Z"))])
+    );
+
+    service
+        .exec_query("SELECT date_trunc('decade', t) FROM s.data")
+        .await
+        .unwrap_err();
+    service
+        .exec_query("SELECT date_trunc('day', t, 'Mars/Olympus') FROM s.data")
+        .await
+        .unwrap_err();
+}
+
+async fn string_functions(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.data (id int, a text, b text)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.data (id, a, b) VALUES \
+             (1, 'Hello', 'World'), \
+             (2, 'Привет', NULL)",
+        )
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query(
+            "SELECT LOWER(a), UPPER(a), SUBSTR(a, 2, 3), CONCAT(a, ', ', b) \
+             FROM s.data ORDER BY id",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            ("hello", "HELLO", "ell", "Hello, World"),
+            ("привет", "ПРИВЕТ", "рив", "Привет, "),
+        ])
+    );
+
+    // Unlike SUBSTR, SUBSTRING counts negative positions from the end as in MySQL.
+    let r = service
+        .exec_query("SELECT SUBSTRING(a, -3), SUBSTRING(a, 2, 3) FROM s.data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[("llo", "ell"), ("вет", "рив")]));
+}
+
+async fn round_greatest_least(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.data (id int, d decimal, f float, i int)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.data (id, d, f, i) VALUES \
+             (1, 1.25, 2.5, 15), \
+             (2, -3.75, -0.45, -25), \
+             (3, NULL, NULL, NULL)",
+        )
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query(
+            "SELECT ROUND(d), ROUND(d, 1), ROUND(f, 1), ROUND(i, -1) FROM s.data ORDER BY id",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            (Some(dec5(1)), Some(dec5f1(1, 3)), Some(2.5), Some(20)),
+            (Some(dec5(-4)), Some(dec5f1(-3, 8)), Some(-0.5), Some(-30)),
+            (None, None, None, None),
+        ])
+    );
+
+    // Without digits, integers are rounded to floats as by the built-in ROUND.
+    let r = service
+        .exec_query("SELECT ROUND(i) FROM s.data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[Some(15.), Some(-25.), None]));
+
+    let r = service
+        .exec_query("SELECT GREATEST(i, id, 10), LEAST(i, id, 10) FROM s.data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[(Some(15), Some(1)), (Some(10), Some(-25)), (None, None)])
+    );
+}
+
 async fn ilike(service: Box<dyn SqlClient>) {
     service.exec_query("CREATE SCHEMA s").await.unwrap();
     service
@@ -1850,6 +2593,86 @@ async fn create_table_with_csv_no_header(service: Box<dyn SqlClient>) {
     );
 }
 
+async fn create_table_with_malformed_parquet(service: Box<dyn SqlClient>) {
+    let _ = service
+        .exec_query("CREATE SCHEMA IF NOT EXISTS test")
+        .await
+        .unwrap();
+    // Not a Parquet file at all and a file with Parquet magic but a broken footer.
+    for (i, content) in ["fruit,number\napple,2\n", "PAR1\x01\x02\x03\x04PAR1"]
+        .iter()
+        .enumerate()
+    {
+        let file = write_tmp_file(content).unwrap();
+        let path = file.path().to_string_lossy();
+        let res = service
+            .exec_query(&format!(
+                "CREATE TABLE test.table_{} (`fruit` text, `number` int) WITH (input_format = 'parquet') LOCATION '{}'",
+                i, path
+            ))
+            .await;
+        assert!(res.is_err(), "Expected Parquet error but got {:?}", res);
+    }
+}
+
+async fn create_table_with_json_lines(service: Box<dyn SqlClient>) {
+    let file = write_tmp_file(indoc! {r#"
+        {"fruit": "apple", "number": 2, "data": "AQI="}
+        {"number": 3, "fruit": "banana", "data": null}
+
+        {"fruit": "cherry"}
+    "#})
+    .unwrap();
+    let path = file.path().to_string_lossy();
+    let _ = service
+        .exec_query("CREATE SCHEMA IF NOT EXISTS test")
+        .await
+        .unwrap();
+    let _ = service
+        .exec_query(format!("CREATE TABLE test.table (`fruit` text, `number` int, `data` bytes) WITH (input_format = 'json_lines') LOCATION '{}'", path).as_str())
+        .await
+        .unwrap();
+    let result = service
+        .exec_query("SELECT * FROM test.table ORDER BY fruit")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&result),
+        vec![
+            vec![
+                TableValue::String("apple".to_string()),
+                TableValue::Int(2),
+                TableValue::Bytes(vec![1, 2])
+            ],
+            vec![
+                TableValue::String("banana".to_string()),
+                TableValue::Int(3),
+                TableValue::Null
+            ],
+            vec![
+                TableValue::String("cherry".to_string()),
+                TableValue::Null,
+                TableValue::Null
+            ],
+        ]
+    );
+
+    let file = write_tmp_file(indoc! {r#"
+        {"fruit": {"name": "apple"}, "number": 2}
+    "#})
+    .unwrap();
+    let path = file.path().to_string_lossy();
+    let res = service
+        .exec_query(format!("CREATE TABLE test.nested (`fruit` text, `number` int) WITH (input_format = 'json_lines') LOCATION '{}'", path).as_str())
+        .await;
+    let error = format!("{:?}", res);
+    assert!(
+        error.contains("nested JSON values are not supported"),
+        "{}",
+        error
+    );
+}
+
 async fn create_table_with_url(service: Box<dyn SqlClient>) {
     let url = "https://data.wprdc.org/dataset/0b584c84-7e35-4f4d-a5a2-b01697470c0f/resource/e95dd941-8e47-4460-9bd8-1e51c194370b/download/bikepghpublic.csv";
 
@@ -2052,6 +2875,141 @@ async fn hyperloglog_empty_group_by(service: Box<dyn SqlClient>) {
     assert_eq!(to_rows(&result), Vec::<Vec<TableValue>>::new());
 }
 
+async fn hyperloglog_approx_distinct_merge(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA hll").await.unwrap();
+    service
+        .exec_query("CREATE TABLE hll.sketches (id int, key int, hll varbinary)")
+        .await
+        .unwrap();
+
+    let result = service
+        .exec_query("SELECT approx_distinct_merge(hll) from hll.sketches")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&result), vec![vec![TableValue::Int(0)]]);
+
+    service
+        .exec_query(
+            "INSERT INTO hll.sketches (id, key, hll) VALUES \
+             (1, 1, X'020C0200C02FF58941D5F0C6'), \
+             (2, 1, X'020C0200C02FF58941D5F0C6'), \
+             (3, 2, X'020C0200C02FF58941D5F0C6'), \
+             (4, 2, NULL)",
+        )
+        .await
+        .unwrap();
+
+    let result = service
+        .exec_query(
+            "SELECT key, approx_distinct_merge(hll), cardinality(merge(hll)) \
+             FROM hll.sketches GROUP BY 1 ORDER BY 1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&result),
+        vec![
+            vec![TableValue::Int(1), TableValue::Int(2), TableValue::Int(2)],
+            vec![TableValue::Int(2), TableValue::Int(2), TableValue::Int(2)],
+        ],
+    );
+
+    // Rewritten into `approx_distinct_merge`, unless the merged sketch is also selected.
+    let result = service
+        .exec_query("SELECT key, cardinality(merge(hll)) FROM hll.sketches GROUP BY 1 ORDER BY 1")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&result),
+        vec![
+            vec![TableValue::Int(1), TableValue::Int(2)],
+            vec![TableValue::Int(2), TableValue::Int(2)],
+        ],
+    );
+    let result = service
+        .exec_query(
+            "SELECT key, cardinality(merge(hll)), merge(hll) FROM hll.sketches GROUP BY 1 ORDER BY 1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.get_rows()[0].values()[1], TableValue::Int(2));
+}
+
+async fn hyperloglog_add_agg(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.Data (id int, key int, name text)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.Data (id, key, name) VALUES \
+             (1, 1, 'a'), (1, 1, 'a'), (2, 1, 'b'), (3, 1, NULL), \
+             (4, 2, 'a'), (5, 2, 'a'), (NULL, 2, 'c')",
+        )
+        .await
+        .unwrap();
+
+    let result = service
+        .exec_query(
+            "SELECT key, cardinality(hll_add_agg(id)), cardinality(hll_add_agg(name)) \
+             FROM s.Data GROUP BY 1 ORDER BY 1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&result),
+        vec![
+            vec![TableValue::Int(1), TableValue::Int(3), TableValue::Int(2)],
+            vec![TableValue::Int(2), TableValue::Int(2), TableValue::Int(2)],
+        ]
+    );
+
+    // Sketches built from the same values are the same.
+    let result = service
+        .exec_query(
+            "SELECT key, hll_add_agg(name) FROM s.Data WHERE name = 'a' GROUP BY 1 ORDER BY 1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        result.get_rows()[0].values()[1],
+        result.get_rows()[1].values()[1]
+    );
+
+    let result = service
+        .exec_query(
+            "SELECT key, cardinality(hll_add_agg_zeta(id)), cardinality(hll_add_agg_zeta(name)) \
+             FROM s.Data GROUP BY 1 ORDER BY 1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&result),
+        vec![
+            vec![TableValue::Int(1), TableValue::Int(3), TableValue::Int(2)],
+            vec![TableValue::Int(2), TableValue::Int(2), TableValue::Int(2)],
+        ]
+    );
+
+    // Stored ZetaSketch sketches merge with each other.
+    service
+        .exec_query("CREATE TABLE s.Sketches (key int, hll varbinary)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.Sketches SELECT key, hll_add_agg_zeta(name) FROM s.Data GROUP BY 1",
+        )
+        .await
+        .unwrap();
+    let result = service
+        .exec_query("SELECT cardinality(merge(hll)) FROM s.Sketches")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&result), vec![vec![TableValue::Int(3)]]);
+}
+
 async fn hyperloglog_inserts(service: Box<dyn SqlClient>) {
     let _ = service
         .exec_query("CREATE SCHEMA IF NOT EXISTS hll")
@@ -3304,7 +4262,7 @@ async fn rolling_window_join(service: Box<dyn SqlClient>) {
         "Sort\
       \n  Projection, [date_to, name, SUM(Table.n)@2:n]\
       \n    CrossJoinAgg, on: day@1 <= date_to@0\
-      \n      Projection, [datetrunc(Utf8(\"day\"),converttz(s.Data.day,Utf8(\"+00:00\")))@0:day, name, SUM(s.Data.n)@2:n]\
+      \n      Projection, [DATE_TRUNC(Utf8(\"day\"),CONVERT_TZ(s.Data.day,Utf8(\"+00:00\")))@0:day, name, SUM(s.Data.n)@2:n]\
       \n        FinalHashAggregate\
       \n          Worker\
       \n            PartialHashAggregate\
@@ -3324,7 +4282,7 @@ async fn rolling_window_join(service: Box<dyn SqlClient>) {
         \n  Projection, [date_to, name, n]\
         \n    Projection, [date_to, name, SUM(Table.n)@2:n]\
         \n      CrossJoinAgg, on: day@1 <= date_to@0\
-        \n        Projection, [datetrunc(Utf8(\"day\"),converttz(s.Data.day,Utf8(\"+00:00\")))@0:day, name, SUM(s.Data.n)@2:n]\
+        \n        Projection, [DATE_TRUNC(Utf8(\"day\"),CONVERT_TZ(s.Data.day,Utf8(\"+00:00\")))@0:day, name, SUM(s.Data.n)@2:n]\
         \n          FinalHashAggregate\
         \n            Worker\
         \n              PartialHashAggregate\
@@ -4482,6 +5440,30 @@ async fn ksql_simple(service: Box<dyn SqlClient>) {
     }
 }
 
+async fn create_kafka_source(service: Box<dyn SqlClient>) {
+    service
+        .exec_query("CREATE SOURCE OR UPDATE kafka AS 'kafka' VALUES (host = 'localhost:9092', use_ssl = true, group_id = 'cube')")
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE SOURCE OR UPDATE kafka_avro AS 'kafka' VALUES (host = 'localhost:9092', format = 'avro', schema_registry_url = 'http://localhost:8081')")
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query("CREATE SOURCE OR UPDATE kafka AS 'kafka' VALUES (user = 'foo')")
+        .await;
+    assert!(r.is_err());
+    let r = service
+        .exec_query("CREATE SOURCE OR UPDATE kafka AS 'kafka' VALUES (host = 'localhost:9092', format = 'avro')")
+        .await;
+    assert!(r.is_err());
+    let r = service
+        .exec_query("CREATE SOURCE OR UPDATE kafka AS 'kafka' VALUES (host = 'localhost:9092', format = 'xml')")
+        .await;
+    assert!(r.is_err());
+}
+
 async fn dimension_only_queries_for_stream_table(service: Box<dyn SqlClient>) {
     service.exec_query("CREATE SCHEMA test").await.unwrap();
     service.exec_query("CREATE TABLE test.events_by_type (foo text, bar timestamp, bar_id text, measure1 int) unique key (foo, bar, bar_id)").await.unwrap();
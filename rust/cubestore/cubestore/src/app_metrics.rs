@@ -2,7 +2,7 @@
 //! The convention is to prefix all metrics with `cs.` (short for CubeStore).
 
 use crate::util::metrics;
-use crate::util::metrics::{Counter, Histogram};
+use crate::util::metrics::{Counter, Gauge, Histogram};
 
 /// The number of process startups.
 pub static STARTUPS: Counter = metrics::counter("cs.startup");
@@ -13,3 +13,10 @@ pub static DATA_QUERY_TIME_MS: Histogram = metrics::histogram("cs.sql.query.data
 /// Incoming SQL queries that only read metadata or do trivial computations.
 pub static META_QUERIES: Counter = metrics::counter("cs.sql.query.meta");
 pub static META_QUERY_TIME_MS: Histogram = metrics::histogram("cs.sql.query.meta.ms");
+
+/// Local copies of remote files removed to keep the local directory under its size limit.
+pub static LOCAL_FILES_EVICTED: Counter = metrics::counter("cs.remotefs.local_files.evicted");
+pub static LOCAL_FILES_EVICTED_BYTES: Counter =
+    metrics::counter("cs.remotefs.local_files.evicted.bytes");
+/// Total size of remote files tracked in the local directory.
+pub static LOCAL_FILES_SIZE: Gauge = metrics::gauge("cs.remotefs.local_files.size");
@@ -5,13 +5,14 @@ use crate::CubeError;
 use arrow::datatypes::SchemaRef;
 use serde::{Deserialize, Serialize};
 use std::io::ErrorKind;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum NetworkMessage {
     /// Route subqueries to other nodes and collect results.
     RouterSelect(SerializedPlan),
+    /// Same as [RouterSelect], but results are streamed back like for [SelectStart].
+    RouterSelectStart(SerializedPlan),
 
     /// Partial select on the worker.
     Select(SerializedPlan),
@@ -53,13 +54,16 @@ const NETWORK_MESSAGE_VERSION: u32 = 1;
 impl NetworkMessage {
     pub fn is_streaming_request(&self) -> bool {
         match self {
-            NetworkMessage::SelectStart(..) => true,
+            NetworkMessage::SelectStart(..) | NetworkMessage::RouterSelectStart(..) => true,
             _ => false,
         }
     }
 
     /// Returns true iff the client accepted the message.
-    pub async fn maybe_send(&self, socket: &mut TcpStream) -> Result<bool, CubeError> {
+    pub async fn maybe_send<S: AsyncWrite + Unpin + Send>(
+        &self,
+        socket: &mut S,
+    ) -> Result<bool, CubeError> {
         match self.send_impl(socket).await {
             Ok(()) => Ok(true),
             Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => Ok(false),
@@ -67,11 +71,17 @@ impl NetworkMessage {
         }
     }
 
-    pub async fn send(&self, socket: &mut TcpStream) -> Result<(), CubeError> {
+    pub async fn send<S: AsyncWrite + Unpin + Send>(
+        &self,
+        socket: &mut S,
+    ) -> Result<(), CubeError> {
         Ok(self.send_impl(socket).await?)
     }
 
-    async fn send_impl(&self, socket: &mut TcpStream) -> Result<(), std::io::Error> {
+    async fn send_impl<S: AsyncWrite + Unpin + Send>(
+        &self,
+        socket: &mut S,
+    ) -> Result<(), std::io::Error> {
         let mut ser = flexbuffers::FlexbufferSerializer::new();
         self.serialize(&mut ser).unwrap();
         let message_buffer = ser.take_buffer();
@@ -90,7 +100,7 @@ impl NetworkMessage {
         Ok(())
     }
 
-    pub async fn receive(socket: &mut TcpStream) -> Result<Self, CubeError> {
+    pub async fn receive<S: AsyncRead + Unpin + Send>(socket: &mut S) -> Result<Self, CubeError> {
         match Self::maybe_receive(socket).await? {
             Some(m) => Ok(m),
             None => Err(CubeError::user("Connection closed unexpectedly. Please check your worker and meta connection environment variables.".to_string())),
@@ -98,7 +108,9 @@ impl NetworkMessage {
     }
 
     /// Either receives a message or waits for the connection to close.
-    pub async fn maybe_receive(socket: &mut TcpStream) -> Result<Option<Self>, CubeError> {
+    pub async fn maybe_receive<S: AsyncRead + Unpin + Send>(
+        socket: &mut S,
+    ) -> Result<Option<Self>, CubeError> {
         let magic = socket.read_u32().await;
         if let Err(e) = &magic {
             // TODO: corner case with `0 < n < 8` read bytes.
@@ -1,5 +1,7 @@
 pub mod message;
 
+pub mod tls;
+
 pub mod transport;
 #[cfg(not(target_os = "windows"))]
 pub mod worker_pool;
@@ -9,6 +11,7 @@ use crate::cluster::worker_pool::{worker_main, MessageProcessor, WorkerPool};
 
 use crate::ack_error;
 use crate::cluster::message::NetworkMessage;
+use crate::cluster::tls::{server_stream, ClusterStream};
 use crate::cluster::transport::{ClusterTransport, MetaStoreTransport, WorkerConnection};
 use crate::config::injection::{DIService, Injector};
 use crate::config::{is_router, WorkerServices};
@@ -28,7 +31,7 @@ use crate::metastore::{
 };
 use crate::queryplanner::query_executor::{QueryExecutor, SerializedRecordBatchStream};
 use crate::queryplanner::serialized_plan::SerializedPlan;
-use crate::remotefs::RemoteFs;
+use crate::remotefs::{PinnedLocalFiles, RemoteFs};
 use crate::store::compaction::CompactionService;
 use crate::store::ChunkDataStore;
 use crate::util::aborting_join_handle::AbortingJoinHandle;
@@ -43,7 +46,7 @@ use datafusion::physical_plan::{RecordBatchStream, SendableRecordBatchStream};
 use flatbuffers::bitflags::_core::pin::Pin;
 use futures::future::join_all;
 use futures::task::{Context, Poll};
-use futures::{Future, Stream};
+use futures::{Future, Stream, StreamExt};
 use futures_timer::Delay;
 use itertools::Itertools;
 use log::{debug, error, info, warn};
@@ -59,7 +62,7 @@ use std::time::Duration;
 use std::time::SystemTime;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::broadcast::{Receiver, Sender};
-use tokio::sync::{oneshot, watch, Notify, RwLock};
+use tokio::sync::{mpsc, oneshot, watch, Notify, RwLock};
 use tokio::task::JoinHandle;
 use tokio::time::timeout;
 use tokio_util::sync::CancellationToken;
@@ -79,6 +82,13 @@ pub trait Cluster: DIService + Send + Sync {
         plan: SerializedPlan,
     ) -> Result<(SchemaRef, Vec<SerializedRecordBatchStream>), CubeError>;
 
+    /// Like [route_select], but streams results as they are requested.
+    async fn route_select_stream(
+        &self,
+        node_name: &str,
+        plan: SerializedPlan,
+    ) -> Result<SendableRecordBatchStream, CubeError>;
+
     /// Runs select on a single worker node to get partial results from that worker.
     async fn run_select(
         &self,
@@ -295,6 +305,18 @@ impl Cluster for ClusterImpl {
         }
     }
 
+    async fn route_select_stream(
+        &self,
+        node_name: &str,
+        plan: SerializedPlan,
+    ) -> Result<SendableRecordBatchStream, CubeError> {
+        self.this
+            .upgrade()
+            .unwrap()
+            .run_select_stream_impl(node_name, NetworkMessage::RouterSelectStart(plan))
+            .await
+    }
+
     #[instrument(level = "trace", skip(self, plan_node))]
     async fn run_select(
         &self,
@@ -320,7 +342,7 @@ impl Cluster for ClusterImpl {
         self.this
             .upgrade()
             .unwrap()
-            .run_select_stream_impl(node_name, plan)
+            .run_select_stream_impl(node_name, NetworkMessage::SelectStart(plan))
             .await
     }
 
@@ -523,6 +545,7 @@ impl Cluster for ClusterImpl {
                 panic!("NotifyJobListenersSuccess sent to worker")
             }
             NetworkMessage::SelectStart(..)
+            | NetworkMessage::RouterSelectStart(..)
             | NetworkMessage::SelectResultSchema(..)
             | NetworkMessage::SelectResultBatch(..) => {
                 panic!("streaming request passed to process_message")
@@ -1077,7 +1100,11 @@ impl ClusterImpl {
         address: &str,
         cluster: Arc<ClusterImpl>,
         on_socket_bound: oneshot::Sender<()>,
-        process_fn: impl Fn(Arc<ClusterImpl>, TcpStream) -> F + Send + Sync + Clone + 'static,
+        process_fn: impl Fn(Arc<ClusterImpl>, Box<dyn ClusterStream>) -> F
+            + Send
+            + Sync
+            + Clone
+            + 'static,
     ) -> Result<(), CubeError> {
         let listener = TcpListener::bind(address.clone()).await?;
         let _ = on_socket_bound.send(());
@@ -1108,6 +1135,14 @@ impl ClusterImpl {
             let process_fn_to_move = process_fn.clone();
 
             cube_ext::spawn(async move {
+                let socket =
+                    match server_stream(cluster_to_move.config_obj.cluster_tls(), socket).await {
+                        Ok(socket) => socket,
+                        Err(e) => {
+                            error!("Network error: {}", e);
+                            return;
+                        }
+                    };
                 process_fn_to_move(cluster_to_move, socket).await;
             });
         }
@@ -1156,6 +1191,14 @@ impl ClusterImpl {
         let start = SystemTime::now();
         debug!("Running select");
         let to_download = plan_node.files_to_download();
+        // Keep the files on local disk until the query finishes.
+        let _pinned_files = PinnedLocalFiles::new(
+            self.remote_fs.clone(),
+            to_download
+                .iter()
+                .map(|(_, remote, _)| remote.clone())
+                .collect_vec(),
+        );
         let file_futures = to_download
             .iter()
             .map(|(partition, remote, file_size)| {
@@ -1331,6 +1374,16 @@ impl ClusterImpl {
                 };
                 Box::new(QueryStream::new(schema, results))
             }
+            NetworkMessage::RouterSelectStart(p) => {
+                match self
+                    .query_executor
+                    .execute_router_plan_stream(p, self.clone())
+                    .await
+                {
+                    Err(e) => Box::new(QueryStream::new_error(e)),
+                    Ok(stream) => Box::new(RouterQueryStream::new(stream)),
+                }
+            }
             _ => panic!("non-streaming request passed to start_stream"),
         }
     }
@@ -1338,9 +1391,8 @@ impl ClusterImpl {
     async fn run_select_stream_impl(
         self: &Arc<Self>,
         node_name: &str,
-        plan: SerializedPlan,
+        init_message: NetworkMessage,
     ) -> Result<SendableRecordBatchStream, CubeError> {
-        let init_message = NetworkMessage::SelectStart(plan);
         let mut c = self.call_streaming(node_name, init_message).await?;
         let schema = match c.receive().await? {
             NetworkMessage::SelectResultSchema(s) => s,
@@ -1584,6 +1636,51 @@ impl MessageStream for QueryStream {
     }
 }
 
+/// Sends batches of the router plan as soon as they are computed.
+pub struct RouterQueryStream {
+    schema: Option<SchemaRef>,
+    /// Batches are read from the plan stream in a separate task, which stops once this is dropped.
+    batches: mpsc::Receiver<Result<RecordBatch, ArrowError>>,
+}
+
+impl RouterQueryStream {
+    pub fn new(mut stream: SendableRecordBatchStream) -> RouterQueryStream {
+        let schema = Some(stream.schema());
+        let (sender, batches) = mpsc::channel(1);
+        cube_ext::spawn(async move {
+            loop {
+                let batch = tokio::select! {
+                    _ = sender.closed() => return,
+                    batch = stream.next() => batch,
+                };
+                match batch {
+                    Some(batch) if sender.send(batch).await.is_ok() => {}
+                    _ => return,
+                }
+            }
+        });
+        RouterQueryStream { schema, batches }
+    }
+}
+
+#[async_trait]
+impl MessageStream for RouterQueryStream {
+    async fn next(&mut self) -> (NetworkMessage, bool) {
+        if let Some(s) = self.schema.take() {
+            return (NetworkMessage::SelectResultSchema(Ok(s)), false);
+        }
+        let batch = match self.batches.recv().await {
+            None => return (NetworkMessage::SelectResultBatch(Ok(None)), true),
+            Some(Err(e)) => return (NetworkMessage::SelectResultBatch(Err(e.into())), true),
+            Some(Ok(batch)) => batch,
+        };
+        match SerializedRecordBatchStream::write(&batch.schema(), vec![batch]) {
+            Ok(mut batches) => (NetworkMessage::SelectResultBatch(Ok(batches.pop())), false),
+            Err(e) => (NetworkMessage::SelectResultBatch(Err(e)), true),
+        }
+    }
+}
+
 fn is_self_reference(name: &str) -> bool {
     name.starts_with("@loop:")
 }
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Regenerates certificates used by tests of cluster TLS. They are valid for 100 years.
+set -e
+cd "$(dirname "$0")"
+openssl req -x509 -newkey rsa:2048 -nodes -keyout ca.key -out ca.pem -days 36500 \
+  -subj "/CN=cubestore-test-ca" \
+  -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign,cRLSign"
+openssl req -newkey rsa:2048 -nodes -keyout node.rsa -out node.csr -subj "/CN=localhost"
+printf "basicConstraints=CA:FALSE\nkeyUsage=critical,digitalSignature,keyEncipherment\nextendedKeyUsage=serverAuth,clientAuth\nsubjectAltName=DNS:localhost\n" > node.ext
+openssl x509 -req -in node.csr -CA ca.pem -CAkey ca.key -CAcreateserial -out node.pem \
+  -days 36500 -extfile node.ext
+openssl pkcs8 -topk8 -nocrypt -in node.rsa -out node.key
+rm -f ca.key ca.srl node.rsa node.csr node.ext
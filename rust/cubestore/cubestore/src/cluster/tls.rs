@@ -0,0 +1,260 @@
+use crate::CubeError;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::internal::pemfile;
+use tokio_rustls::rustls::{
+    AllowAnyAuthenticatedClient, ClientConfig, NoClientAuth, PrivateKey, RootCertStore,
+    ServerConfig,
+};
+use tokio_rustls::webpki::DNSNameRef;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Connection between cluster nodes, either plain TCP or TLS.
+pub trait ClusterStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ClusterStream for T {}
+
+/// TLS for router to worker and worker to metastore connections. Servers always present the node
+/// certificate. With client authentication, clients present it too and servers only accept
+/// clients with certificates signed by the CA.
+pub struct ClusterTls {
+    connector: TlsConnector,
+    acceptor: TlsAcceptor,
+    server_name: Option<String>,
+}
+
+impl fmt::Debug for ClusterTls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClusterTls")
+            .field("server_name", &self.server_name)
+            .finish()
+    }
+}
+
+impl ClusterTls {
+    pub fn load(
+        cert_file: &Path,
+        key_file: &Path,
+        ca_file: &Path,
+        client_auth: bool,
+        server_name: Option<String>,
+    ) -> Result<ClusterTls, CubeError> {
+        Self::from_pem(
+            &std::fs::read(cert_file)?,
+            &std::fs::read(key_file)?,
+            &std::fs::read(ca_file)?,
+            client_auth,
+            server_name,
+        )
+    }
+
+    pub fn from_pem(
+        cert: &[u8],
+        key: &[u8],
+        ca: &[u8],
+        client_auth: bool,
+        server_name: Option<String>,
+    ) -> Result<ClusterTls, CubeError> {
+        let certs = pemfile::certs(&mut &cert[..])
+            .map_err(|_| CubeError::user("Invalid cluster TLS certificate".to_string()))?;
+        if certs.is_empty() {
+            return Err(CubeError::user(
+                "No certificates found in cluster TLS certificate file".to_string(),
+            ));
+        }
+        let key = read_private_key(key)?;
+        let mut roots = RootCertStore::empty();
+        let (added, _) = roots
+            .add_pem_file(&mut &ca[..])
+            .map_err(|_| CubeError::user("Invalid cluster TLS CA bundle".to_string()))?;
+        if added == 0 {
+            return Err(CubeError::user(
+                "No certificates found in cluster TLS CA bundle".to_string(),
+            ));
+        }
+
+        let mut client_config = ClientConfig::new();
+        client_config.root_store = roots.clone();
+        if client_auth {
+            client_config
+                .set_single_client_cert(certs.clone(), key.clone())
+                .map_err(|e| CubeError::user(format!("Invalid cluster TLS key: {}", e)))?;
+        }
+
+        let mut server_config = ServerConfig::new(if client_auth {
+            AllowAnyAuthenticatedClient::new(roots)
+        } else {
+            NoClientAuth::new()
+        });
+        server_config
+            .set_single_cert(certs, key)
+            .map_err(|e| CubeError::user(format!("Invalid cluster TLS key: {}", e)))?;
+
+        Ok(ClusterTls {
+            connector: TlsConnector::from(Arc::new(client_config)),
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            server_name,
+        })
+    }
+
+    /// Performs client handshake over [stream] connected to [address]. The server certificate is
+    /// verified against the configured server name or the host of [address].
+    pub async fn connect(
+        &self,
+        address: &str,
+        stream: TcpStream,
+    ) -> Result<Box<dyn ClusterStream>, CubeError> {
+        let name = match &self.server_name {
+            Some(name) => name.as_str(),
+            None => address.rsplitn(2, ':').last().unwrap_or(address),
+        };
+        let dns_name = DNSNameRef::try_from_ascii_str(name).map_err(|_| {
+            CubeError::user(format!(
+                "Can't verify TLS certificate of {}: '{}' is not a DNS name. Please set CUBESTORE_CLUSTER_TLS_SERVER_NAME.",
+                address, name
+            ))
+        })?;
+        let stream = self
+            .connector
+            .connect(dns_name, stream)
+            .await
+            .map_err(|e| {
+                CubeError::internal(format!("TLS handshake with {} failed: {}", address, e))
+            })?;
+        Ok(Box::new(stream))
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> Result<Box<dyn ClusterStream>, CubeError> {
+        let stream = self
+            .acceptor
+            .accept(stream)
+            .await
+            .map_err(|e| CubeError::internal(format!("TLS handshake failed: {}", e)))?;
+        Ok(Box::new(stream))
+    }
+}
+
+/// Client side of a connection to [address], over TLS if it's configured.
+pub async fn client_stream(
+    tls: &Option<Arc<ClusterTls>>,
+    address: &str,
+    stream: TcpStream,
+) -> Result<Box<dyn ClusterStream>, CubeError> {
+    match tls {
+        Some(tls) => tls.connect(address, stream).await,
+        None => Ok(Box::new(stream)),
+    }
+}
+
+/// Server side of an accepted connection, over TLS if it's configured.
+pub async fn server_stream(
+    tls: &Option<Arc<ClusterTls>>,
+    stream: TcpStream,
+) -> Result<Box<dyn ClusterStream>, CubeError> {
+    match tls {
+        Some(tls) => tls.accept(stream).await,
+        None => Ok(Box::new(stream)),
+    }
+}
+
+fn read_private_key(pem: &[u8]) -> Result<PrivateKey, CubeError> {
+    let invalid_key = || CubeError::user("Invalid cluster TLS private key".to_string());
+    let mut keys = pemfile::pkcs8_private_keys(&mut &pem[..]).map_err(|_| invalid_key())?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut &pem[..]).map_err(|_| invalid_key())?;
+    }
+    keys.into_iter()
+        .next()
+        .ok_or_else(|| CubeError::user("No private keys found in cluster TLS key file".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::message::NetworkMessage;
+    use tokio::net::TcpListener;
+
+    fn test_tls(client_auth: bool) -> Arc<ClusterTls> {
+        Arc::new(
+            ClusterTls::from_pem(
+                include_bytes!("test_certs/node.pem"),
+                include_bytes!("test_certs/node.key"),
+                include_bytes!("test_certs/ca.pem"),
+                client_auth,
+                Some("localhost".to_string()),
+            )
+            .unwrap(),
+        )
+    }
+
+    async fn serve_once(
+        tls: Option<Arc<ClusterTls>>,
+    ) -> (String, tokio::task::JoinHandle<Result<(), CubeError>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await?;
+            let mut socket = server_stream(&tls, socket).await?;
+            match NetworkMessage::receive(&mut socket).await? {
+                NetworkMessage::NotifyJobListeners => {}
+                m => panic!("unexpected message: {:?}", m),
+            }
+            NetworkMessage::NotifyJobListenersSuccess
+                .send(&mut socket)
+                .await
+        });
+        (address, server)
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_roundtrip() {
+        let tls = Some(test_tls(true));
+        let (address, server) = serve_once(tls.clone()).await;
+
+        let socket = TcpStream::connect(&address).await.unwrap();
+        let mut socket = client_stream(&tls, &address, socket).await.unwrap();
+        NetworkMessage::NotifyJobListeners
+            .send(&mut socket)
+            .await
+            .unwrap();
+        let response = NetworkMessage::receive(&mut socket).await.unwrap();
+        assert!(matches!(
+            response,
+            NetworkMessage::NotifyJobListenersSuccess
+        ));
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn tls_server_rejects_plaintext() {
+        let (address, server) = serve_once(Some(test_tls(true))).await;
+
+        let socket = TcpStream::connect(&address).await.unwrap();
+        let mut socket = client_stream(&None, &address, socket).await.unwrap();
+        let _ = NetworkMessage::NotifyJobListeners.send(&mut socket).await;
+        assert!(server.await.unwrap().is_err());
+    }
+
+    #[test]
+    fn invalid_pem() {
+        assert!(ClusterTls::from_pem(
+            b"not a certificate",
+            include_bytes!("test_certs/node.key"),
+            include_bytes!("test_certs/ca.pem"),
+            false,
+            None,
+        )
+        .is_err());
+        assert!(ClusterTls::from_pem(
+            include_bytes!("test_certs/node.pem"),
+            include_bytes!("test_certs/node.key"),
+            b"",
+            false,
+            None,
+        )
+        .is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::cluster::message::NetworkMessage;
+use crate::cluster::tls::{client_stream, ClusterStream};
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::CubeError;
@@ -70,7 +71,7 @@ impl ClusterTransportImpl {
 }
 
 struct Connection {
-    stream: TcpStream,
+    stream: Box<dyn ClusterStream>,
 }
 
 #[async_trait]
@@ -97,6 +98,7 @@ impl ClusterTransport for ClusterTransportImpl {
         .await
         .map_err(|_| CubeError::internal(format!("Connection timeout to {}. Please check your worker connection env variables (CUBESTORE_WORKERS, CUBESTORE_WORKER_PORT, etc.).", worker_node)))?
         .map_err(|e| CubeError::internal(format!("Can't connect to {}: {}", worker_node, e)))?;
+        let stream = client_stream(self.config.cluster_tls(), &worker_node, stream).await?;
         Ok(Box::new(Connection { stream }))
     }
 }
@@ -127,7 +129,7 @@ impl MetaStoreTransport for MetaStoreTransportImpl {
             .as_ref()
             .expect("Meta store remote addr is not defined")
             .to_string();
-        let mut stream = tokio::time::timeout(
+        let stream = tokio::time::timeout(
             Duration::from_secs(self.config.connection_timeout()),
             TcpStream::connect(&meta_remote_addr),
         )
@@ -141,6 +143,8 @@ impl MetaStoreTransport for MetaStoreTransportImpl {
         .map_err(|e| {
             CubeError::internal(format!("Can't connect to {}: {}", meta_remote_addr, e))
         })?;
+        let mut stream =
+            client_stream(self.config.cluster_tls(), &meta_remote_addr, stream).await?;
         m.send(&mut stream).await?;
         let message = NetworkMessage::receive(&mut stream).await?;
         Ok(message)
@@ -17,10 +17,11 @@ pub enum HttpCommand {
     HttpQuery = 1,
     HttpResultSet = 2,
     HttpError = 3,
+    HttpCancel = 4,
 }
 
 pub const ENUM_MIN_HTTP_COMMAND: u8 = 0;
-pub const ENUM_MAX_HTTP_COMMAND: u8 = 3;
+pub const ENUM_MAX_HTTP_COMMAND: u8 = 4;
 
 impl<'a> flatbuffers::Follow<'a> for HttpCommand {
     type Inner = Self;
@@ -54,22 +55,82 @@ impl flatbuffers::Push for HttpCommand {
 }
 
 #[allow(non_camel_case_types)]
-pub const ENUM_VALUES_HTTP_COMMAND: [HttpCommand; 4] = [
+pub const ENUM_VALUES_HTTP_COMMAND: [HttpCommand; 5] = [
     HttpCommand::NONE,
     HttpCommand::HttpQuery,
     HttpCommand::HttpResultSet,
     HttpCommand::HttpError,
+    HttpCommand::HttpCancel,
 ];
 
 #[allow(non_camel_case_types)]
-pub const ENUM_NAMES_HTTP_COMMAND: [&'static str; 4] =
-    ["NONE", "HttpQuery", "HttpResultSet", "HttpError"];
+pub const ENUM_NAMES_HTTP_COMMAND: [&'static str; 5] = [
+    "NONE",
+    "HttpQuery",
+    "HttpResultSet",
+    "HttpError",
+    "HttpCancel",
+];
 
 pub fn enum_name_http_command(e: HttpCommand) -> &'static str {
     let index = e as u8;
     ENUM_NAMES_HTTP_COMMAND[index as usize]
 }
 
+#[allow(non_camel_case_types)]
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum HttpResultFormat {
+    Rows = 0,
+    ArrowIpc = 1,
+}
+
+pub const ENUM_MIN_HTTP_RESULT_FORMAT: u8 = 0;
+pub const ENUM_MAX_HTTP_RESULT_FORMAT: u8 = 1;
+
+impl<'a> flatbuffers::Follow<'a> for HttpResultFormat {
+    type Inner = Self;
+    #[inline]
+    fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        flatbuffers::read_scalar_at::<Self>(buf, loc)
+    }
+}
+
+impl flatbuffers::EndianScalar for HttpResultFormat {
+    #[inline]
+    fn to_little_endian(self) -> Self {
+        let n = u8::to_le(self as u8);
+        let p = &n as *const u8 as *const HttpResultFormat;
+        unsafe { *p }
+    }
+    #[inline]
+    fn from_little_endian(self) -> Self {
+        let n = u8::from_le(self as u8);
+        let p = &n as *const u8 as *const HttpResultFormat;
+        unsafe { *p }
+    }
+}
+
+impl flatbuffers::Push for HttpResultFormat {
+    type Output = HttpResultFormat;
+    #[inline]
+    fn push(&self, dst: &mut [u8], _rest: &[u8]) {
+        flatbuffers::emplace_scalar::<HttpResultFormat>(dst, *self);
+    }
+}
+
+#[allow(non_camel_case_types)]
+pub const ENUM_VALUES_HTTP_RESULT_FORMAT: [HttpResultFormat; 2] =
+    [HttpResultFormat::Rows, HttpResultFormat::ArrowIpc];
+
+#[allow(non_camel_case_types)]
+pub const ENUM_NAMES_HTTP_RESULT_FORMAT: [&'static str; 2] = ["Rows", "ArrowIpc"];
+
+pub fn enum_name_http_result_format(e: HttpResultFormat) -> &'static str {
+    let index = e as u8;
+    ENUM_NAMES_HTTP_RESULT_FORMAT[index as usize]
+}
+
 pub struct HttpCommandUnionTableOffset {}
 pub enum HttpMessageOffset {}
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -160,6 +221,16 @@ impl<'a> HttpMessage<'a> {
             None
         }
     }
+
+    #[inline]
+    #[allow(non_snake_case)]
+    pub fn command_as_http_cancel(&self) -> Option<HttpCancel<'a>> {
+        if self.command_type() == HttpCommand::HttpCancel {
+            self.command().map(|u| HttpCancel::init_from_table(u))
+        } else {
+            None
+        }
+    }
 }
 
 pub struct HttpMessageArgs {
@@ -249,11 +320,15 @@ impl<'a> HttpQuery<'a> {
         if let Some(x) = args.query {
             builder.add_query(x);
         }
+        builder.add_stream(args.stream);
+        builder.add_result_format(args.result_format);
         builder.finish()
     }
 
     pub const VT_QUERY: flatbuffers::VOffsetT = 4;
     pub const VT_TRACE_OBJ: flatbuffers::VOffsetT = 6;
+    pub const VT_RESULT_FORMAT: flatbuffers::VOffsetT = 8;
+    pub const VT_STREAM: flatbuffers::VOffsetT = 10;
 
     #[inline]
     pub fn query(&self) -> Option<&'a str> {
@@ -265,11 +340,25 @@ impl<'a> HttpQuery<'a> {
         self._tab
             .get::<flatbuffers::ForwardsUOffset<&str>>(HttpQuery::VT_TRACE_OBJ, None)
     }
+    #[inline]
+    pub fn result_format(&self) -> HttpResultFormat {
+        self._tab
+            .get::<HttpResultFormat>(HttpQuery::VT_RESULT_FORMAT, Some(HttpResultFormat::Rows))
+            .unwrap()
+    }
+    #[inline]
+    pub fn stream(&self) -> bool {
+        self._tab
+            .get::<bool>(HttpQuery::VT_STREAM, Some(false))
+            .unwrap()
+    }
 }
 
 pub struct HttpQueryArgs<'a> {
     pub query: Option<flatbuffers::WIPOffset<&'a str>>,
     pub trace_obj: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub result_format: HttpResultFormat,
+    pub stream: bool,
 }
 impl<'a> Default for HttpQueryArgs<'a> {
     #[inline]
@@ -277,6 +366,8 @@ impl<'a> Default for HttpQueryArgs<'a> {
         HttpQueryArgs {
             query: None,
             trace_obj: None,
+            result_format: HttpResultFormat::Rows,
+            stream: false,
         }
     }
 }
@@ -296,6 +387,19 @@ impl<'a: 'b, 'b> HttpQueryBuilder<'a, 'b> {
             .push_slot_always::<flatbuffers::WIPOffset<_>>(HttpQuery::VT_TRACE_OBJ, trace_obj);
     }
     #[inline]
+    pub fn add_result_format(&mut self, result_format: HttpResultFormat) {
+        self.fbb_.push_slot::<HttpResultFormat>(
+            HttpQuery::VT_RESULT_FORMAT,
+            result_format,
+            HttpResultFormat::Rows,
+        );
+    }
+    #[inline]
+    pub fn add_stream(&mut self, stream: bool) {
+        self.fbb_
+            .push_slot::<bool>(HttpQuery::VT_STREAM, stream, false);
+    }
+    #[inline]
     pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a>) -> HttpQueryBuilder<'a, 'b> {
         let start = _fbb.start_table();
         HttpQueryBuilder {
@@ -387,6 +491,65 @@ impl<'a: 'b, 'b> HttpErrorBuilder<'a, 'b> {
     }
 }
 
+pub enum HttpCancelOffset {}
+#[derive(Copy, Clone, Debug, PartialEq)]
+
+pub struct HttpCancel<'a> {
+    pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for HttpCancel<'a> {
+    type Inner = HttpCancel<'a>;
+    #[inline]
+    fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Self {
+            _tab: flatbuffers::Table { buf: buf, loc: loc },
+        }
+    }
+}
+
+impl<'a> HttpCancel<'a> {
+    #[inline]
+    pub fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+        HttpCancel { _tab: table }
+    }
+    #[allow(unused_mut)]
+    pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr>(
+        _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr>,
+        _args: &'args HttpCancelArgs,
+    ) -> flatbuffers::WIPOffset<HttpCancel<'bldr>> {
+        let mut builder = HttpCancelBuilder::new(_fbb);
+        builder.finish()
+    }
+}
+
+pub struct HttpCancelArgs {}
+impl<'a> Default for HttpCancelArgs {
+    #[inline]
+    fn default() -> Self {
+        HttpCancelArgs {}
+    }
+}
+pub struct HttpCancelBuilder<'a: 'b, 'b> {
+    fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a>,
+    start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b> HttpCancelBuilder<'a, 'b> {
+    #[inline]
+    pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a>) -> HttpCancelBuilder<'a, 'b> {
+        let start = _fbb.start_table();
+        HttpCancelBuilder {
+            fbb_: _fbb,
+            start_: start,
+        }
+    }
+    #[inline]
+    pub fn finish(self) -> flatbuffers::WIPOffset<HttpCancel<'a>> {
+        let o = self.fbb_.end_table(self.start_);
+        flatbuffers::WIPOffset::new(o.value())
+    }
+}
+
 pub enum HttpResultSetOffset {}
 #[derive(Copy, Clone, Debug, PartialEq)]
 
@@ -415,17 +578,23 @@ impl<'a> HttpResultSet<'a> {
         args: &'args HttpResultSetArgs<'args>,
     ) -> flatbuffers::WIPOffset<HttpResultSet<'bldr>> {
         let mut builder = HttpResultSetBuilder::new(_fbb);
+        if let Some(x) = args.arrow_ipc {
+            builder.add_arrow_ipc(x);
+        }
         if let Some(x) = args.rows {
             builder.add_rows(x);
         }
         if let Some(x) = args.columns {
             builder.add_columns(x);
         }
+        builder.add_has_more(args.has_more);
         builder.finish()
     }
 
     pub const VT_COLUMNS: flatbuffers::VOffsetT = 4;
     pub const VT_ROWS: flatbuffers::VOffsetT = 6;
+    pub const VT_ARROW_IPC: flatbuffers::VOffsetT = 8;
+    pub const VT_HAS_MORE: flatbuffers::VOffsetT = 10;
 
     #[inline]
     pub fn columns(
@@ -443,6 +612,21 @@ impl<'a> HttpResultSet<'a> {
             flatbuffers::Vector<flatbuffers::ForwardsUOffset<HttpRow<'a>>>,
         >>(HttpResultSet::VT_ROWS, None)
     }
+    #[inline]
+    pub fn arrow_ipc(&self) -> Option<&'a [u8]> {
+        self._tab
+            .get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u8>>>(
+                HttpResultSet::VT_ARROW_IPC,
+                None,
+            )
+            .map(|v| v.safe_slice())
+    }
+    #[inline]
+    pub fn has_more(&self) -> bool {
+        self._tab
+            .get::<bool>(HttpResultSet::VT_HAS_MORE, Some(false))
+            .unwrap()
+    }
 }
 
 pub struct HttpResultSetArgs<'a> {
@@ -452,6 +636,8 @@ pub struct HttpResultSetArgs<'a> {
     pub rows: Option<
         flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<HttpRow<'a>>>>,
     >,
+    pub arrow_ipc: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u8>>>,
+    pub has_more: bool,
 }
 impl<'a> Default for HttpResultSetArgs<'a> {
     #[inline]
@@ -459,6 +645,8 @@ impl<'a> Default for HttpResultSetArgs<'a> {
         HttpResultSetArgs {
             columns: None,
             rows: None,
+            arrow_ipc: None,
+            has_more: false,
         }
     }
 }
@@ -488,6 +676,19 @@ impl<'a: 'b, 'b> HttpResultSetBuilder<'a, 'b> {
             .push_slot_always::<flatbuffers::WIPOffset<_>>(HttpResultSet::VT_ROWS, rows);
     }
     #[inline]
+    pub fn add_arrow_ipc(
+        &mut self,
+        arrow_ipc: flatbuffers::WIPOffset<flatbuffers::Vector<'b, u8>>,
+    ) {
+        self.fbb_
+            .push_slot_always::<flatbuffers::WIPOffset<_>>(HttpResultSet::VT_ARROW_IPC, arrow_ipc);
+    }
+    #[inline]
+    pub fn add_has_more(&mut self, has_more: bool) {
+        self.fbb_
+            .push_slot::<bool>(HttpResultSet::VT_HAS_MORE, has_more, false);
+    }
+    #[inline]
     pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a>) -> HttpResultSetBuilder<'a, 'b> {
         let start = _fbb.start_table();
         HttpResultSetBuilder {
@@ -2,6 +2,7 @@
 pub mod injection;
 pub mod processing_loop;
 
+use crate::cluster::tls::ClusterTls;
 use crate::cluster::transport::{
     ClusterTransport, ClusterTransportImpl, MetaStoreTransport, MetaStoreTransportImpl,
 };
@@ -11,10 +12,13 @@ use crate::config::processing_loop::ProcessingLoop;
 use crate::http::HttpServer;
 use crate::import::limits::ConcurrencyLimits;
 use crate::import::{ImportService, ImportServiceImpl};
+use crate::metastore::table::{ParquetCompression, ParquetSettings};
 use crate::metastore::{MetaStore, MetaStoreRpcClient, RocksMetaStore};
 use crate::mysql::{MySqlServer, SqlAuthDefaultImpl, SqlAuthService};
 use crate::queryplanner::query_executor::{QueryExecutor, QueryExecutorImpl};
 use crate::queryplanner::{QueryPlanner, QueryPlannerImpl};
+use crate::remotefs::azure::AzureBlobRemoteFs;
+use crate::remotefs::encrypted::{EncryptedRemoteFs, EncryptionKey, EncryptionKeyring};
 use crate::remotefs::gcs::GCSRemoteFs;
 use crate::remotefs::minio::MINIORemoteFs;
 use crate::remotefs::queue::QueueRemoteFs;
@@ -38,7 +42,7 @@ use rocksdb::{Options, DB};
 use simple_logger::SimpleLogger;
 use std::fmt::Display;
 use std::future::Future;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -236,6 +240,7 @@ pub fn validate_config(c: &dyn ConfigObj) -> ValidationMessages {
         "CUBESTORE_MINIO_BUCKET",
         "CUBESTORE_S3_BUCKET",
         "CUBESTORE_GCS_BUCKET",
+        "CUBESTORE_AZURE_CONTAINER",
         "CUBESTORE_REMOTE_DIR",
     ];
     remote_vars.retain(|v| env::var(v).is_ok());
@@ -269,6 +274,10 @@ pub enum FileStoreProvider {
         bucket_name: String,
         sub_path: Option<String>,
     },
+    Azure {
+        container: String,
+        sub_path: Option<String>,
+    },
 }
 
 #[derive(Clone)]
@@ -309,8 +318,20 @@ pub trait ConfigObj: DIService {
 
     fn meta_store_log_upload_interval(&self) -> u64;
 
+    /// Seconds to keep uploaded metastore checkpoints for. The latest one is always kept.
+    fn meta_store_checkpoint_retention(&self) -> u64;
+
+    /// Checkpoint to restore the metastore from on startup instead of the latest one.
+    fn meta_store_restore_checkpoint(&self) -> &Option<String>;
+
     fn gc_loop_interval(&self) -> u64;
 
+    /// Seconds between removals of orphaned remote files, disabled if 0.
+    fn remote_files_cleanup_interval(&self) -> u64;
+
+    /// Orphaned remote files are removed only if they weren't modified for this many seconds.
+    fn remote_files_cleanup_delay(&self) -> u64;
+
     fn stale_stream_timeout(&self) -> u64;
 
     fn select_workers(&self) -> &Vec<String>;
@@ -344,6 +365,15 @@ pub trait ConfigObj: DIService {
     fn max_cached_queries(&self) -> usize;
 
     fn dump_dir(&self) -> &Option<PathBuf>;
+
+    /// Used for tables that don't override these settings.
+    fn parquet_settings(&self) -> &ParquetSettings;
+
+    /// Upper bound on the size of remote files kept in the local directory, unlimited if not set.
+    fn max_local_files_size(&self) -> Option<u64>;
+
+    /// Connections between cluster nodes are plaintext if not set.
+    fn cluster_tls(&self) -> &Option<Arc<ClusterTls>>;
 }
 
 #[derive(Debug, Clone)]
@@ -367,7 +397,11 @@ pub struct ConfigObjImpl {
     pub import_job_timeout: u64,
     pub meta_store_log_upload_interval: u64,
     pub meta_store_snapshot_interval: u64,
+    pub meta_store_checkpoint_retention: u64,
+    pub meta_store_restore_checkpoint: Option<String>,
     pub gc_loop_interval: u64,
+    pub remote_files_cleanup_interval: u64,
+    pub remote_files_cleanup_delay: u64,
     pub stale_stream_timeout: u64,
     pub select_workers: Vec<String>,
     pub worker_bind_address: Option<String>,
@@ -383,6 +417,11 @@ pub struct ConfigObjImpl {
     pub enable_startup_warmup: bool,
     pub malloc_trim_every_secs: u64,
     pub max_cached_queries: usize,
+    pub parquet_settings: ParquetSettings,
+    pub max_local_files_size: Option<u64>,
+    /// Files are encrypted with the active key before upload when set.
+    pub encryption_keys: Option<EncryptionKeyring>,
+    pub cluster_tls: Option<Arc<ClusterTls>>,
 }
 
 crate::di_service!(ConfigObjImpl, [ConfigObj]);
@@ -445,6 +484,14 @@ impl ConfigObj for ConfigObjImpl {
         self.meta_store_snapshot_interval
     }
 
+    fn meta_store_checkpoint_retention(&self) -> u64 {
+        self.meta_store_checkpoint_retention
+    }
+
+    fn meta_store_restore_checkpoint(&self) -> &Option<String> {
+        &self.meta_store_restore_checkpoint
+    }
+
     fn meta_store_log_upload_interval(&self) -> u64 {
         self.meta_store_log_upload_interval
     }
@@ -453,6 +500,14 @@ impl ConfigObj for ConfigObjImpl {
         self.gc_loop_interval
     }
 
+    fn remote_files_cleanup_interval(&self) -> u64 {
+        self.remote_files_cleanup_interval
+    }
+
+    fn remote_files_cleanup_delay(&self) -> u64 {
+        self.remote_files_cleanup_delay
+    }
+
     fn stale_stream_timeout(&self) -> u64 {
         self.stale_stream_timeout
     }
@@ -518,6 +573,18 @@ impl ConfigObj for ConfigObjImpl {
     fn dump_dir(&self) -> &Option<PathBuf> {
         &self.dump_dir
     }
+
+    fn parquet_settings(&self) -> &ParquetSettings {
+        &self.parquet_settings
+    }
+
+    fn max_local_files_size(&self) -> Option<u64> {
+        self.max_local_files_size
+    }
+
+    fn cluster_tls(&self) -> &Option<Arc<ClusterTls>> {
+        &self.cluster_tls
+    }
 }
 
 lazy_static! {
@@ -526,14 +593,64 @@ lazy_static! {
 }
 
 fn env_bool(name: &str, default: bool) -> bool {
-    env::var(name)
-        .ok()
-        .map(|x| match x.as_str() {
-            "0" => false,
-            "1" => true,
-            _ => panic!("expected '0' or '1' for '{}', found '{}'", name, &x),
+    env_optbool(name).unwrap_or(default)
+}
+
+fn env_optbool(name: &str) -> Option<bool> {
+    env::var(name).ok().map(|x| match x.as_str() {
+        "0" => false,
+        "1" => true,
+        _ => panic!("expected '0' or '1' for '{}', found '{}'", name, &x),
+    })
+}
+
+fn env_encryption_keys() -> Option<EncryptionKeyring> {
+    let id = env::var("CUBESTORE_ENCRYPTION_KEY_ID").unwrap_or("default".to_string());
+    let key = if let Ok(key) = env::var("CUBESTORE_ENCRYPTION_KEY") {
+        EncryptionKey::from_base64(id, &key)
+    } else if let Ok(path) = env::var("CUBESTORE_ENCRYPTION_KEY_FILE") {
+        EncryptionKey::from_file(id, Path::new(&path))
+    } else {
+        return None;
+    };
+    // Comma separated `id:base64 key` pairs of keys that are only used to read older files.
+    let previous = env::var("CUBESTORE_ENCRYPTION_PREVIOUS_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|k| !k.trim().is_empty())
+        .map(|k| match k.split_once(':') {
+            Some((id, key)) => EncryptionKey::from_base64(id.trim().to_string(), key.trim()),
+            None => Err(CubeError::user(format!(
+                "expected 'id:key' in CUBESTORE_ENCRYPTION_PREVIOUS_KEYS, found '{}'",
+                k
+            ))),
         })
-        .unwrap_or(default)
+        .collect::<Result<Vec<_>, _>>();
+    match key
+        .and_then(|key| Ok((key, previous?)))
+        .and_then(|(key, previous)| EncryptionKeyring::new(key, previous))
+    {
+        Ok(keys) => Some(keys),
+        Err(e) => panic!("could not load encryption key: {}", e),
+    }
+}
+
+fn env_cluster_tls() -> Option<Arc<ClusterTls>> {
+    let cert = env::var("CUBESTORE_CLUSTER_TLS_CERT").ok()?;
+    let key = env::var("CUBESTORE_CLUSTER_TLS_KEY")
+        .expect("CUBESTORE_CLUSTER_TLS_KEY required when CUBESTORE_CLUSTER_TLS_CERT is set");
+    let ca = env::var("CUBESTORE_CLUSTER_TLS_CA")
+        .expect("CUBESTORE_CLUSTER_TLS_CA required when CUBESTORE_CLUSTER_TLS_CERT is set");
+    match ClusterTls::load(
+        Path::new(&cert),
+        Path::new(&key),
+        Path::new(&ca),
+        env_bool("CUBESTORE_CLUSTER_TLS_CLIENT_AUTH", false),
+        env::var("CUBESTORE_CLUSTER_TLS_SERVER_NAME").ok(),
+    ) {
+        Ok(tls) => Some(Arc::new(tls)),
+        Err(e) => panic!("could not load cluster TLS settings: {}", e),
+    }
 }
 
 fn env_parse<T>(name: &str, default: T) -> T
@@ -603,6 +720,11 @@ impl Config {
                             bucket_name,
                             sub_path: env::var("CUBESTORE_GCS_SUB_PATH").ok(),
                         }
+                    } else if let Ok(container) = env::var("CUBESTORE_AZURE_CONTAINER") {
+                        FileStoreProvider::Azure {
+                            container,
+                            sub_path: env::var("CUBESTORE_AZURE_SUB_PATH").ok(),
+                        }
                     } else if let Ok(remote_dir) = env::var("CUBESTORE_REMOTE_DIR") {
                         FileStoreProvider::Filesystem {
                             remote_dir: Some(PathBuf::from(remote_dir)),
@@ -628,7 +750,21 @@ impl Config {
                 import_job_timeout: env_parse("CUBESTORE_IMPORT_JOB_TIMEOUT", 600),
                 meta_store_log_upload_interval: 30,
                 meta_store_snapshot_interval: 300,
+                meta_store_checkpoint_retention: env_parse(
+                    "CUBESTORE_METASTORE_CHECKPOINT_RETENTION",
+                    3 * 60,
+                ),
+                meta_store_restore_checkpoint: env::var("CUBESTORE_METASTORE_RESTORE_CHECKPOINT")
+                    .ok(),
                 gc_loop_interval: 60,
+                remote_files_cleanup_interval: env_parse(
+                    "CUBESTORE_REMOTE_FILES_CLEANUP_INTERVAL_SECS",
+                    0,
+                ),
+                remote_files_cleanup_delay: env_parse(
+                    "CUBESTORE_REMOTE_FILES_CLEANUP_DELAY_SECS",
+                    24 * 60 * 60,
+                ),
                 stale_stream_timeout: 60,
                 select_workers: env::var("CUBESTORE_WORKERS")
                     .ok()
@@ -655,6 +791,16 @@ impl Config {
                 enable_startup_warmup: env_bool("CUBESTORE_STARTUP_WARMUP", true),
                 malloc_trim_every_secs: env_parse("CUBESTORE_MALLOC_TRIM_EVERY_SECS", 30),
                 max_cached_queries: env_parse("CUBESTORE_MAX_CACHED_QUERIES", 10_000),
+                parquet_settings: ParquetSettings {
+                    compression: env_optparse::<ParquetCompression>(
+                        "CUBESTORE_PARQUET_COMPRESSION",
+                    ),
+                    dictionary_enabled: env_optbool("CUBESTORE_PARQUET_DICTIONARY"),
+                    statistics_enabled: env_optbool("CUBESTORE_PARQUET_STATISTICS"),
+                },
+                max_local_files_size: env_optparse("CUBESTORE_MAX_LOCAL_FILES_SIZE"),
+                encryption_keys: env_encryption_keys(),
+                cluster_tls: env_cluster_tls(),
             }),
         }
     }
@@ -703,9 +849,17 @@ impl Config {
                 enable_startup_warmup: true,
                 malloc_trim_every_secs: 0,
                 max_cached_queries: 10_000,
+                parquet_settings: ParquetSettings::default(),
+                max_local_files_size: None,
+                encryption_keys: None,
+                cluster_tls: None,
                 meta_store_log_upload_interval: 30,
                 meta_store_snapshot_interval: 300,
+                meta_store_checkpoint_retention: 3 * 60,
+                meta_store_restore_checkpoint: None,
                 gc_loop_interval: 60,
+                remote_files_cleanup_interval: 0,
+                remote_files_cleanup_delay: 24 * 60 * 60,
             }),
         }
     }
@@ -860,12 +1014,27 @@ impl Config {
             .register_typed::<dyn ConfigObj, _, _, _>(async move |_| config_obj_to_register)
             .await;
 
+        let encryption_keys = match &self.config_obj.store_provider {
+            // Without a remote dir there is nothing to protect and no place for encrypted files.
+            FileStoreProvider::Filesystem { remote_dir: None } => None,
+            _ => self.config_obj.encryption_keys.clone(),
+        };
+        // Encrypted files are kept apart from decrypted ones while being transferred.
+        let (remote_fs_name, remote_fs_dir) = if encryption_keys.is_some() {
+            (
+                "unencrypted_remote_fs",
+                self.config_obj.data_dir.join("encrypted"),
+            )
+        } else {
+            ("original_remote_fs", self.config_obj.data_dir.clone())
+        };
+
         match &self.config_obj.store_provider {
             FileStoreProvider::Filesystem { remote_dir } => {
                 let remote_dir = remote_dir.clone();
-                let data_dir = self.config_obj.data_dir.clone();
+                let data_dir = remote_fs_dir.clone();
                 self.injector
-                    .register("original_remote_fs", async move |_| {
+                    .register(remote_fs_name, async move |_| {
                         let arc: Arc<dyn DIService> = LocalDirRemoteFs::new(remote_dir, data_dir);
                         arc
                     })
@@ -876,12 +1045,12 @@ impl Config {
                 bucket_name,
                 sub_path,
             } => {
-                let data_dir = self.config_obj.data_dir.clone();
+                let data_dir = remote_fs_dir.clone();
                 let region = region.to_string();
                 let bucket_name = bucket_name.to_string();
                 let sub_path = sub_path.clone();
                 self.injector
-                    .register("original_remote_fs", async move |_| {
+                    .register(remote_fs_name, async move |_| {
                         let arc: Arc<dyn DIService> =
                             S3RemoteFs::new(data_dir, region, bucket_name, sub_path).unwrap();
                         arc
@@ -892,11 +1061,11 @@ impl Config {
                 bucket_name,
                 sub_path,
             } => {
-                let data_dir = self.config_obj.data_dir.clone();
+                let data_dir = remote_fs_dir.clone();
                 let bucket_name = bucket_name.to_string();
                 let sub_path = sub_path.clone();
                 self.injector
-                    .register("original_remote_fs", async move |_| {
+                    .register(remote_fs_name, async move |_| {
                         let arc: Arc<dyn DIService> =
                             GCSRemoteFs::new(data_dir, bucket_name, sub_path).unwrap();
                         arc
@@ -907,19 +1076,48 @@ impl Config {
                 bucket_name,
                 sub_path,
             } => {
-                let data_dir = self.config_obj.data_dir.clone();
+                let data_dir = remote_fs_dir.clone();
                 let bucket_name = bucket_name.to_string();
                 let sub_path = sub_path.clone();
                 self.injector
-                    .register("original_remote_fs", async move |_| {
+                    .register(remote_fs_name, async move |_| {
                         let arc: Arc<dyn DIService> =
                             MINIORemoteFs::new(data_dir, bucket_name, sub_path).unwrap();
                         arc
                     })
                     .await;
             }
+            FileStoreProvider::Azure {
+                container,
+                sub_path,
+            } => {
+                let data_dir = remote_fs_dir.clone();
+                let container = container.to_string();
+                let sub_path = sub_path.clone();
+                self.injector
+                    .register(remote_fs_name, async move |_| {
+                        let arc: Arc<dyn DIService> =
+                            AzureBlobRemoteFs::new(data_dir, container, sub_path).unwrap();
+                        arc
+                    })
+                    .await;
+            }
             FileStoreProvider::Local => unimplemented!(), // TODO
         };
+
+        if let Some(keys) = encryption_keys {
+            let data_dir = self.config_obj.data_dir.clone();
+            self.injector
+                .register("original_remote_fs", async move |i| {
+                    let arc: Arc<dyn DIService> = EncryptedRemoteFs::new(
+                        i.get_service("unencrypted_remote_fs").await,
+                        keys,
+                        data_dir,
+                    );
+                    arc
+                })
+                .await;
+        }
     }
 
     async fn remote_fs(&self) -> Result<Arc<dyn RemoteFs + 'static>, CubeError> {
@@ -1113,6 +1311,7 @@ impl Config {
                     i.get_service_typed().await,
                     i.get_service_typed().await,
                     i.get_service_typed().await,
+                    i.get_service_typed().await,
                     c.wal_split_threshold() as usize,
                     Duration::from_secs(c.query_timeout()),
                     Duration::from_secs(c.import_job_timeout() * 2),
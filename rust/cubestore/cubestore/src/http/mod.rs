@@ -5,19 +5,23 @@ use std::sync::Arc;
 use warp::{Filter, Rejection, Reply};
 
 use crate::codegen::http_message_generated::{
-    get_root_as_http_message, HttpColumnValue, HttpColumnValueArgs, HttpError, HttpErrorArgs,
-    HttpMessageArgs, HttpQuery, HttpQueryArgs, HttpResultSet, HttpResultSetArgs, HttpRow,
-    HttpRowArgs,
+    get_root_as_http_message, HttpCancel, HttpCancelArgs, HttpColumnValue, HttpColumnValueArgs,
+    HttpError, HttpErrorArgs, HttpMessageArgs, HttpQuery, HttpQueryArgs, HttpResultFormat,
+    HttpResultSet, HttpResultSetArgs, HttpRow, HttpRowArgs,
 };
+use crate::metastore::ColumnType;
 use crate::mysql::SqlAuthService;
+use crate::queryplanner::query_executor::batch_to_dataframe;
 use crate::sql::{SqlQueryContext, SqlService};
-use crate::store::DataFrame;
+use crate::store::{batches_to_arrow_ipc, DataFrame};
 use crate::table::TableValue;
 use crate::util::WorkerLoop;
 use crate::CubeError;
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
 use async_std::fs::File;
 use datafusion::cube_ext;
-use futures::{AsyncWriteExt, SinkExt, Stream, StreamExt};
+use futures::{AsyncWriteExt, SinkExt, Stream, StreamExt, TryStreamExt};
 use hex::ToHex;
 use http_auth_basic::Credentials;
 use log::error;
@@ -79,8 +83,12 @@ impl HttpServer {
     }
 
     pub async fn run_server(&self) -> Result<(), CubeError> {
-        let (tx, mut rx) =
-            mpsc::channel::<(mpsc::Sender<HttpMessage>, SqlQueryContext, HttpMessage)>(100000);
+        let (tx, mut rx) = mpsc::channel::<(
+            mpsc::Sender<HttpMessage>,
+            SqlQueryContext,
+            HttpMessage,
+            RunningQuery,
+        )>(100000);
         let auth_service = self.auth.clone();
         let tx_to_move_filter = warp::any().map(move || tx.clone());
 
@@ -107,13 +115,23 @@ impl HttpServer {
         let query_route = warp::path!("ws")
             .and(context_filter_to_move)
             .and(warp::ws::ws())
-            .and_then(|tx: mpsc::Sender<(mpsc::Sender<HttpMessage>, SqlQueryContext, HttpMessage)>, sql_query_context: SqlQueryContext, ws: Ws| async move {
+            .and_then(|tx: mpsc::Sender<(mpsc::Sender<HttpMessage>, SqlQueryContext, HttpMessage, RunningQuery)>, sql_query_context: SqlQueryContext, ws: Ws| async move {
                 let tx_to_move = tx.clone();
                 let sql_query_context = sql_query_context.clone();
                 Result::<_, Rejection>::Ok(ws.on_upgrade(async move |mut web_socket| {
                     let (response_tx, mut response_rx) = mpsc::channel::<HttpMessage>(10000);
+                    let (finished_tx, mut finished_rx) = mpsc::unbounded_channel::<(u32, u64)>();
+                    // Queries that haven't finished yet by message id, along with the sequence
+                    // number of the query in case the client reuses message ids.
+                    let mut running_queries = HashMap::<u32, (u64, CancellationToken)>::new();
+                    let mut query_seq = 0u64;
                     loop {
                         tokio::select! {
+                            Some((message_id, seq)) = finished_rx.recv() => {
+                                if running_queries.get(&message_id).map(|(s, _)| *s) == Some(seq) {
+                                    running_queries.remove(&message_id);
+                                }
+                            }
                             Some(res) = response_rx.recv() => {
                                 trace!("Sending web socket response");
                                 let send_res = web_socket.send(Message::binary(res.bytes())).await;
@@ -131,11 +149,26 @@ impl HttpServer {
                                         if msg.is_binary() {
                                             match HttpMessage::read(msg.into_bytes()) {
                                                 Err(e) => error!("Websocket message read error: {:?}", e),
+                                                Ok(HttpMessage { message_id, command: HttpCommand::Cancel }) => {
+                                                    trace!("Received web socket cancel message");
+                                                    if let Some((_, cancel_token)) = running_queries.remove(&message_id) {
+                                                        cancel_token.cancel();
+                                                    }
+                                                }
                                                 Ok(msg) => {
                                                     trace!("Received web socket message");
                                                     let message_id = msg.message_id;
+                                                    let cancel_token = CancellationToken::new();
+                                                    query_seq += 1;
+                                                    running_queries.insert(message_id, (query_seq, cancel_token.clone()));
+                                                    let running_query = RunningQuery {
+                                                        message_id,
+                                                        seq: query_seq,
+                                                        cancel_token,
+                                                        finished: finished_tx.clone(),
+                                                    };
                                                     // TODO use timeout instead of try send for burst control however try_send is safer for now
-                                                    if let Err(e) = tx_to_move.try_send((response_tx.clone(), sql_query_context.clone(), msg)) {
+                                                    if let Err(e) = tx_to_move.try_send((response_tx.clone(), sql_query_context.clone(), msg, running_query)) {
                                                         error!("Websocket channel error: {:?}", e);
                                                         let send_res = web_socket.send(
                                                             Message::binary(HttpMessage { message_id, command: HttpCommand::Error { error: e.to_string() } }.bytes())
@@ -163,6 +196,9 @@ impl HttpServer {
                             }
                         };
                     };
+                    for (_, (_, cancel_token)) in running_queries {
+                        cancel_token.cancel();
+                    }
                 }))
             });
 
@@ -197,31 +233,38 @@ impl HttpServer {
                     message_id,
                     command,
                 },
+                running_query,
             )| {
                 cube_ext::spawn(async move {
-                    let res =
-                        HttpServer::process_command(sql_service, sql_query_context, command).await;
-                    let message = match res {
-                        Ok(command) => HttpMessage {
+                    let cancel_token = running_query.cancel_token.clone();
+                    let res = tokio::select! {
+                        res = HttpServer::process_message(
+                            sql_service,
+                            sql_query_context,
                             message_id,
                             command,
-                        },
-                        Err(e) => {
-                            log::error!(
-                                "Error processing HTTP command: {}\n",
-                                e.display_with_backtrace()
-                            );
-                            HttpMessage {
-                                message_id,
-                                command: HttpCommand::Error {
-                                    error: e.to_string(),
-                                },
-                            }
+                            &sender,
+                        ) => res,
+                        _ = cancel_token.cancelled() => {
+                            Err(CubeError::user("Query cancelled".to_string()))
                         }
                     };
-                    if let Err(e) = sender.send(message).await {
-                        error!("Send result channel error: {:?}", e);
+                    if let Err(e) = res {
+                        log::error!(
+                            "Error processing HTTP command: {}\n",
+                            e.display_with_backtrace()
+                        );
+                        let message = HttpMessage {
+                            message_id,
+                            command: HttpCommand::Error {
+                                error: e.to_string(),
+                            },
+                        };
+                        if let Err(e) = sender.send(message).await {
+                            error!("Send result channel error: {:?}", e);
+                        }
                     }
+                    drop(running_query);
                 });
                 Ok(())
             },
@@ -297,17 +340,100 @@ impl HttpServer {
         Ok(warp::reply())
     }
 
+    /// Sends results of the command to `sender`. Streamed queries send a message per chunk of
+    /// rows followed by a message without rows and `has_more` unset.
+    pub async fn process_message(
+        sql_service: Arc<dyn SqlService>,
+        sql_query_context: SqlQueryContext,
+        message_id: u32,
+        command: HttpCommand,
+        sender: &mpsc::Sender<HttpMessage>,
+    ) -> Result<(), CubeError> {
+        let send = |command: HttpCommand| async move {
+            sender
+                .send(HttpMessage {
+                    message_id,
+                    command,
+                })
+                .await
+                .map_err(|e| CubeError::internal(format!("Send result channel error: {:?}", e)))
+        };
+        match command {
+            HttpCommand::Query {
+                query,
+                trace_obj,
+                result_format,
+                stream: true,
+            } => {
+                let mut result = sql_service
+                    .exec_query_stream_with_context(
+                        sql_query_context.with_trace_obj(trace_obj),
+                        &query,
+                    )
+                    .await?;
+                while let Some(batch) = result.batches.next().await {
+                    let batch = batch?;
+                    let schema = result.schema.clone();
+                    let result_set = cube_ext::spawn_blocking(move || {
+                        HttpCommand::result_set(schema, vec![batch], result_format, true)
+                    })
+                    .await??;
+                    send(result_set).await?;
+                }
+                send(HttpCommand::result_set(
+                    result.schema,
+                    Vec::new(),
+                    result_format,
+                    false,
+                )?)
+                .await
+            }
+            command => {
+                send(HttpServer::process_command(sql_service, sql_query_context, command).await?)
+                    .await
+            }
+        }
+    }
+
     pub async fn process_command(
         sql_service: Arc<dyn SqlService>,
         sql_query_context: SqlQueryContext,
         command: HttpCommand,
     ) -> Result<HttpCommand, CubeError> {
         match command {
-            HttpCommand::Query { query, trace_obj } => Ok(HttpCommand::ResultSet {
-                data_frame: sql_service
+            HttpCommand::Query {
+                query,
+                trace_obj,
+                result_format: HttpResultFormat::Rows,
+                ..
+            } => {
+                let data_frame = sql_service
                     .exec_query_with_context(sql_query_context.with_trace_obj(trace_obj), &query)
-                    .await?,
-            }),
+                    .await?;
+                Ok(HttpCommand::ResultSet {
+                    data_frame,
+                    has_more: false,
+                })
+            }
+            HttpCommand::Query {
+                query,
+                trace_obj,
+                result_format: HttpResultFormat::ArrowIpc,
+                ..
+            } => {
+                let result = sql_service
+                    .exec_query_stream_with_context(
+                        sql_query_context.with_trace_obj(trace_obj),
+                        &query,
+                    )
+                    .await?;
+                let batches = result.batches.try_collect::<Vec<_>>().await?;
+                let schema = result.schema;
+                cube_ext::spawn_blocking(move || {
+                    HttpCommand::result_set(schema, batches, HttpResultFormat::ArrowIpc, false)
+                })
+                .await?
+            }
             x => Err(CubeError::user(format!("Unexpected command: {:?}", x))),
         }
     }
@@ -342,6 +468,22 @@ impl HttpServer {
     }
 }
 
+/// Removes the query from the running queries of its connection when dropped, so it happens on
+/// every termination path, including panics and queries dropped before they started.
+pub struct RunningQuery {
+    message_id: u32,
+    seq: u64,
+    cancel_token: CancellationToken,
+    finished: mpsc::UnboundedSender<(u32, u64)>,
+}
+
+impl Drop for RunningQuery {
+    fn drop(&mut self) {
+        // The connection might be closed already.
+        let _ = self.finished.send((self.message_id, self.seq));
+    }
+}
+
 #[derive(Debug)]
 pub struct HttpMessage {
     message_id: u32,
@@ -353,16 +495,66 @@ pub enum HttpCommand {
     Query {
         query: String,
         trace_obj: Option<String>,
+        result_format: HttpResultFormat,
+        stream: bool,
     },
     ResultSet {
         data_frame: Arc<DataFrame>,
+        has_more: bool,
+    },
+    /// Result set requested with `HttpResultFormat::ArrowIpc`.
+    ArrowResultSet {
+        columns: Vec<String>,
+        arrow_ipc: Vec<u8>,
+        has_more: bool,
     },
     Error {
         error: String,
     },
+    /// Cancels the query with the same message id.
+    Cancel,
+}
+
+impl HttpCommand {
+    /// Arrow IPC results are encoded from [batches] directly, rows are converted from them.
+    pub fn result_set(
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+        result_format: HttpResultFormat,
+        has_more: bool,
+    ) -> Result<HttpCommand, CubeError> {
+        Ok(match result_format {
+            HttpResultFormat::Rows => {
+                // Keeps the columns when there are no rows.
+                let batches = if batches.is_empty() {
+                    vec![RecordBatch::new_empty(schema)]
+                } else {
+                    batches
+                };
+                HttpCommand::ResultSet {
+                    data_frame: Arc::new(batch_to_dataframe(&batches)?),
+                    has_more,
+                }
+            }
+            HttpResultFormat::ArrowIpc => HttpCommand::ArrowResultSet {
+                columns: schema.fields().iter().map(|f| f.name().clone()).collect(),
+                arrow_ipc: batches_to_arrow_ipc(&schema, &batches)?,
+                has_more,
+            },
+        })
+    }
 }
 
 impl HttpMessage {
+    /// Streamed results are complete after the first message without `has_more`.
+    pub fn has_more(&self) -> bool {
+        match &self.command {
+            HttpCommand::ResultSet { has_more, .. }
+            | HttpCommand::ArrowResultSet { has_more, .. } => *has_more,
+            _ => false,
+        }
+    }
+
     pub fn bytes(&self) -> Vec<u8> {
         let mut builder = flatbuffers::FlatBufferBuilder::new_with_capacity(1024);
         let args = HttpMessageArgs {
@@ -371,15 +563,23 @@ impl HttpMessage {
                 HttpCommand::Query { .. } => {
                     crate::codegen::http_message_generated::HttpCommand::HttpQuery
                 }
-                HttpCommand::ResultSet { .. } => {
+                HttpCommand::ResultSet { .. } | HttpCommand::ArrowResultSet { .. } => {
                     crate::codegen::http_message_generated::HttpCommand::HttpResultSet
                 }
                 HttpCommand::Error { .. } => {
                     crate::codegen::http_message_generated::HttpCommand::HttpError
                 }
+                HttpCommand::Cancel => {
+                    crate::codegen::http_message_generated::HttpCommand::HttpCancel
+                }
             },
             command: match &self.command {
-                HttpCommand::Query { query, trace_obj } => {
+                HttpCommand::Query {
+                    query,
+                    trace_obj,
+                    result_format,
+                    stream,
+                } => {
                     let query_offset = builder.create_string(&query);
                     let trace_obj_offset = trace_obj.as_ref().map(|o| builder.create_string(o));
                     Some(
@@ -388,6 +588,29 @@ impl HttpMessage {
                             &HttpQueryArgs {
                                 query: Some(query_offset),
                                 trace_obj: trace_obj_offset,
+                                result_format: *result_format,
+                                stream: *stream,
+                            },
+                        )
+                        .as_union_value(),
+                    )
+                }
+                HttpCommand::ArrowResultSet {
+                    columns,
+                    arrow_ipc,
+                    has_more,
+                } => {
+                    let columns = columns.iter().map(|c| c.as_str()).collect::<Vec<_>>();
+                    let columns_vec = builder.create_vector_of_strings(columns.as_slice());
+                    let arrow_ipc = builder.create_vector(arrow_ipc.as_slice());
+                    Some(
+                        HttpResultSet::create(
+                            &mut builder,
+                            &HttpResultSetArgs {
+                                columns: Some(columns_vec),
+                                rows: None,
+                                arrow_ipc: Some(arrow_ipc),
+                                has_more: *has_more,
                             },
                         )
                         .as_union_value(),
@@ -405,7 +628,13 @@ impl HttpMessage {
                         .as_union_value(),
                     )
                 }
-                HttpCommand::ResultSet { data_frame } => {
+                HttpCommand::Cancel => {
+                    Some(HttpCancel::create(&mut builder, &HttpCancelArgs {}).as_union_value())
+                }
+                HttpCommand::ResultSet {
+                    data_frame,
+                    has_more,
+                } => {
                     let columns = data_frame
                         .get_columns()
                         .iter()
@@ -468,7 +697,11 @@ impl HttpMessage {
                                     )
                                 }
                                 TableValue::Timestamp(v) => {
-                                    let string_value = Some(builder.create_string(&v.to_string()));
+                                    let v = match data_frame.get_columns()[i].get_column_type() {
+                                        ColumnType::Date => v.to_date_string(),
+                                        _ => v.to_string(),
+                                    };
+                                    let string_value = Some(builder.create_string(&v));
                                     HttpColumnValue::create(
                                         &mut builder,
                                         &HttpColumnValueArgs { string_value },
@@ -497,6 +730,8 @@ impl HttpMessage {
                             &HttpResultSetArgs {
                                 columns: Some(columns_vec),
                                 rows,
+                                arrow_ipc: None,
+                                has_more: *has_more,
                             },
                         )
                         .as_union_value(),
@@ -520,8 +755,13 @@ impl HttpMessage {
                     HttpCommand::Query {
                         query: query.query().unwrap().to_string(),
                         trace_obj: query.trace_obj().map(|q| q.to_string()),
+                        result_format: query.result_format(),
+                        stream: query.stream(),
                     }
                 }
+                crate::codegen::http_message_generated::HttpCommand::HttpCancel => {
+                    HttpCommand::Cancel
+                }
                 command => {
                     return Err(CubeError::internal(format!(
                         "Unexpected command: {:?}",
@@ -532,3 +772,122 @@ impl HttpMessage {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metastore::Column;
+    use crate::table::Row;
+
+    #[test]
+    fn arrow_result_format() {
+        let query = HttpMessage {
+            message_id: 1,
+            command: HttpCommand::Query {
+                query: "SELECT 1".to_string(),
+                trace_obj: None,
+                result_format: HttpResultFormat::ArrowIpc,
+                stream: false,
+            },
+        };
+        let query = HttpMessage::read(query.bytes()).unwrap();
+        assert_eq!(query.message_id, 1);
+        match query.command {
+            HttpCommand::Query { result_format, .. } => {
+                assert_eq!(result_format, HttpResultFormat::ArrowIpc)
+            }
+            c => panic!("unexpected command: {:?}", c),
+        }
+
+        let result = HttpMessage {
+            message_id: 1,
+            command: HttpCommand::ArrowResultSet {
+                columns: vec!["a".to_string()],
+                arrow_ipc: vec![1, 2, 3],
+                has_more: false,
+            },
+        }
+        .bytes();
+        let result = get_root_as_http_message(&result)
+            .command_as_http_result_set()
+            .unwrap();
+        assert_eq!(result.columns().unwrap().get(0), "a");
+        assert!(result.rows().is_none());
+        assert_eq!(result.arrow_ipc(), Some(&[1u8, 2, 3][..]));
+    }
+
+    #[test]
+    fn streamed_result_messages() {
+        let query = HttpMessage {
+            message_id: 2,
+            command: HttpCommand::Query {
+                query: "SELECT 1".to_string(),
+                trace_obj: None,
+                result_format: HttpResultFormat::Rows,
+                stream: true,
+            },
+        };
+        match HttpMessage::read(query.bytes()).unwrap().command {
+            HttpCommand::Query { stream, .. } => assert!(stream),
+            c => panic!("unexpected command: {:?}", c),
+        }
+
+        let cancel = HttpMessage {
+            message_id: 2,
+            command: HttpCommand::Cancel,
+        };
+        let cancel = HttpMessage::read(cancel.bytes()).unwrap();
+        assert_eq!(cancel.message_id, 2);
+        assert!(matches!(cancel.command, HttpCommand::Cancel));
+
+        let columns = vec![Column::new("a".to_string(), ColumnType::Int, 0)];
+        let chunk = HttpMessage {
+            message_id: 2,
+            command: HttpCommand::ResultSet {
+                data_frame: Arc::new(DataFrame::new(
+                    columns.clone(),
+                    vec![Row::new(vec![TableValue::Int(1)])],
+                )),
+                has_more: true,
+            },
+        };
+        assert!(chunk.has_more());
+        let chunk = chunk.bytes();
+        let chunk = get_root_as_http_message(&chunk)
+            .command_as_http_result_set()
+            .unwrap();
+        assert!(chunk.has_more());
+        assert_eq!(chunk.rows().unwrap().len(), 1);
+
+        let last = HttpMessage {
+            message_id: 2,
+            command: HttpCommand::ResultSet {
+                data_frame: Arc::new(DataFrame::new(columns, Vec::new())),
+                has_more: false,
+            },
+        };
+        assert!(!last.has_more());
+        let last = last.bytes();
+        let last = get_root_as_http_message(&last)
+            .command_as_http_result_set()
+            .unwrap();
+        assert!(!last.has_more());
+        assert_eq!(last.columns().unwrap().get(0), "a");
+        assert_eq!(last.rows().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn running_query_finishes_on_drop() {
+        let (finished_tx, mut finished_rx) = mpsc::unbounded_channel();
+        let query = RunningQuery {
+            message_id: 3,
+            seq: 1,
+            cancel_token: CancellationToken::new(),
+            finished: finished_tx,
+        };
+        assert!(finished_rx.try_recv().is_err());
+        // E.g. the query was dropped from the queue on shutdown.
+        drop(query);
+        assert_eq!(finished_rx.try_recv().unwrap(), (3, 1));
+    }
+}
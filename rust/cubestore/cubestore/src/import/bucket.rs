@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use regex::Regex;
+
+use crate::remotefs::gcs::GCSRemoteFs;
+use crate::remotefs::minio::MINIORemoteFs;
+use crate::remotefs::s3::S3RemoteFs;
+use crate::remotefs::{RemoteFile, RemoteFs};
+use crate::CubeError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BucketProvider {
+    S3,
+    GCS,
+    MINIO,
+}
+
+impl BucketProvider {
+    fn scheme(&self) -> &'static str {
+        match self {
+            BucketProvider::S3 => "s3",
+            BucketProvider::GCS => "gs",
+            BucketProvider::MINIO => "minio",
+        }
+    }
+
+    fn split_scheme(location: &str) -> Option<(BucketProvider, &str)> {
+        for provider in &[
+            BucketProvider::S3,
+            BucketProvider::GCS,
+            BucketProvider::MINIO,
+        ] {
+            if let Some(path) = location.strip_prefix(&format!("{}://", provider.scheme())) {
+                return Some((*provider, path));
+            }
+        }
+        None
+    }
+}
+
+/// Import location which points into an S3, GCS or MinIO bucket, e.g. `s3://bucket/dir/file.csv`.
+/// Key can be a prefix (`s3://bucket/dir/`) or a glob (`gs://bucket/dir/*.csv.gz`) which is
+/// expanded into separate locations before the table is created.
+/// Credentials are taken from the same environment variables the corresponding remote fs uses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketLocation {
+    provider: BucketProvider,
+    bucket: String,
+    key: String,
+}
+
+impl BucketLocation {
+    pub fn is_bucket_location(location: &str) -> bool {
+        BucketProvider::split_scheme(location).is_some()
+    }
+
+    pub fn parse(location: &str) -> Result<BucketLocation, CubeError> {
+        let (provider, path) = BucketProvider::split_scheme(location)
+            .ok_or_else(|| CubeError::internal(format!("Not a bucket location: {}", location)))?;
+        let (bucket, key) = match path.find('/') {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => (path, ""),
+        };
+        if bucket.is_empty() {
+            return Err(CubeError::user(format!(
+                "Bucket name is missing in location '{}'",
+                location
+            )));
+        }
+        Ok(BucketLocation {
+            provider,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    }
+
+    pub fn location(&self) -> String {
+        format!("{}://{}/{}", self.provider.scheme(), self.bucket, self.key)
+    }
+
+    pub fn is_pattern(&self) -> bool {
+        self.key.is_empty() || self.key.ends_with('/') || self.key.contains(is_glob_char)
+    }
+
+    /// Lists files matching a prefix or glob location. Plain file locations are returned as is.
+    pub async fn expand(&self, cache: &BucketRemoteFsCache) -> Result<Vec<String>, CubeError> {
+        if !self.is_pattern() {
+            return Ok(vec![self.location()]);
+        }
+        let pattern_start = self.key.find(is_glob_char).unwrap_or(self.key.len());
+        let (sub_path, pattern) = match self.key[..pattern_start].rfind('/') {
+            Some(i) => (Some(&self.key[..i]), &self.key[i + 1..]),
+            None => (None, self.key.as_str()),
+        };
+        let pattern = if pattern.is_empty() { "**" } else { pattern };
+        let regex = glob_regex(pattern);
+
+        let remote_fs = cache.get(self, sub_path.map(|p| p.to_string()))?;
+        let mut keys = remote_fs
+            .list("")
+            .await?
+            .into_iter()
+            .filter(|p| !p.ends_with('/') && regex.is_match(p))
+            .map(|p| match sub_path {
+                Some(sub_path) => format!("{}/{}", sub_path, p),
+                None => p,
+            })
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return Err(CubeError::user(format!(
+                "No files found for location '{}'",
+                self.location()
+            )));
+        }
+        keys.sort();
+        Ok(keys
+            .into_iter()
+            .map(|key| BucketLocation {
+                provider: self.provider,
+                bucket: self.bucket.clone(),
+                key,
+            })
+            .map(|l| l.location())
+            .collect())
+    }
+
+    pub async fn file_size(&self, cache: &BucketRemoteFsCache) -> Result<Option<u64>, CubeError> {
+        let (sub_path, name) = self.split_key();
+        let remote_fs = cache.get(self, sub_path)?;
+        Ok(remote_fs
+            .list_with_metadata(&name)
+            .await?
+            .iter()
+            .find(|f| f.remote_path() == name)
+            .map(RemoteFile::file_size))
+    }
+
+    /// Downloads the file to [target], which must be unique for every import. Remote fs downloads
+    /// into a path shared by all imports of the file, so concurrent downloads of the same file
+    /// are done one after another.
+    pub async fn download(
+        &self,
+        cache: &BucketRemoteFsCache,
+        target: &Path,
+    ) -> Result<(), CubeError> {
+        if self.is_pattern() {
+            return Err(CubeError::internal(format!(
+                "Trying to download unexpanded location: {}",
+                self.location()
+            )));
+        }
+        let (sub_path, name) = self.split_key();
+        let remote_fs = cache.get(self, sub_path)?;
+        let location = self.location();
+        let lock = cache
+            .downloads
+            .lock()
+            .unwrap()
+            .entry(location.clone())
+            .or_default()
+            .clone();
+        let res = {
+            let _guard = lock.lock().await;
+            match remote_fs.download_file(&name, None).await {
+                Ok(downloaded) => tokio::fs::rename(&downloaded, target)
+                    .await
+                    .map_err(|e| e.into()),
+                Err(e) => Err(e),
+            }
+        };
+        let mut downloads = cache.downloads.lock().unwrap();
+        // Only the map and this download hold the lock.
+        if Arc::strong_count(&lock) == 2 {
+            downloads.remove(&location);
+        }
+        res
+    }
+
+    fn split_key(&self) -> (Option<String>, String) {
+        match self.key.rfind('/') {
+            Some(i) => (
+                Some(self.key[..i].to_string()),
+                self.key[i + 1..].to_string(),
+            ),
+            None => (None, self.key.to_string()),
+        }
+    }
+}
+
+type RemoteFsFactory = dyn Fn(BucketProvider, &str, Option<&str>, PathBuf) -> Result<Arc<dyn RemoteFs>, CubeError>
+    + Send
+    + Sync;
+
+type RemoteFsKey = (BucketProvider, String, Option<String>);
+
+/// Remote fs instances used by bucket imports, one per bucket directory. They are reused, as each
+/// S3 one runs its own credentials refresh thread. At most [MAX_CACHED_REMOTE_FS] of them are
+/// kept, the refresh thread stops once an evicted instance is no longer used.
+pub struct BucketRemoteFsCache {
+    dir: PathBuf,
+    factory: Box<RemoteFsFactory>,
+    /// Instances with the tick of their last use.
+    remote_fs: Mutex<(HashMap<RemoteFsKey, (Arc<dyn RemoteFs>, u64)>, u64)>,
+    /// Locations being downloaded.
+    downloads: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+const MAX_CACHED_REMOTE_FS: usize = 16;
+
+impl BucketRemoteFsCache {
+    /// Downloaded files are kept under `dir` until the import moves them away.
+    pub fn new(dir: PathBuf) -> BucketRemoteFsCache {
+        Self::with_factory(dir, Box::new(create_remote_fs))
+    }
+
+    pub fn with_factory(dir: PathBuf, factory: Box<RemoteFsFactory>) -> BucketRemoteFsCache {
+        BucketRemoteFsCache {
+            dir,
+            factory,
+            remote_fs: Mutex::new((HashMap::new(), 0)),
+            downloads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(
+        &self,
+        location: &BucketLocation,
+        sub_path: Option<String>,
+    ) -> Result<Arc<dyn RemoteFs>, CubeError> {
+        let key = (location.provider, location.bucket.clone(), sub_path);
+        let mut guard = self.remote_fs.lock().unwrap();
+        let (remote_fs, tick) = &mut *guard;
+        *tick += 1;
+        if let Some((fs, last_used)) = remote_fs.get_mut(&key) {
+            *last_used = *tick;
+            return Ok(fs.clone());
+        }
+        if remote_fs.len() >= MAX_CACHED_REMOTE_FS {
+            let evicted = remote_fs
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(k, _)| k.clone())
+                .unwrap();
+            remote_fs.remove(&evicted);
+        }
+        let mut dir = self
+            .dir
+            .join(location.provider.scheme())
+            .join(&location.bucket);
+        if let Some(sub_path) = &key.2 {
+            dir = dir.join(sub_path);
+        }
+        let fs = (self.factory)(key.0, &key.1, key.2.as_deref(), dir)?;
+        remote_fs.insert(key, (fs.clone(), *tick));
+        Ok(fs)
+    }
+}
+
+fn create_remote_fs(
+    provider: BucketProvider,
+    bucket: &str,
+    sub_path: Option<&str>,
+    dir: PathBuf,
+) -> Result<Arc<dyn RemoteFs>, CubeError> {
+    let bucket_name = bucket.to_string();
+    let sub_path = sub_path.map(|p| p.to_string());
+    let remote_fs: Arc<dyn RemoteFs> = match provider {
+        BucketProvider::S3 => {
+            let region = env::var("CUBESTORE_S3_REGION").map_err(|_| {
+                CubeError::user(format!(
+                    "CUBESTORE_S3_REGION is required to import from '{}://{}'",
+                    provider.scheme(),
+                    bucket
+                ))
+            })?;
+            S3RemoteFs::new(dir, region, bucket_name, sub_path)?
+        }
+        BucketProvider::GCS => GCSRemoteFs::new(dir, bucket_name, sub_path)?,
+        BucketProvider::MINIO => MINIORemoteFs::new(dir, bucket_name, sub_path)?,
+    };
+    Ok(remote_fs)
+}
+
+fn is_glob_char(c: char) -> bool {
+    c == '*' || c == '?'
+}
+
+/// `*` and `?` match within a single path segment, `**` matches across segments.
+fn glob_regex(pattern: &str) -> Regex {
+    let mut res = "^".to_string();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                res.push_str(".*");
+            }
+            '*' => res.push_str("[^/]*"),
+            '?' => res.push_str("[^/]"),
+            c => res.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    res.push('$');
+    Regex::new(&res).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remotefs::LocalDirRemoteFs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn parse_bucket_location() {
+        let l = BucketLocation::parse("s3://bucket/dir/file.csv.gz").unwrap();
+        assert_eq!(l.provider, BucketProvider::S3);
+        assert_eq!(l.bucket, "bucket");
+        assert_eq!(l.key, "dir/file.csv.gz");
+        assert!(!l.is_pattern());
+        assert_eq!(l.location(), "s3://bucket/dir/file.csv.gz");
+
+        let l = BucketLocation::parse("gs://bucket/dir/").unwrap();
+        assert_eq!(l.provider, BucketProvider::GCS);
+        assert!(l.is_pattern());
+
+        let l = BucketLocation::parse("minio://bucket/dir/*.csv").unwrap();
+        assert_eq!(l.provider, BucketProvider::MINIO);
+        assert!(l.is_pattern());
+
+        assert!(BucketLocation::parse("s3://bucket").unwrap().is_pattern());
+        assert!(BucketLocation::parse("s3:///file.csv").is_err());
+        assert!(!BucketLocation::is_bucket_location(
+            "http://bucket/file.csv"
+        ));
+        assert!(!BucketLocation::is_bucket_location("temp://file.csv"));
+    }
+
+    #[test]
+    fn glob_matching() {
+        let r = glob_regex("*.csv");
+        assert!(r.is_match("a.csv"));
+        assert!(!r.is_match("a.csv.gz"));
+        assert!(!r.is_match("nested/a.csv"));
+
+        let r = glob_regex("**/part-?.csv");
+        assert!(r.is_match("2021/01/part-1.csv"));
+        assert!(!r.is_match("2021/01/part-10.csv"));
+
+        let r = glob_regex("**");
+        assert!(r.is_match("a/b/c.csv"));
+    }
+
+    #[tokio::test]
+    async fn reuses_remote_fs() {
+        let root = tempfile::tempdir().unwrap();
+        let buckets = root.path().join("buckets");
+        for file in &["dir/a.csv", "dir/b.csv", "dir/c.json", "top.csv"] {
+            let path = buckets.join("bucket").join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, file).unwrap();
+        }
+
+        let created = Arc::new(AtomicUsize::new(0));
+        let created_to_move = created.clone();
+        let cache = BucketRemoteFsCache::with_factory(
+            root.path().join("local"),
+            Box::new(
+                move |_: BucketProvider,
+                      bucket: &str,
+                      sub_path: Option<&str>,
+                      dir: PathBuf|
+                      -> Result<Arc<dyn RemoteFs>, CubeError> {
+                    created_to_move.fetch_add(1, Ordering::SeqCst);
+                    let mut remote_dir = buckets.join(bucket);
+                    if let Some(sub_path) = sub_path {
+                        remote_dir = remote_dir.join(sub_path);
+                    }
+                    Ok(LocalDirRemoteFs::new(Some(remote_dir), dir))
+                },
+            ),
+        );
+
+        let files = BucketLocation::parse("s3://bucket/dir/*.csv")
+            .unwrap()
+            .expand(&cache)
+            .await
+            .unwrap();
+        assert_eq!(
+            files,
+            vec!["s3://bucket/dir/a.csv", "s3://bucket/dir/b.csv"]
+        );
+        for file in files.iter() {
+            let location = BucketLocation::parse(file).unwrap();
+            assert_eq!(location.file_size(&cache).await.unwrap(), Some(9));
+            // Concurrent imports of the same file get their own copies.
+            let targets = (0..2)
+                .map(|i| root.path().join(format!("import-{}", i)))
+                .collect::<Vec<_>>();
+            let results =
+                futures::future::join_all(targets.iter().map(|t| location.download(&cache, t)))
+                    .await;
+            for (target, res) in targets.iter().zip(results) {
+                res.unwrap();
+                assert_eq!(std::fs::read_to_string(target).unwrap(), location.key);
+                std::fs::remove_file(target).unwrap();
+            }
+        }
+        assert!(cache.downloads.lock().unwrap().is_empty());
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+
+        let location = BucketLocation::parse("s3://bucket/top.csv").unwrap();
+        assert_eq!(location.file_size(&cache).await.unwrap(), Some(7));
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+        let location = BucketLocation::parse("gs://bucket/top.csv").unwrap();
+        assert_eq!(location.file_size(&cache).await.unwrap(), Some(7));
+        assert_eq!(created.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn evicts_remote_fs() {
+        let root = tempfile::tempdir().unwrap();
+        let created = Arc::new(AtomicUsize::new(0));
+        let created_to_move = created.clone();
+        let remote_dir = root.path().join("remote");
+        let cache = BucketRemoteFsCache::with_factory(
+            root.path().join("local"),
+            Box::new(
+                move |_: BucketProvider,
+                      _: &str,
+                      _: Option<&str>,
+                      dir: PathBuf|
+                      -> Result<Arc<dyn RemoteFs>, CubeError> {
+                    created_to_move.fetch_add(1, Ordering::SeqCst);
+                    Ok(LocalDirRemoteFs::new(Some(remote_dir.clone()), dir))
+                },
+            ),
+        );
+        let location =
+            |i: usize| BucketLocation::parse(&format!("s3://bucket-{}/a.csv", i)).unwrap();
+        let first = cache.get(&location(0), None).unwrap();
+        for i in 1..=MAX_CACHED_REMOTE_FS {
+            cache.get(&location(i), None).unwrap();
+        }
+        assert_eq!(
+            cache.remote_fs.lock().unwrap().0.len(),
+            MAX_CACHED_REMOTE_FS
+        );
+        assert_eq!(created.load(Ordering::SeqCst), MAX_CACHED_REMOTE_FS + 1);
+        // The least recently used one is evicted and dropped once nobody uses it.
+        let first_weak = Arc::downgrade(&first);
+        drop(first);
+        assert!(first_weak.upgrade().is_none());
+        cache.get(&location(MAX_CACHED_REMOTE_FS), None).unwrap();
+        assert_eq!(created.load(Ordering::SeqCst), MAX_CACHED_REMOTE_FS + 1);
+    }
+}
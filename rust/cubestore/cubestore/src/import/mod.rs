@@ -1,5 +1,6 @@
 use core::mem;
 use core::slice::memchr;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::path::Path;
 use std::pin::Pin;
@@ -20,20 +21,22 @@ use num::ToPrimitive;
 use pin_project_lite::pin_project;
 use tempfile::TempPath;
 use tokio::fs::File;
-use tokio::io::{AsyncBufRead, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
 use tokio::task::JoinHandle;
+use tokio_stream::wrappers::LinesStream;
 
 use cubehll::HllSketch;
 
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
+use crate::import::bucket::{BucketLocation, BucketRemoteFsCache};
 use crate::import::limits::ConcurrencyLimits;
 use crate::metastore::table::Table;
 use crate::metastore::{is_valid_plain_binary_hll, HllFlavour, IdRow};
 use crate::metastore::{Column, ColumnType, ImportFormat, MetaStore};
 use crate::remotefs::RemoteFs;
-use crate::sql::timestamp_from_string;
-use crate::store::ChunkDataStore;
+use crate::sql::{date_from_string, timestamp_from_string};
+use crate::store::{fill_deleted_column, ChunkDataStore};
 use crate::streaming::StreamingService;
 use crate::table::data::{append_row, create_array_builders};
 use crate::table::{Row, TableValue};
@@ -42,7 +45,9 @@ use crate::util::maybe_owned::MaybeOwnedStr;
 use crate::CubeError;
 use datafusion::cube_ext::ordfloat::OrdF64;
 
+pub mod bucket;
 pub mod limits;
+pub mod parquet;
 
 impl ImportFormat {
     async fn row_stream(
@@ -54,13 +59,9 @@ impl ImportFormat {
         match self {
             ImportFormat::CSV | ImportFormat::CSVNoHeader => {
                 let lines_stream: Pin<Box<dyn Stream<Item = Result<String, CubeError>> + Send>> =
-                    if location.contains(".gz") {
-                        let reader = BufReader::new(GzipDecoder::new(BufReader::new(file)));
-                        Box::pin(CsvLineStream::new(reader))
-                    } else {
-                        let reader = BufReader::new(file);
-                        Box::pin(CsvLineStream::new(reader))
-                    };
+                    Box::pin(CsvLineStream::new(ImportFormat::file_reader(
+                        file, &location,
+                    )));
 
                 let mut header_mapping = match self {
                     ImportFormat::CSV => None,
@@ -71,6 +72,12 @@ impl ImportFormat {
                             .map(|(i, c)| (i, c.clone()))
                             .collect(),
                     ),
+                    ImportFormat::JsonLines | ImportFormat::Parquet => {
+                        return Err(CubeError::internal(format!(
+                            "{:?} is not a CSV format",
+                            self
+                        )))
+                    }
                 };
 
                 let rows = lines_stream.map(move |line| -> Result<Option<Row>, CubeError> {
@@ -138,9 +145,90 @@ impl ImportFormat {
                 });
                 Ok(rows.boxed())
             }
+            ImportFormat::JsonLines => {
+                let lines_stream =
+                    LinesStream::new(ImportFormat::file_reader(file, &location).lines());
+                let column_positions: HashMap<String, usize> = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| (c.get_name().to_string(), i))
+                    .collect();
+
+                let rows = lines_stream.map(move |line| -> Result<Option<Row>, CubeError> {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        return Ok(None);
+                    }
+                    let object = match serde_json::from_str(&line)? {
+                        serde_json::Value::Object(object) => object,
+                        x => {
+                            return Err(CubeError::user(format!(
+                                "JSON object is expected for each line but found: {}",
+                                x
+                            )))
+                        }
+                    };
+
+                    let mut row = vec![TableValue::Null; columns.len()];
+                    for (name, value) in object {
+                        let insert_pos = *column_positions.get(&name).ok_or_else(|| {
+                            CubeError::user(format!(
+                                "Column '{}' is not found during import in {:?}",
+                                name, columns
+                            ))
+                        })?;
+                        let column = &columns[insert_pos];
+                        row[insert_pos] =
+                            ImportFormat::parse_json_value(column, value).map_err(|e| {
+                                CubeError::user(format!(
+                                    "Can't parse column value for '{}' column: {}",
+                                    column.get_name(),
+                                    e
+                                ))
+                            })?;
+                    }
+                    Ok(Some(Row::new(row)))
+                });
+                Ok(rows.boxed())
+            }
+            ImportFormat::Parquet => Err(CubeError::internal(
+                "Parquet import is columnar and doesn't produce row stream".to_string(),
+            )),
+        }
+    }
+
+    fn file_reader(file: File, location: &str) -> Pin<Box<dyn AsyncBufRead + Send>> {
+        if location.contains(".gz") {
+            Box::pin(BufReader::new(GzipDecoder::new(BufReader::new(file))))
+        } else {
+            Box::pin(BufReader::new(file))
         }
     }
 
+    fn parse_json_value(
+        column: &Column,
+        value: serde_json::Value,
+    ) -> Result<TableValue, CubeError> {
+        let value = match value {
+            serde_json::Value::Null => return Ok(TableValue::Null),
+            serde_json::Value::String(s) => s,
+            // Snowflake exports HLL sketches as JSON objects.
+            v @ serde_json::Value::Object(_)
+                if column.get_column_type() == &ColumnType::HyperLogLog(HllFlavour::Snowflake) =>
+            {
+                v.to_string()
+            }
+            v @ (serde_json::Value::Object(_) | serde_json::Value::Array(_)) => {
+                return Err(CubeError::user(format!(
+                    "nested JSON values are not supported: {}",
+                    v
+                )))
+            }
+            v @ (serde_json::Value::Bool(_) | serde_json::Value::Number(_)) => v.to_string(),
+        };
+        ImportFormat::parse_column_value(column, &mut Some(MaybeOwnedStr::Owned(value)))
+    }
+
     fn parse_column_value(
         column: &Column,
         value_buf: &mut Option<MaybeOwnedStr>,
@@ -174,6 +262,14 @@ impl ImportFormat {
             ColumnType::Timestamp => TableValue::Timestamp(timestamp_from_string(value)?),
             ColumnType::Float => TableValue::Float(OrdF64(value.parse::<f64>()?)),
             ColumnType::Boolean => TableValue::Boolean(value.to_lowercase() == "true"),
+            ColumnType::Int16 => TableValue::Int(value.parse::<i16>().map_err(|e| {
+                CubeError::user(format!("Can't parse smallint from, {:?}: {}", value, e))
+            })? as i64),
+            ColumnType::Int32 => TableValue::Int(value.parse::<i32>().map_err(|e| {
+                CubeError::user(format!("Can't parse int32 from, {:?}: {}", value, e))
+            })? as i64),
+            ColumnType::Float32 => TableValue::Float(OrdF64(value.parse::<f32>()? as f64)),
+            ColumnType::Date => TableValue::Timestamp(date_from_string(value)?),
         })
     }
 }
@@ -367,6 +463,8 @@ pub trait ImportService: DIService + Send + Sync {
     async fn import_table(&self, table_id: u64) -> Result<(), CubeError>;
     async fn import_table_part(&self, table_id: u64, location: &str) -> Result<(), CubeError>;
     async fn estimate_location_row_count(&self, location: &str) -> Result<u64, CubeError>;
+    /// Replaces prefix and glob bucket locations with the files they match.
+    async fn expand_locations(&self, locations: Vec<String>) -> Result<Vec<String>, CubeError>;
 }
 
 crate::di_service!(MockImportService, [ImportService]);
@@ -378,6 +476,7 @@ pub struct ImportServiceImpl {
     remote_fs: Arc<dyn RemoteFs>,
     config_obj: Arc<dyn ConfigObj>,
     limits: Arc<ConcurrencyLimits>,
+    bucket_remote_fs: BucketRemoteFsCache,
 }
 
 crate::di_service!(ImportServiceImpl, [ImportService]);
@@ -391,6 +490,8 @@ impl ImportServiceImpl {
         config_obj: Arc<dyn ConfigObj>,
         limits: Arc<ConcurrencyLimits>,
     ) -> Arc<ImportServiceImpl> {
+        let bucket_remote_fs =
+            BucketRemoteFsCache::new(config_obj.data_dir().join("tmp").join("buckets"));
         Arc::new(ImportServiceImpl {
             meta_store,
             streaming_service,
@@ -398,6 +499,7 @@ impl ImportServiceImpl {
             remote_fs,
             config_obj,
             limits,
+            bucket_remote_fs,
         })
     }
 
@@ -435,6 +537,11 @@ impl ImportServiceImpl {
                 .update_location_download_size(table_id, location.to_string(), size as u64)
                 .await?;
             Ok((temp_file, None))
+        } else if BucketLocation::is_bucket_location(location) {
+            let (file, path) = self
+                .download_bucket_file(location, table_id, temp_dir)
+                .await?;
+            Ok((file, Some(path)))
         } else {
             Ok((File::open(location.clone()).await?, None))
         }
@@ -447,6 +554,29 @@ impl ImportServiceImpl {
         Ok(File::open(local_file).await?)
     }
 
+    async fn download_bucket_file(
+        &self,
+        location: &str,
+        table_id: u64,
+        temp_dir: &Path,
+    ) -> Result<(File, TempPath), CubeError> {
+        let bucket_location = BucketLocation::parse(location)?;
+        let path = tempfile::Builder::new()
+            .prefix(&table_id.to_string())
+            .tempfile_in(temp_dir)?
+            .into_temp_path();
+        bucket_location
+            .download(&self.bucket_remote_fs, &path)
+            .await?;
+        let file = File::open(&path).await?;
+        let size = file.metadata().await?.len();
+        log::info!("Import downloaded {} ({} bytes)", location, size);
+        self.meta_store
+            .update_location_download_size(table_id, location.to_string(), size)
+            .await?;
+        Ok((file, path))
+    }
+
     fn temp_uploads_path(location: &str) -> String {
         location.replace("temp://", "temp-uploads/")
     }
@@ -473,6 +603,11 @@ impl ImportServiceImpl {
         let (file, tmp_path) = self
             .resolve_location(location.clone(), table.get_id(), &temp_dir)
             .await?;
+        if let ImportFormat::Parquet = format {
+            let res = self.do_import_parquet(table, file).await;
+            mem::drop(tmp_path);
+            return res;
+        }
         let mut row_stream = format
             .row_stream(
                 file,
@@ -516,6 +651,33 @@ impl ImportServiceImpl {
         ingestion.wait_completion().await
     }
 
+    async fn do_import_parquet(&self, table: &IdRow<Table>, file: File) -> Result<(), CubeError> {
+        let file = file.into_std().await;
+        let columns = table.get_row().get_columns().clone();
+        let batch_size = self.config_obj.wal_split_threshold() as usize;
+        // Row groups are decoded on a blocking thread and handed over one by one so only a
+        // bounded number of them stays in memory while ingestion is in progress.
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let reader = cube_ext::spawn_blocking(move || -> Result<(), CubeError> {
+            parquet::read_parquet_file(file, &columns, batch_size, |batch| {
+                tx.blocking_send(batch).is_ok()
+            })
+        });
+
+        let mut ingestion = Ingestion::new(
+            self.meta_store.clone(),
+            self.chunk_store.clone(),
+            self.limits.clone(),
+            table.clone(),
+        );
+        while let Some(batch) = rx.recv().await {
+            ingestion.queue_data_frame(batch).await?;
+        }
+        reader.await??;
+
+        ingestion.wait_completion().await
+    }
+
     fn estimate_rows(location: &str, size: Option<u64>) -> u64 {
         if let Some(size) = size {
             let uncompressed_size = if location.contains(".gz") {
@@ -610,6 +772,11 @@ impl ImportService for ImportServiceImpl {
         } else if location.starts_with("temp://") {
             // TODO do the actual estimation
             Ok(ImportServiceImpl::estimate_rows(location, None))
+        } else if BucketLocation::is_bucket_location(location) {
+            let size = BucketLocation::parse(location)?
+                .file_size(&self.bucket_remote_fs)
+                .await?;
+            Ok(ImportServiceImpl::estimate_rows(location, size))
         } else {
             Ok(ImportServiceImpl::estimate_rows(
                 location,
@@ -617,6 +784,22 @@ impl ImportService for ImportServiceImpl {
             ))
         }
     }
+
+    async fn expand_locations(&self, locations: Vec<String>) -> Result<Vec<String>, CubeError> {
+        let mut res = Vec::with_capacity(locations.len());
+        for location in locations {
+            if BucketLocation::is_bucket_location(&location) {
+                res.extend(
+                    BucketLocation::parse(&location)?
+                        .expand(&self.bucket_remote_fs)
+                        .await?,
+                );
+            } else {
+                res.push(location);
+            }
+        }
+        Ok(res)
+    }
 }
 
 /// Handles row-based data ingestion, e.g. on CSV import and SQL insert.
@@ -625,8 +808,12 @@ pub struct Ingestion {
     chunk_store: Arc<dyn ChunkDataStore>,
     limits: Arc<ConcurrencyLimits>,
     table: IdRow<Table>,
+    /// Chunks are activated together in [Ingestion::wait_completion] instead of one data frame
+    /// at a time.
+    atomic: bool,
 
-    partition_jobs: Vec<JoinHandle<Result<(), CubeError>>>,
+    /// Return ids of chunks that are left to activate.
+    partition_jobs: Vec<JoinHandle<Result<Vec<(u64, Option<u64>)>, CubeError>>>,
 }
 
 impl Ingestion {
@@ -641,19 +828,35 @@ impl Ingestion {
             chunk_store,
             limits,
             table,
+            atomic: false,
             partition_jobs: Vec::new(),
         }
     }
 
+    /// Same as [Ingestion::new], but either all the queued rows become visible or none of them.
+    pub fn new_atomic(
+        meta_store: Arc<dyn MetaStore>,
+        chunk_store: Arc<dyn ChunkDataStore>,
+        limits: Arc<ConcurrencyLimits>,
+        table: IdRow<Table>,
+    ) -> Ingestion {
+        Ingestion {
+            atomic: true,
+            ..Self::new(meta_store, chunk_store, limits, table)
+        }
+    }
+
     pub async fn queue_data_frame(&mut self, rows: Vec<ArrayRef>) -> Result<(), CubeError> {
         let active_data_frame = self.limits.acquire_data_frame().await?;
 
         let meta_store = self.meta_store.clone();
         let chunk_store = self.chunk_store.clone();
         let columns = self.table.get_row().get_columns().clone().clone();
+        let rows = fill_deleted_column(self.table.get_row(), rows);
         let table_id = self.table.get_id();
         // TODO In fact it should be only for inserts. Batch imports should still go straight to disk.
         let in_memory = self.table.get_row().in_memory_ingest();
+        let atomic = self.atomic;
         self.partition_jobs.push(cube_ext::spawn(async move {
             let new_chunks = chunk_store
                 .partition_data(table_id, rows, &columns, in_memory)
@@ -670,15 +873,27 @@ impl Ingestion {
                     Ok((c.get_id(), file_size))
                 })
                 .collect();
-            meta_store.activate_chunks(table_id, new_chunk_ids?).await
+            if atomic {
+                return new_chunk_ids;
+            }
+            meta_store
+                .activate_chunks(table_id, new_chunk_ids?, None)
+                .await?;
+            Ok(Vec::new())
         }));
 
         Ok(())
     }
 
     pub async fn wait_completion(self) -> Result<(), CubeError> {
+        let mut new_chunk_ids = Vec::new();
         for j in self.partition_jobs {
-            j.await??;
+            new_chunk_ids.extend(j.await??);
+        }
+        if !new_chunk_ids.is_empty() {
+            self.meta_store
+                .activate_chunks(self.table.get_id(), new_chunk_ids, None)
+                .await?;
         }
 
         Ok(())
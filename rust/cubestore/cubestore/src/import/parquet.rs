@@ -0,0 +1,403 @@
+use crate::metastore::{is_valid_plain_binary_hll, Column, ColumnType, HllFlavour};
+use crate::table::data::{append_value, create_array_builders};
+use crate::table::{TableValue, TimestampValue};
+use crate::util::decimal::Decimal;
+use crate::CubeError;
+use arrow::array::{
+    Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, Date64Array, DecimalArray,
+    Float32Array, Float64Array, Int16Array, Int32Array, Int64Array, Int8Array, LargeBinaryArray,
+    LargeStringArray, StringArray, TimestampMicrosecondArray, TimestampMillisecondArray,
+    TimestampNanosecondArray, TimestampSecondArray, UInt16Array, UInt32Array, UInt64Array,
+    UInt8Array,
+};
+use arrow::datatypes::{DataType, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use cubehll::HllSketch;
+use datafusion::cube_ext::ordfloat::OrdF64;
+use itertools::Itertools;
+use parquet::arrow::{ArrowReader, ParquetFileArrowReader};
+use parquet::file::reader::SerializedFileReader;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::sync::Arc;
+
+/// Reads Parquet file row group by row group and converts every batch into table columns.
+/// `on_batch` receives columns in table column order and may stop reading by returning `false`.
+pub fn read_parquet_file(
+    file: File,
+    columns: &[Column],
+    batch_size: usize,
+    mut on_batch: impl FnMut(Vec<ArrayRef>) -> bool,
+) -> Result<(), CubeError> {
+    let mut reader = ParquetFileArrowReader::new(Arc::new(SerializedFileReader::new(file)?));
+    let mapping = parquet_column_mapping(&reader.get_schema()?, columns)?;
+    for batch in reader.get_record_reader(batch_size)? {
+        let batch = parquet_batch_to_columns(&batch?, &mapping, columns)?;
+        if !on_batch(batch) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Returns position of Parquet field for every table column.
+pub fn parquet_column_mapping(
+    schema: &Schema,
+    columns: &[Column],
+) -> Result<Vec<usize>, CubeError> {
+    columns
+        .iter()
+        .map(|c| {
+            let (pos, field) = schema
+                .fields()
+                .iter()
+                .find_position(|f| f.name() == c.get_name())
+                .ok_or_else(|| {
+                    CubeError::user(format!(
+                        "Column '{}' is not found during Parquet import in {:?}",
+                        c.get_name(),
+                        schema
+                            .fields()
+                            .iter()
+                            .map(|f| f.name().as_str())
+                            .collect_vec()
+                    ))
+                })?;
+            if !is_compatible_type(field.data_type(), c.get_column_type()) {
+                return Err(CubeError::user(format!(
+                    "Parquet column '{}' of type {:?} can't be imported into column of type {}",
+                    c.get_name(),
+                    field.data_type(),
+                    c.get_column_type()
+                )));
+            }
+            Ok(pos)
+        })
+        .collect()
+}
+
+fn is_compatible_type(data_type: &DataType, column_type: &ColumnType) -> bool {
+    let is_int = matches!(
+        data_type,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+    );
+    match column_type {
+        ColumnType::String => matches!(data_type, DataType::Utf8 | DataType::LargeUtf8),
+        ColumnType::Int | ColumnType::Int16 | ColumnType::Int32 => is_int,
+        ColumnType::Float | ColumnType::Float32 => {
+            is_int || matches!(data_type, DataType::Float32 | DataType::Float64)
+        }
+        ColumnType::Decimal { .. } => {
+            is_int
+                || matches!(
+                    data_type,
+                    DataType::Decimal(_, _) | DataType::Int64Decimal(_)
+                )
+        }
+        ColumnType::Timestamp | ColumnType::Date => matches!(
+            data_type,
+            DataType::Timestamp(_, _) | DataType::Date32 | DataType::Date64
+        ),
+        ColumnType::Boolean => matches!(data_type, DataType::Boolean),
+        ColumnType::HyperLogLog(HllFlavour::Snowflake) => {
+            matches!(data_type, DataType::Utf8 | DataType::LargeUtf8)
+        }
+        ColumnType::Bytes | ColumnType::HyperLogLog(_) => {
+            matches!(data_type, DataType::Binary | DataType::LargeBinary)
+        }
+    }
+}
+
+pub fn parquet_batch_to_columns(
+    batch: &RecordBatch,
+    mapping: &[usize],
+    columns: &[Column],
+) -> Result<Vec<ArrayRef>, CubeError> {
+    let mut builders = create_array_builders(columns);
+    for (i, column) in columns.iter().enumerate() {
+        let array = batch.column(mapping[i]).as_ref();
+        let builder = builders[i].as_mut();
+        for row in 0..batch.num_rows() {
+            let value = parquet_value(array, row, column).map_err(|e| {
+                CubeError::user(format!(
+                    "Can't import Parquet value for '{}' column: {}",
+                    column.get_name(),
+                    e
+                ))
+            })?;
+            append_value(builder, column.get_column_type(), &value);
+        }
+    }
+    Ok(builders.into_iter().map(|mut b| b.finish()).collect())
+}
+
+macro_rules! value {
+    ($array: expr, $row: expr, $array_type: ty) => {
+        $array
+            .as_any()
+            .downcast_ref::<$array_type>()
+            .ok_or_else(|| {
+                CubeError::user(format!(
+                    "Unexpected Parquet array of type {:?}",
+                    $array.data_type()
+                ))
+            })?
+            .value($row)
+    };
+}
+
+fn int_value(array: &dyn Array, row: usize) -> Result<i64, CubeError> {
+    Ok(match array.data_type() {
+        DataType::Int8 => value!(array, row, Int8Array) as i64,
+        DataType::Int16 => value!(array, row, Int16Array) as i64,
+        DataType::Int32 => value!(array, row, Int32Array) as i64,
+        DataType::Int64 => value!(array, row, Int64Array),
+        DataType::UInt8 => value!(array, row, UInt8Array) as i64,
+        DataType::UInt16 => value!(array, row, UInt16Array) as i64,
+        DataType::UInt32 => value!(array, row, UInt32Array) as i64,
+        DataType::UInt64 => {
+            let v = value!(array, row, UInt64Array);
+            i64::try_from(v).map_err(|_| CubeError::user(format!("{} is out of range", v)))?
+        }
+        x => return Err(CubeError::internal(format!("Unexpected int type: {:?}", x))),
+    })
+}
+
+fn string_value(array: &dyn Array, row: usize) -> Result<&str, CubeError> {
+    Ok(match array.data_type() {
+        DataType::LargeUtf8 => value!(array, row, LargeStringArray),
+        _ => value!(array, row, StringArray),
+    })
+}
+
+fn binary_value(array: &dyn Array, row: usize) -> Result<&[u8], CubeError> {
+    Ok(match array.data_type() {
+        DataType::LargeBinary => value!(array, row, LargeBinaryArray),
+        _ => value!(array, row, BinaryArray),
+    })
+}
+
+fn parquet_value(array: &dyn Array, row: usize, column: &Column) -> Result<TableValue, CubeError> {
+    if !array.is_valid(row) {
+        return Ok(TableValue::Null);
+    }
+    Ok(match column.get_column_type() {
+        ColumnType::String => TableValue::String(string_value(array, row)?.to_string()),
+        ColumnType::Int => TableValue::Int(int_value(array, row)?),
+        ColumnType::Int16 => {
+            let v = int_value(array, row)?;
+            i16::try_from(v)
+                .map_err(|_| CubeError::user(format!("{} is out of smallint range", v)))?;
+            TableValue::Int(v)
+        }
+        ColumnType::Int32 => {
+            let v = int_value(array, row)?;
+            i32::try_from(v)
+                .map_err(|_| CubeError::user(format!("{} is out of int32 range", v)))?;
+            TableValue::Int(v)
+        }
+        ColumnType::Float | ColumnType::Float32 => {
+            TableValue::Float(OrdF64(match array.data_type() {
+                DataType::Float32 => value!(array, row, Float32Array) as f64,
+                DataType::Float64 => value!(array, row, Float64Array),
+                _ => int_value(array, row)? as f64,
+            }))
+        }
+        ColumnType::Boolean => TableValue::Boolean(value!(array, row, BooleanArray)),
+        t @ ColumnType::Decimal { .. } => {
+            let target_scale = t.target_scale() as u32;
+            let (raw_value, scale) = match array.data_type() {
+                DataType::Decimal(_, scale) => (value!(array, row, DecimalArray), *scale as u32),
+                DataType::Int64Decimal(scale) => match TableValue::from_array(array, row) {
+                    TableValue::Decimal(d) => (d.raw_value() as i128, *scale as u32),
+                    x => {
+                        return Err(CubeError::internal(format!(
+                            "Unexpected decimal value: {:?}",
+                            x
+                        )))
+                    }
+                },
+                _ => (int_value(array, row)? as i128, 0),
+            };
+            TableValue::Decimal(Decimal::new(rescale_decimal(
+                raw_value,
+                scale,
+                target_scale,
+            )?))
+        }
+        t @ (ColumnType::Timestamp | ColumnType::Date) => {
+            let nanos = match array.data_type() {
+                DataType::Timestamp(TimeUnit::Second, _) => {
+                    value!(array, row, TimestampSecondArray).checked_mul(1_000_000_000)
+                }
+                DataType::Timestamp(TimeUnit::Millisecond, _) => {
+                    value!(array, row, TimestampMillisecondArray).checked_mul(1_000_000)
+                }
+                DataType::Timestamp(TimeUnit::Microsecond, _) => {
+                    value!(array, row, TimestampMicrosecondArray).checked_mul(1_000)
+                }
+                DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+                    Some(value!(array, row, TimestampNanosecondArray))
+                }
+                DataType::Date32 => {
+                    (value!(array, row, Date32Array) as i64).checked_mul(86_400_000_000_000)
+                }
+                DataType::Date64 => value!(array, row, Date64Array).checked_mul(1_000_000),
+                x => {
+                    return Err(CubeError::internal(format!(
+                        "Unexpected timestamp type: {:?}",
+                        x
+                    )))
+                }
+            };
+            let ts = TimestampValue::new(
+                nanos.ok_or_else(|| CubeError::user("timestamp is out of range".to_string()))?,
+            );
+            if let ColumnType::Date = t {
+                TableValue::Timestamp(TimestampValue::from_days(ts.days()))
+            } else {
+                TableValue::Timestamp(ts)
+            }
+        }
+        ColumnType::Bytes => TableValue::Bytes(binary_value(array, row)?.to_vec()),
+        ColumnType::HyperLogLog(HllFlavour::Snowflake) => {
+            TableValue::Bytes(HllSketch::read_snowflake(string_value(array, row)?)?.write())
+        }
+        ColumnType::HyperLogLog(HllFlavour::Postgres) => {
+            TableValue::Bytes(HllSketch::read_hll_storage_spec(binary_value(array, row)?)?.write())
+        }
+        ColumnType::HyperLogLog(f @ (HllFlavour::Airlift | HllFlavour::ZetaSketch)) => {
+            let data = binary_value(array, row)?;
+            is_valid_plain_binary_hll(data, *f)?;
+            TableValue::Bytes(data.to_vec())
+        }
+    })
+}
+
+fn rescale_decimal(value: i128, scale: u32, target_scale: u32) -> Result<i64, CubeError> {
+    let rescaled = if scale <= target_scale {
+        10_i128
+            .checked_pow(target_scale - scale)
+            .and_then(|m| value.checked_mul(m))
+    } else {
+        10_i128.checked_pow(scale - target_scale).map(|d| value / d)
+    };
+    rescaled.and_then(|v| i64::try_from(v).ok()).ok_or_else(|| {
+        CubeError::user(format!(
+            "cannot represent decimal {} with scale {} as scale {} without loosing precision",
+            value, scale, target_scale
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::Field;
+    use parquet::arrow::ArrowWriter;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn parquet_types() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("amount", DataType::Int64, true),
+            Field::new("t", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+            Field::new("extra", DataType::Boolean, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), Some(2), None])),
+                Arc::new(StringArray::from(vec![Some("a"), None, Some("c")])),
+                Arc::new(Int64Array::from(vec![Some(5), Some(-3), Some(0)])),
+                Arc::new(TimestampMicrosecondArray::from(vec![
+                    Some(1_000_000),
+                    None,
+                    Some(3),
+                ])),
+                Arc::new(BooleanArray::from(vec![true, false, true])),
+            ],
+        )
+        .unwrap();
+
+        let file = NamedTempFile::new().unwrap();
+        let mut writer =
+            ArrowWriter::try_new(file.reopen().unwrap(), schema.clone(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let columns = vec![
+            Column::new("t".to_string(), ColumnType::Timestamp, 0),
+            Column::new("id".to_string(), ColumnType::Int, 1),
+            Column::new(
+                "amount".to_string(),
+                ColumnType::Decimal {
+                    scale: 2,
+                    precision: 10,
+                },
+                2,
+            ),
+            Column::new("name".to_string(), ColumnType::String, 3),
+        ];
+
+        let mut batches = Vec::new();
+        read_parquet_file(file.reopen().unwrap(), &columns, 1024, |b| {
+            batches.push(b);
+            true
+        })
+        .unwrap();
+
+        assert_eq!(batches.len(), 1);
+        let rows = (0..3)
+            .map(|i| TableValue::from_columns(&batches[0], i))
+            .collect_vec();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    TableValue::Timestamp(TimestampValue::new(1_000_000_000)),
+                    TableValue::Int(1),
+                    TableValue::Decimal(Decimal::new(500)),
+                    TableValue::String("a".to_string()),
+                ],
+                vec![
+                    TableValue::Null,
+                    TableValue::Int(2),
+                    TableValue::Decimal(Decimal::new(-300)),
+                    TableValue::Null,
+                ],
+                vec![
+                    TableValue::Timestamp(TimestampValue::new(3_000)),
+                    TableValue::Null,
+                    TableValue::Decimal(Decimal::new(0)),
+                    TableValue::String("c".to_string()),
+                ],
+            ]
+        );
+
+        let incompatible = vec![Column::new("name".to_string(), ColumnType::Int, 0)];
+        let err = parquet_column_mapping(&schema, &incompatible).unwrap_err();
+        assert!(err.message.contains("can't be imported"), "{}", err);
+
+        let missing = vec![Column::new("missing".to_string(), ColumnType::Int, 0)];
+        let err = parquet_column_mapping(&schema, &missing).unwrap_err();
+        assert!(err.message.contains("is not found"), "{}", err);
+    }
+
+    #[test]
+    fn decimal_rescale() {
+        assert_eq!(rescale_decimal(12345, 3, 5).unwrap(), 1234500);
+        assert_eq!(rescale_decimal(12345, 3, 2).unwrap(), 1234);
+        assert_eq!(rescale_decimal(-12345, 0, 0).unwrap(), -12345);
+        assert!(rescale_decimal(i64::MAX as i128, 0, 10).is_err());
+    }
+}
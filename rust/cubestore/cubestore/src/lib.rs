@@ -468,6 +468,18 @@ impl From<json::Error> for CubeError {
     }
 }
 
+impl From<rdkafka::error::KafkaError> for CubeError {
+    fn from(v: rdkafka::error::KafkaError) -> Self {
+        CubeError::from_error(v)
+    }
+}
+
+impl From<avro_rs::Error> for CubeError {
+    fn from(v: avro_rs::Error) -> Self {
+        CubeError::from_error(v)
+    }
+}
+
 impl From<reqwest::header::ToStrError> for CubeError {
     fn from(v: reqwest::header::ToStrError) -> Self {
         CubeError::from_error(v)
@@ -30,6 +30,10 @@ impl Index {
             sort_key_size,
             partition_split_key_size,
             multi_index_id,
+            altered: false,
+            altered_partition_id: 0,
+            altered_chunk_id: 0,
+            building: false,
         })
     }
 
@@ -61,6 +65,67 @@ impl Index {
     pub fn multi_index_id(&self) -> Option<u64> {
         self.multi_index_id
     }
+
+    /// Data files of altered indexes may lack added columns or have old column names.
+    pub fn altered(&self) -> bool {
+        self.altered
+    }
+
+    /// Whether data files of the partition may have columns of the index before the last alter.
+    pub fn is_altered_partition(&self, partition_id: u64) -> bool {
+        self.altered && partition_id <= self.altered_partition_id
+    }
+
+    /// Whether data of the chunk may have columns of the index before the last alter.
+    pub fn is_altered_chunk(&self, chunk_id: u64) -> bool {
+        self.altered && chunk_id <= self.altered_chunk_id
+    }
+
+    /// Marks partitions and chunks with ids up to the given ones as written with old columns.
+    pub fn update_altered(&self, partition_id: u64, chunk_id: u64) -> Index {
+        let mut index = self.clone();
+        index.altered = true;
+        index.altered_partition_id = partition_id;
+        index.altered_chunk_id = chunk_id;
+        index
+    }
+
+    /// Called once all data of the index is rewritten with the current columns.
+    pub fn clear_altered(&self) -> Index {
+        let mut index = self.clone();
+        index.altered = false;
+        index
+    }
+
+    /// Building indexes are filled with existing table data and aren't used by queries yet.
+    pub fn building(&self) -> bool {
+        self.building
+    }
+
+    pub fn update_building(&self, building: bool) -> Index {
+        let mut index = self.clone();
+        index.building = building;
+        index
+    }
+
+    pub fn add_column(&self, column: Column) -> Index {
+        let mut index = self.clone();
+        let column_index = index.columns.len();
+        index.columns.push(column.replace_index(column_index));
+        index.altered = true;
+        index
+    }
+
+    pub fn rename_column(&self, old_name: &str, new_name: &str) -> Index {
+        let mut index = self.clone();
+        for c in index.columns.iter_mut() {
+            if c.get_name() == old_name {
+                *c = c.replace_name(new_name.to_string());
+                index.altered = true;
+            }
+        }
+        index
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
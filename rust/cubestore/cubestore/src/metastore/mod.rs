@@ -16,7 +16,8 @@ use rocksdb::{
     DBIterator, Direction, IteratorMode, MergeOperands, Options, ReadOptions, Snapshot, WriteBatch,
     WriteBatchIterator, DB,
 };
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::hash::{Hash, Hasher};
 use std::{collections::hash_map::DefaultHasher, env, io::Cursor, sync::Arc, time};
 use tokio::fs;
@@ -45,7 +46,7 @@ use crate::util::WorkerLoop;
 use crate::CubeError;
 use arrow::datatypes::TimeUnit::Microsecond;
 use arrow::datatypes::{DataType, Field};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use chunks::ChunkRocksTable;
 use core::{fmt, mem};
 use cubehll::HllSketch;
@@ -66,14 +67,14 @@ use rocksdb::checkpoint::Checkpoint;
 use schema::{SchemaRocksIndex, SchemaRocksTable};
 use smallvec::alloc::fmt::Formatter;
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{Debug, Display};
 use std::mem::take;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Mutex;
 use std::time::{Duration, SystemTime};
-use table::Table;
+use table::{ParquetSettings, StreamOffset, Table};
 use table::{TableRocksIndex, TableRocksTable};
 use tokio::fs::File;
 use tokio::sync::broadcast::Sender;
@@ -294,6 +295,20 @@ impl DataFrameValue<String> for Option<Vec<u64>> {
     }
 }
 
+impl DataFrameValue<String> for ParquetSettings {
+    fn value(v: &Self) -> String {
+        format!("{:?}", v)
+    }
+}
+
+impl DataFrameValue<String> for Option<Vec<StreamOffset>> {
+    fn value(v: &Self) -> String {
+        v.as_ref()
+            .map(|v| format!("{:?}", v))
+            .unwrap_or("NULL".to_string())
+    }
+}
+
 impl DataFrameValue<String> for Option<Row> {
     fn value(v: &Self) -> String {
         v.as_ref()
@@ -353,6 +368,10 @@ pub enum ColumnType {
     Decimal { scale: i32, precision: i32 },
     Float,
     Boolean,
+    Int16,
+    Int32,
+    Float32,
+    Date,
 }
 
 impl Display for ColumnType {
@@ -369,6 +388,10 @@ impl Display for ColumnType {
             ColumnType::Timestamp => "timestamp",
             ColumnType::Float => "float",
             ColumnType::Boolean => "boolean",
+            ColumnType::Int16 => "smallint",
+            ColumnType::Int32 => "int32",
+            ColumnType::Float32 => "float32",
+            ColumnType::Date => "date",
         };
         f.write_str(s)
     }
@@ -442,15 +465,63 @@ impl From<&Column> for parquet::schema::types::Type {
                     .build()
                     .unwrap()
             }
+            ColumnType::Int16 => {
+                types::Type::primitive_type_builder(&column.get_name(), Type::INT32)
+                    .with_converted_type(ConvertedType::INT_16)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()
+                    .unwrap()
+            }
+            ColumnType::Int32 => {
+                types::Type::primitive_type_builder(&column.get_name(), Type::INT32)
+                    .with_converted_type(ConvertedType::INT_32)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()
+                    .unwrap()
+            }
+            ColumnType::Float32 => {
+                types::Type::primitive_type_builder(&column.get_name(), Type::FLOAT)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()
+                    .unwrap()
+            }
+            ColumnType::Date => {
+                types::Type::primitive_type_builder(&column.get_name(), Type::INT32)
+                    .with_converted_type(ConvertedType::DATE)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()
+                    .unwrap()
+            }
         }
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Deserialize, Debug, Eq, PartialEq, Hash)]
 pub struct Column {
     name: String,
     column_type: ColumnType,
     column_index: usize,
+    /// Set for columns added by `ALTER TABLE ... ADD COLUMN ... DEFAULT`.
+    #[serde(default)]
+    default_value: Option<TableValue>,
+}
+
+impl Serialize for Column {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Bincode, used to send plans to worker processes, relies on field positions. So missing
+        // defaults are only omitted in human readable formats, i.e. JSON shown to users.
+        let skip_default = serializer.is_human_readable() && self.default_value.is_none();
+        let mut s = serializer.serialize_struct("Column", if skip_default { 3 } else { 4 })?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("column_type", &self.column_type)?;
+        s.serialize_field("column_index", &self.column_index)?;
+        if skip_default {
+            s.skip_field("default_value")?;
+        } else {
+            s.serialize_field("default_value", &self.default_value)?;
+        }
+        s.end()
+    }
 }
 
 impl Into<Field> for Column {
@@ -474,6 +545,10 @@ impl<'a> Into<Field> for &'a Column {
                 ColumnType::Bytes => DataType::Binary,
                 ColumnType::HyperLogLog(_) => DataType::Binary,
                 ColumnType::Float => DataType::Float64,
+                ColumnType::Int16 => DataType::Int16,
+                ColumnType::Int32 => DataType::Int32,
+                ColumnType::Float32 => DataType::Float32,
+                ColumnType::Date => DataType::Date32,
             },
             true,
         )
@@ -496,6 +571,10 @@ impl fmt::Display for Column {
             ColumnType::HyperLogLog(HllFlavour::Postgres) => "HLL_POSTGRES".to_string(),
             ColumnType::HyperLogLog(HllFlavour::Snowflake) => "HLL_SNOWFLAKE".to_string(),
             ColumnType::Float => "FLOAT".to_string(),
+            ColumnType::Int16 => "SMALLINT".to_string(),
+            ColumnType::Int32 => "INT32".to_string(),
+            ColumnType::Float32 => "FLOAT32".to_string(),
+            ColumnType::Date => "DATE".to_string(),
         };
         f.write_fmt(format_args!("{} {}", self.name, column_type))
     }
@@ -505,6 +584,8 @@ impl fmt::Display for Column {
 pub enum ImportFormat {
     CSV,
     CSVNoHeader,
+    Parquet,
+    JsonLines,
 }
 
 data_frame_from! {
@@ -524,7 +605,16 @@ pub struct Index {
     #[serde(default)]
     partition_split_key_size: Option<u64>,
     #[serde(default)]
-    multi_index_id: Option<u64>
+    multi_index_id: Option<u64>,
+    #[serde(default)]
+    altered: bool,
+    /// Partitions and chunks with ids up to these were created before the last alter.
+    #[serde(default)]
+    altered_partition_id: u64,
+    #[serde(default)]
+    altered_chunk_id: u64,
+    #[serde(default)]
+    building: bool
 }
 }
 
@@ -761,6 +851,8 @@ pub trait MetaStore: DIService + Send + Sync {
         is_ready: bool,
         unique_key_column_names: Option<Vec<String>>,
         partition_split_threshold: Option<u64>,
+        ttl_seconds: Option<u64>,
+        parquet_settings: ParquetSettings,
     ) -> Result<IdRow<Table>, CubeError>;
     async fn table_ready(&self, id: u64, is_ready: bool) -> Result<IdRow<Table>, CubeError>;
     async fn update_location_download_size(
@@ -769,6 +861,47 @@ pub trait MetaStore: DIService + Send + Sync {
         location: String,
         download_size: u64,
     ) -> Result<IdRow<Table>, CubeError>;
+    /// Appends the column to the table and all of its indexes. Existing data is read with the
+    /// column default or NULL.
+    async fn add_column(
+        &self,
+        schema_name: String,
+        table_name: String,
+        column: Column,
+    ) -> Result<IdRow<Table>, CubeError>;
+    /// Adds the hidden column of delete tombstones to a table with a unique key. Does nothing if
+    /// the column is already there.
+    async fn add_deleted_column(
+        &self,
+        schema_name: String,
+        table_name: String,
+    ) -> Result<IdRow<Table>, CubeError>;
+    async fn rename_column(
+        &self,
+        schema_name: String,
+        table_name: String,
+        old_column_name: String,
+        new_column_name: String,
+    ) -> Result<IdRow<Table>, CubeError>;
+    async fn rename_table(
+        &self,
+        schema_name: String,
+        table_name: String,
+        new_table_name: String,
+    ) -> Result<IdRow<Table>, CubeError>;
+    /// Applies `(schema, table, new table name)` renames one after another in a single
+    /// transaction, so names can be reused by later renames of the same call.
+    async fn rename_tables(
+        &self,
+        renames: Vec<(String, String, String)>,
+    ) -> Result<Vec<IdRow<Table>>, CubeError>;
+    /// Exchanges names of two tables of the schema in a single transaction.
+    async fn swap_tables(
+        &self,
+        schema_name: String,
+        table_name: String,
+        other_table_name: String,
+    ) -> Result<Vec<IdRow<Table>>, CubeError>;
     async fn get_table(
         &self,
         schema_name: String,
@@ -835,6 +968,8 @@ pub trait MetaStore: DIService + Send + Sync {
         &self,
         seconds_ago: i64,
     ) -> Result<Vec<IdRow<Partition>>, CubeError>;
+    /// Active partitions of altered indexes with data files written before the alter.
+    async fn get_altered_partitions(&self) -> Result<Vec<IdRow<Partition>>, CubeError>;
 
     fn index_table(&self) -> IndexMetaStoreTable;
     async fn create_index(
@@ -843,6 +978,28 @@ pub trait MetaStore: DIService + Send + Sync {
         table_name: String,
         index_def: IndexDef,
     ) -> Result<IdRow<Index>, CubeError>;
+    /// Creates a building index for a table that already has data. Returns the new index, the
+    /// default index and its active partitions with chunks that should be copied into the new one.
+    async fn create_index_for_backfill(
+        &self,
+        schema_name: String,
+        table_name: String,
+        index_def: IndexDef,
+    ) -> Result<
+        (
+            IdRow<Index>,
+            IdRow<Index>,
+            Vec<(IdRow<Partition>, Vec<IdRow<Chunk>>)>,
+        ),
+        CubeError,
+    >;
+    /// Activates copied chunks and makes the index available for queries.
+    async fn finish_index_backfill(
+        &self,
+        index_id: u64,
+        uploaded_chunk_ids: Vec<(u64, Option<u64>)>,
+    ) -> Result<IdRow<Index>, CubeError>;
+    async fn cancel_index_backfill(&self, index_id: u64) -> Result<(), CubeError>;
     async fn get_default_index(&self, table_id: u64) -> Result<IdRow<Index>, CubeError>;
     async fn get_table_indexes(&self, table_id: u64) -> Result<Vec<IdRow<Index>>, CubeError>;
     async fn get_active_partitions_by_index_id(
@@ -937,10 +1094,12 @@ pub trait MetaStore: DIService + Send + Sync {
         uploaded_ids: Vec<(u64, Option<u64>)>,
         index_count: u64,
     ) -> Result<(), CubeError>;
+    /// Stream offsets, if passed, are stored in the same transaction chunks are activated in.
     async fn activate_chunks(
         &self,
         table_id: u64,
         uploaded_chunk_ids: Vec<(u64, Option<u64>)>,
+        stream_offsets: Option<Vec<StreamOffset>>,
     ) -> Result<(), CubeError>;
     async fn delete_chunk(&self, chunk_id: u64) -> Result<IdRow<Chunk>, CubeError>;
     async fn all_inactive_chunks(&self) -> Result<Vec<IdRow<Chunk>>, CubeError>;
@@ -951,6 +1110,7 @@ pub trait MetaStore: DIService + Send + Sync {
     async fn delete_wal(&self, wal_id: u64) -> Result<(), CubeError>;
     async fn wal_uploaded(&self, wal_id: u64) -> Result<IdRow<WAL>, CubeError>;
     async fn get_wals_for_table(&self, table_id: u64) -> Result<Vec<IdRow<WAL>>, CubeError>;
+    async fn all_wals(&self) -> Result<Vec<IdRow<WAL>>, CubeError>;
 
     async fn all_jobs(&self) -> Result<Vec<IdRow<Job>>, CubeError>;
     async fn add_job(&self, job: Job) -> Result<Option<IdRow<Job>>, CubeError>;
@@ -1178,6 +1338,43 @@ impl MemorySequence {
     }
 }
 
+/// Metastore checkpoint in the remote storage that can be restored with
+/// `CUBESTORE_METASTORE_RESTORE_CHECKPOINT`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetaStoreCheckpoint {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub files: u64,
+    pub size: u64,
+    /// Changes made after the checkpoint, they are not applied on restore.
+    pub log_files: u64,
+    /// Used to load the metastore when there's no local copy.
+    pub current: bool,
+}
+
+/// Remote file with the name of the checkpoint the metastore was last restored from, so restarts
+/// with the same `CUBESTORE_METASTORE_RESTORE_CHECKPOINT` don't restore it again.
+const METASTORE_RESTORED_FILE: &str = "metastore-restored";
+
+fn checkpoint_name(millis: u128) -> String {
+    format!("metastore-{}", millis)
+}
+
+/// Accepts both `metastore-<millis>` and `<millis>`.
+fn parse_checkpoint_name(name: &str) -> Option<u128> {
+    u128::from_str(name.trim_start_matches("metastore-")).ok()
+}
+
+/// Parses `metastore-<millis>` and `metastore-<millis>-logs` directories.
+fn parse_checkpoint_dir(dir: &str) -> Option<(u128, bool)> {
+    let name = dir.strip_prefix("metastore-")?;
+    let (millis, is_logs) = match name.strip_suffix("-logs") {
+        Some(millis) => (millis, true),
+        None => (name, false),
+    };
+    Some((u128::from_str(millis).ok()?, is_logs))
+}
+
 #[derive(Clone)]
 pub struct RocksMetaStore {
     pub db: Arc<DB>,
@@ -1970,9 +2167,9 @@ impl RocksMetaStore {
         listeners: Vec<Sender<MetaStoreEvent>>,
         remote_fs: Arc<dyn RemoteFs>,
         config: Arc<dyn ConfigObj>,
-    ) -> Arc<RocksMetaStore> {
-        let meta_store = RocksMetaStore::with_listener_impl(path, listeners, remote_fs, config);
-        Arc::new(meta_store)
+    ) -> Result<Arc<RocksMetaStore>, CubeError> {
+        let meta_store = RocksMetaStore::with_listener_impl(path, listeners, remote_fs, config)?;
+        Ok(Arc::new(meta_store))
     }
 
     pub fn with_listener_impl(
@@ -1980,13 +2177,13 @@ impl RocksMetaStore {
         listeners: Vec<Sender<MetaStoreEvent>>,
         remote_fs: Arc<dyn RemoteFs>,
         config: Arc<dyn ConfigObj>,
-    ) -> RocksMetaStore {
+    ) -> Result<RocksMetaStore, CubeError> {
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.set_prefix_extractor(rocksdb::SliceTransform::create_fixed_prefix(13));
         opts.set_merge_operator_associative("meta_store merge", meta_store_merge);
 
-        let db = DB::open(&opts, path).unwrap();
+        let db = DB::open(&opts, path)?;
         let db_arc = Arc::new(db);
 
         let (rw_loop_tx, rw_loop_rx) = std::sync::mpsc::sync_channel::<
@@ -2022,14 +2219,14 @@ impl RocksMetaStore {
             rw_loop_tx,
             rw_loop_join_handle: Arc::new(AbortingJoinHandle::new(join_handle)),
         };
-        meta_store
+        Ok(meta_store)
     }
 
     pub fn new(
         path: impl AsRef<Path>,
         remote_fs: Arc<dyn RemoteFs>,
         config: Arc<dyn ConfigObj>,
-    ) -> Arc<RocksMetaStore> {
+    ) -> Result<Arc<RocksMetaStore>, CubeError> {
         Self::with_listener(path, vec![], remote_fs, config)
     }
 
@@ -2054,7 +2251,7 @@ impl RocksMetaStore {
             );
         }
 
-        let meta_store = Self::new(path, remote_fs, config);
+        let meta_store = Self::new(path, remote_fs, config)?;
 
         RocksMetaStore::check_all_indexes(&meta_store).await?;
 
@@ -2066,73 +2263,48 @@ impl RocksMetaStore {
         remote_fs: Arc<dyn RemoteFs>,
         config: Arc<dyn ConfigObj>,
     ) -> Result<Arc<RocksMetaStore>, CubeError> {
+        if let Some(checkpoint) = config.meta_store_restore_checkpoint().clone() {
+            let restored = Self::restore_from_checkpoint(
+                path.as_ref(),
+                &checkpoint,
+                remote_fs.clone(),
+                config.clone(),
+            )
+            .await?;
+            if let Some(meta_store) = restored {
+                return Ok(meta_store);
+            }
+        }
         if !fs::metadata(path.as_ref()).await.is_ok() {
-            let re = Regex::new(r"^metastore-(\d+)").unwrap();
-
-            if remote_fs.list("metastore-current").await?.iter().len() > 0 {
+            if let Some(snapshot) = Self::current_checkpoint(&remote_fs).await? {
                 info!("Downloading remote metastore");
-                let current_metastore_file = remote_fs.local_file("metastore-current").await?;
-                if fs::metadata(current_metastore_file.as_str()).await.is_ok() {
-                    fs::remove_file(current_metastore_file.as_str()).await?;
-                }
-                remote_fs.download_file("metastore-current", None).await?;
-
-                let mut file = File::open(current_metastore_file.as_str()).await?;
-                let mut buffer = Vec::new();
-                tokio::io::AsyncReadExt::read_to_end(&mut file, &mut buffer).await?;
-                let last_metastore_snapshot = {
-                    let parse_result = re
-                        .captures(&String::from_utf8(buffer)?)
-                        .map(|c| c.get(1).unwrap().as_str())
-                        .map(|p| u128::from_str(p));
-                    if let Some(Ok(millis)) = parse_result {
-                        Some(millis)
-                    } else {
-                        None
-                    }
-                };
-
-                if let Some(snapshot) = last_metastore_snapshot {
-                    let to_load = remote_fs.list(&format!("metastore-{}", snapshot)).await?;
-                    let meta_store_path = remote_fs.local_file("metastore").await?;
-                    fs::create_dir_all(meta_store_path.to_string()).await?;
-                    for file in to_load.iter() {
-                        // TODO check file size
-                        remote_fs.download_file(file, None).await?;
-                        let local = remote_fs.local_file(file).await?;
-                        let path = Path::new(&local);
-                        fs::copy(
-                            path,
-                            PathBuf::from(&meta_store_path)
-                                .join(path.file_name().unwrap().to_str().unwrap()),
-                        )
-                        .await?;
-                    }
-
-                    let meta_store = Self::new(path.as_ref(), remote_fs.clone(), config);
-
-                    let logs_to_batch = remote_fs
-                        .list(&format!("metastore-{}-logs", snapshot))
-                        .await?;
-                    for log_file in logs_to_batch.iter() {
-                        let path_to_log = remote_fs.local_file(log_file).await?;
-                        let batch = WriteBatchContainer::read_from_file(&path_to_log).await;
-                        if let Ok(batch) = batch {
-                            let db = meta_store.db.clone();
-                            db.write(batch.write_batch())?;
-                        } else if let Err(e) = batch {
-                            error!(
-                                "Corrupted metastore WAL file. Discarding: {:?} {}",
-                                log_file, e
-                            );
-                            break;
-                        }
+                let meta_store_path = remote_fs.local_file("metastore").await?;
+                Self::download_checkpoint(&remote_fs, snapshot, Path::new(&meta_store_path))
+                    .await?;
+
+                let meta_store = Self::new(path.as_ref(), remote_fs.clone(), config)?;
+
+                let logs_to_batch = remote_fs
+                    .list(&format!("metastore-{}-logs", snapshot))
+                    .await?;
+                for log_file in logs_to_batch.iter() {
+                    let path_to_log = remote_fs.local_file(log_file).await?;
+                    let batch = WriteBatchContainer::read_from_file(&path_to_log).await;
+                    if let Ok(batch) = batch {
+                        let db = meta_store.db.clone();
+                        db.write(batch.write_batch())?;
+                    } else if let Err(e) = batch {
+                        error!(
+                            "Corrupted metastore WAL file. Discarding: {:?} {}",
+                            log_file, e
+                        );
+                        break;
                     }
+                }
 
-                    RocksMetaStore::check_all_indexes(&meta_store).await?;
+                RocksMetaStore::check_all_indexes(&meta_store).await?;
 
-                    return Ok(meta_store);
-                }
+                return Ok(meta_store);
             } else {
                 trace!("Can't find metastore-current in {:?}", remote_fs);
             }
@@ -2147,13 +2319,274 @@ impl RocksMetaStore {
             );
         }
 
-        let meta_store = Self::new(path, remote_fs, config);
+        let meta_store = Self::new(path, remote_fs, config)?;
 
         RocksMetaStore::check_all_indexes(&meta_store).await?;
 
         Ok(meta_store)
     }
 
+    /// Replaces the local metastore with the uploaded [checkpoint]. The checkpoint is downloaded
+    /// and verified next to [path] first, so the local metastore stays untouched if restore fails,
+    /// e.g. because files of active partitions or chunks are missing in [remote_fs]. The replaced
+    /// metastore is kept next to it. Returns `None` if the checkpoint was already restored, as
+    /// recorded in [remote_fs].
+    async fn restore_from_checkpoint(
+        path: &Path,
+        checkpoint: &str,
+        remote_fs: Arc<dyn RemoteFs>,
+        config: Arc<dyn ConfigObj>,
+    ) -> Result<Option<Arc<RocksMetaStore>>, CubeError> {
+        let millis = parse_checkpoint_name(checkpoint).ok_or_else(|| {
+            CubeError::user(format!("Invalid metastore checkpoint: '{}'", checkpoint))
+        })?;
+        let name = checkpoint_name(millis);
+        if Self::read_remote_file(&remote_fs, METASTORE_RESTORED_FILE).await? == Some(name.clone())
+        {
+            info!("Metastore was already restored from {}", name);
+            return Ok(None);
+        }
+        if !Self::list_checkpoints(remote_fs.clone())
+            .await?
+            .iter()
+            .any(|c| c.name == name)
+        {
+            return Err(CubeError::user(format!(
+                "Metastore checkpoint {} is not found",
+                name
+            )));
+        }
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let restore_path = PathBuf::from(format!("{}-restore-{}", path.to_string_lossy(), now));
+        info!("Restoring metastore from {}", name);
+        let verified = async {
+            Self::download_checkpoint(&remote_fs, millis, &restore_path).await?;
+            let meta_store = Self::new(&restore_path, remote_fs.clone(), config.clone())?;
+            RocksMetaStore::check_all_indexes(&meta_store).await?;
+            let missing_files = meta_store.missing_remote_files().await?;
+            if !missing_files.is_empty() {
+                return Err(CubeError::user(format!(
+                    "Can't restore metastore from {}: {} files referenced by it are missing in remote storage: {}",
+                    name,
+                    missing_files.len(),
+                    missing_files.iter().take(10).join(", ")
+                )));
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = verified {
+            let _ = fs::remove_dir_all(&restore_path).await;
+            return Err(e);
+        }
+
+        // The verified copy is closed above, so directories can be swapped.
+        let backup = if fs::metadata(path).await.is_ok() {
+            let backup = PathBuf::from(format!("{}-backup-{}", path.to_string_lossy(), now));
+            fs::rename(path, &backup).await?;
+            info!(
+                "Existing metastore is moved to {}",
+                backup.to_string_lossy()
+            );
+            Some(backup)
+        } else {
+            None
+        };
+        if let Err(e) = fs::rename(&restore_path, path).await {
+            if let Some(backup) = backup {
+                fs::rename(&backup, path).await?;
+            }
+            return Err(e.into());
+        }
+
+        let meta_store = Self::new(path, remote_fs.clone(), config)?;
+        RocksMetaStore::check_all_indexes(&meta_store).await?;
+        // Make the restored state current, so it's loaded if the local metastore is lost.
+        meta_store.upload_check_point().await?;
+        Self::upload_remote_file(&remote_fs, METASTORE_RESTORED_FILE, &name).await?;
+        info!("Metastore is restored from {}", name);
+        Ok(Some(meta_store))
+    }
+
+    /// Opens the uploaded [checkpoint] in [path] as is, logs written after it are not applied.
+    pub async fn load_checkpoint(
+        path: impl AsRef<Path>,
+        checkpoint: &str,
+        remote_fs: Arc<dyn RemoteFs>,
+        config: Arc<dyn ConfigObj>,
+    ) -> Result<Arc<RocksMetaStore>, CubeError> {
+        let millis = parse_checkpoint_name(checkpoint).ok_or_else(|| {
+            CubeError::user(format!("Invalid metastore checkpoint: '{}'", checkpoint))
+        })?;
+        Self::download_checkpoint(&remote_fs, millis, path.as_ref()).await?;
+        Self::new(path, remote_fs, config)
+    }
+
+    /// Lists checkpoints uploaded to [remote_fs], oldest first.
+    pub async fn list_checkpoints(
+        remote_fs: Arc<dyn RemoteFs>,
+    ) -> Result<Vec<MetaStoreCheckpoint>, CubeError> {
+        let current = Self::current_checkpoint(&remote_fs).await?;
+        let mut checkpoints = BTreeMap::new();
+        for file in remote_fs.list_with_metadata("metastore-").await? {
+            let dir = file.remote_path().split("/").next().unwrap();
+            let (millis, is_logs) = match parse_checkpoint_dir(dir) {
+                Some(c) => c,
+                None => continue,
+            };
+            let checkpoint = checkpoints
+                .entry(millis)
+                .or_insert_with(|| MetaStoreCheckpoint {
+                    name: checkpoint_name(millis),
+                    created_at: Utc.timestamp_millis(millis as i64),
+                    files: 0,
+                    size: 0,
+                    log_files: 0,
+                    current: current == Some(millis),
+                });
+            if is_logs {
+                checkpoint.log_files += 1;
+            } else {
+                checkpoint.files += 1;
+                checkpoint.size += file.file_size();
+            }
+        }
+        // Logs written before the first checkpoint of a process have no checkpoint to apply to.
+        Ok(checkpoints
+            .into_iter()
+            .map(|(_, c)| c)
+            .filter(|c| c.files != 0)
+            .collect())
+    }
+
+    /// Checkpoint that `metastore-current` points to.
+    async fn current_checkpoint(remote_fs: &Arc<dyn RemoteFs>) -> Result<Option<u128>, CubeError> {
+        let current = match Self::read_remote_file(remote_fs, "metastore-current").await? {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+        let re = Regex::new(r"^metastore-(\d+)").unwrap();
+        let parse_result = re
+            .captures(&current)
+            .map(|c| c.get(1).unwrap().as_str())
+            .map(|p| u128::from_str(p));
+        if let Some(Ok(millis)) = parse_result {
+            Ok(Some(millis))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Downloads the latest version of a small text file, `None` if it doesn't exist.
+    async fn read_remote_file(
+        remote_fs: &Arc<dyn RemoteFs>,
+        remote_path: &str,
+    ) -> Result<Option<String>, CubeError> {
+        if remote_fs.list(remote_path).await?.is_empty() {
+            return Ok(None);
+        }
+        let local_file = remote_fs.local_file(remote_path).await?;
+        if fs::metadata(local_file.as_str()).await.is_ok() {
+            fs::remove_file(local_file.as_str()).await?;
+        }
+        remote_fs.download_file(remote_path, None).await?;
+
+        let mut file = File::open(local_file.as_str()).await?;
+        let mut buffer = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut file, &mut buffer).await?;
+        Ok(Some(String::from_utf8(buffer)?))
+    }
+
+    async fn upload_remote_file(
+        remote_fs: &Arc<dyn RemoteFs>,
+        remote_path: &str,
+        content: &str,
+    ) -> Result<(), CubeError> {
+        let uploads_dir = remote_fs.uploads_dir().await?;
+        let prefix = remote_path.to_string();
+        let (file, file_path) = cube_ext::spawn_blocking(move || {
+            tempfile::Builder::new()
+                .prefix(&prefix)
+                .tempfile_in(uploads_dir)
+        })
+        .await??
+        .into_parts();
+
+        tokio::io::AsyncWriteExt::write_all(&mut fs::File::from_std(file), content.as_bytes())
+            .await?;
+
+        remote_fs
+            .upload_file(file_path.keep()?.to_str().unwrap(), remote_path)
+            .await?;
+        Ok(())
+    }
+
+    async fn download_checkpoint(
+        remote_fs: &Arc<dyn RemoteFs>,
+        snapshot: u128,
+        meta_store_path: &Path,
+    ) -> Result<(), CubeError> {
+        let checkpoint_dir = format!("{}/", checkpoint_name(snapshot));
+        let to_load = remote_fs.list(&checkpoint_name(snapshot)).await?;
+        fs::create_dir_all(meta_store_path).await?;
+        for file in to_load.iter().filter(|f| f.starts_with(&checkpoint_dir)) {
+            let local = remote_fs.local_file(file).await?;
+            // Local files that exist before the download are the ones written on upload, or the
+            // only copy if there's no remote storage, so only downloaded copies are removed.
+            let downloaded = fs::metadata(&local).await.is_err();
+            // TODO check file size
+            remote_fs.download_file(file, None).await?;
+            let path = Path::new(&local);
+            fs::copy(
+                path,
+                meta_store_path.join(path.file_name().unwrap().to_str().unwrap()),
+            )
+            .await?;
+            if downloaded {
+                fs::remove_file(path).await?;
+            }
+        }
+        let local_dir = PathBuf::from(remote_fs.local_file(&checkpoint_name(snapshot)).await?);
+        // Succeed only if nothing else is left there.
+        let _ = fs::remove_dir(local_dir.join("downloads")).await;
+        let _ = fs::remove_dir(local_dir).await;
+        Ok(())
+    }
+
+    /// Remote files of active partitions and chunks that don't exist in the remote storage.
+    async fn missing_remote_files(&self) -> Result<Vec<String>, CubeError> {
+        let existing = self
+            .remote_fs
+            .list("")
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let partitions = self
+            .partition_table()
+            .all_rows()
+            .await?
+            .into_iter()
+            .filter_map(|p| p.get_row().get_full_name(p.get_id()));
+        let chunks = self
+            .chunks_table()
+            .all_rows()
+            .await?
+            .into_iter()
+            .filter(|c| {
+                let c = c.get_row();
+                c.active() && c.uploaded() && !c.in_memory()
+            })
+            .map(|c| c.get_row().get_full_name(c.get_id()));
+        Ok(partitions
+            .chain(chunks)
+            .filter(|f| !existing.contains(f))
+            .collect())
+    }
+
     async fn check_all_indexes(meta_store: &Arc<RocksMetaStore>) -> Result<(), CubeError> {
         let meta_store_to_move = meta_store.clone();
 
@@ -2344,7 +2777,13 @@ impl RocksMetaStore {
             RocksMetaStore::prepare_checkpoint(db, &check_point_time).await?
         };
 
-        RocksMetaStore::upload_checkpoint(remote_fs, remote_path, checkpoint_path).await?;
+        RocksMetaStore::upload_checkpoint(
+            remote_fs,
+            remote_path,
+            checkpoint_path,
+            Duration::from_secs(self.config.meta_store_checkpoint_retention()),
+        )
+        .await?;
         self.write_completed_notify.notify_waiters();
         Ok(())
     }
@@ -2357,10 +2796,12 @@ impl RocksMetaStore {
         *self.last_check_seq.read().await
     }
 
+    /// Uploads the checkpoint, makes it current and deletes checkpoints older than [retention].
     async fn upload_checkpoint(
         remote_fs: Arc<dyn RemoteFs>,
         remote_path: String,
         checkpoint_path: PathBuf,
+        retention: Duration,
     ) -> Result<(), CubeError> {
         let mut dir = fs::read_dir(checkpoint_path).await?;
 
@@ -2388,26 +2829,21 @@ impl RocksMetaStore {
             v?;
         }
 
+        let current = parse_checkpoint_dir(&remote_path).map(|(millis, _)| millis);
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
         let existing_metastore_files = remote_fs.list("metastore-").await?;
         let to_delete = existing_metastore_files
             .into_iter()
             .filter_map(|existing| {
-                let path = existing
-                    .split("/")
-                    .nth(0)
-                    .map(|p| u128::from_str(&p.replace("metastore-", "").replace("-logs", "")));
-                if let Some(Ok(millis)) = path {
-                    if SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis()
-                        - millis
-                        > 3 * 60 * 1000
-                    {
-                        return Some(existing);
-                    }
+                let (millis, _) = parse_checkpoint_dir(existing.split("/").nth(0)?)?;
+                if Some(millis) != current && now.saturating_sub(millis) > retention.as_millis() {
+                    Some(existing)
+                } else {
+                    None
                 }
-                None
             })
             .collect::<Vec<_>>();
         for v in join_all(
@@ -2422,23 +2858,7 @@ impl RocksMetaStore {
             v?;
         }
 
-        let uploads_dir = remote_fs.uploads_dir().await?;
-        let (file, file_path) = cube_ext::spawn_blocking(move || {
-            tempfile::Builder::new()
-                .prefix("metastore-current")
-                .tempfile_in(uploads_dir)
-        })
-        .await??
-        .into_parts();
-
-        tokio::io::AsyncWriteExt::write_all(&mut fs::File::from_std(file), remote_path.as_bytes())
-            .await?;
-
-        remote_fs
-            .upload_file(file_path.keep()?.to_str().unwrap(), "metastore-current")
-            .await?;
-
-        Ok(())
+        Self::upload_remote_file(&remote_fs, "metastore-current", &remote_path).await
     }
 
     async fn prepare_checkpoint(
@@ -2458,12 +2878,11 @@ impl RocksMetaStore {
     }
 
     fn meta_store_path(checkpoint_time: &SystemTime) -> String {
-        format!(
-            "metastore-{}",
+        checkpoint_name(
             checkpoint_time
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap()
-                .as_millis()
+                .as_millis(),
         )
     }
 
@@ -2568,7 +2987,8 @@ impl RocksMetaStore {
             store_path.clone().join("metastore").as_path(),
             remote_fs.clone(),
             config.config_obj(),
-        );
+        )
+        .unwrap();
         (remote_fs, meta_store)
     }
 
@@ -2602,6 +3022,7 @@ impl RocksMetaStore {
         multi_index: Option<&IdRow<MultiIndex>>,
         multi_partitions: &[IdRow<MultiPartition>],
         index_def: IndexDef,
+        building: bool,
     ) -> Result<IdRow<Index>, CubeError> {
         debug_assert_eq!(multi_index.is_some(), !multi_partitions.is_empty());
         if let Some(not_found) = index_def
@@ -2717,7 +3138,8 @@ impl RocksMetaStore {
             // Seq column shouldn't participate in partition split. Otherwise we can't do shared nothing calculations across partitions.
             table_id.get_row().seq_column().map(|_| sorted_key_size - 1),
             multi_index.map(|i| i.id),
-        )?;
+        )?
+        .update_building(building);
         let index_id = rocks_index.insert(index, batch_pipe)?;
         if multi_partitions.is_empty() {
             rocks_partition.insert(Partition::new(index_id.id, None, None, None), batch_pipe)?;
@@ -2959,6 +3381,8 @@ impl MetaStore for RocksMetaStore {
         is_ready: bool,
         unique_key_column_names: Option<Vec<String>>,
         partition_split_threshold: Option<u64>,
+        ttl_seconds: Option<u64>,
+        parquet_settings: ParquetSettings,
     ) -> Result<IdRow<Table>, CubeError> {
         self.write_operation(move |db_ref, batch_pipe| {
             batch_pipe.invalidate_tables_cache();
@@ -3008,6 +3432,8 @@ impl MetaStore for RocksMetaStore {
                 unique_key_column_indices,
                 seq_column_index,
                 partition_split_threshold,
+                ttl_seconds,
+                parquet_settings,
             );
             let table_id = rocks_table.insert(table, batch_pipe)?;
             for index_def in indexes.into_iter() {
@@ -3040,6 +3466,7 @@ impl MetaStore for RocksMetaStore {
                     multi_index.as_ref(),
                     &multi_partitions,
                     index_def,
+                    false,
                 )?;
             }
             let def_index_columns = table_id
@@ -3075,6 +3502,7 @@ impl MetaStore for RocksMetaStore {
                     multi_index: None,
                     columns: def_index_columns,
                 },
+                false,
             )?;
 
             Ok(table_id)
@@ -3109,28 +3537,304 @@ impl MetaStore for RocksMetaStore {
         .await
     }
 
-    async fn get_table(
+    async fn add_column(
         &self,
         schema_name: String,
         table_name: String,
+        column: Column,
     ) -> Result<IdRow<Table>, CubeError> {
-        self.read_operation(move |db_ref| get_table_impl(db_ref, schema_name, table_name))
-            .await
-    }
-
-    async fn get_table_by_id(&self, table_id: u64) -> Result<IdRow<Table>, CubeError> {
-        self.read_operation(move |db_ref| {
-            TableRocksTable::new(db_ref.clone()).get_row_or_not_found(table_id)
+        self.write_operation(move |db_ref, batch_pipe| {
+            batch_pipe.invalidate_tables_cache();
+            let table = get_table_impl(db_ref.clone(), schema_name, table_name)?;
+            if table
+                .get_row()
+                .get_columns()
+                .iter()
+                .any(|c| c.get_name() == column.get_name())
+            {
+                return Err(CubeError::user(format!(
+                    "Column '{}' already exists in table '{}'",
+                    column.get_name(),
+                    table.get_row().get_table_name()
+                )));
+            }
+            let rocks_index = IndexRocksTable::new(db_ref.clone());
+            for index_id in rocks_index.get_row_ids_by_index(
+                &IndexIndexKey::TableId(table.get_id()),
+                &IndexRocksIndex::TableID,
+            )? {
+                let (partition_id, chunk_id) =
+                    last_partition_and_chunk_ids(db_ref.clone(), index_id)?;
+                rocks_index.update_with_fn(
+                    index_id,
+                    |i| {
+                        i.add_column(column.clone())
+                            .update_altered(partition_id, chunk_id)
+                    },
+                    batch_pipe,
+                )?;
+            }
+            let rocks_table = TableRocksTable::new(db_ref.clone());
+            Ok(rocks_table.update_with_fn(table.get_id(), |t| t.add_column(column), batch_pipe)?)
         })
         .await
     }
 
-    async fn get_tables(&self) -> Result<Vec<IdRow<Table>>, CubeError> {
-        self.read_operation_out_of_queue(|db_ref| TableRocksTable::new(db_ref).all_rows())
-            .await
-    }
-
-    async fn get_tables_with_path(
+    async fn add_deleted_column(
+        &self,
+        schema_name: String,
+        table_name: String,
+    ) -> Result<IdRow<Table>, CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            let table = get_table_impl(db_ref.clone(), schema_name, table_name)?;
+            if table.get_row().deleted_column().is_some() {
+                return Ok(table);
+            }
+            if table.get_row().unique_key_columns().is_none() {
+                return Err(CubeError::user(format!(
+                    "DELETE is supported only for tables with unique key but '{}' doesn't have one",
+                    table.get_row().get_table_name()
+                )));
+            }
+            batch_pipe.invalidate_tables_cache();
+            let column = table
+                .get_row()
+                .add_deleted_column()
+                .deleted_column()
+                .unwrap()
+                .clone();
+            let rocks_index = IndexRocksTable::new(db_ref.clone());
+            for index_id in rocks_index.get_row_ids_by_index(
+                &IndexIndexKey::TableId(table.get_id()),
+                &IndexRocksIndex::TableID,
+            )? {
+                let (partition_id, chunk_id) =
+                    last_partition_and_chunk_ids(db_ref.clone(), index_id)?;
+                rocks_index.update_with_fn(
+                    index_id,
+                    |i| {
+                        i.add_column(column.clone())
+                            .update_altered(partition_id, chunk_id)
+                    },
+                    batch_pipe,
+                )?;
+            }
+            let rocks_table = TableRocksTable::new(db_ref.clone());
+            Ok(rocks_table.update_with_fn(
+                table.get_id(),
+                |t| t.add_deleted_column(),
+                batch_pipe,
+            )?)
+        })
+        .await
+    }
+
+    async fn rename_column(
+        &self,
+        schema_name: String,
+        table_name: String,
+        old_column_name: String,
+        new_column_name: String,
+    ) -> Result<IdRow<Table>, CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            batch_pipe.invalidate_tables_cache();
+            let table = get_table_impl(db_ref.clone(), schema_name, table_name)?;
+            let columns = table.get_row().get_columns();
+            if columns.iter().all(|c| c.get_name() != &old_column_name) {
+                return Err(CubeError::user(format!(
+                    "Column '{}' is not found in table '{}'",
+                    old_column_name,
+                    table.get_row().get_table_name()
+                )));
+            }
+            if columns.iter().any(|c| c.get_name() == &new_column_name) {
+                return Err(CubeError::user(format!(
+                    "Column '{}' already exists in table '{}'",
+                    new_column_name,
+                    table.get_row().get_table_name()
+                )));
+            }
+            let rocks_index = IndexRocksTable::new(db_ref.clone());
+            for index_id in rocks_index.get_row_ids_by_index(
+                &IndexIndexKey::TableId(table.get_id()),
+                &IndexRocksIndex::TableID,
+            )? {
+                let (partition_id, chunk_id) =
+                    last_partition_and_chunk_ids(db_ref.clone(), index_id)?;
+                rocks_index.update_with_fn(
+                    index_id,
+                    |i| {
+                        i.rename_column(&old_column_name, &new_column_name)
+                            .update_altered(partition_id, chunk_id)
+                    },
+                    batch_pipe,
+                )?;
+            }
+            let rocks_table = TableRocksTable::new(db_ref.clone());
+            Ok(rocks_table.update_with_fn(
+                table.get_id(),
+                |t| t.rename_column(&old_column_name, &new_column_name),
+                batch_pipe,
+            )?)
+        })
+        .await
+    }
+
+    async fn rename_table(
+        &self,
+        schema_name: String,
+        table_name: String,
+        new_table_name: String,
+    ) -> Result<IdRow<Table>, CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            batch_pipe.invalidate_tables_cache();
+            let table = get_table_impl(db_ref.clone(), schema_name.clone(), table_name)?;
+            let rocks_table = TableRocksTable::new(db_ref.clone());
+            let existing = rocks_table.get_row_ids_by_index(
+                &TableIndexKey::ByName(table.get_row().get_schema_id(), new_table_name.clone()),
+                &TableRocksIndex::Name,
+            )?;
+            if !existing.is_empty() {
+                return Err(CubeError::user(format!(
+                    "Table '{}.{}' already exists",
+                    schema_name, new_table_name
+                )));
+            }
+            Ok(rocks_table.update_with_fn(
+                table.get_id(),
+                |t| t.rename(new_table_name),
+                batch_pipe,
+            )?)
+        })
+        .await
+    }
+
+    async fn rename_tables(
+        &self,
+        renames: Vec<(String, String, String)>,
+    ) -> Result<Vec<IdRow<Table>>, CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            batch_pipe.invalidate_tables_cache();
+            let rocks_table = TableRocksTable::new(db_ref.clone());
+            let rocks_schema = SchemaRocksTable::new(db_ref.clone());
+            // Tables renamed so far with their current names.
+            let mut renamed: Vec<(IdRow<Table>, String)> = Vec::new();
+            for (schema_name, table_name, new_table_name) in renames {
+                let schema_id = rocks_schema
+                    .get_single_row_by_index(&schema_name, &SchemaRocksIndex::Name)?
+                    .get_id();
+                let current = |renamed: &Vec<(IdRow<Table>, String)>, name: &str| {
+                    renamed
+                        .iter()
+                        .position(|(t, n)| t.get_row().get_schema_id() == schema_id && n == name)
+                };
+                let stored = |renamed: &Vec<(IdRow<Table>, String)>,
+                              name: &str|
+                 -> Result<Option<u64>, CubeError> {
+                    Ok(rocks_table
+                        .get_row_ids_by_index(
+                            &TableIndexKey::ByName(schema_id, name.to_string()),
+                            &TableRocksIndex::Name,
+                        )?
+                        .into_iter()
+                        .find(|id| renamed.iter().all(|(t, _)| t.get_id() != *id)))
+                };
+
+                let pos = match current(&renamed, &table_name) {
+                    Some(pos) => pos,
+                    None => {
+                        let id = stored(&renamed, &table_name)?.ok_or_else(|| {
+                            CubeError::user(format!(
+                                "Table '{}.{}' does not exist",
+                                schema_name, table_name
+                            ))
+                        })?;
+                        renamed.push((rocks_table.get_row_or_not_found(id)?, table_name.clone()));
+                        renamed.len() - 1
+                    }
+                };
+                if current(&renamed, &new_table_name).is_some()
+                    || stored(&renamed, &new_table_name)?.is_some()
+                {
+                    return Err(CubeError::user(format!(
+                        "Table '{}.{}' already exists",
+                        schema_name, new_table_name
+                    )));
+                }
+                renamed[pos].1 = new_table_name;
+            }
+            let mut res = Vec::with_capacity(renamed.len());
+            for (table, name) in renamed {
+                res.push(rocks_table.update_with_fn(
+                    table.get_id(),
+                    |t| t.rename(name),
+                    batch_pipe,
+                )?);
+            }
+            Ok(res)
+        })
+        .await
+    }
+
+    async fn swap_tables(
+        &self,
+        schema_name: String,
+        table_name: String,
+        other_table_name: String,
+    ) -> Result<Vec<IdRow<Table>>, CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            batch_pipe.invalidate_tables_cache();
+            let table = get_table_impl(db_ref.clone(), schema_name.clone(), table_name.clone())?;
+            let other_table = get_table_impl(
+                db_ref.clone(),
+                schema_name.clone(),
+                other_table_name.clone(),
+            )?;
+            if table.get_id() == other_table.get_id() {
+                return Err(CubeError::user(format!(
+                    "Can't swap table '{}.{}' with itself",
+                    schema_name, table_name
+                )));
+            }
+            let rocks_table = TableRocksTable::new(db_ref.clone());
+            Ok(vec![
+                rocks_table.update_with_fn(
+                    other_table.get_id(),
+                    |t| t.rename(table_name),
+                    batch_pipe,
+                )?,
+                rocks_table.update_with_fn(
+                    table.get_id(),
+                    |t| t.rename(other_table_name),
+                    batch_pipe,
+                )?,
+            ])
+        })
+        .await
+    }
+
+    async fn get_table(
+        &self,
+        schema_name: String,
+        table_name: String,
+    ) -> Result<IdRow<Table>, CubeError> {
+        self.read_operation(move |db_ref| get_table_impl(db_ref, schema_name, table_name))
+            .await
+    }
+
+    async fn get_table_by_id(&self, table_id: u64) -> Result<IdRow<Table>, CubeError> {
+        self.read_operation(move |db_ref| {
+            TableRocksTable::new(db_ref.clone()).get_row_or_not_found(table_id)
+        })
+        .await
+    }
+
+    async fn get_tables(&self) -> Result<Vec<IdRow<Table>>, CubeError> {
+        self.read_operation_out_of_queue(|db_ref| TableRocksTable::new(db_ref).all_rows())
+            .await
+    }
+
+    async fn get_tables_with_path(
         &self,
         include_non_ready: bool,
     ) -> Result<Arc<Vec<TablePath>>, CubeError> {
@@ -3701,6 +4405,30 @@ impl MetaStore for RocksMetaStore {
         .await
     }
 
+    async fn get_altered_partitions(&self) -> Result<Vec<IdRow<Partition>>, CubeError> {
+        self.read_operation_out_of_queue(move |db_ref| {
+            let partitions_table = PartitionRocksTable::new(db_ref.clone());
+            let mut partitions = Vec::new();
+            for index in IndexRocksTable::new(db_ref.clone()).all_rows()? {
+                if !index.get_row().altered() {
+                    continue;
+                }
+                for p in partitions_table.get_rows_by_index(
+                    &PartitionIndexKey::ByIndexId(index.get_id()),
+                    &PartitionRocksIndex::IndexId,
+                )? {
+                    if p.get_row().has_main_table_file()
+                        && index.get_row().is_altered_partition(p.get_id())
+                    {
+                        partitions.push(p);
+                    }
+                }
+            }
+            Ok(partitions)
+        })
+        .await
+    }
+
     fn index_table(&self) -> IndexMetaStoreTable {
         IndexMetaStoreTable {
             rocks_meta_store: self.clone(),
@@ -3743,11 +4471,99 @@ impl MetaStore for RocksMetaStore {
                 None,
                 &[],
                 index_def,
+                false,
             )?)
         })
         .await
     }
 
+    async fn create_index_for_backfill(
+        &self,
+        schema_name: String,
+        table_name: String,
+        index_def: IndexDef,
+    ) -> Result<
+        (
+            IdRow<Index>,
+            IdRow<Index>,
+            Vec<(IdRow<Partition>, Vec<IdRow<Chunk>>)>,
+        ),
+        CubeError,
+    > {
+        self.write_operation(move |db_ref, batch_pipe| {
+            let rocks_index = IndexRocksTable::new(db_ref.clone());
+            let rocks_partition = PartitionRocksTable::new(db_ref.clone());
+            let rocks_chunk = ChunkRocksTable::new(db_ref.clone());
+
+            let table = get_table_impl(db_ref.clone(), schema_name, table_name)?;
+            let default_index = get_default_index_impl(db_ref.clone(), table.get_id())?;
+
+            let mut partitions = Vec::new();
+            for partition in rocks_partition.get_rows_by_index(
+                &PartitionIndexKey::ByIndexId(default_index.get_id()),
+                &PartitionRocksIndex::IndexId,
+            )? {
+                if !partition.get_row().is_active() {
+                    continue;
+                }
+                let chunks =
+                    RocksMetaStore::chunks_by_partition(partition.get_id(), &rocks_chunk, false)?;
+                if chunks.iter().any(|c| c.get_row().in_memory()) {
+                    return Err(CubeError::user(format!(
+                        "Can't create '{}' index while '{}' table has in memory chunks. Please retry after they are persisted",
+                        index_def.name,
+                        table.get_row().get_table_name()
+                    )));
+                }
+                partitions.push((partition, chunks));
+            }
+
+            let index = RocksMetaStore::add_index(
+                batch_pipe,
+                &rocks_index,
+                &rocks_partition,
+                table.get_row().get_columns(),
+                &table,
+                None,
+                &[],
+                index_def,
+                true,
+            )?;
+            Ok((index, default_index, partitions))
+        })
+        .await
+    }
+
+    async fn finish_index_backfill(
+        &self,
+        index_id: u64,
+        uploaded_chunk_ids: Vec<(u64, Option<u64>)>,
+    ) -> Result<IdRow<Index>, CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            Self::activate_chunks_impl(db_ref.clone(), batch_pipe, &uploaded_chunk_ids)?;
+            Ok(IndexRocksTable::new(db_ref).update_with_fn(
+                index_id,
+                |i| i.update_building(false),
+                batch_pipe,
+            )?)
+        })
+        .await
+    }
+
+    async fn cancel_index_backfill(&self, index_id: u64) -> Result<(), CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            let index = IndexRocksTable::new(db_ref.clone()).get_row_or_not_found(index_id)?;
+            if !index.get_row().building() {
+                return Err(CubeError::internal(format!(
+                    "Can't cancel backfill of already built index: {:?}",
+                    index
+                )));
+            }
+            RocksMetaStore::drop_index(db_ref, batch_pipe, index_id, false)
+        })
+        .await
+    }
+
     async fn get_default_index(&self, table_id: u64) -> Result<IdRow<Index>, CubeError> {
         self.read_operation(move |db_ref| get_default_index_impl(db_ref, table_id))
             .await
@@ -4052,15 +4868,34 @@ impl MetaStore for RocksMetaStore {
         &self,
         table_id: u64,
         uploaded_chunk_ids: Vec<(u64, Option<u64>)>,
+        stream_offsets: Option<Vec<StreamOffset>>,
     ) -> Result<(), CubeError> {
         trace!(
             "Activating chunks ({})",
             uploaded_chunk_ids.iter().map(|(id, _)| id).join(", ")
         );
         self.write_operation(move |db, pipe| {
+            if stream_offsets.is_some() {
+                // Rows of in-memory chunks are lost on restarts and must be consumed again.
+                let chunks = ChunkRocksTable::new(db.clone());
+                for (id, _) in &uploaded_chunk_ids {
+                    if chunks.get_row_or_not_found(*id)?.get_row().in_memory() {
+                        return Err(CubeError::internal(format!(
+                            "Can't persist stream offsets along with in-memory chunk {}",
+                            id
+                        )));
+                    }
+                }
+            }
             TableRocksTable::new(db.clone()).update_with_fn(
                 table_id,
-                |t| t.update_has_data(true),
+                |t| {
+                    let t = t.update_has_data(true);
+                    match &stream_offsets {
+                        Some(offsets) => t.update_stream_offsets(offsets),
+                        None => t,
+                    }
+                },
                 pipe,
             )?;
             let (_, partition_rows) =
@@ -4189,6 +5024,11 @@ impl MetaStore for RocksMetaStore {
         .await
     }
 
+    async fn all_wals(&self) -> Result<Vec<IdRow<WAL>>, CubeError> {
+        self.read_operation(move |db_ref| WALRocksTable::new(db_ref).all_rows())
+            .await
+    }
+
     async fn delete_wal(&self, wal_id: u64) -> Result<(), CubeError> {
         self.write_operation(move |db_ref, batch_pipe| {
             WALRocksTable::new(db_ref.clone()).delete(wal_id, batch_pipe)?;
@@ -4422,6 +5262,7 @@ impl MetaStore for RocksMetaStore {
                     &IndexIndexKey::TableId(table.get_id()),
                     &IndexRocksIndex::TableID,
                 )?;
+                indexes.retain(|i| !i.get_row().building());
                 indexes.insert(0, get_default_index_impl(db.clone(), table.get_id())?);
 
                 r.push((schema, table, indexes))
@@ -4792,6 +5633,74 @@ fn get_default_index_impl(db_ref: DbTableRef, table_id: u64) -> Result<IdRow<Ind
 /// Note that [current_active] and [new_active] are snapshots at some older point in time. The
 /// relevant partitions might be dropped or changed by the time this function runs. Implementation
 /// must take great care to avoid inconsistencies caused by this.
+/// Ids of the last partition and chunk of the index. Data files of partitions and chunks created
+/// later are written with the current index columns.
+fn last_partition_and_chunk_ids(
+    db_ref: DbTableRef,
+    index_id: u64,
+) -> Result<(u64, u64), CubeError> {
+    let partition_ids = PartitionRocksTable::new(db_ref.clone()).get_row_ids_by_index(
+        &PartitionIndexKey::ByIndexId(index_id),
+        &PartitionRocksIndex::IndexId,
+    )?;
+    let chunk_table = ChunkRocksTable::new(db_ref);
+    let mut last_chunk_id = 0;
+    for partition_id in partition_ids.iter() {
+        for chunk_id in chunk_table.get_row_ids_by_index(
+            &ChunkIndexKey::ByPartitionId(*partition_id),
+            &ChunkRocksIndex::PartitionId,
+        )? {
+            last_chunk_id = last_chunk_id.max(chunk_id);
+        }
+    }
+    Ok((partition_ids.into_iter().max().unwrap_or(0), last_chunk_id))
+}
+
+/// Clears [Index::altered] when no partition or chunk created before the alter is left after
+/// the swap of [current_active] with [new_active].
+fn clear_altered_if_rewritten(
+    db_ref: DbTableRef,
+    batch_pipe: &mut BatchPipe,
+    index_id: u64,
+    current_active: &[(IdRow<Partition>, Vec<IdRow<Chunk>>)],
+    new_active: &[(IdRow<Partition>, u64)],
+) -> Result<(), CubeError> {
+    let index_table = IndexRocksTable::new(db_ref.clone());
+    let index = match index_table.get_row(index_id)? {
+        Some(index) if index.get_row().altered() => index,
+        _ => return Ok(()),
+    };
+    let deactivated_chunks = current_active
+        .iter()
+        .flat_map(|(_, chunks)| chunks.iter().map(|c| c.get_id()))
+        .collect::<HashSet<_>>();
+    let chunk_table = ChunkRocksTable::new(db_ref.clone());
+    for p in PartitionRocksTable::new(db_ref.clone()).get_rows_by_index(
+        &PartitionIndexKey::ByIndexId(index_id),
+        &PartitionRocksIndex::IndexId,
+    )? {
+        let deactivated = current_active.iter().any(|(c, _)| c.get_id() == p.get_id());
+        let activated = new_active.iter().any(|(n, _)| n.get_id() == p.get_id());
+        let has_file = activated || !deactivated && p.get_row().has_main_table_file();
+        if has_file && index.get_row().is_altered_partition(p.get_id()) {
+            return Ok(());
+        }
+        for c in chunk_table.get_rows_by_index(
+            &ChunkIndexKey::ByPartitionId(p.get_id()),
+            &ChunkRocksIndex::PartitionId,
+        )? {
+            // Uploaded inactive chunks are never activated again.
+            let dropped = deactivated_chunks.contains(&c.get_id())
+                || c.get_row().uploaded() && !c.get_row().active();
+            if !dropped && index.get_row().is_altered_chunk(c.get_id()) {
+                return Ok(());
+            }
+        }
+    }
+    index_table.update_with_fn(index_id, |i| i.clear_altered(), batch_pipe)?;
+    Ok(())
+}
+
 fn swap_active_partitions_impl(
     db_ref: DbTableRef,
     batch_pipe: &mut BatchPipe,
@@ -4932,13 +5841,23 @@ fn swap_active_partitions_impl(
         )));
     }
 
+    if let Some((current, _)) = current_active.first() {
+        clear_altered_if_rewritten(
+            db_ref,
+            batch_pipe,
+            current.get_row().get_index_id(),
+            current_active,
+            new_active,
+        )?;
+    }
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::config::{Config, ConfigObjImpl};
     use crate::remotefs::LocalDirRemoteFs;
     use futures_timer::Delay;
     use std::thread::sleep;
@@ -4953,6 +5872,27 @@ mod tests {
         assert_eq!(format_table_value!(s, name, String), "foo");
     }
 
+    #[test]
+    fn column_serialization() {
+        let plain = Column::new("a".to_string(), ColumnType::Int, 0);
+        let with_default = Column::new("b".to_string(), ColumnType::Int, 1)
+            .with_default_value(Some(TableValue::Int(10)));
+        assert_eq!(
+            serde_json::to_string(&plain).unwrap(),
+            r#"{"name":"a","column_type":"Int","column_index":0}"#
+        );
+        for c in &[plain, with_default] {
+            let json: Column = serde_json::from_str(&serde_json::to_string(c).unwrap()).unwrap();
+            assert_eq!(&json, c);
+            let bytes = bincode::serialize(c).unwrap();
+            assert_eq!(&bincode::deserialize::<Column>(&bytes).unwrap(), c);
+            let mut ser = flexbuffers::FlexbufferSerializer::new();
+            c.serialize(&mut ser).unwrap();
+            let r = flexbuffers::Reader::get_root(ser.view()).unwrap();
+            assert_eq!(&Column::deserialize(r).unwrap(), c);
+        }
+    }
+
     #[tokio::test]
     async fn schema_test() {
         let config = Config::test("schema_test");
@@ -4967,7 +5907,8 @@ mod tests {
                 store_path.join("metastore").as_path(),
                 remote_fs,
                 config.config_obj(),
-            );
+            )
+            .unwrap();
 
             let schema_1 = meta_store
                 .create_schema("foo".to_string(), false)
@@ -5155,7 +6096,8 @@ mod tests {
                 store_path.join("metastore").as_path(),
                 remote_fs,
                 config.config_obj(),
-            );
+            )
+            .unwrap();
 
             meta_store
                 .create_schema("foo".to_string(), false)
@@ -5202,7 +6144,8 @@ mod tests {
                 store_path.clone().join("metastore").as_path(),
                 remote_fs,
                 config.config_obj(),
-            );
+            )
+            .unwrap();
 
             let schema_1 = meta_store
                 .create_schema("foo".to_string(), false)
@@ -5232,6 +6175,8 @@ mod tests {
                     true,
                     None,
                     None,
+                    None,
+                    ParquetSettings::default(),
                 )
                 .await
                 .unwrap();
@@ -5249,6 +6194,8 @@ mod tests {
                     true,
                     None,
                     None,
+                    None,
+                    ParquetSettings::default(),
                 )
                 .await
                 .is_err());
@@ -5272,6 +6219,181 @@ mod tests {
             .unwrap();
             let expected_res = vec![IdRow::new(1, expected_index)];
             assert_eq!(meta_store.get_table_indexes(1).await.unwrap(), expected_res);
+
+            let ttl_table = meta_store
+                .create_table(
+                    "foo".to_string(),
+                    "ttl".to_string(),
+                    columns.clone(),
+                    None,
+                    None,
+                    vec![],
+                    true,
+                    None,
+                    None,
+                    Some(60),
+                    ParquetSettings::default(),
+                )
+                .await
+                .unwrap();
+            let created_at = ttl_table.get_row().created_at().unwrap();
+            assert!(!ttl_table.get_row().is_expired(created_at));
+            assert!(ttl_table
+                .get_row()
+                .is_expired(created_at + chrono::Duration::seconds(60)));
+            assert!(!table1
+                .get_row()
+                .is_expired(created_at + chrono::Duration::days(365)));
+
+            let offset = |partition, offset| StreamOffset { partition, offset };
+            meta_store
+                .activate_chunks(table1_id, vec![], Some(vec![offset(1, 10), offset(0, 5)]))
+                .await
+                .unwrap();
+            meta_store
+                .activate_chunks(table1_id, vec![], Some(vec![offset(1, 20)]))
+                .await
+                .unwrap();
+            meta_store
+                .activate_chunks(table1_id, vec![], None)
+                .await
+                .unwrap();
+            assert_eq!(
+                meta_store
+                    .get_table_by_id(table1_id)
+                    .await
+                    .unwrap()
+                    .get_row()
+                    .stream_offsets(),
+                &Some(vec![offset(0, 5), offset(1, 20)])
+            );
+            let in_memory_chunk = meta_store.create_chunk(1, 10, true).await.unwrap();
+            assert!(meta_store
+                .activate_chunks(
+                    table1_id,
+                    vec![(in_memory_chunk.get_id(), None)],
+                    Some(vec![offset(0, 15)])
+                )
+                .await
+                .is_err());
+        }
+        let _ = fs::remove_dir_all(store_path.clone());
+        let _ = fs::remove_dir_all(remote_store_path.clone());
+    }
+
+    #[tokio::test]
+    async fn altered_index_test() {
+        let config = Config::test("altered_index_test");
+        let store_path = env::current_dir().unwrap().join("test-altered-index-local");
+        let remote_store_path = env::current_dir()
+            .unwrap()
+            .join("test-altered-index-remote");
+        let _ = fs::remove_dir_all(store_path.clone());
+        let _ = fs::remove_dir_all(remote_store_path.clone());
+        let remote_fs = LocalDirRemoteFs::new(Some(remote_store_path.clone()), store_path.clone());
+        {
+            let meta_store = RocksMetaStore::new(
+                store_path.clone().join("metastore").as_path(),
+                remote_fs,
+                config.config_obj(),
+            )
+            .unwrap();
+            meta_store
+                .create_schema("foo".to_string(), false)
+                .await
+                .unwrap();
+            let table = meta_store
+                .create_table(
+                    "foo".to_string(),
+                    "boo".to_string(),
+                    vec![Column::new("col1".to_string(), ColumnType::Int, 0)],
+                    None,
+                    None,
+                    vec![],
+                    true,
+                    None,
+                    None,
+                    None,
+                    ParquetSettings::default(),
+                )
+                .await
+                .unwrap();
+            let index = meta_store.get_default_index(table.get_id()).await.unwrap();
+            let partition = meta_store
+                .get_active_partitions_by_index_id(index.get_id())
+                .await
+                .unwrap()
+                .remove(0);
+
+            // Writes a partition file with chunk rows of the partition.
+            let compact = |partition: IdRow<Partition>, chunks: Vec<IdRow<Chunk>>| {
+                let meta_store = meta_store.clone();
+                async move {
+                    let rows = partition.get_row().main_table_row_count()
+                        + chunks
+                            .iter()
+                            .map(|c| c.get_row().get_row_count())
+                            .sum::<u64>();
+                    let new = meta_store
+                        .create_partition(Partition::new_child(&partition, None))
+                        .await
+                        .unwrap();
+                    meta_store
+                        .swap_active_partitions(
+                            vec![(partition, chunks)],
+                            vec![(new.clone(), 1)],
+                            vec![(rows, (None, None))],
+                        )
+                        .await
+                        .unwrap();
+                    meta_store.get_partition(new.get_id()).await.unwrap()
+                }
+            };
+
+            let chunk = meta_store
+                .create_chunk(partition.get_id(), 5, false)
+                .await
+                .unwrap();
+            meta_store
+                .activate_chunks(table.get_id(), vec![(chunk.get_id(), Some(1))], None)
+                .await
+                .unwrap();
+            let chunk = meta_store.get_chunk(chunk.get_id()).await.unwrap();
+            let partition = compact(partition, vec![chunk]).await;
+
+            meta_store
+                .add_column(
+                    "foo".to_string(),
+                    "boo".to_string(),
+                    Column::new("col2".to_string(), ColumnType::Int, 1),
+                )
+                .await
+                .unwrap();
+            assert!(meta_store
+                .get_index(index.get_id())
+                .await
+                .unwrap()
+                .get_row()
+                .altered());
+            let altered = meta_store.get_altered_partitions().await.unwrap();
+            assert_eq!(
+                altered.iter().map(|p| p.get_id()).collect_vec(),
+                vec![partition.get_id()]
+            );
+
+            // Rewriting the last partition written with old columns clears the flag.
+            compact(partition, vec![]).await;
+            assert!(!meta_store
+                .get_index(index.get_id())
+                .await
+                .unwrap()
+                .get_row()
+                .altered());
+            assert!(meta_store
+                .get_altered_partitions()
+                .await
+                .unwrap()
+                .is_empty());
         }
         let _ = fs::remove_dir_all(store_path.clone());
         let _ = fs::remove_dir_all(remote_store_path.clone());
@@ -5480,6 +6602,278 @@ mod tests {
             fs::remove_dir_all(config.remote_dir()).unwrap();
         }
     }
+
+    #[tokio::test]
+    async fn restore_from_checkpoint() {
+        let checkpoint;
+        {
+            let config = Config::test("restore_from_checkpoint");
+
+            let _ = fs::remove_dir_all(config.local_dir());
+            let _ = fs::remove_dir_all(config.remote_dir());
+
+            let services = config.configure().await;
+            services.start_processing_loops().await.unwrap();
+            let rocks_meta_store = services.rocks_meta_store.as_ref().unwrap();
+            services
+                .meta_store
+                .create_schema("foo1".to_string(), false)
+                .await
+                .unwrap();
+            rocks_meta_store.upload_check_point().await.unwrap();
+            Delay::new(Duration::from_millis(10)).await;
+            services
+                .meta_store
+                .create_schema("foo".to_string(), false)
+                .await
+                .unwrap();
+            rocks_meta_store.upload_check_point().await.unwrap();
+
+            let checkpoints = RocksMetaStore::list_checkpoints(rocks_meta_store.remote_fs.clone())
+                .await
+                .unwrap();
+            assert_eq!(checkpoints.len(), 2);
+            assert!(!checkpoints[0].current);
+            assert!(checkpoints[1].current);
+            assert!(checkpoints[0].files > 0);
+            checkpoint = checkpoints[0].name.clone();
+
+            services.stop_processing_loops().await.unwrap();
+            Delay::new(Duration::from_millis(1000)).await; // TODO logger init conflict
+        }
+
+        {
+            let config = Config::test("restore_from_checkpoint").update_config(|c| ConfigObjImpl {
+                meta_store_restore_checkpoint: Some(checkpoint.clone()),
+                ..c
+            });
+
+            let services = config.configure().await;
+            services
+                .meta_store
+                .get_schema("foo1".to_string())
+                .await
+                .unwrap();
+            assert!(services
+                .meta_store
+                .get_schema("foo".to_string())
+                .await
+                .is_err());
+
+            let checkpoints = RocksMetaStore::list_checkpoints(
+                services
+                    .rocks_meta_store
+                    .as_ref()
+                    .unwrap()
+                    .remote_fs
+                    .clone(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(checkpoints.len(), 3);
+            assert!(checkpoints[2].current);
+
+            let local_dir = config.local_dir().clone();
+            let backups = fs::read_dir(local_dir.clone())
+                .unwrap()
+                .filter(|e| {
+                    let name = e.as_ref().unwrap().file_name();
+                    name.to_string_lossy().starts_with("metastore-backup-")
+                })
+                .count();
+            assert_eq!(backups, 1);
+
+            // Restarts with the same config keep the restored metastore.
+            let remote_fs = services
+                .rocks_meta_store
+                .as_ref()
+                .unwrap()
+                .remote_fs
+                .clone();
+            assert_eq!(
+                RocksMetaStore::read_remote_file(&remote_fs, METASTORE_RESTORED_FILE)
+                    .await
+                    .unwrap(),
+                Some(checkpoint.clone())
+            );
+            assert!(RocksMetaStore::restore_from_checkpoint(
+                &config.local_dir().join("metastore"),
+                &checkpoint,
+                remote_fs,
+                config.config_obj(),
+            )
+            .await
+            .unwrap()
+            .is_none());
+
+            fs::remove_dir_all(local_dir).unwrap();
+            fs::remove_dir_all(config.remote_dir()).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn restore_dropped_table() {
+        let config = Config::test("restore_dropped_table").update_config(|c| ConfigObjImpl {
+            not_used_timeout: 0,
+            meta_store_log_upload_interval: 1,
+            meta_store_checkpoint_retention: 3600,
+            gc_loop_interval: 1,
+            ..c
+        });
+        let _ = fs::remove_dir_all(config.local_dir());
+        let _ = fs::remove_dir_all(config.remote_dir());
+
+        let checkpoint;
+        {
+            let services = config.configure().await;
+            services.start_processing_loops().await.unwrap();
+            let service = services.sql_service.clone();
+            service.exec_query("CREATE SCHEMA foo").await.unwrap();
+            service
+                .exec_query("CREATE TABLE foo.numbers (num int)")
+                .await
+                .unwrap();
+            service
+                .exec_query("INSERT INTO foo.numbers (num) VALUES (1), (2)")
+                .await
+                .unwrap();
+
+            let rocks_meta_store = services.rocks_meta_store.as_ref().unwrap();
+            rocks_meta_store.upload_check_point().await.unwrap();
+            let checkpoints = RocksMetaStore::list_checkpoints(rocks_meta_store.remote_fs.clone())
+                .await
+                .unwrap();
+            checkpoint = checkpoints.iter().find(|c| c.current).unwrap().name.clone();
+
+            service.exec_query("DROP TABLE foo.numbers").await.unwrap();
+            // Longer than GC took to remove files of dropped tables without checkpoint retention.
+            Delay::new(Duration::from_millis(4000)).await;
+
+            services.stop_processing_loops().await.unwrap();
+            Delay::new(Duration::from_millis(1000)).await; // TODO logger init conflict
+        }
+
+        {
+            let config = Config::test("restore_dropped_table").update_config(|c| ConfigObjImpl {
+                meta_store_restore_checkpoint: Some(checkpoint.clone()),
+                ..c
+            });
+            // Restore fails if files of the table were removed.
+            let services = config.configure().await;
+            services.start_processing_loops().await.unwrap();
+            let r = services
+                .sql_service
+                .exec_query("SELECT count(*) FROM foo.numbers")
+                .await
+                .unwrap();
+            assert_eq!(r.get_rows(), &vec![Row::new(vec![TableValue::Int(2)])]);
+
+            services.stop_processing_loops().await.unwrap();
+            Delay::new(Duration::from_millis(1000)).await; // TODO logger init conflict
+            fs::remove_dir_all(config.local_dir()).unwrap();
+            fs::remove_dir_all(config.remote_dir()).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn cleanup_keeps_files_of_checkpoints() {
+        let config = Config::test("cleanup_keeps_files_of_checkpoints");
+        let _ = fs::remove_dir_all(config.local_dir());
+        let _ = fs::remove_dir_all(config.remote_dir());
+
+        let files;
+        {
+            let services = config.configure().await;
+            services.start_processing_loops().await.unwrap();
+            let service = services.sql_service.clone();
+            service.exec_query("CREATE SCHEMA foo").await.unwrap();
+            service
+                .exec_query("CREATE TABLE foo.numbers (num int)")
+                .await
+                .unwrap();
+            service
+                .exec_query("INSERT INTO foo.numbers (num) VALUES (1), (2)")
+                .await
+                .unwrap();
+            let rocks_meta_store = services.rocks_meta_store.as_ref().unwrap();
+            rocks_meta_store.upload_check_point().await.unwrap();
+
+            files = services
+                .remote_fs
+                .list("")
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|f| f.ends_with(".parquet"))
+                .collect::<Vec<_>>();
+            assert!(!files.is_empty());
+            service.exec_query("DROP TABLE foo.numbers").await.unwrap();
+
+            services.stop_processing_loops().await.unwrap();
+            Delay::new(Duration::from_millis(1000)).await; // TODO logger init conflict
+        }
+
+        {
+            // Removals scheduled by the GC are lost on restart, only the checkpoint refers to
+            // files of the dropped table.
+            let config = Config::test("cleanup_keeps_files_of_checkpoints").update_config(|c| {
+                ConfigObjImpl {
+                    remote_files_cleanup_delay: 0,
+                    ..c
+                }
+            });
+            let services = config.configure().await;
+            services.start_processing_loops().await.unwrap();
+            let orphaned = "1000000-abcdefgh.parquet";
+            fs::write(config.remote_dir().join(orphaned), "data").unwrap();
+            Delay::new(Duration::from_millis(10)).await;
+
+            let removed = services
+                .scheduler
+                .remove_orphaned_remote_files(false)
+                .await
+                .unwrap();
+            assert_eq!(
+                removed.iter().map(|f| f.remote_path()).collect::<Vec<_>>(),
+                vec![orphaned]
+            );
+            for f in files.iter() {
+                assert!(config.remote_dir().join(f).exists(), "{} is removed", f);
+            }
+
+            services.stop_processing_loops().await.unwrap();
+            Delay::new(Duration::from_millis(1000)).await; // TODO logger init conflict
+            fs::remove_dir_all(config.local_dir()).unwrap();
+            fs::remove_dir_all(config.remote_dir()).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn restore_from_missing_checkpoint() {
+        let config =
+            Config::test("restore_from_missing_checkpoint").update_config(|c| ConfigObjImpl {
+                meta_store_restore_checkpoint: Some("metastore-1".to_string()),
+                ..c
+            });
+        let _ = fs::remove_dir_all(config.local_dir());
+        let _ = fs::remove_dir_all(config.remote_dir());
+        let remote_fs = LocalDirRemoteFs::new(
+            Some(config.remote_dir().clone()),
+            config.local_dir().clone(),
+        );
+
+        let res = RocksMetaStore::load_from_remote(
+            config.meta_store_path(),
+            remote_fs,
+            config.config_obj(),
+        )
+        .await;
+        assert!(res.is_err());
+        assert!(!config.meta_store_path().exists());
+
+        let _ = fs::remove_dir_all(config.local_dir());
+        let _ = fs::remove_dir_all(config.remote_dir());
+    }
 }
 
 impl RocksMetaStore {
@@ -14,6 +14,28 @@ pub enum SourceCredentials {
         password: Option<String>,
         url: String,
     },
+    Kafka {
+        user: Option<String>,
+        password: Option<String>,
+        /// Comma separated list of bootstrap servers.
+        host: String,
+        use_ssl: bool,
+        /// Consumer group of each table is this prefix followed by the table id.
+        group_id: Option<String>,
+        value_format: KafkaValueFormat,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Hash, PartialEq)]
+pub enum KafkaValueFormat {
+    /// JSON objects with column names as keys.
+    Json,
+    /// Records with fields named after columns. The schema is either fixed or resolved through
+    /// the schema registry for values in the Confluent wire format.
+    Avro {
+        schema: Option<String>,
+        schema_registry_url: Option<String>,
+    },
 }
 
 impl DataFrameValue<String> for SourceCredentials {
@@ -4,6 +4,7 @@ use super::{
 use crate::data_frame_from;
 use crate::metastore::{IdRow, ImportFormat, MetaStoreEvent, Schema};
 use crate::rocks_table_impl;
+use crate::table::TableValue;
 use crate::{base_rocks_secondary_index, CubeError};
 use byteorder::{BigEndian, WriteBytesExt};
 use chrono::DateTime;
@@ -12,6 +13,7 @@ use itertools::Itertools;
 use rocksdb::DB;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::io::Write;
+use std::str::FromStr;
 use std::sync::Arc;
 
 data_frame_from! {
@@ -36,8 +38,76 @@ pub struct Table {
     #[serde(default)]
     location_download_sizes: Option<Vec<u64>>,
     #[serde(default)]
-    partition_split_threshold: Option<u64>
+    partition_split_threshold: Option<u64>,
+    #[serde(default)]
+    ttl_seconds: Option<u64>,
+    #[serde(default)]
+    parquet_settings: ParquetSettings,
+    #[serde(default)]
+    stream_offsets: Option<Vec<StreamOffset>>,
+    #[serde(default)]
+    deleted_column_index: Option<u64>
+}
 }
+
+/// Position of a streaming table in a partition of its source: the offset of the next row to
+/// consume.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
+pub struct StreamOffset {
+    pub partition: u64,
+    pub offset: u64,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
+pub enum ParquetCompression {
+    Uncompressed,
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+}
+
+impl FromStr for ParquetCompression {
+    type Err = CubeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "none" | "uncompressed" => ParquetCompression::Uncompressed,
+            "snappy" => ParquetCompression::Snappy,
+            "gzip" => ParquetCompression::Gzip,
+            "lz4" => ParquetCompression::Lz4,
+            "zstd" => ParquetCompression::Zstd,
+            _ => {
+                return Err(CubeError::user(format!(
+                    "Unknown parquet compression: '{}'",
+                    s
+                )))
+            }
+        })
+    }
+}
+
+/// Settings of parquet files written for the table. Unset values fall back to server defaults
+/// and then to defaults of the parquet writer.
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Default)]
+pub struct ParquetSettings {
+    #[serde(default)]
+    pub compression: Option<ParquetCompression>,
+    #[serde(default)]
+    pub dictionary_enabled: Option<bool>,
+    #[serde(default)]
+    pub statistics_enabled: Option<bool>,
+}
+
+impl ParquetSettings {
+    /// Values of [self] take precedence over [defaults].
+    pub fn or(&self, defaults: &ParquetSettings) -> ParquetSettings {
+        ParquetSettings {
+            compression: self.compression.or(defaults.compression),
+            dictionary_enabled: self.dictionary_enabled.or(defaults.dictionary_enabled),
+            statistics_enabled: self.statistics_enabled.or(defaults.statistics_enabled),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -65,6 +135,8 @@ impl Table {
         unique_key_column_indices: Option<Vec<u64>>,
         seq_column_index: Option<u64>,
         partition_split_threshold: Option<u64>,
+        ttl_seconds: Option<u64>,
+        parquet_settings: ParquetSettings,
     ) -> Table {
         let location_download_sizes = locations.as_ref().map(|locations| vec![0; locations.len()]);
         Table {
@@ -80,6 +152,10 @@ impl Table {
             seq_column_index,
             location_download_sizes,
             partition_split_threshold,
+            ttl_seconds,
+            parquet_settings,
+            stream_offsets: None,
+            deleted_column_index: None,
         }
     }
     pub fn get_columns(&self) -> &Vec<Column> {
@@ -116,6 +192,29 @@ impl Table {
         self.is_ready
     }
 
+    pub fn add_column(&self, column: Column) -> Self {
+        let mut table = self.clone();
+        let column_index = table.columns.len();
+        table.columns.push(column.replace_index(column_index));
+        table
+    }
+
+    pub fn rename_column(&self, old_name: &str, new_name: &str) -> Self {
+        let mut table = self.clone();
+        for c in table.columns.iter_mut() {
+            if c.get_name() == old_name {
+                *c = c.replace_name(new_name.to_string());
+            }
+        }
+        table
+    }
+
+    pub fn rename(&self, table_name: String) -> Self {
+        let mut table = self.clone();
+        table.table_name = table_name;
+        table
+    }
+
     pub fn update_is_ready(&self, is_ready: bool) -> Self {
         let mut table = self.clone();
         table.is_ready = is_ready;
@@ -174,6 +273,36 @@ impl Table {
             .map(|c| &self.columns[*c as usize])
     }
 
+    /// Hidden column that marks tombstones written by DELETE. Only present in tables with a
+    /// unique key after the first DELETE.
+    pub fn deleted_column(&self) -> Option<&Column> {
+        self.deleted_column_index
+            .as_ref()
+            .map(|c| &self.columns[*c as usize])
+    }
+
+    pub fn add_deleted_column(&self) -> Self {
+        let mut table = self.clone();
+        let column_index = table.columns.len();
+        table.columns.push(Column::new(
+            "__deleted".to_string(),
+            ColumnType::Boolean,
+            column_index,
+        ));
+        table.deleted_column_index = Some(column_index as u64);
+        table
+    }
+
+    /// Columns provided by writers of the table and seen by readers. The deleted column is filled
+    /// with NULLs when omitted and is never shown.
+    pub fn insertable_columns(&self) -> Vec<Column> {
+        self.columns
+            .iter()
+            .filter(|c| Some(c.get_index() as u64) != self.deleted_column_index)
+            .cloned()
+            .collect()
+    }
+
     pub fn in_memory_ingest(&self) -> bool {
         self.seq_column_index.is_some()
     }
@@ -195,6 +324,45 @@ impl Table {
             .map(|v| *v)
             .unwrap_or(config_partition_split_threshold)
     }
+
+    pub fn ttl_seconds(&self) -> &Option<u64> {
+        &self.ttl_seconds
+    }
+
+    pub fn parquet_settings(&self) -> &ParquetSettings {
+        &self.parquet_settings
+    }
+
+    pub fn stream_offsets(&self) -> &Option<Vec<StreamOffset>> {
+        &self.stream_offsets
+    }
+
+    /// Offsets of partitions missing in [offsets] are kept.
+    pub fn update_stream_offsets(&self, offsets: &[StreamOffset]) -> Self {
+        let mut table = self.clone();
+        let stream_offsets = table.stream_offsets.get_or_insert_with(Vec::new);
+        for offset in offsets {
+            match stream_offsets
+                .iter_mut()
+                .find(|o| o.partition == offset.partition)
+            {
+                Some(o) => o.offset = offset.offset,
+                None => stream_offsets.push(*offset),
+            }
+        }
+        stream_offsets.sort_by_key(|o| o.partition);
+        table
+    }
+
+    /// Tables without ttl or creation time never expire.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        match (self.ttl_seconds, self.created_at.as_ref()) {
+            (Some(ttl), Some(created_at)) => {
+                now.signed_duration_since(created_at.clone()).num_seconds() >= ttl as i64
+            }
+            _ => false,
+        }
+    }
 }
 
 impl Column {
@@ -203,6 +371,7 @@ impl Column {
             name,
             column_type,
             column_index,
+            default_value: None,
         }
     }
     pub fn get_name(&self) -> &String {
@@ -222,6 +391,26 @@ impl Column {
             name: self.name.clone(),
             column_type: self.column_type.clone(),
             column_index,
+            default_value: self.default_value.clone(),
+        }
+    }
+
+    pub fn replace_name(&self, name: String) -> Column {
+        Column {
+            name,
+            ..self.clone()
+        }
+    }
+
+    /// Value of the column in rows written before it was added to the table.
+    pub fn default_value(&self) -> Option<&TableValue> {
+        self.default_value.as_ref()
+    }
+
+    pub fn with_default_value(self, default_value: Option<TableValue>) -> Column {
+        Column {
+            default_value,
+            ..self
         }
     }
 }
@@ -1,13 +1,16 @@
 use crate::config::processing_loop::ProcessingLoop;
+use crate::sql::parser::{PlaceholderValue, PreparedStatement};
 use crate::sql::{SqlQueryContext, SqlService};
 use crate::table::TableValue;
 use crate::util::time_span::warn_long;
 use crate::{metastore, CubeError};
 use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
 use datafusion::cube_ext;
 use hex::ToHex;
 use log::{error, info, warn};
 use msql_srv::*;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::io;
 use std::sync::Arc;
@@ -20,40 +23,16 @@ struct Backend {
     sql_service: Arc<dyn SqlService>,
     auth: Arc<dyn SqlAuthService>,
     user: Option<String>,
+    statements: HashMap<u32, PreparedStatement>,
+    next_statement_id: u32,
 }
 
-#[async_trait]
-impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
-    type Error = io::Error;
-
-    async fn on_prepare<'a>(
-        &'a mut self,
-        _query: &'a str,
-        info: StatementMetaWriter<'a, W>,
-    ) -> Result<(), Self::Error> {
-        info.reply(42, &[], &[])
-    }
-
-    async fn on_execute<'a>(
-        &'a mut self,
-        _id: u32,
-        _params: ParamParser<'a>,
-        results: QueryResultWriter<'a, W>,
-    ) -> Result<(), Self::Error> {
-        results.completed(0, 0)
-    }
-
-    async fn on_close<'a>(&'a mut self, _stmt: u32)
-    where
-        W: 'async_trait,
-    {
-    }
-
-    async fn on_query<'a>(
+impl Backend {
+    async fn execute<'a, W: io::Write + Send>(
         &'a mut self,
         query: &'a str,
         results: QueryResultWriter<'a, W>,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), io::Error> {
         let start = SystemTime::now();
         let res = self
             .sql_service
@@ -79,44 +58,27 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
         let columns = data_frame
             .get_columns()
             .iter()
-            .map(|c| Column {
-                table: "result".to_string(), // TODO
+            .enumerate()
+            .map(|(i, c)| Column {
+                table: data_frame.source_table(i).unwrap_or("").to_string(),
                 column: c.get_name().to_string(),
-                coltype: match c.get_column_type() {
-                    metastore::ColumnType::String => ColumnType::MYSQL_TYPE_STRING,
-                    metastore::ColumnType::Timestamp => ColumnType::MYSQL_TYPE_STRING,
-                    metastore::ColumnType::Int => ColumnType::MYSQL_TYPE_LONGLONG,
-                    metastore::ColumnType::Decimal { .. } => ColumnType::MYSQL_TYPE_DECIMAL,
-                    metastore::ColumnType::Boolean => ColumnType::MYSQL_TYPE_STRING,
-                    metastore::ColumnType::Bytes => ColumnType::MYSQL_TYPE_STRING,
-                    metastore::ColumnType::HyperLogLog(_) => ColumnType::MYSQL_TYPE_STRING,
-                    metastore::ColumnType::Float => ColumnType::MYSQL_TYPE_STRING,
-                },
-                colflags: ColumnFlags::empty(),
+                coltype: mysql_column_type(c.get_column_type()),
+                colflags: mysql_column_flags(c.get_column_type()),
+                column_length: mysql_column_length(c.get_column_type()),
+                decimals: mysql_column_decimals(c.get_column_type()),
             })
             .collect::<Vec<_>>();
 
+        // Results of prepared statements are written in the binary protocol, so values must
+        // match their column types.
         let mut rw = results.start(&columns)?;
         for row in data_frame.get_rows().iter() {
             for (i, value) in row.values().iter().enumerate() {
-                match value {
-                    TableValue::String(s) => rw.write_col(s)?,
-                    TableValue::Timestamp(s) => rw.write_col(s.to_string())?,
-                    TableValue::Int(i) => rw.write_col(i)?,
-                    TableValue::Decimal(v) => {
-                        let scale = u8::try_from(
-                            data_frame.get_columns()[i].get_column_type().target_scale(),
-                        )
-                        .unwrap();
-                        rw.write_col(v.to_string(scale))?
-                    }
-                    TableValue::Boolean(v) => rw.write_col(v.to_string())?,
-                    TableValue::Float(v) => rw.write_col(v.to_string())?,
-                    TableValue::Bytes(b) => {
-                        rw.write_col(format!("0x{}", b.encode_hex_upper::<String>()))?
-                    }
-                    TableValue::Null => rw.write_col(Option::<String>::None)?,
-                }
+                write_value(
+                    &mut rw,
+                    value,
+                    data_frame.get_columns()[i].get_column_type(),
+                )?;
             }
             rw.end_row()?;
         }
@@ -131,6 +93,82 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
         }
         Ok(())
     }
+}
+
+#[async_trait]
+impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
+    type Error = io::Error;
+
+    async fn on_prepare<'a>(
+        &'a mut self,
+        query: &'a str,
+        info: StatementMetaWriter<'a, W>,
+    ) -> Result<(), Self::Error> {
+        let statement = match PreparedStatement::new(query) {
+            Ok(s) => s,
+            Err(e) => {
+                let e = CubeError::from(e);
+                error!("Error during preparing {}: {}", query, e);
+                return info.error(ErrorKind::ER_PARSE_ERROR, e.message.as_bytes());
+            }
+        };
+        let id = self.next_statement_id;
+        self.next_statement_id = self.next_statement_id.wrapping_add(1);
+        // Parameter types are not inferred, clients send values with their own types.
+        let params = (0..statement.num_params())
+            .map(|_| Column {
+                table: String::new(),
+                column: "?".to_string(),
+                coltype: ColumnType::MYSQL_TYPE_VAR_STRING,
+                colflags: ColumnFlags::empty(),
+                column_length: STRING_COLUMN_LENGTH,
+                decimals: 0,
+            })
+            .collect::<Vec<_>>();
+        self.statements.insert(id, statement);
+        info.reply(id, &params, &[])
+    }
+
+    async fn on_execute<'a>(
+        &'a mut self,
+        id: u32,
+        params: ParamParser<'a>,
+        results: QueryResultWriter<'a, W>,
+    ) -> Result<(), Self::Error> {
+        let statement = match self.statements.get(&id) {
+            Some(s) => s,
+            None => {
+                return results.error(
+                    ErrorKind::ER_UNKNOWN_STMT_HANDLER,
+                    format!("Unknown prepared statement {}", id).as_bytes(),
+                )
+            }
+        };
+        let query = params
+            .into_iter()
+            .map(|p| placeholder_value(p.value, p.coltype))
+            .collect::<Result<Vec<_>, CubeError>>()
+            .and_then(|params| Ok(statement.bind(&params)?));
+        match query {
+            Ok(query) => self.execute(&query, results).await,
+            Err(e) => results.error(ErrorKind::ER_WRONG_ARGUMENTS, e.message.as_bytes()),
+        }
+    }
+
+    async fn on_close<'a>(&'a mut self, stmt: u32)
+    where
+        W: 'async_trait,
+    {
+        self.statements.remove(&stmt);
+    }
+
+    async fn on_query<'a>(
+        &'a mut self,
+        query: &'a str,
+        results: QueryResultWriter<'a, W>,
+    ) -> Result<(), Self::Error> {
+        self.execute(query, results).await
+    }
 
     async fn on_auth<'a>(&'a mut self, user: Vec<u8>) -> Result<Option<Vec<u8>>, Self::Error>
     where
@@ -195,6 +233,8 @@ impl ProcessingLoop for MySqlServer {
                         sql_service,
                         auth,
                         user: None,
+                        statements: HashMap::new(),
+                        next_statement_id: 1,
                     },
                     socket,
                 )
@@ -229,6 +269,165 @@ impl MySqlServer {
     }
 }
 
+fn mysql_column_type(t: &metastore::ColumnType) -> ColumnType {
+    match t {
+        metastore::ColumnType::String => ColumnType::MYSQL_TYPE_STRING,
+        metastore::ColumnType::Timestamp => ColumnType::MYSQL_TYPE_DATETIME,
+        metastore::ColumnType::Int => ColumnType::MYSQL_TYPE_LONGLONG,
+        metastore::ColumnType::Decimal { .. } => ColumnType::MYSQL_TYPE_NEWDECIMAL,
+        metastore::ColumnType::Boolean => ColumnType::MYSQL_TYPE_TINY,
+        metastore::ColumnType::Bytes => ColumnType::MYSQL_TYPE_BLOB,
+        metastore::ColumnType::HyperLogLog(_) => ColumnType::MYSQL_TYPE_BLOB,
+        metastore::ColumnType::Float => ColumnType::MYSQL_TYPE_DOUBLE,
+        metastore::ColumnType::Int16 => ColumnType::MYSQL_TYPE_SHORT,
+        metastore::ColumnType::Int32 => ColumnType::MYSQL_TYPE_LONG,
+        metastore::ColumnType::Float32 => ColumnType::MYSQL_TYPE_FLOAT,
+        metastore::ColumnType::Date => ColumnType::MYSQL_TYPE_DATE,
+    }
+}
+
+/// Length reported for columns without a fixed maximum length.
+const STRING_COLUMN_LENGTH: u32 = 1024;
+
+/// Maximum display width of values, as MySQL reports it. Clients use it with [mysql_column_decimals]
+/// to get precision and scale of decimals.
+fn mysql_column_length(t: &metastore::ColumnType) -> u32 {
+    match t {
+        metastore::ColumnType::Decimal { precision, .. } => {
+            let scale = t.target_scale() as u32;
+            // Digits, sign and decimal point.
+            (*precision as u32).max(scale) + 1 + if scale > 0 { 1 } else { 0 }
+        }
+        metastore::ColumnType::Int => 20,
+        metastore::ColumnType::Int32 => 11,
+        metastore::ColumnType::Int16 => 6,
+        metastore::ColumnType::Boolean => 1,
+        metastore::ColumnType::Float => 22,
+        metastore::ColumnType::Float32 => 12,
+        metastore::ColumnType::Date => 10,
+        // Timestamps are stored with millisecond precision.
+        metastore::ColumnType::Timestamp => 23,
+        metastore::ColumnType::String
+        | metastore::ColumnType::Bytes
+        | metastore::ColumnType::HyperLogLog(_) => STRING_COLUMN_LENGTH,
+    }
+}
+
+/// Scale of decimals as written by [write_value], MySQL reports 31 for floating point numbers.
+fn mysql_column_decimals(t: &metastore::ColumnType) -> u8 {
+    match t {
+        metastore::ColumnType::Decimal { .. } => t.target_scale() as u8,
+        metastore::ColumnType::Float | metastore::ColumnType::Float32 => 31,
+        metastore::ColumnType::Timestamp => 3,
+        _ => 0,
+    }
+}
+
+fn mysql_column_flags(t: &metastore::ColumnType) -> ColumnFlags {
+    match t {
+        metastore::ColumnType::Bytes | metastore::ColumnType::HyperLogLog(_) => {
+            ColumnFlags::BLOB_FLAG | ColumnFlags::BINARY_FLAG
+        }
+        _ => ColumnFlags::empty(),
+    }
+}
+
+/// Writes [value] as the type chosen by [mysql_column_type] for [column_type].
+fn write_value<W: io::Write>(
+    rw: &mut RowWriter<W>,
+    value: &TableValue,
+    column_type: &metastore::ColumnType,
+) -> io::Result<()> {
+    match (value, column_type) {
+        (TableValue::Null, _) => rw.write_col(Option::<String>::None),
+        (TableValue::Int(v), metastore::ColumnType::Int) => rw.write_col(*v),
+        (TableValue::Int(v), metastore::ColumnType::Int16) => rw.write_col(*v as i16),
+        (TableValue::Int(v), metastore::ColumnType::Int32) => rw.write_col(*v as i32),
+        (TableValue::Timestamp(t), metastore::ColumnType::Date) => {
+            rw.write_col(Utc.timestamp_nanos(t.get_time_stamp()).naive_utc().date())
+        }
+        (TableValue::Timestamp(t), metastore::ColumnType::Timestamp) => {
+            rw.write_col(Utc.timestamp_nanos(t.get_time_stamp()).naive_utc())
+        }
+        (TableValue::Boolean(v), metastore::ColumnType::Boolean) => rw.write_col(*v as i8),
+        (TableValue::Float(v), metastore::ColumnType::Float) => rw.write_col(v.0),
+        (TableValue::Float(v), metastore::ColumnType::Float32) => rw.write_col(v.0 as f32),
+        (TableValue::Bytes(b), metastore::ColumnType::Bytes)
+        | (TableValue::Bytes(b), metastore::ColumnType::HyperLogLog(_)) => {
+            rw.write_col(b.as_slice())
+        }
+        (TableValue::String(s), _) => rw.write_col(s),
+        (TableValue::Timestamp(t), _) => rw.write_col(t.to_string()),
+        (TableValue::Int(v), _) => rw.write_col(v.to_string()),
+        (TableValue::Decimal(v), _) => {
+            let scale = u8::try_from(column_type.target_scale()).unwrap();
+            rw.write_col(v.to_string(scale))
+        }
+        (TableValue::Boolean(v), _) => rw.write_col(v.to_string()),
+        (TableValue::Float(v), _) => rw.write_col(v.to_string()),
+        (TableValue::Bytes(b), _) => rw.write_col(format!("0x{}", b.encode_hex_upper::<String>())),
+    }
+}
+
+/// Converts a parameter of a prepared statement to the literal put in place of its placeholder.
+fn placeholder_value(v: Value, coltype: ColumnType) -> Result<PlaceholderValue, CubeError> {
+    Ok(match v.into_inner() {
+        ValueInner::NULL => PlaceholderValue::Null,
+        // Strings are sent as bytes too, binary values are told apart by their type.
+        ValueInner::Bytes(b) => match coltype {
+            ColumnType::MYSQL_TYPE_TINY_BLOB
+            | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+            | ColumnType::MYSQL_TYPE_LONG_BLOB
+            | ColumnType::MYSQL_TYPE_BLOB => PlaceholderValue::Bytes(b.to_vec()),
+            _ => match std::str::from_utf8(b) {
+                Ok(s) => PlaceholderValue::String(s.to_string()),
+                Err(_) => PlaceholderValue::Bytes(b.to_vec()),
+            },
+        },
+        ValueInner::Int(v) => PlaceholderValue::Number(v.to_string()),
+        ValueInner::UInt(v) => PlaceholderValue::Number(v.to_string()),
+        ValueInner::Double(v) => PlaceholderValue::Number(v.to_string()),
+        ValueInner::Date(b) | ValueInner::Datetime(b) => {
+            PlaceholderValue::String(binary_datetime_to_string(b)?)
+        }
+        ValueInner::Time(_) => {
+            return Err(CubeError::user(
+                "TIME parameters of prepared statements are not supported".to_string(),
+            ))
+        }
+    })
+}
+
+/// Formats DATE and DATETIME values of the binary protocol: year, month and day, optionally
+/// followed by hours, minutes, seconds and microseconds.
+fn binary_datetime_to_string(b: &[u8]) -> Result<String, CubeError> {
+    let invalid = || CubeError::user(format!("Invalid date parameter: {:?}", b));
+    match b.len() {
+        0 => Ok("0000-00-00".to_string()),
+        4 | 7 | 11 => {
+            let date = format!(
+                "{:04}-{:02}-{:02}",
+                u16::from_le_bytes([b[0], b[1]]),
+                b[2],
+                b[3]
+            );
+            if b.len() == 4 {
+                return Ok(date);
+            }
+            let micros = if b.len() == 11 {
+                u32::from_le_bytes([b[7], b[8], b[9], b[10]])
+            } else {
+                0
+            };
+            Ok(format!(
+                "{}T{:02}:{:02}:{:02}.{:06}",
+                date, b[4], b[5], b[6], micros
+            ))
+        }
+        _ => Err(invalid()),
+    }
+}
+
 #[async_trait]
 pub trait SqlAuthService: Send + Sync {
     async fn authenticate(&self, user: Option<String>) -> Result<Option<String>, CubeError>;
@@ -244,3 +443,510 @@ impl SqlAuthService for SqlAuthDefaultImpl {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metastore::Column as TableColumn;
+    use crate::sql::{QueryPlans, QueryResultStream};
+    use crate::store::DataFrame;
+    use crate::table::{Row, TimestampValue};
+    use crate::util::decimal::Decimal;
+    use datafusion::cube_ext::ordfloat::OrdF64;
+    use std::path::Path;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// Records executed queries and answers all of them with the same row.
+    pub struct RecordingSqlService {
+        queries: Mutex<Vec<String>>,
+    }
+
+    crate::di_service!(RecordingSqlService, [SqlService]);
+
+    #[async_trait]
+    impl SqlService for RecordingSqlService {
+        async fn exec_query(&self, query: &str) -> Result<Arc<DataFrame>, CubeError> {
+            self.exec_query_with_context(
+                SqlQueryContext {
+                    user: None,
+                    trace_obj: None,
+                },
+                query,
+            )
+            .await
+        }
+
+        async fn exec_query_with_context(
+            &self,
+            _context: SqlQueryContext,
+            query: &str,
+        ) -> Result<Arc<DataFrame>, CubeError> {
+            self.queries.lock().unwrap().push(query.to_string());
+            let columns = vec![
+                TableColumn::new("id".to_string(), metastore::ColumnType::Int, 0),
+                TableColumn::new("name".to_string(), metastore::ColumnType::String, 1),
+                TableColumn::new("data".to_string(), metastore::ColumnType::Bytes, 2),
+                TableColumn::new("time".to_string(), metastore::ColumnType::Timestamp, 3),
+                TableColumn::new("flag".to_string(), metastore::ColumnType::Boolean, 4),
+                TableColumn::new("value".to_string(), metastore::ColumnType::Float, 5),
+                TableColumn::new(
+                    "amount".to_string(),
+                    metastore::ColumnType::Decimal {
+                        scale: 2,
+                        precision: 10,
+                    },
+                    6,
+                ),
+            ];
+            let row = Row::new(vec![
+                TableValue::Int(-5),
+                TableValue::Null,
+                TableValue::Bytes(vec![0xff, 0x00]),
+                // 2021-01-02T03:04:05Z
+                TableValue::Timestamp(TimestampValue::new(1609556645 * 1_000_000_000)),
+                TableValue::Boolean(true),
+                TableValue::Float(OrdF64(1.5)),
+                TableValue::Decimal(Decimal::new(12345)),
+            ]);
+            let mut source_tables = vec![Some("t".to_string()); columns.len()];
+            source_tables[5] = None;
+            Ok(Arc::new(
+                DataFrame::new(columns, vec![row]).with_source_tables(source_tables),
+            ))
+        }
+
+        async fn exec_query_stream_with_context(
+            &self,
+            _context: SqlQueryContext,
+            _query: &str,
+        ) -> Result<QueryResultStream, CubeError> {
+            unimplemented!()
+        }
+
+        async fn plan_query(&self, _query: &str) -> Result<QueryPlans, CubeError> {
+            unimplemented!()
+        }
+
+        async fn upload_temp_file(
+            &self,
+            _context: SqlQueryContext,
+            _name: String,
+            _file_path: &Path,
+        ) -> Result<(), CubeError> {
+            unimplemented!()
+        }
+
+        async fn temp_uploads_dir(&self, _context: SqlQueryContext) -> Result<String, CubeError> {
+            unimplemented!()
+        }
+    }
+
+    /// Column definition sent by the server: table, name, type code and flags.
+    #[derive(Debug)]
+    pub struct ColumnDef {
+        table: String,
+        name: String,
+        coltype: u8,
+        flags: u16,
+    }
+
+    /// Rows of binary resultsets, values are left in their wire encoding without length prefixes.
+    type BinaryRows = Vec<Vec<Option<Vec<u8>>>>;
+
+    /// Bare-bones client of the MySQL protocol that uses prepared statements only.
+    pub struct TestClient {
+        socket: TcpStream,
+        seq: u8,
+    }
+
+    impl TestClient {
+        pub async fn connect(sql_service: Arc<dyn SqlService>) -> TestClient {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                let (socket, _) = listener.accept().await.unwrap();
+                AsyncMysqlIntermediary::run_on(
+                    Backend {
+                        sql_service,
+                        auth: Arc::new(SqlAuthDefaultImpl),
+                        user: None,
+                        statements: HashMap::new(),
+                        next_statement_id: 1,
+                    },
+                    socket,
+                )
+                .await
+            });
+            let mut client = TestClient {
+                socket: TcpStream::connect(address).await.unwrap(),
+                seq: 0,
+            };
+            client.read_packet().await.unwrap();
+
+            const CLIENT_PROTOCOL_41: u32 = 0x200;
+            const CLIENT_SECURE_CONNECTION: u32 = 0x8000;
+            const CLIENT_PLUGIN_AUTH: u32 = 0x80000;
+            let mut response = Vec::new();
+            response.extend_from_slice(
+                &(CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_PLUGIN_AUTH).to_le_bytes(),
+            );
+            response.extend_from_slice(&(1u32 << 24).to_le_bytes());
+            // utf8_general_ci
+            response.push(33);
+            response.extend_from_slice(&[0; 23]);
+            response.extend_from_slice(b"test\0");
+            // Empty auth response.
+            response.push(0);
+            response.extend_from_slice(b"mysql_native_password\0");
+            client.write_packet(&response).await;
+
+            let mut reply = client.read_packet().await.unwrap();
+            if reply[0] == 0xfe {
+                // Auth switch request.
+                client.write_packet(&[]).await;
+                reply = client.read_packet().await.unwrap();
+            }
+            assert_eq!(reply[0], 0x00, "handshake failed: {:?}", reply);
+            client
+        }
+
+        async fn read_packet(&mut self) -> io::Result<Vec<u8>> {
+            let mut header = [0; 4];
+            self.socket.read_exact(&mut header).await?;
+            self.seq = header[3].wrapping_add(1);
+            let mut payload =
+                vec![0; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+            self.socket.read_exact(&mut payload).await?;
+            Ok(payload)
+        }
+
+        async fn write_packet(&mut self, payload: &[u8]) {
+            let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+            packet.push(self.seq);
+            packet.extend_from_slice(payload);
+            self.socket.write_all(&packet).await.unwrap();
+            self.seq = self.seq.wrapping_add(1);
+        }
+
+        async fn command(&mut self, command: u8, payload: &[u8]) {
+            self.seq = 0;
+            let mut packet = vec![command];
+            packet.extend_from_slice(payload);
+            self.write_packet(&packet).await;
+        }
+
+        /// Returns the statement id and the number of its parameters.
+        pub async fn prepare(&mut self, query: &str) -> Result<(u32, u16), Vec<u8>> {
+            self.command(0x16, query.as_bytes()).await;
+            let reply = self.read_packet().await.unwrap();
+            if reply[0] != 0x00 {
+                return Err(reply);
+            }
+            let id = u32::from_le_bytes([reply[1], reply[2], reply[3], reply[4]]);
+            let num_columns = u16::from_le_bytes([reply[5], reply[6]]);
+            let num_params = u16::from_le_bytes([reply[7], reply[8]]);
+            for count in [num_params, num_columns] {
+                if count != 0 {
+                    self.read_until_eof().await;
+                }
+            }
+            Ok((id, num_params))
+        }
+
+        /// Parameters are pairs of type codes and values encoded as the binary protocol expects.
+        pub async fn execute(
+            &mut self,
+            id: u32,
+            params: &[(u8, Option<Vec<u8>>)],
+        ) -> io::Result<Result<(Vec<ColumnDef>, BinaryRows), Vec<u8>>> {
+            let mut payload = id.to_le_bytes().to_vec();
+            // No cursor, one iteration.
+            payload.push(0);
+            payload.extend_from_slice(&1u32.to_le_bytes());
+            if !params.is_empty() {
+                let mut nulls = vec![0u8; (params.len() + 7) / 8];
+                for (i, (_, v)) in params.iter().enumerate() {
+                    if v.is_none() {
+                        nulls[i / 8] |= 1 << (i % 8);
+                    }
+                }
+                payload.extend_from_slice(&nulls);
+                // New parameters are bound.
+                payload.push(1);
+                for (t, _) in params {
+                    payload.extend_from_slice(&[*t, 0]);
+                }
+                for (_, v) in params {
+                    if let Some(v) = v {
+                        payload.extend_from_slice(v);
+                    }
+                }
+            }
+            self.command(0x17, &payload).await;
+
+            let reply = self.read_packet().await?;
+            if reply[0] == 0xff {
+                return Ok(Err(reply));
+            }
+            let mut columns = Vec::new();
+            for _ in 0..reply[0] {
+                let def = self.read_packet().await?;
+                let mut pos = 0;
+                // Catalog, schema, table, original table, name and original name.
+                let mut strings = Vec::new();
+                for _ in 0..6 {
+                    let (s, next) = read_lenenc_bytes(&def, pos);
+                    strings.push(String::from_utf8(s.to_vec()).unwrap());
+                    pos = next;
+                }
+                // Skip the length of fixed fields, charset and column length.
+                pos += 1 + 2 + 4;
+                columns.push(ColumnDef {
+                    table: strings[2].clone(),
+                    name: strings[4].clone(),
+                    coltype: def[pos],
+                    flags: u16::from_le_bytes([def[pos + 1], def[pos + 2]]),
+                });
+            }
+            self.read_until_eof().await;
+
+            let mut rows = Vec::new();
+            loop {
+                let row = self.read_packet().await?;
+                if is_eof(&row) {
+                    break;
+                }
+                assert_eq!(row[0], 0x00);
+                // Null bitmap of binary rows has an offset of 2 bits.
+                let mut pos = 1 + (columns.len() + 9) / 8;
+                let mut values = Vec::new();
+                for (i, c) in columns.iter().enumerate() {
+                    if row[1 + (i + 2) / 8] & (1 << ((i + 2) % 8)) != 0 {
+                        values.push(None);
+                        continue;
+                    }
+                    let (value, next) = match c.coltype {
+                        0x01 => (&row[pos..pos + 1], pos + 1),
+                        0x02 => (&row[pos..pos + 2], pos + 2),
+                        0x03 | 0x04 => (&row[pos..pos + 4], pos + 4),
+                        0x05 | 0x08 => (&row[pos..pos + 8], pos + 8),
+                        0x07 | 0x0a | 0x0c => {
+                            let len = row[pos] as usize;
+                            (&row[pos + 1..pos + 1 + len], pos + 1 + len)
+                        }
+                        _ => read_lenenc_bytes(&row, pos),
+                    };
+                    values.push(Some(value.to_vec()));
+                    pos = next;
+                }
+                rows.push(values);
+            }
+            Ok(Ok((columns, rows)))
+        }
+
+        pub async fn close(&mut self, id: u32) {
+            self.command(0x19, &id.to_le_bytes()).await;
+        }
+
+        async fn read_until_eof(&mut self) {
+            while !is_eof(&self.read_packet().await.unwrap()) {}
+        }
+    }
+
+    fn is_eof(packet: &[u8]) -> bool {
+        packet[0] == 0xfe && packet.len() < 9
+    }
+
+    /// Returns the string and the position after it.
+    fn read_lenenc_bytes(b: &[u8], pos: usize) -> (&[u8], usize) {
+        let (len, start) = match b[pos] {
+            0xfc => (
+                u16::from_le_bytes([b[pos + 1], b[pos + 2]]) as usize,
+                pos + 3,
+            ),
+            0xfd => (
+                u32::from_le_bytes([b[pos + 1], b[pos + 2], b[pos + 3], 0]) as usize,
+                pos + 4,
+            ),
+            0xfe => (
+                u64::from_le_bytes([
+                    b[pos + 1],
+                    b[pos + 2],
+                    b[pos + 3],
+                    b[pos + 4],
+                    b[pos + 5],
+                    b[pos + 6],
+                    b[pos + 7],
+                    b[pos + 8],
+                ]) as usize,
+                pos + 9,
+            ),
+            l => (l as usize, pos + 1),
+        };
+        (&b[start..start + len], start + len)
+    }
+
+    fn lenenc_bytes(b: &[u8]) -> Vec<u8> {
+        assert!(b.len() < 0xfb);
+        let mut r = vec![b.len() as u8];
+        r.extend_from_slice(b);
+        r
+    }
+
+    #[test]
+    fn decimal_column_definition() {
+        let t = metastore::ColumnType::Decimal {
+            scale: 2,
+            precision: 10,
+        };
+        assert_eq!(mysql_column_length(&t), 12);
+        assert_eq!(mysql_column_decimals(&t), 2);
+        // Scales above 5 are stored as 10.
+        let t = metastore::ColumnType::Decimal {
+            scale: 7,
+            precision: 18,
+        };
+        assert_eq!(mysql_column_length(&t), 20);
+        assert_eq!(mysql_column_decimals(&t), 10);
+        let t = metastore::ColumnType::Decimal {
+            scale: 0,
+            precision: 5,
+        };
+        assert_eq!(mysql_column_length(&t), 6);
+        assert_eq!(mysql_column_decimals(&t), 0);
+    }
+
+    #[tokio::test]
+    async fn prepared_statements() {
+        let service = Arc::new(RecordingSqlService {
+            queries: Mutex::new(Vec::new()),
+        });
+        let mut client = TestClient::connect(service.clone()).await;
+
+        let (id, num_params) = client
+            .prepare("SELECT * FROM s.t WHERE id = ? AND name = ? AND data = ? AND flag = ?")
+            .await
+            .unwrap();
+        assert_eq!(num_params, 4);
+        let (columns, rows) = client
+            .execute(
+                id,
+                &[
+                    // MYSQL_TYPE_LONGLONG
+                    (0x08, Some((-5i64).to_le_bytes().to_vec())),
+                    // MYSQL_TYPE_VAR_STRING
+                    (0xfd, Some(lenenc_bytes(b"x'y"))),
+                    // MYSQL_TYPE_BLOB, not valid UTF-8.
+                    (0xfc, Some(lenenc_bytes(&[0xff, 0x00]))),
+                    // MYSQL_TYPE_NULL
+                    (0x06, None),
+                ],
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            service.queries.lock().unwrap().as_slice(),
+            &["SELECT * FROM s.t WHERE id = (-5) AND name = 'x''y' AND data = X'FF00' AND flag = NULL"
+                .to_string()]
+        );
+        assert_eq!(
+            columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            vec!["id", "name", "data", "time", "flag", "value", "amount"]
+        );
+        // BLOB_FLAG and BINARY_FLAG.
+        assert_eq!(columns[2].flags & 0x90, 0x90);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][0], Some((-5i64).to_le_bytes().to_vec()));
+        assert_eq!(rows[0][1], None);
+        assert_eq!(rows[0][2], Some(vec![0xff, 0x00]));
+
+        // Statements are independent and can be executed many times.
+        let (other_id, _) = client.prepare("SELECT 1").await.unwrap();
+        assert_ne!(other_id, id);
+        client.execute(other_id, &[]).await.unwrap().unwrap();
+        client
+            .execute(
+                id,
+                &[
+                    (0x08, Some(1i64.to_le_bytes().to_vec())),
+                    (0x06, None),
+                    (0x06, None),
+                    (0x06, None),
+                ],
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            service.queries.lock().unwrap()[1..],
+            [
+                "SELECT 1".to_string(),
+                "SELECT * FROM s.t WHERE id = 1 AND name = NULL AND data = NULL AND flag = NULL"
+                    .to_string()
+            ]
+        );
+
+        assert!(client.prepare("SELECT FROM WHERE ?").await.is_err());
+
+        client.close(id).await;
+        // Closed statements either get an error or the connection is dropped.
+        if let Ok(r) = client.execute(id, &[]).await {
+            assert!(r.is_err());
+        }
+        assert_eq!(service.queries.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn result_column_types() {
+        let service = Arc::new(RecordingSqlService {
+            queries: Mutex::new(Vec::new()),
+        });
+        let mut client = TestClient::connect(service).await;
+        let (id, _) = client.prepare("SELECT * FROM s.t").await.unwrap();
+        let (columns, rows) = client.execute(id, &[]).await.unwrap().unwrap();
+
+        assert_eq!(
+            columns
+                .iter()
+                .map(|c| (c.name.as_str(), c.coltype))
+                .collect::<Vec<_>>(),
+            vec![
+                // MYSQL_TYPE_LONGLONG
+                ("id", 0x08),
+                // MYSQL_TYPE_STRING
+                ("name", 0xfe),
+                // MYSQL_TYPE_BLOB
+                ("data", 0xfc),
+                // MYSQL_TYPE_DATETIME
+                ("time", 0x0c),
+                // MYSQL_TYPE_TINY
+                ("flag", 0x01),
+                // MYSQL_TYPE_DOUBLE
+                ("value", 0x05),
+                // MYSQL_TYPE_NEWDECIMAL
+                ("amount", 0xf6),
+            ]
+        );
+        assert_eq!(
+            columns.iter().map(|c| c.table.as_str()).collect::<Vec<_>>(),
+            vec!["t", "t", "t", "t", "t", "", "t"]
+        );
+
+        assert_eq!(
+            rows,
+            vec![vec![
+                Some((-5i64).to_le_bytes().to_vec()),
+                None,
+                Some(vec![0xff, 0x00]),
+                // Year, month, day, hours, minutes and seconds.
+                Some(vec![0xe5, 0x07, 1, 2, 3, 4, 5]),
+                Some(vec![1]),
+                Some(1.5f64.to_le_bytes().to_vec()),
+                Some(b"123.45".to_vec()),
+            ]]
+        );
+    }
+}
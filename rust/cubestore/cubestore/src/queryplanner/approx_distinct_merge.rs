@@ -0,0 +1,266 @@
+use crate::queryplanner::optimizations::rewrite_plan::{rewrite_plan, PlanRewriter};
+use crate::queryplanner::udfs::{aggregate_udf_by_kind, CubeAggregateUDFKind};
+use arrow::datatypes::DataType;
+use datafusion::error::DataFusionError;
+use datafusion::execution::context::ExecutionProps;
+use datafusion::logical_plan::{Column, DFField, DFSchema, Expr, ExprRewriter, LogicalPlan};
+use datafusion::optimizer::optimizer::OptimizerRule;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Replaces `CARDINALITY(MERGE(x))` with `APPROX_DISTINCT_MERGE(x)`, which estimates the result
+/// without writing out the merged sketch. Only applies to projections right above aggregates and
+/// only when the merged sketch is not used in any other way.
+pub struct ApproxDistinctMerge;
+impl OptimizerRule for ApproxDistinctMerge {
+    fn optimize(
+        &self,
+        plan: &LogicalPlan,
+        _execution_props: &ExecutionProps,
+    ) -> Result<LogicalPlan, DataFusionError> {
+        return rewrite_plan(plan, &(), &mut Rewriter {});
+
+        struct Rewriter {}
+        impl PlanRewriter for Rewriter {
+            type Context = ();
+
+            fn rewrite(&mut self, n: LogicalPlan, _: &()) -> Result<LogicalPlan, DataFusionError> {
+                Ok(rewrite_projection(&n)?.unwrap_or(n))
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "approx_distinct_merge"
+    }
+}
+
+fn rewrite_projection(p: &LogicalPlan) -> Result<Option<LogicalPlan>, DataFusionError> {
+    let (expr, input, schema) = match p {
+        LogicalPlan::Projection {
+            expr,
+            input,
+            schema,
+        } => (expr, input, schema),
+        _ => return Ok(None),
+    };
+    let (agg_input, group_expr, aggr_expr, agg_schema) = match input.as_ref() {
+        LogicalPlan::Aggregate {
+            input,
+            group_expr,
+            aggr_expr,
+            schema,
+        } => (input, group_expr, aggr_expr, schema),
+        _ => return Ok(None),
+    };
+
+    // Map columns holding the merged sketches to their replacements.
+    let approx_distinct_merge =
+        Arc::new(aggregate_udf_by_kind(CubeAggregateUDFKind::ApproxDistinctMerge).descriptor());
+    let mut new_aggr_expr = aggr_expr.clone();
+    let mut new_fields = agg_schema.fields().clone();
+    let mut replacements = HashMap::new();
+    for i in 0..aggr_expr.len() {
+        let args = match &aggr_expr[i] {
+            Expr::AggregateUDF { fun, args } if fun.name == "MERGE" => args,
+            _ => continue,
+        };
+        let e = Expr::AggregateUDF {
+            fun: approx_distinct_merge.clone(),
+            args: args.clone(),
+        };
+        let name = e.name(agg_input.schema())?;
+        if agg_schema.fields().iter().any(|f| f.name() == &name) {
+            // Already computed, we could reuse it, but such queries are rare.
+            continue;
+        }
+        let field_index = group_expr.len() + i;
+        let field = DFField::new(None, &name, DataType::UInt64, true);
+        replacements.insert(
+            agg_schema.field(field_index).qualified_column(),
+            field.qualified_column(),
+        );
+        new_aggr_expr[i] = e;
+        new_fields[field_index] = field;
+    }
+
+    let mut new_expr = replace_cardinality(expr, &replacements)?;
+    // Keep sketches that are used outside of CARDINALITY.
+    let mut used = HashSet::new();
+    for e in &new_expr {
+        e.clone().rewrite(&mut CollectColumns {
+            of: &replacements,
+            used: &mut used,
+        })?;
+    }
+    if !used.is_empty() {
+        for i in 0..aggr_expr.len() {
+            let field_index = group_expr.len() + i;
+            if used.contains(&agg_schema.field(field_index).qualified_column()) {
+                new_aggr_expr[i] = aggr_expr[i].clone();
+                new_fields[field_index] = agg_schema.field(field_index).clone();
+            }
+        }
+        replacements.retain(|c, _| !used.contains(c));
+        new_expr = replace_cardinality(expr, &replacements)?;
+    }
+    if replacements.is_empty() {
+        return Ok(None);
+    }
+
+    // Keep the output names intact.
+    for i in 0..new_expr.len() {
+        if new_expr[i] != expr[i] && !matches!(new_expr[i], Expr::Alias(..)) {
+            new_expr[i] = Expr::Alias(
+                Box::new(new_expr[i].clone()),
+                schema.field(i).name().clone(),
+            );
+        }
+    }
+    return Ok(Some(LogicalPlan::Projection {
+        expr: new_expr,
+        input: Arc::new(LogicalPlan::Aggregate {
+            input: agg_input.clone(),
+            group_expr: group_expr.clone(),
+            aggr_expr: new_aggr_expr,
+            schema: Arc::new(DFSchema::new(new_fields)?),
+        }),
+        schema: schema.clone(),
+    }));
+}
+
+fn replace_cardinality(
+    exprs: &[Expr],
+    replacements: &HashMap<Column, Column>,
+) -> Result<Vec<Expr>, DataFusionError> {
+    return exprs
+        .iter()
+        .map(|e| e.clone().rewrite(&mut ReplaceCardinality { replacements }))
+        .collect();
+
+    struct ReplaceCardinality<'a> {
+        replacements: &'a HashMap<Column, Column>,
+    }
+    impl ExprRewriter for ReplaceCardinality<'_> {
+        fn mutate(&mut self, expr: Expr) -> Result<Expr, DataFusionError> {
+            match &expr {
+                Expr::ScalarUDF { fun, args } if fun.name == "CARDINALITY" && args.len() == 1 => {
+                    if let Expr::Column(c) = &args[0] {
+                        if let Some(r) = self.replacements.get(c) {
+                            return Ok(Expr::Column(r.clone()));
+                        }
+                    }
+                    Ok(expr)
+                }
+                _ => Ok(expr),
+            }
+        }
+    }
+}
+
+struct CollectColumns<'a> {
+    of: &'a HashMap<Column, Column>,
+    used: &'a mut HashSet<Column>,
+}
+impl ExprRewriter for CollectColumns<'_> {
+    fn mutate(&mut self, expr: Expr) -> Result<Expr, DataFusionError> {
+        if let Expr::Column(c) = &expr {
+            if self.of.contains_key(c) {
+                self.used.insert(c.clone());
+            }
+        }
+        Ok(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queryplanner::udfs::{
+        aggregate_kind_by_name, scalar_kind_by_name, scalar_udf_by_kind,
+    };
+    use crate::sql::parser::{CubeStoreParser, Statement};
+    use arrow::datatypes::{Field, Schema};
+    use datafusion::catalog::TableReference;
+    use datafusion::datasource::empty::EmptyTable;
+    use datafusion::datasource::TableProvider;
+    use datafusion::execution::context::ExecutionContext;
+    use datafusion::physical_plan::udaf::AggregateUDF;
+    use datafusion::physical_plan::udf::ScalarUDF;
+    use datafusion::sql::parser::Statement as DFStatement;
+    use datafusion::sql::planner::{ContextProvider, SqlToRel};
+
+    #[test]
+    fn test_rewrite() {
+        let (before, after) = plans("SELECT key, CARDINALITY(MERGE(hll)) FROM s.Data GROUP BY 1");
+        assert_eq!(aggregates(&after), vec!["APPROX_DISTINCT_MERGE"]);
+        assert_eq!(after.schema(), before.schema());
+
+        let (before, after) = plans("SELECT CARDINALITY(MERGE(hll)) + 1 c FROM s.Data");
+        assert_eq!(aggregates(&after), vec!["APPROX_DISTINCT_MERGE"]);
+        assert_eq!(after.schema(), before.schema());
+
+        // Sketches are still needed.
+        let (_, after) =
+            plans("SELECT key, CARDINALITY(MERGE(hll)), MERGE(hll) FROM s.Data GROUP BY 1");
+        assert_eq!(aggregates(&after), vec!["MERGE"]);
+
+        let (_, after) = plans("SELECT key, MERGE(hll) FROM s.Data GROUP BY 1");
+        assert_eq!(aggregates(&after), vec!["MERGE"]);
+    }
+
+    /// Returns the plans before and after the rewrite.
+    fn plans(s: &str) -> (LogicalPlan, LogicalPlan) {
+        let statement;
+        if let Statement::Statement(s) = CubeStoreParser::new(s).unwrap().parse_statement().unwrap()
+        {
+            statement = s;
+        } else {
+            panic!("not a statement")
+        }
+        let plan = SqlToRel::new(&Provider {})
+            .statement_to_plan(&DFStatement::Statement(statement))
+            .unwrap();
+        let plan = ExecutionContext::new().optimize(&plan).unwrap();
+        let rewritten = ApproxDistinctMerge {}
+            .optimize(&plan, &ExecutionProps::new())
+            .unwrap();
+        (plan, rewritten)
+    }
+
+    fn aggregates(p: &LogicalPlan) -> Vec<String> {
+        match p {
+            LogicalPlan::Projection { input, .. } => match input.as_ref() {
+                LogicalPlan::Aggregate { aggr_expr, .. } => aggr_expr
+                    .iter()
+                    .map(|e| match e {
+                        Expr::AggregateUDF { fun, .. } => fun.name.clone(),
+                        e => panic!("unexpected aggregate: {:?}", e),
+                    })
+                    .collect(),
+                p => panic!("unexpected plan: {:?}", p),
+            },
+            p => panic!("unexpected plan: {:?}", p),
+        }
+    }
+
+    struct Provider {}
+    impl ContextProvider for Provider {
+        fn get_table_provider(&self, _name: TableReference) -> Option<Arc<dyn TableProvider>> {
+            Some(Arc::new(EmptyTable::new(Arc::new(Schema::new(vec![
+                Field::new("key", DataType::Int64, true),
+                Field::new("hll", DataType::Binary, true),
+            ])))))
+        }
+
+        fn get_function_meta(&self, name: &str) -> Option<Arc<ScalarUDF>> {
+            let kind = scalar_kind_by_name(&name.to_uppercase())?;
+            Some(Arc::new(scalar_udf_by_kind(kind).descriptor()))
+        }
+
+        fn get_aggregate_meta(&self, name: &str) -> Option<Arc<AggregateUDF>> {
+            let kind = aggregate_kind_by_name(&name.to_uppercase())?;
+            Some(Arc::new(aggregate_udf_by_kind(kind).descriptor()))
+        }
+    }
+}
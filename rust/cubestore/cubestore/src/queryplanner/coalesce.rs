@@ -71,6 +71,46 @@ pub fn coalesce(values: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionErr
     return Ok(values.last().unwrap().clone());
 }
 
+/// Implements `GREATEST` and `LEAST` over arrays of the same type and length. As in MySQL, the
+/// result is NULL if any of the values is NULL.
+pub fn greatest_least(arrays: &[ArrayRef], greatest: bool) -> Result<ArrayRef, DataFusionError> {
+    if arrays.is_empty() {
+        return Err(DataFusionError::Execution(
+            "empty inputs to greatest or least".to_string(),
+        ));
+    }
+    macro_rules! apply_greatest_least {
+        ($start: expr, $arr: ty, $builder_ty: ty, $scalar_enum: ident $($rest: tt)*) => {{
+            let arrays = arrays
+                .iter()
+                .map(|a| {
+                    a.as_any().downcast_ref::<$arr>().ok_or_else(|| {
+                        DataFusionError::Internal("expected array of the same type".to_string())
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut b = <$builder_ty>::new($start.len());
+            for i in 0..$start.len() {
+                if arrays.iter().any(|a| a.is_null(i)) {
+                    b.append_null()?;
+                    continue;
+                }
+                let mut r = arrays[0].value(i);
+                for a in &arrays[1..] {
+                    let v = a.value(i);
+                    if (greatest && r < v) || (!greatest && v < r) {
+                        r = v;
+                    }
+                }
+                b.append_value(r)?;
+            }
+            Ok(Arc::new(b.finish()))
+        }};
+    }
+    let start = &arrays[0];
+    cube_match_array!(start, apply_greatest_least)
+}
+
 fn do_coalesce(start: &ArrayRef, rest: &[ColumnarValue]) -> Result<ArrayRef, DataFusionError> {
     macro_rules! match_scalar {
         ($v: pat, Int64Decimal) => {
@@ -0,0 +1,262 @@
+use arrow::array::{Array, TimestampNanosecondArray};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc,
+};
+use chrono_tz::Tz;
+use datafusion::error::DataFusionError;
+
+/// Time zone argument of `DATE_TRUNC` and `CONVERT_TZ`. Accepts IANA names, e.g.
+/// `America/Los_Angeles`, and fixed offsets, e.g. `+03:00`.
+#[derive(Debug, Clone, Copy)]
+pub enum TimeZoneArg {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl TimeZoneArg {
+    pub fn parse(s: &str) -> Result<TimeZoneArg, DataFusionError> {
+        if let Some(offset) = parse_offset(s) {
+            return Ok(TimeZoneArg::Fixed(offset));
+        }
+        s.parse::<Tz>()
+            .map(TimeZoneArg::Named)
+            .map_err(|_| DataFusionError::Execution(format!("Unknown time zone: '{}'", s)))
+    }
+
+    /// Wall clock time in this time zone at the UTC instant `t`.
+    pub fn to_local(&self, t: &NaiveDateTime) -> NaiveDateTime {
+        match self {
+            TimeZoneArg::Fixed(o) => DateTime::<Utc>::from_utc(*t, Utc)
+                .with_timezone(o)
+                .naive_local(),
+            TimeZoneArg::Named(tz) => DateTime::<Utc>::from_utc(*t, Utc)
+                .with_timezone(tz)
+                .naive_local(),
+        }
+    }
+
+    /// UTC instant of the wall clock time `t` in this time zone. Ambiguous times resolve to the
+    /// earliest instant, times skipped by DST transitions are moved forward by an hour.
+    pub fn to_utc(&self, t: &NaiveDateTime) -> Result<NaiveDateTime, DataFusionError> {
+        let resolve = |t: &NaiveDateTime| match self {
+            TimeZoneArg::Fixed(o) => o.from_local_datetime(t).earliest().map(|t| t.naive_utc()),
+            TimeZoneArg::Named(tz) => tz.from_local_datetime(t).earliest().map(|t| t.naive_utc()),
+        };
+        resolve(t)
+            .or_else(|| resolve(&(*t + Duration::hours(1))))
+            .ok_or_else(|| DataFusionError::Execution(format!("Can't convert {} to {:?}", t, self)))
+    }
+}
+
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let sign = match s.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = match s[1..].find(':') {
+        Some(i) => (&s[1..i + 1], &s[i + 2..]),
+        None => (&s[1..], "0"),
+    };
+    let hours = hours.parse::<i32>().ok()?;
+    let minutes = minutes.parse::<i32>().ok()?;
+    if 14 < hours || 59 < minutes {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Granularity {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Granularity {
+    pub fn parse(s: &str) -> Result<Granularity, DataFusionError> {
+        Ok(match s.to_lowercase().as_str() {
+            "second" => Granularity::Second,
+            "minute" => Granularity::Minute,
+            "hour" => Granularity::Hour,
+            "day" => Granularity::Day,
+            "week" => Granularity::Week,
+            "month" => Granularity::Month,
+            "quarter" => Granularity::Quarter,
+            "year" => Granularity::Year,
+            _ => {
+                return Err(DataFusionError::Execution(format!(
+                    "Unsupported date_trunc granularity: '{}'",
+                    s
+                )))
+            }
+        })
+    }
+
+    /// Weeks start on Monday.
+    pub fn trunc(&self, t: &NaiveDateTime) -> NaiveDateTime {
+        let date = t.date();
+        match self {
+            Granularity::Second => date.and_hms(t.hour(), t.minute(), t.second()),
+            Granularity::Minute => date.and_hms(t.hour(), t.minute(), 0),
+            Granularity::Hour => date.and_hms(t.hour(), 0, 0),
+            Granularity::Day => date.and_hms(0, 0, 0),
+            Granularity::Week => (date
+                - Duration::days(date.weekday().num_days_from_monday() as i64))
+            .and_hms(0, 0, 0),
+            Granularity::Month => {
+                NaiveDate::from_ymd(date.year(), date.month(), 1).and_hms(0, 0, 0)
+            }
+            Granularity::Quarter => {
+                NaiveDate::from_ymd(date.year(), (date.month() - 1) / 3 * 3 + 1, 1).and_hms(0, 0, 0)
+            }
+            Granularity::Year => NaiveDate::from_ymd(date.year(), 1, 1).and_hms(0, 0, 0),
+        }
+    }
+}
+
+/// Truncates UTC timestamps to the start of the period in the time zone `tz`.
+pub fn date_trunc(
+    granularity: Granularity,
+    t: &TimestampNanosecondArray,
+    tz: Option<TimeZoneArg>,
+) -> Result<TimestampNanosecondArray, DataFusionError> {
+    t.iter()
+        .map(|t| t.map(|t| date_trunc_value(granularity, t, tz)).transpose())
+        .collect()
+}
+
+fn date_trunc_value(
+    granularity: Granularity,
+    t: i64,
+    tz: Option<TimeZoneArg>,
+) -> Result<i64, DataFusionError> {
+    let utc = from_nanos(t);
+    Ok(match tz {
+        None => granularity.trunc(&utc).timestamp_nanos(),
+        Some(tz) => tz
+            .to_utc(&granularity.trunc(&tz.to_local(&utc)))?
+            .timestamp_nanos(),
+    })
+}
+
+/// Converts wall clock time in `from` to wall clock time in `to`. Timestamps are treated as UTC
+/// when `from` is not set.
+pub fn convert_tz(
+    t: &TimestampNanosecondArray,
+    from: Option<TimeZoneArg>,
+    to: TimeZoneArg,
+) -> Result<TimestampNanosecondArray, DataFusionError> {
+    t.iter()
+        .map(|t| t.map(|t| convert_tz_value(t, from, to)).transpose())
+        .collect()
+}
+
+fn convert_tz_value(
+    t: i64,
+    from: Option<TimeZoneArg>,
+    to: TimeZoneArg,
+) -> Result<i64, DataFusionError> {
+    let utc = match from {
+        None => from_nanos(t),
+        Some(from) => from.to_utc(&from_nanos(t))?,
+    };
+    Ok(to.to_local(&utc).timestamp_nanos())
+}
+
+fn from_nanos(t: i64) -> NaiveDateTime {
+    Utc.timestamp_nanos(t).naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> i64 {
+        DateTime::parse_from_rfc3339(s).unwrap().timestamp_nanos()
+    }
+
+    fn trunc(granularity: &str, t: &str, tz: Option<&str>) -> i64 {
+        let tz = tz.map(|tz| TimeZoneArg::parse(tz).unwrap());
+        let r = date_trunc(
+            Granularity::parse(granularity).unwrap(),
+            &TimestampNanosecondArray::from(vec![ts(t)]),
+            tz,
+        )
+        .unwrap();
+        r.value(0)
+    }
+
+    #[test]
+    fn date_trunc_granularities() {
+        let t = "2021-08-19T15:31:12.345Z";
+        assert_eq!(trunc("second", t, None), ts("2021-08-19T15:31:12Z"));
+        assert_eq!(trunc("minute", t, None), ts("2021-08-19T15:31:00Z"));
+        assert_eq!(trunc("hour", t, None), ts("2021-08-19T15:00:00Z"));
+        assert_eq!(trunc("day", t, None), ts("2021-08-19T00:00:00Z"));
+        assert_eq!(trunc("week", t, None), ts("2021-08-16T00:00:00Z"));
+        assert_eq!(trunc("MONTH", t, None), ts("2021-08-01T00:00:00Z"));
+        assert_eq!(trunc("quarter", t, None), ts("2021-07-01T00:00:00Z"));
+        assert_eq!(trunc("year", t, None), ts("2021-01-01T00:00:00Z"));
+        assert!(Granularity::parse("decade").is_err());
+    }
+
+    #[test]
+    fn date_trunc_time_zones() {
+        let t = "2021-01-01T03:00:00Z";
+        assert_eq!(
+            trunc("day", t, Some("America/Los_Angeles")),
+            ts("2020-12-31T00:00:00-08:00")
+        );
+        assert_eq!(
+            trunc("day", t, Some("+05:00")),
+            ts("2021-01-01T00:00:00+05:00")
+        );
+        assert_eq!(
+            trunc("quarter", t, Some("-04:30")),
+            ts("2020-10-01T00:00:00-04:30")
+        );
+        assert_eq!(
+            trunc("week", "2021-03-17T12:00:00Z", Some("Europe/Berlin")),
+            ts("2021-03-15T00:00:00+01:00")
+        );
+        // Day of the DST switch.
+        assert_eq!(
+            trunc("day", "2021-03-28T12:00:00Z", Some("Europe/Berlin")),
+            ts("2021-03-28T00:00:00+01:00")
+        );
+        assert!(TimeZoneArg::parse("Mars/Olympus").is_err());
+        assert!(TimeZoneArg::parse("+25:00").is_err());
+    }
+
+    #[test]
+    fn convert_time_zones() {
+        let convert = |t: &str, from: Option<&str>, to: &str| {
+            let r = convert_tz(
+                &TimestampNanosecondArray::from(vec![Some(ts(t)), None]),
+                from.map(|tz| TimeZoneArg::parse(tz).unwrap()),
+                TimeZoneArg::parse(to).unwrap(),
+            )
+            .unwrap();
+            assert!(r.is_null(1));
+            r.value(0)
+        };
+        assert_eq!(
+            convert("2021-01-01T00:00:00Z", None, "+00:00"),
+            ts("2021-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            convert("2021-01-01T00:00:00Z", None, "America/New_York"),
+            ts("2020-12-31T19:00:00Z")
+        );
+        assert_eq!(
+            convert("2021-07-01T12:00:00Z", Some("Europe/Berlin"), "+01:00"),
+            ts("2021-07-01T11:00:00Z")
+        );
+    }
+}
@@ -2,7 +2,7 @@ use crate::CubeError;
 use cubehll::HllSketch;
 use cubezetasketch::HyperLogLogPlusPlus;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Hll {
     Airlift(HllSketch),              // Compatible with Athena, Presto, etc.
     ZetaSketch(HyperLogLogPlusPlus), // Compatible with BigQuery.
@@ -47,6 +47,14 @@ impl Hll {
         }
     }
 
+    pub fn insert_bytes(&mut self, value: &[u8]) -> Result<(), CubeError> {
+        match self {
+            Hll::Airlift(h) => h.insert_bytes(value),
+            Hll::ZetaSketch(h) => h.add_bytes(value)?,
+        }
+        return Ok(());
+    }
+
     /// Clients are responsible for calling `is_compatible` before running this function.
     /// On error, `self` may end up in inconsistent state and must be discarded.
     pub fn merge_with(&mut self, other: &Hll) -> Result<(), CubeError> {
@@ -69,7 +69,7 @@ impl InfoSchemaTableDef for SystemTablesTableDef {
                     Arc::new(StringArray::from(
                         tables
                             .iter()
-                            .map(|row| format!("{:?}", row.table.get_row().get_columns()))
+                            .map(|row| format!("{:?}", row.table.get_row().insertable_columns()))
                             .collect::<Vec<_>>(),
                     ))
                 }),
@@ -191,6 +191,80 @@ impl InfoSchemaTableDef for SystemTablesTableDef {
                     ))
                 }),
             ),
+            (
+                Field::new("ttl_seconds", DataType::UInt64, true),
+                Box::new(|tables| {
+                    let array = tables
+                        .iter()
+                        .map(|row| row.table.get_row().ttl_seconds().clone())
+                        .collect::<Vec<_>>();
+                    Arc::new(UInt64Array::from(array))
+                }),
+            ),
+            (
+                Field::new("parquet_compression", DataType::Utf8, true),
+                Box::new(|tables| {
+                    let array = tables
+                        .iter()
+                        .map(|row| {
+                            row.table
+                                .get_row()
+                                .parquet_settings()
+                                .compression
+                                .map(|c| format!("{:?}", c).to_lowercase())
+                        })
+                        .collect::<Vec<_>>();
+                    Arc::new(StringArray::from(
+                        array
+                            .iter()
+                            .map(|v| v.as_ref().map(|v| v.as_str()))
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("parquet_dictionary", DataType::Boolean, true),
+                Box::new(|tables| {
+                    Arc::new(BooleanArray::from(
+                        tables
+                            .iter()
+                            .map(|row| row.table.get_row().parquet_settings().dictionary_enabled)
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("parquet_statistics", DataType::Boolean, true),
+                Box::new(|tables| {
+                    Arc::new(BooleanArray::from(
+                        tables
+                            .iter()
+                            .map(|row| row.table.get_row().parquet_settings().statistics_enabled)
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("stream_offsets", DataType::Utf8, true),
+                Box::new(|tables| {
+                    let array = tables
+                        .iter()
+                        .map(|row| {
+                            row.table
+                                .get_row()
+                                .stream_offsets()
+                                .as_ref()
+                                .map(|v| format!("{:?}", v))
+                        })
+                        .collect::<Vec<_>>();
+                    Arc::new(StringArray::from(
+                        array
+                            .iter()
+                            .map(|v| v.as_ref().map(|v| v.as_str()))
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
         ]
     }
 }
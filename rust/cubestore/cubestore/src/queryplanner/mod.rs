@@ -1,3 +1,4 @@
+mod approx_distinct_merge;
 pub mod hll;
 mod optimizations;
 pub mod panic;
@@ -10,6 +11,7 @@ pub mod serialized_plan;
 mod topk;
 pub use topk::MIN_TOPK_STREAM_ROWS;
 mod coalesce;
+mod datetime;
 mod filter_by_key_range;
 pub mod info_schema;
 mod now;
@@ -18,8 +20,9 @@ pub mod udfs;
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::metastore::multi_index::MultiPartition;
-use crate::metastore::table::{Table, TablePath};
+use crate::metastore::table::{ParquetSettings, Table, TablePath};
 use crate::metastore::{IdRow, MetaStore};
+use crate::queryplanner::approx_distinct_merge::ApproxDistinctMerge;
 use crate::queryplanner::info_schema::info_schema_schemata::SchemataInfoSchemaTableDef;
 use crate::queryplanner::info_schema::info_schema_tables::TablesInfoSchemaTableDef;
 use crate::queryplanner::info_schema::system_chunks::SystemChunksTableDef;
@@ -29,7 +32,7 @@ use crate::queryplanner::info_schema::system_partitions::SystemPartitionsTableDe
 use crate::queryplanner::info_schema::system_tables::SystemTablesTableDef;
 use crate::queryplanner::now::MaterializeNow;
 use crate::queryplanner::planning::{choose_index_ext, ClusterSendNode};
-use crate::queryplanner::query_executor::{batch_to_dataframe, ClusterSendExec};
+use crate::queryplanner::query_executor::{batch_to_dataframe, source_tables, ClusterSendExec};
 use crate::queryplanner::serialized_plan::SerializedPlan;
 use crate::queryplanner::topk::ClusterAggregateTopK;
 use crate::queryplanner::udfs::aggregate_udf_by_kind;
@@ -124,6 +127,7 @@ impl QueryPlanner for QueryPlannerImpl {
     async fn execute_meta_plan(&self, plan: LogicalPlan) -> Result<DataFrame, CubeError> {
         let ctx = self.execution_context().await?;
 
+        let source_tables = source_tables(plan.schema());
         let plan_ctx = ctx.clone();
         let plan_to_move = plan.clone();
         let physical_plan =
@@ -136,7 +140,7 @@ impl QueryPlanner for QueryPlannerImpl {
         app_metrics::META_QUERY_TIME_MS.report(execution_time.as_millis() as i64);
         debug!("Meta query data processing time: {:?}", execution_time,);
         let data_frame = cube_ext::spawn_blocking(move || batch_to_dataframe(&results)).await??;
-        Ok(data_frame)
+        Ok(data_frame.with_source_tables(source_tables))
     }
 }
 
@@ -152,7 +156,9 @@ impl QueryPlannerImpl {
 impl QueryPlannerImpl {
     async fn execution_context(&self) -> Result<Arc<ExecutionContext>, CubeError> {
         Ok(Arc::new(ExecutionContext::with_config(
-            ExecutionConfig::new().add_optimizer_rule(Arc::new(MaterializeNow {})),
+            ExecutionConfig::new()
+                .add_optimizer_rule(Arc::new(MaterializeNow {}))
+                .add_optimizer_rule(Arc::new(ApproxDistinctMerge {})),
         )))
     }
 }
@@ -222,6 +228,8 @@ impl ContextProvider for MetaStoreSchemaProvider {
                     None,
                     None,
                     None,
+                    None,
+                    ParquetSettings::default(),
                 ),
             ),
             schema: Arc::new(IdRow::new(0, metastore::Schema::new(schema.to_string()))),
@@ -232,11 +240,12 @@ impl ContextProvider for MetaStoreSchemaProvider {
             .get(&TableKey(&name))
             .map(|table| -> Arc<dyn TableProvider> {
                 let table = unsafe { &*table.0 };
+                // The deleted column is hidden, CubeTable exposes the same columns to physical plans.
                 let schema = Arc::new(Schema::new(
                     table
                         .table
                         .get_row()
-                        .get_columns()
+                        .insertable_columns()
                         .iter()
                         .map(|c| c.clone().into())
                         .collect::<Vec<_>>(),
@@ -287,6 +296,14 @@ impl ContextProvider for MetaStoreSchemaProvider {
             "unix_timestamp" | "UNIX_TIMESTAMP" => CubeScalarUDFKind::UnixTimestamp,
             "date_add" | "DATE_ADD" => CubeScalarUDFKind::DateAdd,
             "date_sub" | "DATE_SUB" => CubeScalarUDFKind::DateSub,
+            // Take over built-in functions of the same name, returning the same results for the
+            // arguments they accept.
+            "date_trunc" | "DATE_TRUNC" => CubeScalarUDFKind::DateTrunc,
+            "convert_tz" | "CONVERT_TZ" => CubeScalarUDFKind::ConvertTz,
+            "substring" | "SUBSTRING" => CubeScalarUDFKind::Substring,
+            "round" | "ROUND" => CubeScalarUDFKind::Round,
+            "greatest" | "GREATEST" => CubeScalarUDFKind::Greatest,
+            "least" | "LEAST" => CubeScalarUDFKind::Least,
             _ => return None,
         };
         return Some(Arc::new(scalar_udf_by_kind(kind).descriptor()));
@@ -297,6 +314,11 @@ impl ContextProvider for MetaStoreSchemaProvider {
         // TODO: case-insensitive names.
         let kind = match name {
             "merge" | "MERGE" => CubeAggregateUDFKind::MergeHll,
+            "approx_distinct_merge" | "APPROX_DISTINCT_MERGE" => {
+                CubeAggregateUDFKind::ApproxDistinctMerge
+            }
+            "hll_add_agg" | "HLL_ADD_AGG" => CubeAggregateUDFKind::HllAddAgg,
+            "hll_add_agg_zeta" | "HLL_ADD_AGG_ZETA" => CubeAggregateUDFKind::HllAddAggZeta,
             _ => return None,
         };
         return Some(Arc::new(aggregate_udf_by_kind(kind).descriptor()));
@@ -889,7 +889,7 @@ pub mod tests {
 
     use crate::config::Config;
     use crate::metastore::multi_index::MultiPartition;
-    use crate::metastore::table::{Table, TablePath};
+    use crate::metastore::table::{ParquetSettings, Table, TablePath};
     use crate::metastore::{Chunk, Column, ColumnType, IdRow, Index, Partition, Schema};
     use crate::queryplanner::planning::{choose_index, try_extract_cluster_send, PlanIndexStore};
     use crate::queryplanner::pretty_printers::PPOptions;
@@ -1295,6 +1295,8 @@ pub mod tests {
             None,
             None,
             None,
+            None,
+            ParquetSettings::default(),
         ));
         i.indices.push(
             Index::try_new(
@@ -1338,6 +1340,8 @@ pub mod tests {
             None,
             None,
             None,
+            None,
+            ParquetSettings::default(),
         ));
         i.indices.push(
             Index::try_new(
@@ -1385,6 +1389,8 @@ pub mod tests {
             None,
             None,
             None,
+            None,
+            ParquetSettings::default(),
         ));
 
         i
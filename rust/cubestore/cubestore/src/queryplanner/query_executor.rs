@@ -10,15 +10,17 @@ use crate::queryplanner::planning::get_worker_plan;
 use crate::queryplanner::pretty_printers::{pp_phys_plan, pp_plan};
 use crate::queryplanner::serialized_plan::{IndexSnapshot, RowFilter, RowRange, SerializedPlan};
 use crate::store::DataFrame;
+use crate::table::parquet::read_index_file;
 use crate::table::{Row, TableValue, TimestampValue};
 use crate::{app_metrics, CubeError};
 use arrow::array::{
-    make_array, Array, ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array,
-    Int64Decimal0Array, Int64Decimal10Array, Int64Decimal1Array, Int64Decimal2Array,
-    Int64Decimal3Array, Int64Decimal4Array, Int64Decimal5Array, MutableArrayData, StringArray,
-    TimestampMicrosecondArray, TimestampNanosecondArray, UInt64Array,
+    make_array, Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, Float32Array,
+    Float64Array, Int16Array, Int32Array, Int64Array, Int64Decimal0Array, Int64Decimal10Array,
+    Int64Decimal1Array, Int64Decimal2Array, Int64Decimal3Array, Int64Decimal4Array,
+    Int64Decimal5Array, MutableArrayData, StringArray, TimestampMicrosecondArray,
+    TimestampNanosecondArray, UInt64Array,
 };
-use arrow::datatypes::{DataType, Schema, SchemaRef, TimeUnit};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
 use arrow::ipc::reader::StreamReader;
 use arrow::ipc::writer::MemStreamWriter;
 use arrow::record_batch::RecordBatch;
@@ -30,12 +32,13 @@ use datafusion::error::DataFusionError;
 use datafusion::error::Result as DFResult;
 use datafusion::execution::context::{ExecutionConfig, ExecutionContext};
 use datafusion::logical_plan;
-use datafusion::logical_plan::{Expr, LogicalPlan};
+use datafusion::logical_plan::{DFSchema, Expr, LogicalPlan};
 use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::physical_plan::expressions::is_null;
+use datafusion::physical_plan::filter::FilterExec;
 use datafusion::physical_plan::memory::MemoryExec;
 use datafusion::physical_plan::merge::MergeExec;
 use datafusion::physical_plan::merge_sort::{LastRowByUniqueKeyExec, MergeSortExec};
-use datafusion::physical_plan::parquet::ParquetExec;
 use datafusion::physical_plan::projection::ProjectionExec;
 use datafusion::physical_plan::{
     collect, ExecutionPlan, OptimizerHints, Partitioning, PhysicalExpr, SendableRecordBatchStream,
@@ -63,6 +66,13 @@ pub trait QueryExecutor: DIService + Send + Sync {
         cluster: Arc<dyn Cluster>,
     ) -> Result<(SchemaRef, Vec<RecordBatch>), CubeError>;
 
+    /// Same as [execute_router_plan], but returns the batches as soon as they are computed.
+    async fn execute_router_plan_stream(
+        &self,
+        plan: SerializedPlan,
+        cluster: Arc<dyn Cluster>,
+    ) -> Result<SendableRecordBatchStream, CubeError>;
+
     async fn execute_worker_plan(
         &self,
         plan: SerializedPlan,
@@ -139,6 +149,26 @@ impl QueryExecutor for QueryExecutorImpl {
         Ok((split_plan.schema(), results?))
     }
 
+    #[instrument(level = "trace", skip(self, plan, cluster))]
+    async fn execute_router_plan_stream(
+        &self,
+        plan: SerializedPlan,
+        cluster: Arc<dyn Cluster>,
+    ) -> Result<SendableRecordBatchStream, CubeError> {
+        let (physical_plan, _) = self.router_plan(plan, cluster).await?;
+        trace!(
+            "Router Query Physical Plan: {}",
+            pp_phys_plan(physical_plan.as_ref())
+        );
+        let physical_plan: Arc<dyn ExecutionPlan> =
+            if physical_plan.output_partitioning().partition_count() == 1 {
+                physical_plan
+            } else {
+                Arc::new(MergeExec::new(physical_plan))
+            };
+        Ok(physical_plan.execute(0).await?)
+    }
+
     #[instrument(level = "trace", skip(self, plan, remote_to_local_names))]
     async fn execute_worker_plan(
         &self,
@@ -297,14 +327,14 @@ impl CubeTable {
         let schema = Arc::new(Schema::new(
             // Tables are always exposed only using table columns order instead of index one because
             // index isn't selected until logical optimization plan is done.
-            // Projection indices would refer to these table columns
+            // Projection indices would refer to these columns, the same as of the logical table.
             index_snapshot
                 .table_path
                 .table
                 .get_row()
-                .get_columns()
-                .iter()
-                .map(|c| c.clone().into())
+                .insertable_columns()
+                .into_iter()
+                .map(|c| c.into())
                 .collect(),
         ));
         Ok(Self {
@@ -345,17 +375,35 @@ impl CubeTable {
 
         let mut partition_execs = Vec::<Arc<dyn ExecutionPlan>>::new();
         let table_cols = self.index_snapshot.table().get_row().get_columns();
+        let table_fields = table_cols
+            .iter()
+            .map(|c| c.clone().into())
+            .collect::<Vec<Field>>();
         let index_cols = self.index_snapshot.index().get_row().get_columns();
 
         // We always introduce projection because index and table columns do not match in general
         // case so we can use simpler code without branching to handle it.
+        // The deleted column is internal and isn't in the exposed schema, it can be anywhere among
+        // table columns after ALTER TABLE, so the projection is mapped to them by name. It's only
+        // read along with the unique key below.
         let table_projection = table_projection
             .clone()
-            .unwrap_or((0..self.schema.fields().len()).collect::<Vec<_>>());
+            .unwrap_or((0..self.schema.fields().len()).collect::<Vec<_>>())
+            .iter()
+            .map(|i| {
+                let name = self.schema.field(*i).name();
+                table_cols
+                    .iter()
+                    .find_position(|c| c.get_name() == name)
+                    .unwrap()
+                    .0
+            })
+            .collect::<Vec<_>>();
 
         // Prepare projection
         // If it's non last row query just return projection itself
         // If it's last row query re-project it as (key1, key2, __seq, col3, col4)
+        // Tables with deletes also keep __deleted to drop tombstones after the last row is found.
         let table_projection_with_seq_column = {
             let table = self.index_snapshot.table_path.table.get_row();
             if let Some(mut key_columns) = table.unique_key_columns() {
@@ -363,6 +411,7 @@ impl CubeTable {
                     "Seq column is undefined for table: {}",
                     table.get_table_name()
                 )));
+                key_columns.extend(table.deleted_column());
                 let mut with_seq = Vec::new();
                 for column in key_columns {
                     if !with_seq.iter().any(|s| *s == column.get_index()) {
@@ -403,14 +452,11 @@ impl CubeTable {
             index_cols
                 .iter()
                 .map(|i| {
-                    self.schema
-                        .field(
-                            table_cols
-                                .iter()
-                                .find_position(|c| c.get_name() == i.get_name())
-                                .unwrap()
-                                .0,
-                        )
+                    table_fields[table_cols
+                        .iter()
+                        .find_position(|c| c.get_name() == i.get_name())
+                        .unwrap()
+                        .0]
                         .clone()
                 })
                 .collect(),
@@ -450,14 +496,14 @@ impl CubeTable {
                     .remote_to_local_names
                     .get(remote_path.as_str())
                     .expect(format!("Missing remote path {}", remote_path).as_str());
-                let arc: Arc<dyn ExecutionPlan> = Arc::new(ParquetExec::try_from_path(
+                // TODO: propagate limit
+                let arc = read_index_file(
                     &local_path,
+                    self.index_snapshot.index.get_row(),
                     index_projection_or_none_on_schema_match.clone(),
                     predicate.clone(),
                     batch_size,
-                    1,
-                    None, // TODO: propagate limit
-                )?);
+                )?;
                 let arc = FilterByKeyRangeExec::issue_filters(arc, filter.clone(), key_len);
                 partition_execs.push(arc);
             }
@@ -492,14 +538,14 @@ impl CubeTable {
                         .remote_to_local_names
                         .get(&remote_path)
                         .expect(format!("Missing remote path {}", remote_path).as_str());
-                    Arc::new(ParquetExec::try_from_path(
+                    // TODO: propagate limit
+                    read_index_file(
                         local_path,
+                        self.index_snapshot.index.get_row(),
                         index_projection_or_none_on_schema_match.clone(),
                         predicate.clone(),
                         batch_size,
-                        1,
-                        None, // TODO: propagate limit
-                    )?)
+                    )?
                 };
 
                 let node = FilterByKeyRangeExec::issue_filters(node, filter.clone(), key_len);
@@ -549,7 +595,7 @@ impl CubeTable {
             Arc::new(Schema::new(
                 table_projection_with_seq_column
                     .iter()
-                    .map(|i| self.schema.field(*i).clone())
+                    .map(|i| table_fields[*i].clone())
                     .collect(),
             ))
         };
@@ -608,6 +654,23 @@ impl CubeTable {
                     })
                     .collect::<Result<Vec<_>, _>>()?,
             )?);
+            if let Some(deleted_column) = self
+                .index_snapshot
+                .table_path
+                .table
+                .get_row()
+                .deleted_column()
+            {
+                exec = Arc::new(FilterExec::try_new(
+                    is_null(Arc::new(
+                        datafusion::physical_plan::expressions::Column::new_with_schema(
+                            deleted_column.get_name().as_str(),
+                            &exec.schema(),
+                        )?,
+                    ))?,
+                    exec,
+                )?);
+            }
 
             // At this point data is projected for last row query and we need to re-project it to what actually queried
             let s = exec.schema();
@@ -655,13 +718,15 @@ impl CubeTable {
             .collect::<Vec<_>>()
     }
 
+    /// Projection indices refer to [Table::insertable_columns], as the schema of the table does.
     pub fn project_to_table(
         table: &IdRow<Table>,
         projection_column_indices: &Vec<usize>,
     ) -> Vec<Column> {
+        let columns = table.get_row().insertable_columns();
         projection_column_indices
             .iter()
-            .map(|i| table.get_row().get_columns()[*i].clone())
+            .map(|i| columns[*i].clone())
             .collect::<Vec<_>>()
     }
 }
@@ -1078,6 +1143,18 @@ macro_rules! convert_array {
     }};
 }
 
+/// Names of tables the columns of [schema] are read from, without the schema name.
+pub fn source_tables(schema: &DFSchema) -> Vec<Option<String>> {
+    schema
+        .fields()
+        .iter()
+        .map(|f| {
+            f.qualifier()
+                .map(|q| q.rsplit('.').next().unwrap_or(q).to_string())
+        })
+        .collect()
+}
+
 pub fn batch_to_dataframe(batches: &Vec<RecordBatch>) -> Result<DataFrame, CubeError> {
     let mut cols = vec![];
     let mut all_rows = vec![];
@@ -1108,6 +1185,28 @@ pub fn batch_to_dataframe(batches: &Vec<RecordBatch>) -> Result<DataFrame, CubeE
             match array.data_type() {
                 DataType::UInt64 => convert_array!(array, num_rows, rows, UInt64Array, Int, i64),
                 DataType::Int64 => convert_array!(array, num_rows, rows, Int64Array, Int, i64),
+                DataType::Int32 => convert_array!(array, num_rows, rows, Int32Array, Int, i64),
+                DataType::Int16 => convert_array!(array, num_rows, rows, Int16Array, Int, i64),
+                DataType::Float32 => {
+                    let a = array.as_any().downcast_ref::<Float32Array>().unwrap();
+                    for i in 0..num_rows {
+                        rows[i].push(if a.is_null(i) {
+                            TableValue::Null
+                        } else {
+                            TableValue::Float((a.value(i) as f64).into())
+                        });
+                    }
+                }
+                DataType::Date32 => {
+                    let a = array.as_any().downcast_ref::<Date32Array>().unwrap();
+                    for i in 0..num_rows {
+                        rows[i].push(if a.is_null(i) {
+                            TableValue::Null
+                        } else {
+                            TableValue::Timestamp(TimestampValue::from_days(a.value(i)))
+                        });
+                    }
+                }
                 DataType::Float64 => {
                     let a = array.as_any().downcast_ref::<Float64Array>().unwrap();
                     for i in 0..num_rows {
@@ -1238,14 +1337,16 @@ pub fn arrow_to_column_type(arrow_type: DataType) -> Result<ColumnType, CubeErro
         DataType::Utf8 | DataType::LargeUtf8 => Ok(ColumnType::String),
         DataType::Timestamp(_, _) => Ok(ColumnType::Timestamp),
         DataType::Float16 | DataType::Float64 => Ok(ColumnType::Float),
+        DataType::Float32 => Ok(ColumnType::Float32),
+        DataType::Date32 => Ok(ColumnType::Date),
+        DataType::Int16 => Ok(ColumnType::Int16),
+        DataType::Int32 => Ok(ColumnType::Int32),
         DataType::Int64Decimal(scale) => Ok(ColumnType::Decimal {
             scale: scale as i32,
             precision: 18,
         }),
         DataType::Boolean => Ok(ColumnType::Boolean),
         DataType::Int8
-        | DataType::Int16
-        | DataType::Int32
         | DataType::Int64
         | DataType::UInt8
         | DataType::UInt16
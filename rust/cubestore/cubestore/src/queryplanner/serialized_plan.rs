@@ -19,8 +19,8 @@ use datafusion::cube_ext::joinagg::CrossJoinAgg;
 use datafusion::cube_ext::rolling::RollingWindowAggregate;
 use datafusion::logical_plan::window_frames::WindowFrameBound;
 use datafusion::logical_plan::{
-    Column, DFSchemaRef, Expr, JoinConstraint, JoinType, LogicalPlan, Operator, Partitioning,
-    PlanVisitor,
+    Column, DFSchema, DFSchemaRef, Expr, JoinConstraint, JoinType, LogicalPlan, Operator,
+    Partitioning, PlanVisitor,
 };
 use datafusion::physical_plan::{aggregates, functions};
 use datafusion::scalar::ScalarValue;
@@ -246,6 +246,32 @@ pub struct WorkerContext {
 }
 
 impl SerializedLogicalPlan {
+    /// Schema of the plan output, same as [LogicalPlan::schema] of the deserialized plan.
+    pub fn schema(&self) -> DFSchemaRef {
+        match self {
+            SerializedLogicalPlan::Projection { schema, .. }
+            | SerializedLogicalPlan::Aggregate { schema, .. }
+            | SerializedLogicalPlan::Union { schema, .. }
+            | SerializedLogicalPlan::Join { schema, .. }
+            | SerializedLogicalPlan::EmptyRelation { schema, .. }
+            | SerializedLogicalPlan::Alias { schema, .. }
+            | SerializedLogicalPlan::ClusterAggregateTopK { schema, .. }
+            | SerializedLogicalPlan::CrossJoinAgg { schema, .. }
+            | SerializedLogicalPlan::RollingWindowAgg { schema, .. } => schema.clone(),
+            SerializedLogicalPlan::TableScan {
+                projected_schema, ..
+            } => projected_schema.clone(),
+            SerializedLogicalPlan::CrossJoin { join_schema, .. } => join_schema.clone(),
+            SerializedLogicalPlan::Filter { input, .. }
+            | SerializedLogicalPlan::Sort { input, .. }
+            | SerializedLogicalPlan::Limit { input, .. }
+            | SerializedLogicalPlan::Skip { input, .. }
+            | SerializedLogicalPlan::Repartition { input, .. }
+            | SerializedLogicalPlan::ClusterSend { input, .. } => input.schema(),
+            SerializedLogicalPlan::Panic {} => Arc::new(DFSchema::empty()),
+        }
+    }
+
     fn logical_plan(&self, worker_context: &WorkerContext) -> Result<LogicalPlan, CubeError> {
         debug_assert!(worker_context
             .worker_partition_ids
@@ -680,6 +706,10 @@ impl SerializedPlan {
         })
     }
 
+    pub fn schema(&self) -> DFSchemaRef {
+        self.logical_plan.schema()
+    }
+
     pub fn index_snapshots(&self) -> &Vec<IndexSnapshot> {
         &self.schema_snapshot.index_snapshots.indices
     }
@@ -1,9 +1,18 @@
-use crate::queryplanner::coalesce::{coalesce, SUPPORTED_COALESCE_TYPES};
+use crate::queryplanner::coalesce::{coalesce, greatest_least, SUPPORTED_COALESCE_TYPES};
+use crate::queryplanner::datetime::{convert_tz, date_trunc, Granularity, TimeZoneArg};
 use crate::queryplanner::hll::Hll;
 use crate::CubeError;
-use arrow::array::{Array, BinaryArray, TimestampNanosecondArray, UInt64Builder};
+use arrow::array::{
+    Array, ArrayRef, BinaryArray, Float32Array, Float64Array, Int16Array, Int32Array, Int64Array,
+    Int64Decimal0Array, Int64Decimal10Array, Int64Decimal1Array, Int64Decimal2Array,
+    Int64Decimal3Array, Int64Decimal4Array, Int64Decimal5Array, StringArray,
+    TimestampNanosecondArray, UInt64Builder,
+};
+use arrow::compute::kernels::cast::cast;
 use arrow::datatypes::{DataType, IntervalUnit, TimeUnit};
 use chrono::{TimeZone, Utc};
+use cubehll::HllSketch;
+use cubezetasketch::HyperLogLogPlusPlus;
 use datafusion::cube_ext::datetime::{date_addsub_array, date_addsub_scalar};
 use datafusion::error::DataFusionError;
 use datafusion::physical_plan::functions::Signature;
@@ -14,6 +23,7 @@ use datafusion::scalar::ScalarValue;
 use serde_derive::{Deserialize, Serialize};
 use smallvec::smallvec;
 use smallvec::SmallVec;
+use std::convert::TryFrom;
 use std::sync::Arc;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
@@ -24,6 +34,12 @@ pub enum CubeScalarUDFKind {
     UnixTimestamp,
     DateAdd,
     DateSub,
+    DateTrunc,
+    ConvertTz,
+    Substring,
+    Round,
+    Greatest,
+    Least,
 }
 
 pub trait CubeScalarUDF {
@@ -40,6 +56,12 @@ pub fn scalar_udf_by_kind(k: CubeScalarUDFKind) -> Box<dyn CubeScalarUDF> {
         CubeScalarUDFKind::UnixTimestamp => Box::new(UnixTimestamp {}),
         CubeScalarUDFKind::DateAdd => Box::new(DateAddSub { is_add: true }),
         CubeScalarUDFKind::DateSub => Box::new(DateAddSub { is_add: false }),
+        CubeScalarUDFKind::DateTrunc => Box::new(DateTrunc {}),
+        CubeScalarUDFKind::ConvertTz => Box::new(ConvertTz {}),
+        CubeScalarUDFKind::Substring => Box::new(Substring {}),
+        CubeScalarUDFKind::Round => Box::new(Round {}),
+        CubeScalarUDFKind::Greatest => Box::new(GreatestLeast { greatest: true }),
+        CubeScalarUDFKind::Least => Box::new(GreatestLeast { greatest: false }),
     }
 }
 
@@ -63,24 +85,48 @@ pub fn scalar_kind_by_name(n: &str) -> Option<CubeScalarUDFKind> {
     if n == "DATE_SUB" {
         return Some(CubeScalarUDFKind::DateSub);
     }
+    if n == "DATE_TRUNC" {
+        return Some(CubeScalarUDFKind::DateTrunc);
+    }
+    if n == "CONVERT_TZ" {
+        return Some(CubeScalarUDFKind::ConvertTz);
+    }
+    if n == "SUBSTRING" {
+        return Some(CubeScalarUDFKind::Substring);
+    }
+    if n == "ROUND" {
+        return Some(CubeScalarUDFKind::Round);
+    }
+    if n == "GREATEST" {
+        return Some(CubeScalarUDFKind::Greatest);
+    }
+    if n == "LEAST" {
+        return Some(CubeScalarUDFKind::Least);
+    }
     return None;
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum CubeAggregateUDFKind {
-    MergeHll, // merge(), accepting the HyperLogLog sketches.
+    MergeHll,            // merge(), accepting the HyperLogLog sketches.
+    ApproxDistinctMerge, // cardinality(merge()) without writing out the merged sketch.
+    HllAddAgg,           // hll_add_agg(), building the HyperLogLog sketch from values.
+    HllAddAggZeta,       // hll_add_agg_zeta(), same as hll_add_agg(), but builds ZetaSketch.
 }
 
 pub trait CubeAggregateUDF {
     fn kind(&self) -> CubeAggregateUDFKind;
     fn name(&self) -> &str;
     fn descriptor(&self) -> AggregateUDF;
-    fn accumulator(&self) -> Box<dyn Accumulator>;
+    fn accumulator(&self) -> Result<Box<dyn Accumulator>, DataFusionError>;
 }
 
 pub fn aggregate_udf_by_kind(k: CubeAggregateUDFKind) -> Box<dyn CubeAggregateUDF> {
     match k {
         CubeAggregateUDFKind::MergeHll => Box::new(HllMergeUDF {}),
+        CubeAggregateUDFKind::ApproxDistinctMerge => Box::new(ApproxDistinctMergeUDF {}),
+        CubeAggregateUDFKind::HllAddAgg => Box::new(HllAddAggUDF { zeta: false }),
+        CubeAggregateUDFKind::HllAddAggZeta => Box::new(HllAddAggUDF { zeta: true }),
     }
 }
 
@@ -89,6 +135,15 @@ pub fn aggregate_kind_by_name(n: &str) -> Option<CubeAggregateUDFKind> {
     if n == "MERGE" {
         return Some(CubeAggregateUDFKind::MergeHll);
     }
+    if n == "APPROX_DISTINCT_MERGE" {
+        return Some(CubeAggregateUDFKind::ApproxDistinctMerge);
+    }
+    if n == "HLL_ADD_AGG" {
+        return Some(CubeAggregateUDFKind::HllAddAgg);
+    }
+    if n == "HLL_ADD_AGG_ZETA" {
+        return Some(CubeAggregateUDFKind::HllAddAggZeta);
+    }
     return None;
 }
 
@@ -285,6 +340,454 @@ impl CubeScalarUDF for DateAddSub {
     }
 }
 
+struct DateTrunc {}
+impl DateTrunc {
+    fn signature() -> Signature {
+        Signature::OneOf(vec![
+            Signature::Exact(vec![
+                DataType::Utf8,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+            ]),
+            Signature::Exact(vec![
+                DataType::Utf8,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                DataType::Utf8,
+            ]),
+        ])
+    }
+}
+impl CubeScalarUDF for DateTrunc {
+    fn kind(&self) -> CubeScalarUDFKind {
+        CubeScalarUDFKind::DateTrunc
+    }
+
+    fn name(&self) -> &str {
+        "DATE_TRUNC"
+    }
+
+    fn descriptor(&self) -> ScalarUDF {
+        return ScalarUDF {
+            name: self.name().to_string(),
+            signature: Self::signature(),
+            return_type: Arc::new(|_| {
+                Ok(Arc::new(DataType::Timestamp(TimeUnit::Nanosecond, None)))
+            }),
+            fun: Arc::new(|inputs| {
+                let granularity = Granularity::parse(string_literal_arg(inputs, 0, "DATE_TRUNC")?)?;
+                let tz = if inputs.len() == 3 {
+                    Some(TimeZoneArg::parse(string_literal_arg(
+                        inputs,
+                        2,
+                        "DATE_TRUNC",
+                    )?)?)
+                } else {
+                    None
+                };
+                evaluate_arrays(&inputs[1..2], |a| {
+                    let t = downcast_arg::<TimestampNanosecondArray>(&a[0], "DATE_TRUNC")?;
+                    Ok(Arc::new(date_trunc(granularity, t, tz)?))
+                })
+            }),
+        };
+    }
+}
+
+/// `CONVERT_TZ(t, to_tz)` converts UTC timestamps, `CONVERT_TZ(t, from_tz, to_tz)` converts
+/// between arbitrary time zones.
+struct ConvertTz {}
+impl ConvertTz {
+    fn signature() -> Signature {
+        Signature::OneOf(vec![
+            Signature::Exact(vec![
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                DataType::Utf8,
+            ]),
+            Signature::Exact(vec![
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                DataType::Utf8,
+                DataType::Utf8,
+            ]),
+        ])
+    }
+}
+impl CubeScalarUDF for ConvertTz {
+    fn kind(&self) -> CubeScalarUDFKind {
+        CubeScalarUDFKind::ConvertTz
+    }
+
+    fn name(&self) -> &str {
+        "CONVERT_TZ"
+    }
+
+    fn descriptor(&self) -> ScalarUDF {
+        return ScalarUDF {
+            name: self.name().to_string(),
+            signature: Self::signature(),
+            return_type: Arc::new(|_| {
+                Ok(Arc::new(DataType::Timestamp(TimeUnit::Nanosecond, None)))
+            }),
+            fun: Arc::new(|inputs| {
+                let to = TimeZoneArg::parse(string_literal_arg(
+                    inputs,
+                    inputs.len() - 1,
+                    "CONVERT_TZ",
+                )?)?;
+                let from = if inputs.len() == 3 {
+                    Some(TimeZoneArg::parse(string_literal_arg(
+                        inputs,
+                        1,
+                        "CONVERT_TZ",
+                    )?)?)
+                } else {
+                    None
+                };
+                evaluate_arrays(&inputs[0..1], |a| {
+                    let t = downcast_arg::<TimestampNanosecondArray>(&a[0], "CONVERT_TZ")?;
+                    Ok(Arc::new(convert_tz(t, from, to)?))
+                })
+            }),
+        };
+    }
+}
+
+/// `SUBSTRING(s, start[, len])` with MySQL semantics: positions are 1-based and count
+/// characters, negative `start` counts from the end of the string.
+struct Substring {}
+impl Substring {
+    fn signature() -> Signature {
+        Signature::OneOf(vec![
+            Signature::Exact(vec![DataType::Utf8, DataType::Int64]),
+            Signature::Exact(vec![DataType::Utf8, DataType::Int64, DataType::Int64]),
+        ])
+    }
+}
+impl CubeScalarUDF for Substring {
+    fn kind(&self) -> CubeScalarUDFKind {
+        CubeScalarUDFKind::Substring
+    }
+
+    fn name(&self) -> &str {
+        "SUBSTRING"
+    }
+
+    fn descriptor(&self) -> ScalarUDF {
+        return ScalarUDF {
+            name: self.name().to_string(),
+            signature: Self::signature(),
+            return_type: Arc::new(|_| Ok(Arc::new(DataType::Utf8))),
+            fun: Arc::new(|inputs| {
+                evaluate_arrays(inputs, |a| {
+                    let s = downcast_arg::<StringArray>(&a[0], "SUBSTRING")?;
+                    let start = downcast_arg::<Int64Array>(&a[1], "SUBSTRING")?;
+                    let len = a
+                        .get(2)
+                        .map(|l| downcast_arg::<Int64Array>(l, "SUBSTRING"))
+                        .transpose()?;
+                    Ok(Arc::new(
+                        (0..s.len())
+                            .map(|i| {
+                                if s.is_null(i)
+                                    || start.is_null(i)
+                                    || len.map(|l| l.is_null(i)).unwrap_or(false)
+                                {
+                                    return None;
+                                }
+                                Some(substring(
+                                    s.value(i),
+                                    start.value(i),
+                                    len.map(|l| l.value(i)),
+                                ))
+                            })
+                            .collect::<StringArray>(),
+                    ))
+                })
+            }),
+        };
+    }
+}
+
+fn substring(s: &str, start: i64, len: Option<i64>) -> String {
+    let chars = s.chars().count() as i64;
+    let from = if 0 < start { start - 1 } else { chars + start };
+    if start == 0 || from < 0 || chars <= from {
+        return String::new();
+    }
+    let len = len.unwrap_or(chars).max(0);
+    s.chars().skip(from as usize).take(len as usize).collect()
+}
+
+/// `ROUND(x[, digits])` rounds half away from zero. With `digits`, the result keeps the type of
+/// `x`, so rounding of decimals does not change their scale. Without `digits`, decimals keep
+/// their type too, while other numbers are returned as floats as the built-in `ROUND` did.
+struct Round {}
+impl Round {
+    fn signature() -> Signature {
+        let types = vec![
+            DataType::Int16,
+            DataType::Int32,
+            DataType::Int64,
+            DataType::Int64Decimal(0),
+            DataType::Int64Decimal(1),
+            DataType::Int64Decimal(2),
+            DataType::Int64Decimal(3),
+            DataType::Int64Decimal(4),
+            DataType::Int64Decimal(5),
+            DataType::Int64Decimal(10),
+            DataType::Float32,
+            DataType::Float64,
+        ];
+        Signature::OneOf(
+            types
+                .into_iter()
+                .flat_map(|t| {
+                    vec![
+                        Signature::Exact(vec![t.clone()]),
+                        Signature::Exact(vec![t, DataType::Int64]),
+                    ]
+                })
+                .collect(),
+        )
+    }
+
+    fn returns_float(inputs: &[DataType]) -> bool {
+        inputs.len() == 1
+            && matches!(
+                inputs[0],
+                DataType::Int16 | DataType::Int32 | DataType::Int64
+            )
+    }
+}
+impl CubeScalarUDF for Round {
+    fn kind(&self) -> CubeScalarUDFKind {
+        CubeScalarUDFKind::Round
+    }
+
+    fn name(&self) -> &str {
+        "ROUND"
+    }
+
+    fn descriptor(&self) -> ScalarUDF {
+        return ScalarUDF {
+            name: self.name().to_string(),
+            signature: Self::signature(),
+            return_type: Arc::new(|inputs| {
+                let ts = type_coercion::data_types(inputs, &Self::signature())?;
+                if Self::returns_float(&ts) {
+                    return Ok(Arc::new(DataType::Float64));
+                }
+                Ok(Arc::new(ts[0].clone()))
+            }),
+            fun: Arc::new(|inputs| {
+                let digits = match inputs.get(1) {
+                    None => 0,
+                    Some(ColumnarValue::Scalar(ScalarValue::Int64(Some(d)))) => *d,
+                    Some(_) => {
+                        return Err(DataFusionError::Execution(
+                            "Second argument of `ROUND` must be a non-null integer literal"
+                                .to_string(),
+                        ))
+                    }
+                };
+                evaluate_arrays(&inputs[0..1], |a| {
+                    if Self::returns_float(&[a[0].data_type().clone()]) {
+                        return Ok(cast(&a[0], &DataType::Float64)?);
+                    }
+                    round_array(&a[0], digits)
+                })
+            }),
+        };
+    }
+}
+
+fn round_array(a: &ArrayRef, digits: i64) -> Result<ArrayRef, DataFusionError> {
+    macro_rules! round_int_array {
+        ($arr: ty, $scale: expr) => {{
+            let a = downcast_arg::<$arr>(a, "ROUND")?;
+            let r = a
+                .iter()
+                .map(|v| v.map(|v| round_int(v, $scale, digits)).transpose())
+                .collect::<Result<$arr, _>>()?;
+            Ok(Arc::new(r) as ArrayRef)
+        }};
+    }
+    macro_rules! round_narrow_int_array {
+        ($arr: ty, $native: ty) => {{
+            let a = downcast_arg::<$arr>(a, "ROUND")?;
+            let r = a
+                .iter()
+                .map(|v| {
+                    v.map(|v| {
+                        let r = round_int(v as i64, 0, digits)?;
+                        <$native>::try_from(r).map_err(|_| round_overflow(v))
+                    })
+                    .transpose()
+                })
+                .collect::<Result<$arr, _>>()?;
+            Ok(Arc::new(r) as ArrayRef)
+        }};
+    }
+    match a.data_type() {
+        DataType::Int16 => round_narrow_int_array!(Int16Array, i16),
+        DataType::Int32 => round_narrow_int_array!(Int32Array, i32),
+        DataType::Int64 => round_int_array!(Int64Array, 0),
+        DataType::Int64Decimal(0) => round_int_array!(Int64Decimal0Array, 0),
+        DataType::Int64Decimal(1) => round_int_array!(Int64Decimal1Array, 1),
+        DataType::Int64Decimal(2) => round_int_array!(Int64Decimal2Array, 2),
+        DataType::Int64Decimal(3) => round_int_array!(Int64Decimal3Array, 3),
+        DataType::Int64Decimal(4) => round_int_array!(Int64Decimal4Array, 4),
+        DataType::Int64Decimal(5) => round_int_array!(Int64Decimal5Array, 5),
+        DataType::Int64Decimal(10) => round_int_array!(Int64Decimal10Array, 10),
+        DataType::Float32 => {
+            let a = downcast_arg::<Float32Array>(a, "ROUND")?;
+            let m = 10f64.powi(digits.max(-308).min(308) as i32);
+            Ok(Arc::new(
+                a.iter()
+                    .map(|v| v.map(|v| round_float(v as f64, m, digits) as f32))
+                    .collect::<Float32Array>(),
+            ))
+        }
+        DataType::Float64 => {
+            let a = downcast_arg::<Float64Array>(a, "ROUND")?;
+            let m = 10f64.powi(digits.max(-308).min(308) as i32);
+            Ok(Arc::new(
+                a.iter()
+                    .map(|v| v.map(|v| round_float(v, m, digits)))
+                    .collect::<Float64Array>(),
+            ))
+        }
+        t => Err(DataFusionError::Internal(format!(
+            "Unsupported type in `ROUND`: {}",
+            t
+        ))),
+    }
+}
+
+/// `m` is `10^digits`.
+fn round_float(v: f64, m: f64, digits: i64) -> f64 {
+    if 15 < digits {
+        v
+    } else {
+        (v * m).round() / m
+    }
+}
+
+/// Rounds `v` with `scale` decimal digits to `digits` decimal digits.
+fn round_int(v: i64, scale: i64, digits: i64) -> Result<i64, DataFusionError> {
+    if scale <= digits {
+        return Ok(v);
+    }
+    // Any i64 rounds to zero with larger shifts, so the factor can be capped to fit into i128.
+    let shift = (scale - digits).min(38);
+    let factor = 10i128.pow(shift as u32);
+    let rem = v as i128 % factor;
+    let mut r = v as i128 - rem;
+    if factor <= rem.abs() * 2 {
+        r += rem.signum() * factor;
+    }
+    i64::try_from(r).map_err(|_| round_overflow(v))
+}
+
+fn round_overflow(v: impl std::fmt::Display) -> DataFusionError {
+    DataFusionError::Execution(format!("Overflow in `ROUND` of {}", v))
+}
+
+struct GreatestLeast {
+    greatest: bool,
+}
+impl GreatestLeast {
+    fn signature() -> Signature {
+        Signature::Variadic(SUPPORTED_COALESCE_TYPES.to_vec())
+    }
+
+    fn name_static(&self) -> &'static str {
+        match self.greatest {
+            true => "GREATEST",
+            false => "LEAST",
+        }
+    }
+}
+impl CubeScalarUDF for GreatestLeast {
+    fn kind(&self) -> CubeScalarUDFKind {
+        match self.greatest {
+            true => CubeScalarUDFKind::Greatest,
+            false => CubeScalarUDFKind::Least,
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.name_static()
+    }
+
+    fn descriptor(&self) -> ScalarUDF {
+        let name = self.name_static();
+        let greatest = self.greatest;
+        return ScalarUDF {
+            name: self.name().to_string(),
+            signature: Self::signature(),
+            return_type: Arc::new(move |inputs| {
+                if inputs.is_empty() {
+                    return Err(DataFusionError::Plan(format!(
+                        "{} requires at least 1 argument",
+                        name
+                    )));
+                }
+                let ts = type_coercion::data_types(inputs, &Self::signature())?;
+                Ok(Arc::new(ts[0].clone()))
+            }),
+            fun: Arc::new(move |inputs| evaluate_arrays(inputs, |a| greatest_least(a, greatest))),
+        };
+    }
+}
+
+/// Evaluates `f` over array arguments. Scalar arguments are turned into arrays, the result is a
+/// scalar when all arguments are scalars.
+fn evaluate_arrays(
+    inputs: &[ColumnarValue],
+    f: impl FnOnce(&[ArrayRef]) -> Result<ArrayRef, DataFusionError>,
+) -> Result<ColumnarValue, DataFusionError> {
+    let len = inputs.iter().find_map(|i| match i {
+        ColumnarValue::Array(a) => Some(a.len()),
+        ColumnarValue::Scalar(_) => None,
+    });
+    let arrays = inputs
+        .iter()
+        .map(|i| i.clone().into_array(len.unwrap_or(1)))
+        .collect::<Vec<_>>();
+    let r = f(&arrays)?;
+    match len {
+        Some(_) => Ok(ColumnarValue::Array(r)),
+        None => Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(&r, 0)?)),
+    }
+}
+
+fn downcast_arg<'a, T: Array + 'static>(
+    a: &'a ArrayRef,
+    fun: &str,
+) -> Result<&'a T, DataFusionError> {
+    a.as_any().downcast_ref::<T>().ok_or_else(|| {
+        DataFusionError::Internal(format!(
+            "Unexpected argument type in `{}`: {}",
+            fun,
+            a.data_type()
+        ))
+    })
+}
+
+fn string_literal_arg<'a>(
+    inputs: &'a [ColumnarValue],
+    i: usize,
+    fun: &str,
+) -> Result<&'a str, DataFusionError> {
+    match &inputs[i] {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(s))) => Ok(s.as_str()),
+        _ => Err(DataFusionError::Execution(format!(
+            "Argument {} of `{}` must be a non-null string literal",
+            i + 1,
+            fun
+        ))),
+    }
+}
+
 struct HllCardinality {}
 impl CubeScalarUDF for HllCardinality {
     fn kind(&self) -> CubeScalarUDFKind {
@@ -344,8 +847,8 @@ impl CubeAggregateUDF for HllMergeUDF {
             state_type: Arc::new(|_| Ok(Arc::new(vec![DataType::Binary]))),
         };
     }
-    fn accumulator(&self) -> Box<dyn Accumulator> {
-        return Box::new(HllMergeAccumulator { acc: None });
+    fn accumulator(&self) -> Result<Box<dyn Accumulator>, DataFusionError> {
+        return Ok(Box::new(HllMergeAccumulator { acc: None }));
     }
 }
 
@@ -433,6 +936,283 @@ impl HllMergeAccumulator {
     }
 }
 
+struct ApproxDistinctMergeUDF {}
+impl CubeAggregateUDF for ApproxDistinctMergeUDF {
+    fn kind(&self) -> CubeAggregateUDFKind {
+        return CubeAggregateUDFKind::ApproxDistinctMerge;
+    }
+    fn name(&self) -> &str {
+        return "APPROX_DISTINCT_MERGE";
+    }
+    fn descriptor(&self) -> AggregateUDF {
+        return AggregateUDF {
+            name: self.name().to_string(),
+            signature: Signature::Exact(vec![DataType::Binary]),
+            return_type: Arc::new(|_| Ok(Arc::new(DataType::UInt64))),
+            accumulator: Arc::new(|| Ok(Box::new(ApproxDistinctMergeAccumulator::new()))),
+            state_type: Arc::new(|_| Ok(Arc::new(vec![DataType::Binary]))),
+        };
+    }
+    fn accumulator(&self) -> Result<Box<dyn Accumulator>, DataFusionError> {
+        return Ok(Box::new(ApproxDistinctMergeAccumulator::new()));
+    }
+}
+
+/// Same as `CARDINALITY(MERGE(x))`. Partial states are still sketches, but the final result is
+/// estimated directly from the merged sketch without serializing it.
+#[derive(Debug)]
+struct ApproxDistinctMergeAccumulator {
+    merge: HllMergeAccumulator,
+}
+
+impl ApproxDistinctMergeAccumulator {
+    fn new() -> ApproxDistinctMergeAccumulator {
+        ApproxDistinctMergeAccumulator {
+            merge: HllMergeAccumulator { acc: None },
+        }
+    }
+}
+
+impl Accumulator for ApproxDistinctMergeAccumulator {
+    fn reset(&mut self) {
+        self.merge.reset()
+    }
+
+    fn state(&self) -> Result<SmallVec<[ScalarValue; 2]>, DataFusionError> {
+        self.merge.state()
+    }
+
+    fn update(&mut self, row: &[ScalarValue]) -> Result<(), DataFusionError> {
+        self.merge.update(row)
+    }
+
+    fn merge(&mut self, states: &[ScalarValue]) -> Result<(), DataFusionError> {
+        self.merge.merge(states)
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, DataFusionError> {
+        // Cardinality of ZetaSketch requires mutable access, hence the copy.
+        let cardinality = match &self.merge.acc {
+            None => 0,
+            Some(s) => s.clone().cardinality(),
+        };
+        return Ok(ScalarValue::UInt64(Some(cardinality)));
+    }
+}
+
+/// `HLL_ADD_AGG(x)` builds an Airlift sketch from the values of `x`, so results can be passed
+/// to `MERGE` and `CARDINALITY` along with sketches imported from Presto or Athena.
+/// `HLL_ADD_AGG_ZETA(x)` builds a ZetaSketch instead, to be used along with sketches imported
+/// from BigQuery.
+struct HllAddAggUDF {
+    zeta: bool,
+}
+impl HllAddAggUDF {
+    /// Same as the default precision of `approx_set` in Presto.
+    const NUM_BUCKETS: u32 = 4096;
+    /// Same as the default precision of `HLL_COUNT.INIT` in BigQuery.
+    const ZETA_PRECISION: i32 = 15;
+
+    fn signature() -> Signature {
+        Signature::OneOf(
+            vec![
+                DataType::Int64,
+                DataType::Int64Decimal(0),
+                DataType::Int64Decimal(1),
+                DataType::Int64Decimal(2),
+                DataType::Int64Decimal(3),
+                DataType::Int64Decimal(4),
+                DataType::Int64Decimal(5),
+                DataType::Int64Decimal(10),
+                DataType::Float64,
+                DataType::Boolean,
+                DataType::Utf8,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+            ]
+            .into_iter()
+            .map(|t| Signature::Exact(vec![t]))
+            .collect(),
+        )
+    }
+
+    fn new_sketch(zeta: bool) -> Result<Hll, DataFusionError> {
+        if zeta {
+            Ok(Hll::ZetaSketch(
+                HyperLogLogPlusPlus::new(
+                    Self::ZETA_PRECISION,
+                    Self::ZETA_PRECISION + HyperLogLogPlusPlus::DEFAULT_SPARSE_PRECISION_DELTA,
+                )
+                .map_err(|e| DataFusionError::Internal(e.to_string()))?,
+            ))
+        } else {
+            Ok(Hll::Airlift(
+                HllSketch::new(Self::NUM_BUCKETS)
+                    .map_err(|e| DataFusionError::Internal(e.to_string()))?,
+            ))
+        }
+    }
+}
+impl CubeAggregateUDF for HllAddAggUDF {
+    fn kind(&self) -> CubeAggregateUDFKind {
+        if self.zeta {
+            return CubeAggregateUDFKind::HllAddAggZeta;
+        } else {
+            return CubeAggregateUDFKind::HllAddAgg;
+        }
+    }
+    fn name(&self) -> &str {
+        if self.zeta {
+            return "HLL_ADD_AGG_ZETA";
+        } else {
+            return "HLL_ADD_AGG";
+        }
+    }
+    fn descriptor(&self) -> AggregateUDF {
+        let zeta = self.zeta;
+        return AggregateUDF {
+            name: self.name().to_string(),
+            signature: Self::signature(),
+            return_type: Arc::new(|_| Ok(Arc::new(DataType::Binary))),
+            accumulator: Arc::new(move || Ok(Box::new(HllAddAggAccumulator::new(zeta)?))),
+            state_type: Arc::new(|_| Ok(Arc::new(vec![DataType::Binary]))),
+        };
+    }
+    fn accumulator(&self) -> Result<Box<dyn Accumulator>, DataFusionError> {
+        return Ok(Box::new(HllAddAggAccumulator::new(self.zeta)?));
+    }
+}
+
+#[derive(Debug)]
+struct HllAddAggAccumulator {
+    acc: Hll,
+    /// Kept to reset the accumulator without re-validating sketch parameters.
+    empty: Hll,
+}
+
+impl HllAddAggAccumulator {
+    fn new(zeta: bool) -> Result<HllAddAggAccumulator, DataFusionError> {
+        let empty = HllAddAggUDF::new_sketch(zeta)?;
+        Ok(HllAddAggAccumulator {
+            acc: empty.clone(),
+            empty,
+        })
+    }
+}
+
+impl Accumulator for HllAddAggAccumulator {
+    fn reset(&mut self) {
+        self.acc = self.empty.clone();
+    }
+
+    fn state(&self) -> Result<SmallVec<[ScalarValue; 2]>, DataFusionError> {
+        return Ok(smallvec![self.evaluate()?]);
+    }
+
+    fn update(&mut self, row: &[ScalarValue]) -> Result<(), DataFusionError> {
+        assert_eq!(row.len(), 1);
+        // Integers are hashed the same way as `BIGINT` values in Presto and `INT64` in BigQuery.
+        match &row[0] {
+            ScalarValue::Int64(Some(v))
+            | ScalarValue::Int64Decimal(Some(v), _)
+            | ScalarValue::TimestampNanosecond(Some(v)) => {
+                self.acc.insert_bytes(&v.to_le_bytes())?
+            }
+            ScalarValue::Float64(Some(v)) => self.acc.insert_bytes(&v.to_bits().to_le_bytes())?,
+            ScalarValue::Boolean(Some(v)) => self.acc.insert_bytes(&[*v as u8])?,
+            ScalarValue::Utf8(Some(v)) => self.acc.insert_bytes(v.as_bytes())?,
+            ScalarValue::Int64(None)
+            | ScalarValue::Int64Decimal(None, _)
+            | ScalarValue::TimestampNanosecond(None)
+            | ScalarValue::Float64(None)
+            | ScalarValue::Boolean(None)
+            | ScalarValue::Utf8(None) => {} // ignore NULL.
+            v => {
+                return Err(CubeError::internal(format!(
+                    "invalid scalar value passed to HLL_ADD_AGG: {:?}",
+                    v
+                ))
+                .into())
+            }
+        }
+        return Ok(());
+    }
+
+    fn merge(&mut self, states: &[ScalarValue]) -> Result<(), DataFusionError> {
+        assert_eq!(states.len(), 1);
+        let data = match &states[0] {
+            ScalarValue::Binary(Some(d)) => d,
+            ScalarValue::Binary(None) => return Ok(()), // ignore NULL.
+            _ => {
+                return Err(CubeError::internal("invalid state in HLL_ADD_AGG".to_string()).into())
+            }
+        };
+        let s = read_sketch(data)?;
+        if !self.acc.is_compatible(&s) {
+            return Err(CubeError::internal(
+                "cannot merge two incompatible HLL sketches".to_string(),
+            )
+            .into());
+        }
+        self.acc.merge_with(&s)?;
+        return Ok(());
+    }
+
+    fn evaluate(&self) -> Result<ScalarValue, DataFusionError> {
+        return Ok(ScalarValue::Binary(Some(self.acc.write())));
+    }
+}
+
 fn read_sketch(data: &[u8]) -> Result<Hll, DataFusionError> {
     return Hll::read(&data).map_err(|e| DataFusionError::Execution(e.message));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substring_positions() {
+        assert_eq!(substring("quadratically", 5, None), "ratically");
+        assert_eq!(substring("quadratically", 5, Some(6)), "ratica");
+        assert_eq!(substring("Sakila", -3, None), "ila");
+        assert_eq!(substring("Sakila", -5, Some(3)), "aki");
+        assert_eq!(substring("Sakila", 0, None), "");
+        assert_eq!(substring("Sakila", 7, None), "");
+        assert_eq!(substring("Sakila", -7, None), "");
+        assert_eq!(substring("Sakila", 2, Some(-1)), "");
+        assert_eq!(substring("привет", 2, Some(3)), "рив");
+    }
+
+    #[test]
+    fn round_decimals() {
+        assert_eq!(round_int(12345, 2, 1).unwrap(), 12350);
+        assert_eq!(round_int(12344, 2, 1).unwrap(), 12340);
+        assert_eq!(round_int(-12345, 2, 0).unwrap(), -12300);
+        assert_eq!(round_int(-12355, 2, 1).unwrap(), -12360);
+        assert_eq!(round_int(12345, 2, 2).unwrap(), 12345);
+        assert_eq!(round_int(12345, 2, 5).unwrap(), 12345);
+        assert_eq!(round_int(12345, 0, -2).unwrap(), 12300);
+        assert_eq!(round_int(15, 0, -1).unwrap(), 20);
+        assert_eq!(round_int(12345, 0, -19).unwrap(), 0);
+        assert_eq!(round_int(i64::MIN, 0, -40).unwrap(), 0);
+        assert!(round_int(i64::MAX, 0, -19).is_err());
+        assert!(round_int(i64::MIN, 0, -19).is_err());
+        assert!(round_int(i64::MAX, 0, -1).is_err());
+    }
+
+    #[test]
+    fn scalar_kinds_roundtrip() {
+        for k in &[
+            CubeScalarUDFKind::DateTrunc,
+            CubeScalarUDFKind::ConvertTz,
+            CubeScalarUDFKind::Substring,
+            CubeScalarUDFKind::Round,
+            CubeScalarUDFKind::Greatest,
+            CubeScalarUDFKind::Least,
+        ] {
+            let udf = scalar_udf_by_kind(*k);
+            let kind = scalar_kind_by_name(udf.name()).unwrap();
+            assert_eq!(scalar_udf_by_kind(kind).name(), udf.name());
+        }
+    }
+}
@@ -0,0 +1,764 @@
+use crate::di_service;
+use crate::remotefs::{LocalDirRemoteFs, RemoteFile, RemoteFs};
+use crate::util::lock::acquire_lock;
+use crate::CubeError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use datafusion::cube_ext;
+use log::{debug, info};
+use reqwest::{Method, Response};
+use ring::hmac;
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tempfile::{NamedTempFile, PathPersistError};
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::sync::Mutex;
+use url::Url;
+
+const API_VERSION: &str = "2020-04-08";
+/// Files larger than this are uploaded in several blocks.
+const BLOCK_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Clone)]
+pub enum AzureCredentials {
+    SharedKey { account: String, key: Vec<u8> },
+    Sas(String),
+}
+
+impl fmt::Debug for AzureCredentials {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Do not expose Azure (secret) credentials.
+        match self {
+            AzureCredentials::SharedKey { account, .. } => f
+                .debug_struct("SharedKey")
+                .field("account", account)
+                .finish_non_exhaustive(),
+            AzureCredentials::Sas(_) => f.write_str("Sas"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AzureBlobRemoteFs {
+    dir: PathBuf,
+    endpoint: Url,
+    container: String,
+    sub_path: Option<String>,
+    credentials: AzureCredentials,
+    client: reqwest::Client,
+    delete_mut: Mutex<()>,
+}
+
+impl AzureBlobRemoteFs {
+    pub fn new(
+        dir: PathBuf,
+        container: String,
+        sub_path: Option<String>,
+    ) -> Result<Arc<Self>, CubeError> {
+        let account = env::var("CUBESTORE_AZURE_ACCOUNT").map_err(|_| {
+            CubeError::user(
+                "CUBESTORE_AZURE_ACCOUNT required when CUBESTORE_AZURE_CONTAINER is set"
+                    .to_string(),
+            )
+        })?;
+        let endpoint = env::var("CUBESTORE_AZURE_ENDPOINT")
+            .unwrap_or_else(|_| format!("https://{}.blob.core.windows.net", account));
+        let credentials = if let Ok(token) = env::var("CUBESTORE_AZURE_SAS_TOKEN") {
+            AzureCredentials::Sas(token)
+        } else if let Ok(key) = env::var("CUBESTORE_AZURE_ACCESS_KEY") {
+            AzureCredentials::SharedKey {
+                account,
+                key: base64::decode(key)?,
+            }
+        } else {
+            return Err(CubeError::user(
+                "CUBESTORE_AZURE_ACCESS_KEY or CUBESTORE_AZURE_SAS_TOKEN required when CUBESTORE_AZURE_CONTAINER is set".to_string(),
+            ));
+        };
+        Ok(Self::new_with_credentials(
+            dir,
+            Url::parse(&endpoint)?,
+            container,
+            sub_path,
+            credentials,
+        ))
+    }
+
+    /// `endpoint` is the URL of the storage account, e.g. `https://account.blob.core.windows.net`
+    /// or `http://127.0.0.1:10000/devstoreaccount1` for the emulator.
+    pub fn new_with_credentials(
+        dir: PathBuf,
+        endpoint: Url,
+        container: String,
+        sub_path: Option<String>,
+        credentials: AzureCredentials,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            dir,
+            endpoint,
+            container,
+            sub_path,
+            credentials,
+            client: reqwest::Client::new(),
+            delete_mut: Mutex::new(()),
+        })
+    }
+}
+
+di_service!(AzureBlobRemoteFs, [RemoteFs]);
+
+#[async_trait]
+impl RemoteFs for AzureBlobRemoteFs {
+    async fn upload_file(
+        &self,
+        temp_upload_path: &str,
+        remote_path: &str,
+    ) -> Result<u64, CubeError> {
+        let time = SystemTime::now();
+        debug!("Uploading {}", remote_path);
+        let url = self.blob_url(remote_path)?;
+        let mut file = File::open(temp_upload_path).await?;
+        let mut block_ids = Vec::new();
+        loop {
+            let mut block = Vec::with_capacity(BLOCK_SIZE);
+            (&mut file)
+                .take(BLOCK_SIZE as u64)
+                .read_to_end(&mut block)
+                .await?;
+            let last = block.len() < BLOCK_SIZE;
+            if last && block_ids.is_empty() {
+                // Small files fit into a single request.
+                self.request(
+                    Method::PUT,
+                    url.clone(),
+                    vec![("x-ms-blob-type", "BlockBlob".to_string())],
+                    block,
+                )
+                .await?;
+                break;
+            }
+            if !block.is_empty() {
+                // All block ids of a blob must have the same length.
+                let block_id = base64::encode(format!("{:08}", block_ids.len()));
+                let mut block_url = url.clone();
+                block_url
+                    .query_pairs_mut()
+                    .append_pair("comp", "block")
+                    .append_pair("blockid", &block_id);
+                self.request(Method::PUT, block_url, vec![], block).await?;
+                block_ids.push(block_id);
+            }
+            if last {
+                let mut block_list_url = url.clone();
+                block_list_url
+                    .query_pairs_mut()
+                    .append_pair("comp", "blocklist");
+                self.request(
+                    Method::PUT,
+                    block_list_url,
+                    vec![],
+                    block_list_xml(&block_ids).into_bytes(),
+                )
+                .await?;
+                break;
+            }
+        }
+
+        let local_path = self.dir.as_path().join(remote_path);
+        if Path::new(temp_upload_path) != local_path {
+            fs::create_dir_all(local_path.parent().unwrap())
+                .await
+                .map_err(|e| {
+                    CubeError::internal(format!(
+                        "Create dir {}: {}",
+                        local_path.parent().as_ref().unwrap().to_string_lossy(),
+                        e
+                    ))
+                })?;
+            fs::rename(&temp_upload_path, local_path.clone()).await?;
+        }
+        info!("Uploaded {} ({:?})", remote_path, time.elapsed()?);
+        Ok(fs::metadata(local_path).await?.len())
+    }
+
+    async fn download_file(
+        &self,
+        remote_path: &str,
+        _expected_file_size: Option<u64>,
+    ) -> Result<String, CubeError> {
+        let mut local_file = self.dir.as_path().join(remote_path);
+        let local_dir = local_file.parent().unwrap();
+        let downloads_dir = local_dir.join("downloads");
+
+        fs::create_dir_all(&downloads_dir).await?;
+        if !local_file.exists() {
+            let time = SystemTime::now();
+            debug!("Downloading {}", remote_path);
+            let mut response = self
+                .request(Method::GET, self.blob_url(remote_path)?, vec![], Vec::new())
+                .await?;
+            let (temp_file, temp_path) =
+                cube_ext::spawn_blocking(move || NamedTempFile::new_in(downloads_dir))
+                    .await??
+                    .into_parts();
+            let mut writer = BufWriter::new(tokio::fs::File::from_std(temp_file));
+            let mut size = 0;
+            while let Some(chunk) = response.chunk().await? {
+                writer.write_all(&chunk).await?;
+                size += chunk.len();
+            }
+            writer.flush().await?;
+
+            local_file = cube_ext::spawn_blocking(move || -> Result<PathBuf, PathPersistError> {
+                temp_path.persist(&local_file)?;
+                Ok(local_file)
+            })
+            .await??;
+
+            info!(
+                "Downloaded {} ({:?}) ({} bytes)",
+                remote_path,
+                time.elapsed()?,
+                size
+            );
+        }
+        Ok(local_file.into_os_string().into_string().unwrap())
+    }
+
+    async fn delete_file(&self, remote_path: &str) -> Result<(), CubeError> {
+        let time = SystemTime::now();
+        debug!("Deleting {}", remote_path);
+        self.request(
+            Method::DELETE,
+            self.blob_url(remote_path)?,
+            vec![],
+            Vec::new(),
+        )
+        .await?;
+        info!("Deleting {} ({:?})", remote_path, time.elapsed()?);
+
+        let _guard = acquire_lock("delete file", self.delete_mut.lock()).await?;
+        let local = self.dir.as_path().join(remote_path);
+        if fs::metadata(local.clone()).await.is_ok() {
+            fs::remove_file(local.clone()).await?;
+            LocalDirRemoteFs::remove_empty_paths(self.dir.as_path().to_path_buf(), local.clone())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, remote_prefix: &str) -> Result<Vec<String>, CubeError> {
+        Ok(self
+            .list_with_metadata(remote_prefix)
+            .await?
+            .into_iter()
+            .map(|f| f.remote_path)
+            .collect::<Vec<_>>())
+    }
+
+    async fn list_with_metadata(&self, remote_prefix: &str) -> Result<Vec<RemoteFile>, CubeError> {
+        let prefix = self.blob_name(remote_prefix);
+        let leading_path = self.blob_name("");
+        let mut result = Vec::new();
+        let mut marker = None;
+        loop {
+            let mut url = self.container_url()?;
+            {
+                let mut query = url.query_pairs_mut();
+                query
+                    .append_pair("restype", "container")
+                    .append_pair("comp", "list")
+                    .append_pair("prefix", &prefix);
+                if let Some(marker) = &marker {
+                    query.append_pair("marker", marker);
+                }
+            }
+            let body = self
+                .request(Method::GET, url, vec![], Vec::new())
+                .await?
+                .text()
+                .await?;
+            let page: EnumerationResults = serde_xml_rs::from_str(&body)
+                .map_err(|e| CubeError::internal(format!("Can't parse Azure blob list: {}", e)))?;
+            for blob in page.blobs.blobs {
+                result.push(RemoteFile {
+                    remote_path: blob
+                        .name
+                        .strip_prefix(&leading_path)
+                        .unwrap_or(&blob.name)
+                        .to_string(),
+                    updated: DateTime::parse_from_rfc2822(&blob.properties.last_modified)?
+                        .with_timezone(&Utc),
+                    file_size: blob.properties.content_length,
+                });
+            }
+            match page.next_marker {
+                Some(m) if !m.is_empty() => marker = Some(m),
+                _ => break,
+            }
+        }
+        Ok(result)
+    }
+
+    async fn local_path(&self) -> String {
+        self.dir.to_str().unwrap().to_owned()
+    }
+
+    async fn local_file(&self, remote_path: &str) -> Result<String, CubeError> {
+        let buf = self.dir.join(remote_path);
+        fs::create_dir_all(buf.parent().unwrap()).await?;
+        Ok(buf.to_str().unwrap().to_string())
+    }
+}
+
+impl AzureBlobRemoteFs {
+    fn blob_name(&self, remote_path: &str) -> String {
+        match &self.sub_path {
+            Some(sub_path) => format!("{}/{}", sub_path, remote_path),
+            None => remote_path.to_string(),
+        }
+    }
+
+    fn container_url(&self) -> Result<Url, CubeError> {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .map_err(|_| CubeError::user(format!("Invalid Azure endpoint: {}", self.endpoint)))?
+            .pop_if_empty()
+            .push(&self.container);
+        Ok(url)
+    }
+
+    fn blob_url(&self, remote_path: &str) -> Result<Url, CubeError> {
+        let mut url = self.container_url()?;
+        url.path_segments_mut()
+            .unwrap()
+            .extend(self.blob_name(remote_path).split('/'));
+        Ok(url)
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        mut url: Url,
+        mut headers: Vec<(&'static str, String)>,
+        body: Vec<u8>,
+    ) -> Result<Response, CubeError> {
+        headers.push((
+            "x-ms-date",
+            Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        ));
+        headers.push(("x-ms-version", API_VERSION.to_string()));
+        match &self.credentials {
+            AzureCredentials::Sas(token) => {
+                let token = token.trim_start_matches('?');
+                let query = match url.query() {
+                    Some(q) => format!("{}&{}", q, token),
+                    None => token.to_string(),
+                };
+                url.set_query(Some(&query));
+            }
+            AzureCredentials::SharedKey { account, key } => {
+                let string_to_sign = string_to_sign(account, &method, &url, &headers, body.len());
+                let signature = hmac::sign(
+                    &hmac::Key::new(hmac::HMAC_SHA256, key),
+                    string_to_sign.as_bytes(),
+                );
+                headers.push((
+                    "Authorization",
+                    format!(
+                        "SharedKey {}:{}",
+                        account,
+                        base64::encode(signature.as_ref())
+                    ),
+                ));
+            }
+        }
+
+        let path = url.path().to_string();
+        let mut request = self.client.request(method.clone(), url).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(CubeError::internal(format!(
+                "Azure Blob Storage {} {} returned {}: {}",
+                method, path, status, text
+            )));
+        }
+        Ok(response)
+    }
+}
+
+/// See https://docs.microsoft.com/en-us/rest/api/storageservices/authorize-with-shared-key.
+/// We never send headers that take part in the signature other than `x-ms-*` and Content-Length.
+fn string_to_sign(
+    account: &str,
+    method: &Method,
+    url: &Url,
+    headers: &[(&str, String)],
+    content_length: usize,
+) -> String {
+    let content_length = if content_length == 0 {
+        String::new()
+    } else {
+        content_length.to_string()
+    };
+    let mut ms_headers = headers
+        .iter()
+        .filter(|(name, _)| name.starts_with("x-ms-"))
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect::<Vec<_>>();
+    ms_headers.sort();
+
+    let mut resource = format!("/{}{}", account, url.path());
+    let mut params = url.query_pairs().into_owned().collect::<Vec<_>>();
+    params.sort();
+    for (name, value) in params {
+        resource.push_str(&format!("\n{}:{}", name.to_lowercase(), value));
+    }
+
+    format!(
+        "{}\n\n\n{}\n\n\n\n\n\n\n\n\n{}{}",
+        method.as_str(),
+        content_length,
+        ms_headers.join(""),
+        resource
+    )
+}
+
+fn block_list_xml(block_ids: &[String]) -> String {
+    let mut xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?><BlockList>".to_string();
+    for id in block_ids {
+        xml.push_str(&format!("<Latest>{}</Latest>", id));
+    }
+    xml.push_str("</BlockList>");
+    xml
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EnumerationResults {
+    blobs: Blobs,
+    next_marker: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Blobs {
+    #[serde(rename = "Blob", default)]
+    blobs: Vec<Blob>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Blob {
+    name: String,
+    properties: BlobProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlobProperties {
+    #[serde(rename = "Last-Modified")]
+    last_modified: String,
+    #[serde(rename = "Content-Length")]
+    content_length: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+    use warp::http::{HeaderMap, StatusCode};
+    use warp::Filter;
+
+    #[test]
+    fn shared_key_string_to_sign() {
+        let url = Url::parse(
+            "http://127.0.0.1:10000/devstoreaccount1/container/dir/b.parquet?comp=block&blockid=MDAwMDAwMDA%3D",
+        )
+        .unwrap();
+        let headers = vec![
+            ("x-ms-version", API_VERSION.to_string()),
+            ("x-ms-date", "Fri, 01 Jan 2021 00:00:00 GMT".to_string()),
+        ];
+        assert_eq!(
+            string_to_sign("devstoreaccount1", &Method::PUT, &url, &headers, 5),
+            "PUT\n\n\n5\n\n\n\n\n\n\n\n\n\
+             x-ms-date:Fri, 01 Jan 2021 00:00:00 GMT\n\
+             x-ms-version:2020-04-08\n\
+             /devstoreaccount1/devstoreaccount1/container/dir/b.parquet\n\
+             blockid:MDAwMDAwMDA=\n\
+             comp:block"
+        );
+    }
+
+    const SHARED_KEY: &[u8] = b"stand-in key";
+
+    /// Implements the subset of the Blob service API used by [AzureBlobRemoteFs], in the
+    /// path-style addressing of the storage emulator. Lists are split into pages of two blobs.
+    /// Requests must carry either the `sig=test` SAS token or a SharedKey signature made with
+    /// [SHARED_KEY].
+    #[derive(Default)]
+    struct StandIn {
+        blobs: HashMap<String, Vec<u8>>,
+        blocks: HashMap<(String, String), Vec<u8>>,
+    }
+
+    impl StandIn {
+        fn authorized(
+            method: &Method,
+            path: &str,
+            query: &str,
+            headers: &HeaderMap,
+            query_pairs: &HashMap<String, String>,
+            body_len: usize,
+        ) -> bool {
+            if query_pairs.get("sig").map(|s| s.as_str()) == Some("test") {
+                return true;
+            }
+            let authorization = match headers.get("authorization").map(|h| h.to_str()) {
+                Some(Ok(a)) => a,
+                _ => return false,
+            };
+            let signature = match authorization.strip_prefix("SharedKey devstoreaccount1:") {
+                Some(s) => match base64::decode(s) {
+                    Ok(s) => s,
+                    Err(_) => return false,
+                },
+                None => return false,
+            };
+            let ms_headers = headers
+                .iter()
+                .filter(|(name, _)| name.as_str().starts_with("x-ms-"))
+                .map(|(name, value)| (name.as_str(), value.to_str().unwrap().to_string()))
+                .collect::<Vec<_>>();
+            let url = Url::parse(&format!("http://127.0.0.1{}?{}", path, query)).unwrap();
+            let string_to_sign =
+                string_to_sign("devstoreaccount1", method, &url, &ms_headers, body_len);
+            hmac::verify(
+                &hmac::Key::new(hmac::HMAC_SHA256, SHARED_KEY),
+                string_to_sign.as_bytes(),
+                &signature,
+            )
+            .is_ok()
+        }
+
+        fn handle(
+            &mut self,
+            method: &str,
+            path: &str,
+            query: HashMap<String, String>,
+            body: Vec<u8>,
+        ) -> (StatusCode, Vec<u8>) {
+            let blob = path
+                .trim_start_matches("/devstoreaccount1/container")
+                .trim_start_matches('/')
+                .to_string();
+            match (method, query.get("comp").map(|s| s.as_str())) {
+                ("PUT", None) => {
+                    self.blobs.insert(blob, body);
+                    (StatusCode::CREATED, Vec::new())
+                }
+                ("PUT", Some("block")) => {
+                    self.blocks
+                        .insert((blob, query.get("blockid").unwrap().clone()), body);
+                    (StatusCode::CREATED, Vec::new())
+                }
+                ("PUT", Some("blocklist")) => {
+                    let ids = regex::Regex::new("<Latest>([^<]*)</Latest>").unwrap();
+                    let mut data = Vec::new();
+                    for id in ids.captures_iter(std::str::from_utf8(&body).unwrap()) {
+                        match self.blocks.remove(&(blob.clone(), id[1].to_string())) {
+                            Some(block) => data.extend(block),
+                            None => return (StatusCode::BAD_REQUEST, Vec::new()),
+                        }
+                    }
+                    self.blobs.insert(blob, data);
+                    (StatusCode::CREATED, Vec::new())
+                }
+                ("GET", Some("list")) => {
+                    let prefix = query.get("prefix").cloned().unwrap_or_default();
+                    let marker = query.get("marker").cloned().unwrap_or_default();
+                    let mut names = self
+                        .blobs
+                        .keys()
+                        .filter(|n| n.starts_with(&prefix) && **n > marker)
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    names.sort();
+                    let mut xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+                        <EnumerationResults ServiceEndpoint=\"http://127.0.0.1/devstoreaccount1\" ContainerName=\"container\">\
+                        <Blobs>"
+                        .to_string();
+                    for name in names.iter().take(2) {
+                        xml.push_str(&format!(
+                            "<Blob><Name>{}</Name><Properties>\
+                             <Last-Modified>Fri, 01 Jan 2021 00:00:00 GMT</Last-Modified>\
+                             <Content-Length>{}</Content-Length><BlobType>BlockBlob</BlobType>\
+                             </Properties></Blob>",
+                            name,
+                            self.blobs[name].len()
+                        ));
+                    }
+                    xml.push_str("</Blobs>");
+                    if names.len() > 2 {
+                        xml.push_str(&format!("<NextMarker>{}</NextMarker>", names[1]));
+                    } else {
+                        xml.push_str("<NextMarker />");
+                    }
+                    xml.push_str("</EnumerationResults>");
+                    (StatusCode::OK, xml.into_bytes())
+                }
+                ("GET", None) => match self.blobs.get(&blob) {
+                    Some(data) => (StatusCode::OK, data.clone()),
+                    None => (StatusCode::NOT_FOUND, Vec::new()),
+                },
+                ("DELETE", None) => match self.blobs.remove(&blob) {
+                    Some(_) => (StatusCode::ACCEPTED, Vec::new()),
+                    None => (StatusCode::NOT_FOUND, Vec::new()),
+                },
+                _ => (StatusCode::BAD_REQUEST, Vec::new()),
+            }
+        }
+    }
+
+    fn start_stand_in() -> Url {
+        let state = Arc::new(std::sync::Mutex::new(StandIn::default()));
+        let route = warp::method()
+            .and(warp::path::full())
+            .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+            .and(warp::header::headers_cloned())
+            .and(warp::body::bytes())
+            .map(
+                move |method: Method,
+                      path: warp::path::FullPath,
+                      raw_query: String,
+                      headers: HeaderMap,
+                      body: warp::hyper::body::Bytes| {
+                    let query = url::form_urlencoded::parse(raw_query.as_bytes())
+                        .into_owned()
+                        .collect::<HashMap<_, _>>();
+                    if !StandIn::authorized(
+                        &method,
+                        path.as_str(),
+                        &raw_query,
+                        &headers,
+                        &query,
+                        body.len(),
+                    ) {
+                        return warp::http::Response::builder()
+                            .status(StatusCode::FORBIDDEN)
+                            .body(Vec::new())
+                            .unwrap();
+                    }
+                    let (status, body) = state.lock().unwrap().handle(
+                        method.as_str(),
+                        path.as_str(),
+                        query,
+                        body.to_vec(),
+                    );
+                    warp::http::Response::builder()
+                        .status(status)
+                        .body(body)
+                        .unwrap()
+                },
+            );
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        Url::parse(&format!("http://{}/devstoreaccount1", addr)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn blob_operations_sas() {
+        blob_operations(
+            AzureCredentials::Sas("?sv=2020-04-08&sig=test".to_string()),
+            AzureCredentials::Sas("sig=wrong".to_string()),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn blob_operations_shared_key() {
+        blob_operations(
+            AzureCredentials::SharedKey {
+                account: "devstoreaccount1".to_string(),
+                key: SHARED_KEY.to_vec(),
+            },
+            AzureCredentials::SharedKey {
+                account: "devstoreaccount1".to_string(),
+                key: b"wrong".to_vec(),
+            },
+        )
+        .await;
+    }
+
+    async fn blob_operations(credentials: AzureCredentials, wrong_credentials: AzureCredentials) {
+        let endpoint = start_stand_in();
+        let dir = TempDir::new().unwrap();
+        let fs = AzureBlobRemoteFs::new_with_credentials(
+            dir.path().to_path_buf(),
+            endpoint.clone(),
+            "container".to_string(),
+            Some("sub".to_string()),
+            credentials,
+        );
+
+        let large = (0..2 * BLOCK_SIZE + 10)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let files = vec![
+            ("a.parquet", b"hello".to_vec()),
+            ("dir/b.parquet", large),
+            ("c.parquet", Vec::new()),
+            ("d.parquet", b"world".to_vec()),
+        ];
+        for (name, data) in files.iter() {
+            let temp_path = fs.temp_upload_path(name).await.unwrap();
+            std::fs::write(&temp_path, data).unwrap();
+            let size = fs.upload_file(&temp_path, name).await.unwrap();
+            assert_eq!(size, data.len() as u64);
+        }
+
+        let mut listed = fs.list("").await.unwrap();
+        listed.sort();
+        assert_eq!(
+            listed,
+            vec!["a.parquet", "c.parquet", "d.parquet", "dir/b.parquet"]
+        );
+        let listed = fs.list_with_metadata("dir/").await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].remote_path(), "dir/b.parquet");
+        assert_eq!(listed[0].file_size(), 2 * BLOCK_SIZE as u64 + 10);
+
+        for (name, data) in files.iter() {
+            std::fs::remove_file(fs.local_file(name).await.unwrap()).unwrap();
+            let local_path = fs.download_file(name, None).await.unwrap();
+            assert_eq!(&std::fs::read(local_path).unwrap(), data);
+        }
+
+        fs.delete_file("a.parquet").await.unwrap();
+        assert!(!dir.path().join("a.parquet").exists());
+        assert_eq!(fs.list("a").await.unwrap(), Vec::<String>::new());
+        assert!(fs.download_file("a.parquet", None).await.is_err());
+
+        let unauthorized = AzureBlobRemoteFs::new_with_credentials(
+            dir.path().to_path_buf(),
+            endpoint,
+            "container".to_string(),
+            Some("sub".to_string()),
+            wrong_credentials,
+        );
+        assert!(unauthorized.list("").await.is_err());
+    }
+}
@@ -0,0 +1,543 @@
+use crate::di_service;
+use crate::remotefs::{RemoteFile, RemoteFs};
+use crate::CubeError;
+use async_trait::async_trait;
+use datafusion::cube_ext;
+use log::debug;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+use tokio::fs;
+
+/// Identifies encrypted files. The version byte is bumped on incompatible format changes.
+const MAGIC: &[u8] = b"CSENC\x01";
+/// Files are encrypted in segments to avoid loading them into memory entirely.
+const SEGMENT_SIZE: usize = 1 << 20;
+const TAG_LEN: usize = 16;
+const SEGMENT_OVERHEAD: usize = NONCE_LEN + TAG_LEN;
+
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: String,
+    key: Vec<u8>,
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl EncryptionKey {
+    pub fn new(id: String, key: Vec<u8>) -> Result<EncryptionKey, CubeError> {
+        if key.len() != AES_256_GCM.key_len() {
+            return Err(CubeError::user(format!(
+                "Encryption key must be {} bytes long, {} bytes provided",
+                AES_256_GCM.key_len(),
+                key.len()
+            )));
+        }
+        if id.is_empty() || id.len() > u8::MAX as usize {
+            return Err(CubeError::user(format!(
+                "Encryption key id must be from 1 to {} bytes long: '{}'",
+                u8::MAX,
+                id
+            )));
+        }
+        Ok(EncryptionKey { id, key })
+    }
+
+    /// Key is expected to be encoded with base64.
+    pub fn from_base64(id: String, key: &str) -> Result<EncryptionKey, CubeError> {
+        let key = base64::decode(key.trim())
+            .map_err(|e| CubeError::user(format!("Can't decode encryption key: {}", e)))?;
+        Self::new(id, key)
+    }
+
+    pub fn from_file(id: String, path: &Path) -> Result<EncryptionKey, CubeError> {
+        let key = std::fs::read_to_string(path).map_err(|e| {
+            CubeError::user(format!(
+                "Can't read encryption key from {}: {}",
+                path.to_string_lossy(),
+                e
+            ))
+        })?;
+        Self::from_base64(id, &key)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Keys of [EncryptedRemoteFs]. New files are encrypted with the active key, previous keys are
+/// only used to decrypt files written before the active key was changed.
+#[derive(Clone, Debug)]
+pub struct EncryptionKeyring {
+    active_key_id: String,
+    keys: Vec<EncryptionKey>,
+}
+
+impl EncryptionKeyring {
+    pub fn new(
+        active: EncryptionKey,
+        previous: Vec<EncryptionKey>,
+    ) -> Result<EncryptionKeyring, CubeError> {
+        let active_key_id = active.id.clone();
+        let mut keys = vec![active];
+        for key in previous {
+            if keys.iter().any(|k| k.id == key.id) {
+                return Err(CubeError::user(format!(
+                    "Encryption key id '{}' is used more than once",
+                    key.id
+                )));
+            }
+            keys.push(key);
+        }
+        Ok(EncryptionKeyring {
+            active_key_id,
+            keys,
+        })
+    }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.active_key_id
+    }
+}
+
+/// Encrypts files with AES-GCM before uploading them to `remote_fs` and decrypts them on
+/// download. Id of the key is written into the header of every file. Files without the header,
+/// i.e. uploaded before encryption was enabled, are downloaded as is.
+///
+/// Local copies in `dir` stay decrypted. `remote_fs` must use another local directory, it only
+/// keeps encrypted files there while transferring them.
+pub struct EncryptedRemoteFs {
+    remote_fs: Arc<dyn RemoteFs>,
+    key_id: String,
+    keys: Arc<HashMap<String, LessSafeKey>>,
+    dir: PathBuf,
+}
+
+impl Debug for EncryptedRemoteFs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedRemoteFs")
+            .field("remote_fs", &self.remote_fs)
+            .field("key_id", &self.key_id)
+            .field("dir", &self.dir)
+            .finish()
+    }
+}
+
+di_service!(EncryptedRemoteFs, [RemoteFs]);
+
+impl EncryptedRemoteFs {
+    pub fn new(
+        remote_fs: Arc<dyn RemoteFs>,
+        keyring: EncryptionKeyring,
+        dir: PathBuf,
+    ) -> Arc<EncryptedRemoteFs> {
+        let keys = keyring
+            .keys
+            .into_iter()
+            .map(|k| {
+                let unbound_key = UnboundKey::new(&AES_256_GCM, &k.key).unwrap();
+                (k.id, LessSafeKey::new(unbound_key))
+            })
+            .collect();
+        Arc::new(EncryptedRemoteFs {
+            remote_fs,
+            key_id: keyring.active_key_id,
+            keys: Arc::new(keys),
+            dir,
+        })
+    }
+
+    fn header_len(&self) -> u64 {
+        (MAGIC.len() + 1 + self.key_id.len()) as u64
+    }
+
+    #[cfg(test)]
+    fn encrypted_size(&self, size: u64) -> u64 {
+        // The last segment is always shorter than `SEGMENT_SIZE`, it might be empty.
+        let segments = size / SEGMENT_SIZE as u64 + 1;
+        self.header_len() + size + segments * SEGMENT_OVERHEAD as u64
+    }
+
+    /// Assumes the file is encrypted with the active key. Sizes of files encrypted with keys with
+    /// ids of another length or not encrypted at all are off by a few bytes.
+    fn decrypted_size(&self, encrypted_size: u64) -> u64 {
+        let body = encrypted_size.saturating_sub(self.header_len());
+        if body < SEGMENT_OVERHEAD as u64 {
+            return 0;
+        }
+        let full_segments =
+            (body - SEGMENT_OVERHEAD as u64) / (SEGMENT_SIZE + SEGMENT_OVERHEAD) as u64;
+        body - (full_segments + 1) * SEGMENT_OVERHEAD as u64
+    }
+
+    async fn remove_transfer_copy(&self, remote_path: &str) {
+        if let Ok(path) = self.remote_fs.local_file(remote_path).await {
+            if let Err(e) = fs::remove_file(&path).await {
+                log::error!("Error during removal of encrypted file {}: {}", path, e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RemoteFs for EncryptedRemoteFs {
+    async fn upload_file(
+        &self,
+        temp_upload_path: &str,
+        remote_path: &str,
+    ) -> Result<u64, CubeError> {
+        let encrypted_path = self.remote_fs.temp_upload_path(remote_path).await?;
+        let keys = self.keys.clone();
+        let key_id = self.key_id.clone();
+        let from = PathBuf::from(temp_upload_path);
+        let to = PathBuf::from(&encrypted_path);
+        cube_ext::spawn_blocking(move || encrypt_file(&keys[&key_id], &key_id, &from, &to))
+            .await??;
+        self.remote_fs
+            .upload_file(&encrypted_path, remote_path)
+            .await?;
+        self.remove_transfer_copy(remote_path).await;
+
+        let local_path = self.local_file(remote_path).await?;
+        if Path::new(temp_upload_path) != Path::new(&local_path) {
+            fs::rename(temp_upload_path, &local_path)
+                .await
+                .map_err(|e| {
+                    CubeError::internal(format!(
+                        "Rename {} -> {}: {}",
+                        temp_upload_path, local_path, e
+                    ))
+                })?;
+        }
+        Ok(fs::metadata(local_path).await?.len())
+    }
+
+    async fn download_file(
+        &self,
+        remote_path: &str,
+        expected_file_size: Option<u64>,
+    ) -> Result<String, CubeError> {
+        let local_path = self.local_file(remote_path).await?;
+        if fs::metadata(&local_path).await.is_ok() {
+            return Ok(local_path);
+        }
+        debug!("Downloading encrypted {}", remote_path);
+        // Size of the encrypted file depends on the key it is encrypted with, so only the
+        // decrypted size is checked.
+        let encrypted_path = self.remote_fs.download_file(remote_path, None).await?;
+
+        let downloads_dir = Path::new(&local_path).parent().unwrap().join("downloads");
+        fs::create_dir_all(&downloads_dir).await?;
+        let keys = self.keys.clone();
+        let to = PathBuf::from(&local_path);
+        let res = cube_ext::spawn_blocking(move || -> Result<(), CubeError> {
+            let temp_path = NamedTempFile::new_in(downloads_dir)?.into_temp_path();
+            decrypt_file(&keys, Path::new(&encrypted_path), &temp_path)?;
+            if let Some(expected_file_size) = expected_file_size {
+                let size = std::fs::metadata(&temp_path)?.len();
+                if size != expected_file_size {
+                    return Err(CubeError::corrupt_data(format!(
+                        "Expected file size is {} but {} received",
+                        expected_file_size, size
+                    )));
+                }
+            }
+            temp_path.persist(&to)?;
+            Ok(())
+        })
+        .await?;
+        self.remove_transfer_copy(remote_path).await;
+        res.map_err(|e| CubeError {
+            message: format!("Can't decrypt {}: {}", remote_path, e.message),
+            ..e
+        })?;
+        Ok(local_path)
+    }
+
+    async fn delete_file(&self, remote_path: &str) -> Result<(), CubeError> {
+        self.remote_fs.delete_file(remote_path).await?;
+        let local_path = self.dir.join(remote_path);
+        if fs::metadata(&local_path).await.is_ok() {
+            fs::remove_file(&local_path).await?;
+        }
+        Ok(())
+    }
+
+    async fn list(&self, remote_prefix: &str) -> Result<Vec<String>, CubeError> {
+        self.remote_fs.list(remote_prefix).await
+    }
+
+    async fn list_with_metadata(&self, remote_prefix: &str) -> Result<Vec<RemoteFile>, CubeError> {
+        Ok(self
+            .remote_fs
+            .list_with_metadata(remote_prefix)
+            .await?
+            .into_iter()
+            .map(|f| RemoteFile {
+                file_size: self.decrypted_size(f.file_size),
+                ..f
+            })
+            .collect())
+    }
+
+    async fn local_path(&self) -> String {
+        self.dir.to_str().unwrap().to_owned()
+    }
+
+    async fn local_file(&self, remote_path: &str) -> Result<String, CubeError> {
+        let buf = self.dir.join(remote_path);
+        fs::create_dir_all(buf.parent().unwrap()).await?;
+        Ok(buf.to_str().unwrap().to_string())
+    }
+}
+
+fn header(key_id: &str) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.push(key_id.len() as u8);
+    header.extend_from_slice(key_id.as_bytes());
+    header
+}
+
+/// Binds every segment to its file header and position, so segments can't be reordered, dropped
+/// or moved between files.
+fn segment_aad(header: &[u8], index: u64, last: bool) -> Vec<u8> {
+    let mut aad = header.to_vec();
+    aad.extend_from_slice(&index.to_be_bytes());
+    aad.push(last as u8);
+    aad
+}
+
+fn encrypt_file(key: &LessSafeKey, key_id: &str, from: &Path, to: &Path) -> Result<(), CubeError> {
+    let mut reader = BufReader::new(File::open(from)?);
+    let mut writer = BufWriter::new(File::create(to)?);
+    let header = header(key_id);
+    writer.write_all(&header)?;
+    let rng = SystemRandom::new();
+    let mut index = 0;
+    loop {
+        let mut segment = Vec::with_capacity(SEGMENT_SIZE + TAG_LEN);
+        let read = (&mut reader)
+            .take(SEGMENT_SIZE as u64)
+            .read_to_end(&mut segment)?;
+        let last = read < SEGMENT_SIZE;
+        let mut nonce = [0; NONCE_LEN];
+        rng.fill(&mut nonce)
+            .map_err(|_| CubeError::internal("Can't generate nonce".to_string()))?;
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(segment_aad(&header, index, last)),
+            &mut segment,
+        )
+        .map_err(|_| CubeError::internal("Can't encrypt file segment".to_string()))?;
+        writer.write_all(&nonce)?;
+        writer.write_all(&segment)?;
+        if last {
+            break;
+        }
+        index += 1;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn decrypt_file(
+    keys: &HashMap<String, LessSafeKey>,
+    from: &Path,
+    to: &Path,
+) -> Result<(), CubeError> {
+    let mut reader = BufReader::new(File::open(from)?);
+    let mut writer = BufWriter::new(File::create(to)?);
+    let mut magic = Vec::with_capacity(MAGIC.len() + 1);
+    (&mut reader)
+        .take(MAGIC.len() as u64 + 1)
+        .read_to_end(&mut magic)?;
+    if magic.len() <= MAGIC.len() || &magic[..MAGIC.len()] != MAGIC {
+        // Uploaded before encryption was enabled.
+        writer.write_all(&magic)?;
+        std::io::copy(&mut reader, &mut writer)?;
+        writer.flush()?;
+        return Ok(());
+    }
+    let mut file_key_id = vec![0; magic[MAGIC.len()] as usize];
+    reader.read_exact(&mut file_key_id)?;
+    let key_id = String::from_utf8_lossy(&file_key_id).to_string();
+    let key = keys.get(&key_id).ok_or_else(|| {
+        CubeError::user(format!(
+            "File is encrypted with key '{}', which is not configured",
+            key_id
+        ))
+    })?;
+
+    let header = header(&key_id);
+    let mut index = 0;
+    loop {
+        let mut segment = Vec::with_capacity(SEGMENT_SIZE + SEGMENT_OVERHEAD);
+        let read = (&mut reader)
+            .take((SEGMENT_SIZE + SEGMENT_OVERHEAD) as u64)
+            .read_to_end(&mut segment)?;
+        if read < SEGMENT_OVERHEAD {
+            return Err(CubeError::corrupt_data("File is truncated".to_string()));
+        }
+        let last = read < SEGMENT_SIZE + SEGMENT_OVERHEAD;
+        let mut nonce = [0; NONCE_LEN];
+        nonce.copy_from_slice(&segment[..NONCE_LEN]);
+        let plain = key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(segment_aad(&header, index, last)),
+                &mut segment[NONCE_LEN..],
+            )
+            .map_err(|_| {
+                CubeError::corrupt_data(format!("Can't decrypt segment {} of the file", index))
+            })?;
+        writer.write_all(plain)?;
+        if last {
+            break;
+        }
+        index += 1;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remotefs::LocalDirRemoteFs;
+    use tempfile::TempDir;
+
+    fn key(id: &str) -> EncryptionKey {
+        EncryptionKey::new(id.to_string(), vec![id.as_bytes()[0]; 32]).unwrap()
+    }
+
+    fn encrypted_fs(root: &Path, key_id: &str) -> Arc<EncryptedRemoteFs> {
+        encrypted_fs_with_keys(root, key_id, &[])
+    }
+
+    fn encrypted_fs_with_keys(
+        root: &Path,
+        key_id: &str,
+        previous: &[&str],
+    ) -> Arc<EncryptedRemoteFs> {
+        let remote_fs = LocalDirRemoteFs::new(Some(root.join("remote")), root.join("encrypted"));
+        let keyring =
+            EncryptionKeyring::new(key(key_id), previous.iter().map(|id| key(id)).collect())
+                .unwrap();
+        EncryptedRemoteFs::new(remote_fs, keyring, root.join("local"))
+    }
+
+    #[tokio::test]
+    async fn upload_and_download() {
+        let root = TempDir::new().unwrap();
+        let fs = encrypted_fs(root.path(), "test");
+        let data = (0..2 * SEGMENT_SIZE + 123)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        let temp_path = fs.temp_upload_path("1.parquet").await.unwrap();
+        std::fs::write(&temp_path, &data).unwrap();
+        let size = fs.upload_file(&temp_path, "1.parquet").await.unwrap();
+        assert_eq!(size, data.len() as u64);
+
+        let local_path = fs.local_file("1.parquet").await.unwrap();
+        assert_eq!(std::fs::read(&local_path).unwrap(), data);
+        let remote_path = root.path().join("remote").join("1.parquet");
+        let encrypted = std::fs::read(&remote_path).unwrap();
+        assert!(encrypted.starts_with(&header("test")));
+        assert_eq!(encrypted.len() as u64, fs.encrypted_size(data.len() as u64));
+        assert!(!root.path().join("encrypted").join("1.parquet").exists());
+
+        let listed = fs.list_with_metadata("1.parquet").await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].file_size(), data.len() as u64);
+
+        std::fs::remove_file(&local_path).unwrap();
+        let downloaded = fs
+            .download_file("1.parquet", Some(data.len() as u64))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&downloaded).unwrap(), data);
+
+        // Files can't be read with another key.
+        let other_fs = encrypted_fs(root.path(), "other");
+        assert!(other_fs.download_file("1.parquet", None).await.is_err());
+
+        // Modified files are rejected.
+        std::fs::remove_file(&local_path).unwrap();
+        let mut tampered = encrypted;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        std::fs::write(&remote_path, &tampered).unwrap();
+        let err = fs.download_file("1.parquet", None).await.unwrap_err();
+        assert!(err.is_corrupt_data(), "{:?}", err);
+
+        fs.delete_file("1.parquet").await.unwrap();
+        assert!(!remote_path.exists());
+        assert!(!Path::new(&local_path).exists());
+    }
+
+    #[tokio::test]
+    async fn key_rotation_and_plain_files() {
+        let root = TempDir::new().unwrap();
+        let old_fs = encrypted_fs(root.path(), "old");
+        let temp_path = old_fs.temp_upload_path("1.parquet").await.unwrap();
+        std::fs::write(&temp_path, b"encrypted with old").unwrap();
+        old_fs.upload_file(&temp_path, "1.parquet").await.unwrap();
+        std::fs::create_dir_all(root.path().join("remote")).unwrap();
+        std::fs::write(root.path().join("remote").join("2.parquet"), b"plain").unwrap();
+        let local_dir = root.path().join("local");
+        std::fs::remove_dir_all(&local_dir).unwrap();
+
+        let fs = encrypted_fs_with_keys(root.path(), "new", &["old"]);
+        let downloaded = fs.download_file("1.parquet", Some(18)).await.unwrap();
+        assert_eq!(std::fs::read(&downloaded).unwrap(), b"encrypted with old");
+        let downloaded = fs.download_file("2.parquet", Some(5)).await.unwrap();
+        assert_eq!(std::fs::read(&downloaded).unwrap(), b"plain");
+
+        // New files are written with the active key.
+        let temp_path = fs.temp_upload_path("3.parquet").await.unwrap();
+        std::fs::write(&temp_path, b"encrypted with new").unwrap();
+        fs.upload_file(&temp_path, "3.parquet").await.unwrap();
+        let encrypted = std::fs::read(root.path().join("remote").join("3.parquet")).unwrap();
+        assert!(encrypted.starts_with(&header("new")));
+
+        std::fs::remove_dir_all(&local_dir).unwrap();
+        assert!(old_fs.download_file("3.parquet", None).await.is_err());
+        let err = fs.download_file("1.parquet", Some(5)).await.unwrap_err();
+        assert!(err.is_corrupt_data(), "{:?}", err);
+        assert!(EncryptionKeyring::new(key("a"), vec![key("b"), key("a")]).is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        let root = TempDir::new().unwrap();
+        let fs = encrypted_fs(root.path(), "test");
+        for size in [
+            0,
+            1,
+            SEGMENT_SIZE - 1,
+            SEGMENT_SIZE,
+            SEGMENT_SIZE + 1,
+            3 * SEGMENT_SIZE,
+        ]
+        .iter()
+        {
+            let size = *size as u64;
+            assert_eq!(fs.decrypted_size(fs.encrypted_size(size)), size);
+        }
+    }
+}
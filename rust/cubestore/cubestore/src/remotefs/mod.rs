@@ -1,3 +1,5 @@
+pub mod azure;
+pub mod encrypted;
 pub mod gcs;
 pub mod minio;
 pub mod queue;
@@ -35,6 +37,10 @@ impl RemoteFile {
     pub fn updated(&self) -> &DateTime<Utc> {
         &self.updated
     }
+
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
 }
 
 #[async_trait]
@@ -83,6 +89,35 @@ pub trait RemoteFs: DIService + Send + Sync + Debug {
     async fn local_path(&self) -> String;
 
     async fn local_file(&self, remote_path: &str) -> Result<String, CubeError>;
+
+    /// Protects local copies of `remote_paths` from eviction until `unpin_local_files` is called.
+    /// Only implementations with a bounded local cache need to take care of this.
+    fn pin_local_files(&self, _remote_paths: &[String]) {}
+
+    fn unpin_local_files(&self, _remote_paths: &[String]) {}
+}
+
+/// Keeps local copies of files pinned until dropped, see [RemoteFs::pin_local_files]. Pin files
+/// before downloading them and keep the guard until they are read.
+pub struct PinnedLocalFiles {
+    remote_fs: Arc<dyn RemoteFs>,
+    remote_paths: Vec<String>,
+}
+
+impl PinnedLocalFiles {
+    pub fn new(remote_fs: Arc<dyn RemoteFs>, remote_paths: Vec<String>) -> PinnedLocalFiles {
+        remote_fs.pin_local_files(&remote_paths);
+        PinnedLocalFiles {
+            remote_fs,
+            remote_paths,
+        }
+    }
+}
+
+impl Drop for PinnedLocalFiles {
+    fn drop(&mut self) {
+        self.remote_fs.unpin_local_files(&self.remote_paths)
+    }
 }
 
 pub fn ensure_temp_file_is_dropped(path: String) {
@@ -1,3 +1,4 @@
+use crate::app_metrics;
 use crate::config::ConfigObj;
 use crate::di_service;
 use crate::remotefs::{RemoteFile, RemoteFs};
@@ -9,14 +10,18 @@ use datafusion::cube_ext;
 use deadqueue::unlimited;
 use futures::future::join_all;
 use log::error;
+use lru::LruCache;
 use smallvec::alloc::fmt::Formatter;
-use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::Metadata;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use tokio::sync::{broadcast, watch, RwLock};
 use tokio::time::Duration;
+use uuid::Uuid;
 
 pub struct QueueRemoteFs {
     config: Arc<dyn ConfigObj>,
@@ -26,6 +31,8 @@ pub struct QueueRemoteFs {
     // TODO not used
     deleted: RwLock<HashSet<String>>,
     downloading: RwLock<HashSet<String>>,
+    /// Only set when the local directory size is limited, see `ConfigObj::max_local_files_size`.
+    local_files: Option<Arc<Mutex<LocalFilesCache>>>,
     _result_receiver: broadcast::Receiver<RemoteFsOpResult>,
     result_sender: broadcast::Sender<RemoteFsOpResult>,
     stopped_rx: watch::Receiver<bool>,
@@ -63,6 +70,13 @@ impl QueueRemoteFs {
     pub fn new(config: Arc<dyn ConfigObj>, remote_fs: Arc<dyn RemoteFs>) -> Arc<Self> {
         let (stopped_tx, stopped_rx) = watch::channel(false);
         let (tx, rx) = broadcast::channel(16384);
+        // Without uploads the local directory is the only copy of the data, never evict from it.
+        let local_files = match config.max_local_files_size() {
+            Some(max_size) if config.upload_to_remote() => {
+                Some(Arc::new(Mutex::new(LocalFilesCache::new(max_size))))
+            }
+            _ => None,
+        };
         Arc::new(Self {
             config,
             remote_fs,
@@ -70,6 +84,7 @@ impl QueueRemoteFs {
             download_queue: unlimited::Queue::new(),
             deleted: RwLock::new(HashSet::new()),
             downloading: RwLock::new(HashSet::new()),
+            local_files,
             result_sender: tx,
             _result_receiver: rx,
             stopped_tx,
@@ -78,6 +93,9 @@ impl QueueRemoteFs {
     }
 
     pub async fn wait_processing_loops(queue_remote_fs: Arc<Self>) -> Result<(), CubeError> {
+        if let Err(e) = queue_remote_fs.init_local_files_cache().await {
+            error!("Error while loading local files into cache: {:?}", e);
+        }
         let mut futures = Vec::new();
         for _ in 0..queue_remote_fs.config.upload_concurrency() {
             let to_move = queue_remote_fs.clone();
@@ -305,6 +323,13 @@ impl QueueRemoteFs {
                 log::trace!("The files being removed are {:?}", local_files);
             }
 
+            if let Some(cache) = &self.local_files {
+                let mut cache = cache.lock().unwrap();
+                for f in local_files.iter() {
+                    cache.remove(f);
+                }
+            }
+
             let local_dir_copy = local_dir.clone();
             cube_ext::spawn_blocking(move || {
                 for f in local_files {
@@ -337,6 +362,9 @@ impl RemoteFs for QueueRemoteFs {
             let res = receiver.recv().await?;
             if let RemoteFsOpResult::Upload(file, result) = res {
                 if &file == remote_path {
+                    if let Ok(size) = &result {
+                        self.track_local_file(remote_path, *size).await;
+                    }
                     return result;
                 }
             }
@@ -351,17 +379,19 @@ impl RemoteFs for QueueRemoteFs {
         // We might be lucky and the file has already been downloaded.
         if let Ok(local_path) = self.local_file(remote_path).await {
             let metadata = tokio::fs::metadata(&local_path).await;
-            if metadata.is_ok() {
+            if let Ok(metadata) = metadata {
+                let size = metadata.len();
                 if let Err(e) = QueueRemoteFs::check_file_size(
                     remote_path,
                     expected_file_size,
                     &local_path,
-                    metadata.unwrap(),
+                    metadata,
                 )
                 .await
                 {
                     return Err(e);
                 }
+                self.track_local_file(remote_path, size).await;
                 return Ok(local_path);
             }
         }
@@ -383,6 +413,7 @@ impl RemoteFs for QueueRemoteFs {
                 if &file == remote_path {
                     let local_path = self.local_file(remote_path).await?;
                     let metadata = tokio::fs::metadata(&local_path).await?;
+                    let size = metadata.len();
                     if let Err(e) = QueueRemoteFs::check_file_size(
                         remote_path,
                         expected_file_size,
@@ -393,6 +424,9 @@ impl RemoteFs for QueueRemoteFs {
                     {
                         return Err(e);
                     }
+                    if result.is_ok() {
+                        self.track_local_file(remote_path, size).await;
+                    }
                     return result;
                 }
             }
@@ -411,6 +445,9 @@ impl RemoteFs for QueueRemoteFs {
             let res = receiver.recv().await?;
             if let RemoteFsOpResult::Delete(file, result) = res {
                 if &file == remote_path {
+                    if let Some(cache) = &self.local_files {
+                        cache.lock().unwrap().remove(remote_path);
+                    }
                     return result;
                 }
             }
@@ -432,9 +469,111 @@ impl RemoteFs for QueueRemoteFs {
     async fn local_file(&self, remote_path: &str) -> Result<String, CubeError> {
         self.remote_fs.local_file(remote_path).await
     }
+
+    fn pin_local_files(&self, remote_paths: &[String]) {
+        if let Some(cache) = &self.local_files {
+            let mut cache = cache.lock().unwrap();
+            for p in remote_paths {
+                cache.pin(p);
+            }
+        }
+    }
+
+    fn unpin_local_files(&self, remote_paths: &[String]) {
+        if let Some(cache) = &self.local_files {
+            let mut cache = cache.lock().unwrap();
+            for p in remote_paths {
+                cache.unpin(p);
+            }
+        }
+    }
 }
 
 impl QueueRemoteFs {
+    /// Fills the cache with files left in the local directory by previous runs, oldest first.
+    async fn init_local_files_cache(&self) -> Result<(), CubeError> {
+        let cache = match &self.local_files {
+            None => return Ok(()),
+            Some(c) => c.clone(),
+        };
+        let local_dir = self.local_path().await;
+        let mut files = cube_ext::spawn_blocking(
+            move || -> Result<Vec<(SystemTime, String, u64)>, std::io::Error> {
+                let mut files = Vec::new();
+                list_local_files(Path::new(&local_dir), "", &mut files)?;
+                Ok(files)
+            },
+        )
+        .await??;
+        files.sort();
+        {
+            let mut cache = cache.lock().unwrap();
+            for (_, file, size) in files {
+                cache.touch(&file, size);
+            }
+        }
+        self.evict_local_files(None).await;
+        Ok(())
+    }
+
+    /// Records an access to the local copy of `remote_path` and evicts other files if the local
+    /// directory grows over the limit.
+    async fn track_local_file(&self, remote_path: &str, size: u64) {
+        if let Some(cache) = &self.local_files {
+            cache.lock().unwrap().touch(remote_path, size);
+            self.evict_local_files(Some(remote_path.to_string())).await;
+        }
+    }
+
+    async fn evict_local_files(&self, keep: Option<String>) {
+        let cache = match &self.local_files {
+            None => return,
+            Some(c) => c.clone(),
+        };
+        let local_dir = self.local_path().await;
+        let res = cube_ext::spawn_blocking(move || {
+            let local_dir = Path::new(&local_dir);
+            let evicted_dir = local_dir.join("downloads");
+            if let Err(e) = std::fs::create_dir_all(&evicted_dir) {
+                log::error!("Error while creating {}: {}", evicted_dir.display(), e);
+                return;
+            }
+            // Victims are only moved away under the lock, so nobody can pin and read them while
+            // they are deleted.
+            let evicted = {
+                let mut cache = cache.lock().unwrap();
+                let mut evicted = Vec::new();
+                for (file, size) in cache.evict(keep.as_deref()) {
+                    let evicted_path = evicted_dir.join(format!("evicted-{}", Uuid::new_v4()));
+                    match std::fs::rename(local_dir.join(&file), &evicted_path) {
+                        Ok(()) => evicted.push((file, size, Some(evicted_path))),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                            evicted.push((file, size, None))
+                        }
+                        Err(e) => log::error!("Error while evicting local file {}: {}", file, e),
+                    }
+                }
+                app_metrics::LOCAL_FILES_SIZE.report(cache.size() as i64);
+                evicted
+            };
+            for (file, size, evicted_path) in evicted {
+                log::debug!("Evicting local copy of {} ({} bytes)", file, size);
+                if let Some(path) = evicted_path {
+                    if let Err(e) = std::fs::remove_file(&path) {
+                        log::error!("Error while evicting local file {}: {}", file, e);
+                        continue;
+                    }
+                }
+                app_metrics::LOCAL_FILES_EVICTED.increment();
+                app_metrics::LOCAL_FILES_EVICTED_BYTES.add(size as i64);
+            }
+        })
+        .await;
+        if let Err(e) = res {
+            error!("Error during local files eviction: {:?}", e);
+        }
+    }
+
     async fn check_file_size(
         remote_path: &str,
         expected_file_size: Option<u64>,
@@ -454,3 +593,196 @@ impl QueueRemoteFs {
         Ok(())
     }
 }
+
+/// Directories of the local dir that don't hold copies of remote files: the metastore database,
+/// files waiting for upload, partial downloads, query dumps and encrypted files in transfer.
+const LOCAL_ONLY_DIRS: [&str; 5] = ["metastore", "uploads", "downloads", "dumps", "encrypted"];
+
+/// Collects files under `dir` with their access times, sizes and remote paths, i.e. paths
+/// relative to the local dir. `prefix` is the remote path of `dir`.
+fn list_local_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<(SystemTime, String, u64)>,
+) -> Result<(), std::io::Error> {
+    for res_entry in dir.read_dir()? {
+        let entry = match res_entry {
+            Err(_) => continue,
+            Ok(e) => e,
+        };
+        let metadata = match entry.metadata() {
+            Err(_) => continue,
+            Ok(m) => m,
+        };
+        let file_name = match entry.file_name().into_string() {
+            Err(_) => continue,
+            Ok(name) => name,
+        };
+        if metadata.is_dir() {
+            if prefix.is_empty() && LOCAL_ONLY_DIRS.contains(&file_name.as_str()) {
+                continue;
+            }
+            let dir_prefix = format!("{}{}/", prefix, file_name);
+            if let Err(e) = list_local_files(&entry.path(), &dir_prefix, files) {
+                log::error!("Error while listing local directory {}: {}", dir_prefix, e);
+            }
+            continue;
+        }
+        if !metadata.is_file() {
+            continue;
+        }
+        let accessed = metadata
+            .accessed()
+            .or_else(|_| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        files.push((accessed, format!("{}{}", prefix, file_name), metadata.len()));
+    }
+    Ok(())
+}
+
+/// Sizes and access order of remote files kept in the local directory. Least recently used files
+/// are evicted once their total size exceeds `max_size`, unless they are pinned by running queries.
+struct LocalFilesCache {
+    max_size: u64,
+    size: u64,
+    files: LruCache<String, u64>,
+    pins: HashMap<String, usize>,
+}
+
+impl LocalFilesCache {
+    fn new(max_size: u64) -> LocalFilesCache {
+        LocalFilesCache {
+            max_size,
+            size: 0,
+            files: LruCache::unbounded(),
+            pins: HashMap::new(),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn touch(&mut self, remote_path: &str, size: u64) {
+        if let Some(old_size) = self.files.put(remote_path.to_string(), size) {
+            self.size -= old_size;
+        }
+        self.size += size;
+    }
+
+    fn remove(&mut self, remote_path: &str) {
+        if let Some(size) = self.files.pop(&remote_path.to_string()) {
+            self.size -= size;
+        }
+    }
+
+    fn pin(&mut self, remote_path: &str) {
+        *self.pins.entry(remote_path.to_string()).or_insert(0) += 1;
+    }
+
+    fn unpin(&mut self, remote_path: &str) {
+        if let Entry::Occupied(mut e) = self.pins.entry(remote_path.to_string()) {
+            *e.get_mut() -= 1;
+            if *e.get() == 0 {
+                e.remove();
+            }
+        }
+    }
+
+    /// Forgets least recently used files until the total size fits into `max_size` and returns
+    /// them with their sizes. Pinned files and `keep` are never evicted.
+    fn evict(&mut self, keep: Option<&str>) -> Vec<(String, u64)> {
+        let mut excess = self.size.saturating_sub(self.max_size);
+        if excess == 0 {
+            return Vec::new();
+        }
+        let mut evicted = Vec::new();
+        for (file, size) in self.files.iter().rev() {
+            if excess == 0 {
+                break;
+            }
+            if keep == Some(file.as_str()) || self.pins.contains_key(file) {
+                continue;
+            }
+            excess = excess.saturating_sub(*size);
+            evicted.push((file.clone(), *size));
+        }
+        for (file, _) in evicted.iter() {
+            self.remove(file);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_files_eviction() {
+        let mut cache = LocalFilesCache::new(100);
+        cache.touch("a", 40);
+        cache.touch("b", 40);
+        cache.touch("c", 10);
+        assert_eq!(cache.evict(None), vec![]);
+
+        // Access makes `a` the most recently used file.
+        cache.touch("a", 40);
+        cache.touch("d", 30);
+        assert_eq!(cache.evict(Some("d")), vec![("b".to_string(), 40)]);
+        assert_eq!(cache.size(), 80);
+
+        // Pinned files stay until unpinned.
+        cache.pin("c");
+        cache.pin("c");
+        cache.touch("e", 50);
+        assert_eq!(cache.evict(Some("e")), vec![("a".to_string(), 40)]);
+        assert_eq!(cache.size(), 90);
+
+        cache.unpin("c");
+        cache.touch("f", 50);
+        assert_eq!(
+            cache.evict(Some("f")),
+            vec![("d".to_string(), 30), ("e".to_string(), 50)]
+        );
+        assert_eq!(cache.size(), 60);
+        cache.unpin("c");
+        cache.touch("g", 50);
+        assert_eq!(cache.evict(Some("g")), vec![("c".to_string(), 10)]);
+        assert_eq!(cache.size(), 100);
+
+        cache.remove("f");
+        assert_eq!(cache.size(), 50);
+    }
+
+    #[test]
+    fn local_files_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        for f in &[
+            "1.parquet",
+            "metastore-1/000001.sst",
+            "metastore/CURRENT",
+            "uploads/2.parquet",
+            "downloads/3.parquet",
+        ] {
+            let path = dir.path().join(f);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"abc").unwrap();
+        }
+
+        let mut files = Vec::new();
+        list_local_files(dir.path(), "", &mut files).unwrap();
+        let mut files = files
+            .into_iter()
+            .map(|(_, f, size)| (f, size))
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                ("1.parquet".to_string(), 3),
+                ("metastore-1/000001.sst".to_string(), 3)
+            ]
+        );
+    }
+}
@@ -4,9 +4,10 @@ use crate::metastore::job::{Job, JobType};
 use crate::metastore::partition::partition_file_name;
 use crate::metastore::table::Table;
 use crate::metastore::{
-    deactivate_table_on_corrupt_data, IdRow, MetaStore, MetaStoreEvent, Partition, RowKey, TableId,
+    deactivate_table_on_corrupt_data, IdRow, MetaStore, MetaStoreEvent, MetaStoreTable, Partition,
+    RocksMetaStore, RowKey, TableId,
 };
-use crate::remotefs::RemoteFs;
+use crate::remotefs::{RemoteFile, RemoteFs};
 use crate::store::{ChunkStore, WALStore};
 use crate::util::time_span::warn_long_fut;
 use crate::util::WorkerLoop;
@@ -17,7 +18,8 @@ use flatbuffers::bitflags::_core::cmp::Ordering;
 use flatbuffers::bitflags::_core::time::Duration;
 use futures_timer::Delay;
 use log::error;
-use std::collections::{BinaryHeap, HashSet};
+use regex::Regex;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::{broadcast, Mutex, Notify, RwLock};
@@ -34,6 +36,10 @@ pub struct SchedulerImpl {
     gc_loop: Arc<DataGCLoop>,
     config: Arc<dyn ConfigObj>,
     reconcile_loop: WorkerLoop,
+    remote_files_cleanup_loop: WorkerLoop,
+    /// Remote files referenced by uploaded metastore checkpoints, by checkpoint name. Checkpoints
+    /// don't change once uploaded.
+    checkpoint_remote_files: Mutex<HashMap<String, Arc<HashSet<String>>>>,
 }
 
 crate::di_service!(SchedulerImpl, []);
@@ -62,6 +68,8 @@ impl SchedulerImpl {
             gc_loop,
             config,
             reconcile_loop: WorkerLoop::new("Reconcile"),
+            remote_files_cleanup_loop: WorkerLoop::new("Remote files cleanup"),
+            checkpoint_remote_files: Mutex::new(HashMap::new()),
         }
     }
 
@@ -70,7 +78,8 @@ impl SchedulerImpl {
     ) -> Vec<JoinHandle<Result<(), CubeError>>> {
         let scheduler2 = scheduler.clone();
         let scheduler3 = scheduler.clone();
-        vec![
+        let scheduler4 = scheduler.clone();
+        let mut loops = vec![
             cube_ext::spawn(async move {
                 let gc_loop = scheduler.gc_loop.clone();
                 gc_loop.run().await;
@@ -91,7 +100,28 @@ impl SchedulerImpl {
                     .await;
                 Ok(())
             }),
-        ]
+        ];
+        let cleanup_interval = scheduler4.config.remote_files_cleanup_interval();
+        if cleanup_interval != 0 {
+            loops.push(cube_ext::spawn(async move {
+                scheduler4
+                    .remote_files_cleanup_loop
+                    .process(
+                        scheduler4.clone(),
+                        async move |_| Ok(Delay::new(Duration::from_secs(cleanup_interval)).await),
+                        async move |s, _| {
+                            let removed = s.remove_orphaned_remote_files(false).await?;
+                            if !removed.is_empty() {
+                                log::info!("Removed {} orphaned remote files", removed.len());
+                            }
+                            Ok(())
+                        },
+                    )
+                    .await;
+                Ok(())
+            }));
+        }
+        loops
     }
 
     async fn run_scheduler(scheduler: Arc<SchedulerImpl>) {
@@ -155,6 +185,16 @@ impl SchedulerImpl {
             error!("Error during dropping not ready tables: {}", e);
         };
 
+        if let Err(e) = warn_long_fut(
+            "Drop expired tables reconciliation",
+            Duration::from_millis(5000),
+            self.drop_expired_tables(),
+        )
+        .await
+        {
+            error!("Error during dropping expired tables: {}", e);
+        };
+
         if let Err(e) = warn_long_fut(
             "Remove inactive chunks",
             Duration::from_millis(5000),
@@ -185,6 +225,16 @@ impl SchedulerImpl {
             error!("Error scheduling partitions compaction: {}", e);
         }
 
+        if let Err(e) = warn_long_fut(
+            "Scheduling rewrite of altered partitions",
+            Duration::from_millis(5000),
+            self.schedule_altered_partitions_compaction(),
+        )
+        .await
+        {
+            error!("Error scheduling rewrite of altered partitions: {}", e);
+        }
+
         if let Err(e) = warn_long_fut(
             "Scheduling repartition",
             Duration::from_millis(5000),
@@ -273,6 +323,14 @@ impl SchedulerImpl {
         Ok(())
     }
 
+    /// Compaction rewrites data files with current columns of altered indexes.
+    async fn schedule_altered_partitions_compaction(&self) -> Result<(), CubeError> {
+        for p in self.meta_store.get_altered_partitions().await? {
+            self.schedule_partition_to_compact(&p).await?;
+        }
+        Ok(())
+    }
+
     async fn remove_inactive_not_uploaded_chunks(&self) -> Result<(), CubeError> {
         let all_inactive_not_uploaded_chunks =
             self.meta_store.all_inactive_not_uploaded_chunks().await?;
@@ -341,6 +399,22 @@ impl SchedulerImpl {
         Ok(())
     }
 
+    /// Partitions and chunks of dropped tables are removed by the data GC loop.
+    async fn drop_expired_tables(&self) -> Result<(), CubeError> {
+        let now = Utc::now();
+        // Using get_tables_with_path due to it's cached
+        let tables = self.meta_store.get_tables_with_path(true).await?;
+        for table in tables.iter() {
+            if table.table.get_row().is_expired(now) {
+                log::info!("Dropping expired table: {}", table.table_name());
+                if let Err(e) = self.meta_store.drop_table(table.table.get_id()).await {
+                    error!("Error dropping expired table {}: {}", table.table_name(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn remove_orphaned_jobs(&self) -> Result<(), CubeError> {
         let orphaned_jobs = self
             .meta_store
@@ -356,9 +430,100 @@ impl SchedulerImpl {
     pub fn stop_processing_loops(&self) -> Result<(), CubeError> {
         self.cancel_token.cancel();
         self.reconcile_loop.stop();
+        self.remote_files_cleanup_loop.stop();
         Ok(())
     }
 
+    /// Removes data files that no partition, chunk or WAL refers to, e.g. left after deleting rows
+    /// while a node was down. Files of rows deleted recently are kept while retained metastore
+    /// checkpoints refer to them. Files modified within the cleanup delay and files already
+    /// scheduled for removal are kept as well. Returns orphaned files, they are only listed if
+    /// [dry_run] is set.
+    pub async fn remove_orphaned_remote_files(
+        &self,
+        dry_run: bool,
+    ) -> Result<Vec<RemoteFile>, CubeError> {
+        let modified_before =
+            Utc::now() - chrono::Duration::seconds(self.config.remote_files_cleanup_delay() as i64);
+        // Listed before reading the metastore, so rows created in between refer to recent files.
+        let files = self.remote_fs.list_with_metadata("").await?;
+
+        let mut referenced = HashSet::new();
+        add_remote_files(self.meta_store.as_ref(), &mut referenced).await?;
+        for files in self.checkpoint_remote_files().await? {
+            referenced.extend(files.iter().cloned());
+        }
+        let pending_removal = self.gc_loop.pending_remote_files().await;
+
+        let data_file = Regex::new(r"^\d+(-[0-9a-z]+)?(\.chunk)?\.parquet$|^\d+\.wal$").unwrap();
+        let orphaned = files
+            .into_iter()
+            .filter(|f| {
+                data_file.is_match(f.remote_path())
+                    && f.updated() < &modified_before
+                    && !referenced.contains(f.remote_path())
+                    && !pending_removal.contains(f.remote_path())
+            })
+            .collect::<Vec<_>>();
+        if !dry_run {
+            for f in orphaned.iter() {
+                log::info!("Removing orphaned remote file: {}", f.remote_path());
+                if let Err(e) = self.remote_fs.delete_file(f.remote_path()).await {
+                    log::error!(
+                        "Could not remove orphaned remote file({}): {}",
+                        f.remote_path(),
+                        e
+                    );
+                }
+            }
+        }
+        Ok(orphaned)
+    }
+
+    /// Remote files referenced by each uploaded metastore checkpoint. Only checkpoints not seen
+    /// before are downloaded, removed checkpoints are dropped from the cache.
+    async fn checkpoint_remote_files(&self) -> Result<Vec<Arc<HashSet<String>>>, CubeError> {
+        let checkpoints = RocksMetaStore::list_checkpoints(self.remote_fs.clone()).await?;
+        let mut cache = self.checkpoint_remote_files.lock().await;
+        cache.retain(|name, _| checkpoints.iter().any(|c| &c.name == name));
+        let mut result = Vec::with_capacity(checkpoints.len());
+        for checkpoint in checkpoints {
+            if let Some(files) = cache.get(&checkpoint.name) {
+                result.push(files.clone());
+                continue;
+            }
+            let uploads_dir = self.remote_fs.uploads_dir().await?;
+            let dir = cube_ext::spawn_blocking(move || {
+                tempfile::Builder::new()
+                    .prefix("metastore-checkpoint")
+                    .tempdir_in(uploads_dir)
+            })
+            .await??;
+            let mut files = HashSet::new();
+            {
+                let meta_store = RocksMetaStore::load_checkpoint(
+                    dir.path(),
+                    &checkpoint.name,
+                    self.remote_fs.clone(),
+                    self.config.clone(),
+                )
+                .await?;
+                add_remote_files(meta_store.as_ref(), &mut files).await?;
+            }
+            let files = Arc::new(files);
+            cache.insert(checkpoint.name, files.clone());
+            result.push(files);
+        }
+        Ok(result)
+    }
+
+    /// Files of deleted and deactivated rows are kept for [delay_secs] plus the metastore
+    /// checkpoint retention, so that retained checkpoints referring to them can be restored.
+    fn remote_file_removal_deadline(&self, delay_secs: u64) -> Instant {
+        Instant::now()
+            + Duration::from_secs(delay_secs + self.config.meta_store_checkpoint_retention())
+    }
+
     async fn process_event(&self, event: MetaStoreEvent) -> Result<(), CubeError> {
         if let MetaStoreEvent::Insert(TableId::WALs, row_id)
         | MetaStoreEvent::Update(TableId::WALs, row_id) = event
@@ -442,8 +607,8 @@ impl SchedulerImpl {
             } else if chunk.get_row().uploaded() {
                 let file_name =
                     ChunkStore::chunk_remote_path(chunk.get_id(), chunk.get_row().suffix());
-                let deadline = Instant::now()
-                    + Duration::from_secs(self.config.meta_store_log_upload_interval() * 2);
+                let deadline = self
+                    .remote_file_removal_deadline(self.config.meta_store_log_upload_interval() * 2);
                 self.gc_loop
                     .send(GCTimedTask {
                         deadline,
@@ -456,8 +621,9 @@ impl SchedulerImpl {
             // remove file only if partition is active otherwise it should be removed when it's deactivated
             if partition.get_row().is_active() {
                 if let Some(file_name) = partition.get_row().get_full_name(partition.get_id()) {
-                    let deadline = Instant::now()
-                        + Duration::from_secs(self.config.meta_store_log_upload_interval() * 2);
+                    let deadline = self.remote_file_removal_deadline(
+                        self.config.meta_store_log_upload_interval() * 2,
+                    );
                     self.gc_loop
                         .send(GCTimedTask {
                             deadline,
@@ -475,7 +641,7 @@ impl SchedulerImpl {
                     let file_name =
                         partition_file_name(partition.get_id(), partition.get_row().suffix());
                     let deadline =
-                        Instant::now() + Duration::from_secs(self.config.not_used_timeout());
+                        self.remote_file_removal_deadline(self.config.not_used_timeout());
                     self.gc_loop
                         .send(GCTimedTask {
                             deadline,
@@ -748,6 +914,26 @@ enum GCTask {
     DeletePartition(/*partition_id*/ u64),
 }
 
+/// Adds files of all partitions, chunks and WALs in [meta_store] to [files].
+async fn add_remote_files(
+    meta_store: &dyn MetaStore,
+    files: &mut HashSet<String>,
+) -> Result<(), CubeError> {
+    for p in meta_store.partition_table().all_rows().await? {
+        files.insert(partition_file_name(p.get_id(), p.get_row().suffix()));
+    }
+    for c in meta_store.chunks_table().all_rows().await? {
+        files.insert(ChunkStore::chunk_remote_path(
+            c.get_id(),
+            c.get_row().suffix(),
+        ));
+    }
+    for w in meta_store.all_wals().await? {
+        files.insert(WALStore::wal_remote_path(w.get_id()));
+    }
+    Ok(())
+}
+
 /// Cleans up deactivated partitions and chunks on remote fs.
 /// Ensures enough time has passed that queries over those files finish.
 struct DataGCLoop {
@@ -798,6 +984,19 @@ impl DataGCLoop {
         Ok(())
     }
 
+    async fn pending_remote_files(&self) -> HashSet<String> {
+        self.pending
+            .read()
+            .await
+            .1
+            .iter()
+            .filter_map(|t| match t {
+                GCTask::RemoveRemoteFile(remote_path) => Some(remote_path.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
     async fn run(&self) {
         loop {
             tokio::select! {
@@ -1,22 +1,26 @@
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 
 use arrow::array::*;
 use arrow::compute::kernels::cast_utils::string_to_timestamp_nanos;
+use arrow::datatypes::{DataType as ArrowDataType, Schema as ArrowSchema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use chrono::format::Fixed::Nanosecond3;
 use chrono::format::Item::{Fixed, Literal, Numeric, Space};
 use chrono::format::Numeric::{Day, Hour, Minute, Month, Second, Year};
 use chrono::format::Pad::Zero;
 use chrono::format::Parsed;
-use chrono::{ParseResult, Utc};
+use chrono::{NaiveDate, ParseResult, Utc};
 use datafusion::cube_ext;
-use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_plan::{ExecutionPlan, SendableRecordBatchStream};
 use datafusion::sql::parser::Statement as DFStatement;
 use futures::future::join_all;
+use futures::{Stream, StreamExt, TryStreamExt};
 use hex::FromHex;
 use itertools::Itertools;
 use log::trace;
@@ -28,8 +32,8 @@ use sqlparser::dialect::Dialect;
 use tempfile::TempDir;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
-use tokio::time::timeout;
-use tracing::instrument;
+use tokio::time::{timeout, timeout_at, Instant};
+use tracing::{instrument, Instrument};
 use tracing_futures::WithSubscriber;
 
 use cubehll::HllSketch;
@@ -42,19 +46,24 @@ use crate::import::limits::ConcurrencyLimits;
 use crate::import::{ImportService, Ingestion};
 use crate::metastore::job::JobType;
 use crate::metastore::multi_index::MultiIndex;
-use crate::metastore::source::SourceCredentials;
+use crate::metastore::source::{KafkaValueFormat, SourceCredentials};
+use crate::metastore::table::ParquetSettings;
 use crate::metastore::{
-    is_valid_plain_binary_hll, table::Table, HllFlavour, IdRow, ImportFormat, Index, IndexDef,
-    MetaStoreTable, RowKey, Schema, TableId,
+    is_valid_plain_binary_hll, table::Table, Chunk, HllFlavour, IdRow, ImportFormat, Index,
+    IndexDef, MetaStoreTable, Partition, RocksMetaStore, RowKey, Schema, TableId,
 };
 use crate::queryplanner::panic::PanicWorkerNode;
-use crate::queryplanner::query_executor::{batch_to_dataframe, QueryExecutor};
+use crate::queryplanner::query_executor::{batch_to_dataframe, source_tables, QueryExecutor};
 use crate::queryplanner::serialized_plan::{RowFilter, SerializedPlan};
 use crate::queryplanner::{PlanningMeta, QueryPlan, QueryPlanner};
 use crate::remotefs::RemoteFs;
+use crate::scheduler::SchedulerImpl;
 use crate::sql::cache::SqlResultCache;
-use crate::sql::parser::{CubeStoreParser, PartitionedIndexRef, SystemCommand};
-use crate::store::ChunkDataStore;
+use crate::sql::parser::{
+    AlterTableOperation, CubeStoreParser, PartitionedIndexRef, SystemCommand,
+};
+use crate::store::{ChunkDataStore, ROW_GROUP_SIZE};
+use crate::table::parquet::read_index_file;
 use crate::table::{data, Row, TableValue, TimestampValue};
 use crate::telemetry::incoming_traffic_agent_event;
 use crate::util::decimal::Decimal;
@@ -82,6 +91,15 @@ pub trait SqlService: DIService + Send + Sync {
         query: &str,
     ) -> Result<Arc<DataFrame>, CubeError>;
 
+    /// Same as [exec_query_with_context], but rows of selects are delivered in chunks as soon as
+    /// they are produced instead of being collected into a single data frame. Results of other
+    /// statements arrive as a single chunk.
+    async fn exec_query_stream_with_context(
+        &self,
+        context: SqlQueryContext,
+        query: &str,
+    ) -> Result<QueryResultStream, CubeError>;
+
     /// Exposed only for tests. Worker plan created as if all partitions are on the same worker.
     async fn plan_query(&self, query: &str) -> Result<QueryPlans, CubeError>;
 
@@ -95,6 +113,12 @@ pub trait SqlService: DIService + Send + Sync {
     async fn temp_uploads_dir(&self, context: SqlQueryContext) -> Result<String, CubeError>;
 }
 
+pub struct QueryResultStream {
+    /// All batches have this schema, even if there are no batches at all.
+    pub schema: SchemaRef,
+    pub batches: Pin<Box<dyn Stream<Item = Result<RecordBatch, CubeError>> + Send>>,
+}
+
 pub struct QueryPlans {
     pub router: Arc<dyn ExecutionPlan>,
     pub worker: Arc<dyn ExecutionPlan>,
@@ -118,6 +142,7 @@ pub struct SqlServiceImpl {
     db: Arc<dyn MetaStore>,
     chunk_store: Arc<dyn ChunkDataStore>,
     remote_fs: Arc<dyn RemoteFs>,
+    scheduler: Arc<SchedulerImpl>,
     limits: Arc<ConcurrencyLimits>,
     query_planner: Arc<dyn QueryPlanner>,
     query_executor: Arc<dyn QueryExecutor>,
@@ -143,6 +168,7 @@ impl SqlServiceImpl {
         import_service: Arc<dyn ImportService>,
         config_obj: Arc<dyn ConfigObj>,
         remote_fs: Arc<dyn RemoteFs>,
+        scheduler: Arc<SchedulerImpl>,
         rows_per_chunk: usize,
         query_timeout: Duration,
         create_table_timeout: Duration,
@@ -161,6 +187,7 @@ impl SqlServiceImpl {
             query_timeout,
             create_table_timeout,
             remote_fs,
+            scheduler,
             cache: SqlResultCache::new(max_cached_queries),
         })
     }
@@ -177,16 +204,18 @@ impl SqlServiceImpl {
         &self,
         schema_name: String,
         table_name: String,
-        columns: &Vec<ColumnDef>,
+        columns_to_set: Vec<Column>,
         external: bool,
+        is_ready: bool,
         locations: Option<Vec<String>>,
         import_format: Option<ImportFormat>,
         indexes: Vec<Statement>,
         unique_key: Option<Vec<Ident>>,
         partitioned_index: Option<PartitionedIndexRef>,
+        ttl_seconds: Option<u64>,
+        parquet_settings: ParquetSettings,
         trace_obj: &Option<String>,
     ) -> Result<IdRow<Table>, CubeError> {
-        let columns_to_set = convert_columns_type(columns)?;
         let mut indexes_to_create = Vec::new();
         if let Some(mut p) = partitioned_index {
             let part_index_name = match p.name.0.as_mut_slice() {
@@ -248,13 +277,20 @@ impl SqlServiceImpl {
                     None,
                     None,
                     indexes_to_create,
-                    true,
+                    is_ready,
                     unique_key.map(|keys| keys.iter().map(|c| c.value.to_string()).collect()),
                     None,
+                    ttl_seconds,
+                    parquet_settings,
                 )
                 .await;
         }
 
+        let locations = match locations {
+            Some(locations) => Some(self.import_service.expand_locations(locations).await?),
+            None => None,
+        };
+
         let listener = self.cluster.job_result_listener();
 
         let partition_split_threshold = if let Some(locations) = locations.as_ref() {
@@ -301,6 +337,8 @@ impl SqlServiceImpl {
                 false,
                 unique_key.map(|keys| keys.iter().map(|c| c.value.to_string()).collect()),
                 partition_split_threshold,
+                ttl_seconds,
+                parquet_settings,
             )
             .await?;
 
@@ -402,18 +440,145 @@ impl SqlServiceImpl {
         name: String,
         columns: &Vec<Ident>,
     ) -> Result<IdRow<Index>, CubeError> {
-        Ok(self
+        let index_def = IndexDef {
+            name,
+            multi_index: None,
+            columns: columns.iter().map(|c| c.value.to_string()).collect(),
+        };
+        let table = self
             .db
-            .create_index(
-                schema_name,
-                table_name,
-                IndexDef {
-                    name,
-                    multi_index: None,
-                    columns: columns.iter().map(|c| c.value.to_string()).collect(),
-                },
-            )
-            .await?)
+            .get_table(schema_name.clone(), table_name.clone())
+            .await?;
+        if !*table.get_row().has_data() {
+            return Ok(self
+                .db
+                .create_index(schema_name, table_name, index_def)
+                .await?);
+        }
+
+        let (index, default_index, partitions) = self
+            .db
+            .create_index_for_backfill(schema_name, table_name, index_def)
+            .await?;
+        match self
+            .backfill_index(&index, &default_index, partitions)
+            .await
+        {
+            Ok(chunks) => self.db.finish_index_backfill(index.get_id(), chunks).await,
+            Err(e) => {
+                if let Err(inner) = self.db.cancel_index_backfill(index.get_id()).await {
+                    log::error!(
+                        "Cancel backfill of index ({}) after error failed: {}",
+                        index.get_id(),
+                        inner
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Copies data of the default index into chunks of the new index. Returns uploaded chunks
+    /// which are activated together with the index.
+    async fn backfill_index(
+        &self,
+        index: &IdRow<Index>,
+        default_index: &IdRow<Index>,
+        partitions: Vec<(IdRow<Partition>, Vec<IdRow<Chunk>>)>,
+    ) -> Result<Vec<(u64, Option<u64>)>, CubeError> {
+        let default_columns = default_index.get_row().get_columns();
+        let positions = index
+            .get_row()
+            .get_columns()
+            .iter()
+            .map(|c| {
+                default_columns
+                    .iter()
+                    .position(|d| d.get_name() == c.get_name())
+                    .ok_or_else(|| {
+                        CubeError::internal(format!(
+                            "Column '{}' is not found in {:?}",
+                            c.get_name(),
+                            default_index
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut remote_files = Vec::new();
+        for (partition, chunks) in partitions {
+            if let Some(f) = partition.get_row().get_full_name(partition.get_id()) {
+                remote_files.push((f, partition.get_row().file_size()));
+            }
+            for c in chunks {
+                remote_files.push((
+                    c.get_row().get_full_name(c.get_id()),
+                    c.get_row().file_size(),
+                ));
+            }
+        }
+
+        // Rows are read batch by batch and written in chunks of bounded size.
+        let mut uploaded = Vec::new();
+        let mut pending = Vec::new();
+        let mut pending_rows = 0;
+        for (remote_file, size) in remote_files {
+            let local_file = self.remote_fs.download_file(&remote_file, size).await?;
+            let mut batches = read_index_file(
+                &local_file,
+                default_index.get_row(),
+                None,
+                None,
+                ROW_GROUP_SIZE,
+            )?
+            .execute(0)
+            .await?;
+            while let Some(batch) = batches.next().await.transpose()? {
+                pending_rows += batch.num_rows();
+                pending.push(batch);
+                if pending_rows >= self.rows_per_chunk {
+                    uploaded.append(
+                        &mut self
+                            .write_backfill_chunks(index, &positions, take(&mut pending))
+                            .await?,
+                    );
+                    pending_rows = 0;
+                }
+            }
+        }
+        if pending_rows > 0 {
+            uploaded.append(
+                &mut self
+                    .write_backfill_chunks(index, &positions, pending)
+                    .await?,
+            );
+        }
+        Ok(uploaded)
+    }
+
+    /// Writes [positions] columns of [batches] into chunks of [index] and waits for the upload.
+    async fn write_backfill_chunks(
+        &self,
+        index: &IdRow<Index>,
+        positions: &[usize],
+        batches: Vec<RecordBatch>,
+    ) -> Result<Vec<(u64, Option<u64>)>, CubeError> {
+        let mut columns = Vec::with_capacity(positions.len());
+        for i in positions.iter() {
+            columns.push(arrow::compute::concat(
+                &batches.iter().map(|b| b.column(*i).as_ref()).collect_vec(),
+            )?);
+        }
+        let jobs = self
+            .chunk_store
+            .partition_index_data(index.get_id(), columns)
+            .await?;
+        let mut uploaded = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            let (chunk, file_size) = job.await??;
+            uploaded.push((chunk.get_id(), file_size));
+        }
+        Ok(uploaded)
     }
 
     async fn insert_data<'a>(
@@ -459,6 +624,223 @@ impl SqlServiceImpl {
         Ok(data.len() as u64)
     }
 
+    async fn insert_select(
+        &self,
+        schema_name: String,
+        table_name: String,
+        columns: &Vec<Ident>,
+        query: Box<Query>,
+    ) -> Result<u64, CubeError> {
+        let table = self
+            .db
+            .get_table(schema_name.clone(), table_name.clone())
+            .await?;
+        let table_columns = table.get_row().get_columns();
+        let real_col = if columns.is_empty() {
+            table.get_row().insertable_columns()
+        } else {
+            let mut real_col = Vec::with_capacity(columns.len());
+            for column in columns {
+                let c = table_columns
+                    .iter()
+                    .find(|c| *c.get_name() == column.value)
+                    .ok_or_else(|| {
+                        CubeError::user(format!(
+                            "Column {} is not present in table {}.{}.",
+                            column.value, schema_name, table_name
+                        ))
+                    })?;
+                real_col.push(c.clone());
+            }
+            real_col
+        };
+        let batches = self.query_stream(query).await?;
+        self.ingest_stream(&table, real_col, batches, None).await
+    }
+
+    /// Writes query results into [columns] of the [table] through the same pipeline as inserts.
+    /// With [tombstone_seq] set, rows are written as tombstones of the table rows they copy and
+    /// get sequence values starting from it.
+    async fn ingest_stream(
+        &self,
+        table: &IdRow<Table>,
+        columns: Vec<Column>,
+        mut batches: SendableRecordBatchStream,
+        tombstone_seq: Option<i64>,
+    ) -> Result<u64, CubeError> {
+        if batches.schema().fields().len() != columns.len() {
+            return Err(CubeError::user(format!(
+                "Query returns {} columns but {} are inserted into table {}",
+                batches.schema().fields().len(),
+                columns.len(),
+                table.get_row().get_table_name()
+            )));
+        }
+        let columns = Arc::new(columns);
+
+        // Rows become visible all at once, when the whole query result is written.
+        let mut ingestion = Ingestion::new_atomic(
+            self.db.clone(),
+            self.chunk_store.clone(),
+            self.limits.clone(),
+            table.clone(),
+        );
+        let mut inserted_rows = 0;
+        let mut pending = Vec::new();
+        let mut pending_rows = 0;
+        loop {
+            let batch = batches.next().await.transpose()?;
+            if let Some(batch) = &batch {
+                pending_rows += batch.num_rows();
+                pending.push(batch.clone());
+            }
+            if pending_rows > 0 && (pending_rows >= self.rows_per_chunk || batch.is_none()) {
+                let batches = take(&mut pending);
+                let columns = columns.clone();
+                let table = table.clone();
+                let rows = cube_ext::spawn_blocking(move || {
+                    let rows = select_batches_to_chunk(&batches, &columns)?;
+                    match tombstone_seq {
+                        Some(seq) => to_tombstones(table.get_row(), rows, seq),
+                        None => Ok(rows),
+                    }
+                })
+                .await??;
+                ingestion.queue_data_frame(rows).await?;
+                inserted_rows += pending_rows;
+                pending_rows = 0;
+            }
+            if batch.is_none() {
+                break;
+            }
+        }
+        ingestion.wait_completion().await?;
+        Ok(inserted_rows as u64)
+    }
+
+    /// Deletes rows matching [selection] from a table with unique key. Matching rows are copied
+    /// into tombstones with sequence values above all the table has, so they replace the rows on
+    /// merge.
+    ///
+    /// No lock is held between reading the maximum sequence value and writing tombstones, so rows
+    /// inserted concurrently with lower sequence values are hidden by tombstones as well. Tables
+    /// fed by streams are rejected: their sequence values are set by the source, e.g. Kafka
+    /// offsets of separate partitions, and aren't comparable with the ones of tombstones.
+    async fn delete_rows(
+        &self,
+        schema_name: String,
+        table_name: String,
+        selection: Option<Expr>,
+    ) -> Result<u64, CubeError> {
+        let table = self
+            .db
+            .get_table(schema_name.clone(), table_name.clone())
+            .await?;
+        let is_streaming = table
+            .get_row()
+            .locations()
+            .map_or(false, |ls| ls.iter().any(|l| Table::is_stream_location(l)));
+        if is_streaming {
+            return Err(CubeError::user(format!(
+                "DELETE is not supported for table '{}.{}' fed by a stream",
+                schema_name, table_name
+            )));
+        }
+        let table = self
+            .db
+            .add_deleted_column(schema_name.clone(), table_name.clone())
+            .await?;
+        let seq_column = table.get_row().seq_column().ok_or_else(|| {
+            CubeError::internal(format!(
+                "Seq column is not defined for table '{}'",
+                table.get_row().get_table_name()
+            ))
+        })?;
+        let table_name = ObjectName(vec![
+            Ident::with_quote('`', schema_name),
+            Ident::with_quote('`', table_name),
+        ]);
+        let seq_ident = Expr::Identifier(Ident::with_quote('`', seq_column.get_name()));
+
+        let mut max_seq_query = select_query(
+            "SELECT x FROM t WHERE x IS NOT NULL ORDER BY x DESC LIMIT 1",
+            table_name.clone(),
+            vec![SelectItem::UnnamedExpr(seq_ident.clone())],
+            Some(Expr::IsNotNull(Box::new(seq_ident.clone()))),
+        )?;
+        max_seq_query.order_by[0].expr = seq_ident;
+        let max_seq = self
+            .query_stream(max_seq_query)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?
+            .iter()
+            .filter(|b| b.num_rows() > 0)
+            .map(|b| {
+                b.column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .value(0)
+            })
+            .next()
+            .unwrap_or(0);
+
+        let columns = table.get_row().insertable_columns();
+        let query = select_query(
+            "SELECT x FROM t",
+            table_name,
+            columns
+                .iter()
+                .map(|c| {
+                    SelectItem::UnnamedExpr(Expr::Identifier(Ident::with_quote('`', c.get_name())))
+                })
+                .collect(),
+            selection,
+        )?;
+        let batches = self.query_stream(query).await?;
+        self.ingest_stream(&table, columns, batches, Some(max_seq + 1))
+            .await
+    }
+
+    /// Runs the query for `INSERT ... SELECT` and `DELETE`, batches are streamed as they are
+    /// computed. Unlike `SELECT`, the results are not looked up in or put into [SqlResultCache]:
+    /// they are consumed once and may be larger than the cache is meant for.
+    async fn query_stream(
+        &self,
+        query: Box<Query>,
+    ) -> Result<SendableRecordBatchStream, CubeError> {
+        let logical_plan = self
+            .query_planner
+            .logical_plan(DFStatement::Statement(Statement::Query(query)))
+            .await?;
+        match logical_plan {
+            QueryPlan::Select(plan, workers) => {
+                app_metrics::DATA_QUERIES.increment();
+                self.select_stream(plan, workers).await
+            }
+            QueryPlan::Meta(_) => Err(CubeError::user(
+                "Writing results of queries to system tables is not supported".to_string(),
+            )),
+        }
+    }
+
+    async fn select_stream(
+        &self,
+        plan: SerializedPlan,
+        workers: Vec<String>,
+    ) -> Result<SendableRecordBatchStream, CubeError> {
+        if workers.len() == 0 {
+            self.query_executor
+                .execute_router_plan_stream(plan, self.cluster.clone())
+                .await
+        } else {
+            // Pick one of the workers to run as main for the request.
+            let i = thread_rng().sample(Uniform::new(0, workers.len()));
+            self.cluster.route_select_stream(&workers[i], plan).await
+        }
+    }
+
     async fn dump_select_inputs(
         &self,
         query: &str,
@@ -627,6 +1009,55 @@ impl SqlService for SqlServiceImpl {
                     }
                     panic!("worker did not panic")
                 }
+                SystemCommand::MetaStoreCheckpoints => {
+                    let checkpoints =
+                        RocksMetaStore::list_checkpoints(self.remote_fs.clone()).await?;
+                    let columns = vec![
+                        Column::new("name".to_string(), ColumnType::String, 0),
+                        Column::new("created_at".to_string(), ColumnType::Timestamp, 1),
+                        Column::new("files".to_string(), ColumnType::Int, 2),
+                        Column::new("size".to_string(), ColumnType::Int, 3),
+                        Column::new("log_files".to_string(), ColumnType::Int, 4),
+                        Column::new("current".to_string(), ColumnType::Boolean, 5),
+                    ];
+                    let rows = checkpoints
+                        .into_iter()
+                        .map(|c| {
+                            Row::new(vec![
+                                TableValue::String(c.name),
+                                TableValue::Timestamp(TimestampValue::new(
+                                    c.created_at.timestamp_nanos(),
+                                )),
+                                TableValue::Int(c.files as i64),
+                                TableValue::Int(c.size as i64),
+                                TableValue::Int(c.log_files as i64),
+                                TableValue::Boolean(c.current),
+                            ])
+                        })
+                        .collect();
+                    Ok(Arc::new(DataFrame::new(columns, rows)))
+                }
+                SystemCommand::CleanupRemoteFiles { dry_run } => {
+                    let files = self.scheduler.remove_orphaned_remote_files(dry_run).await?;
+                    let columns = vec![
+                        Column::new("remote_path".to_string(), ColumnType::String, 0),
+                        Column::new("size".to_string(), ColumnType::Int, 1),
+                        Column::new("updated".to_string(), ColumnType::Timestamp, 2),
+                    ];
+                    let rows = files
+                        .into_iter()
+                        .map(|f| {
+                            Row::new(vec![
+                                TableValue::String(f.remote_path().to_string()),
+                                TableValue::Int(f.file_size() as i64),
+                                TableValue::Timestamp(TimestampValue::new(
+                                    f.updated().timestamp_nanos(),
+                                )),
+                            ])
+                        })
+                        .collect();
+                    Ok(Arc::new(DataFrame::new(columns, rows)))
+                }
             },
             CubeStoreStatement::Statement(Statement::SetVariable { .. }) => {
                 Ok(Arc::new(DataFrame::new(vec![], vec![])))
@@ -646,6 +1077,7 @@ impl SqlService for SqlServiceImpl {
                         columns,
                         external,
                         with_options,
+                        query,
                         ..
                     },
                 indexes,
@@ -671,6 +1103,8 @@ impl SqlService for SqlServiceImpl {
                                 match input_format.as_str() {
                                     "csv" => Result::Ok(ImportFormat::CSV),
                                     "csv_no_header" => Result::Ok(ImportFormat::CSVNoHeader),
+                                    "parquet" => Result::Ok(ImportFormat::Parquet),
+                                    "json_lines" => Result::Ok(ImportFormat::JsonLines),
                                     _ => Err(CubeError::user(format!(
                                         "Bad input format {}",
                                         option.value
@@ -683,18 +1117,74 @@ impl SqlService for SqlServiceImpl {
                             ))),
                         }
                     })?;
+                let ttl_seconds = with_options
+                    .iter()
+                    .find(|&opt| opt.name.value == "ttl")
+                    .map(|option| parse_ttl(&option.value))
+                    .transpose()?;
+                let parquet_settings = parse_parquet_settings(&with_options)?;
+
+                if let Some(query) = query {
+                    if external || !columns.is_empty() {
+                        return Err(CubeError::user(
+                            "Columns and locations can't be set for CREATE TABLE AS SELECT"
+                                .to_string(),
+                        ));
+                    }
+                    let batches = self.query_stream(query).await?;
+                    let columns = columns_from_schema(batches.schema().as_ref())?;
+                    // Table stays invisible for queries until all results are written.
+                    let table = self
+                        .create_table(
+                            schema_name.clone(),
+                            table_name.clone(),
+                            columns,
+                            false,
+                            false,
+                            None,
+                            None,
+                            indexes,
+                            unique_key,
+                            partitioned_index,
+                            ttl_seconds,
+                            parquet_settings,
+                            &context.trace_obj,
+                        )
+                        .await?;
+                    let columns = table.get_row().get_columns().clone();
+                    let res = match self.ingest_stream(&table, columns, batches, false).await {
+                        Ok(_) => self.db.table_ready(table.get_id(), true).await,
+                        Err(e) => Err(e),
+                    };
+                    return match res {
+                        Ok(table) => Ok(Arc::new(DataFrame::from(vec![table]))),
+                        Err(e) => {
+                            if let Err(inner) = self.db.drop_table(table.get_id()).await {
+                                log::error!(
+                                    "Drop table ({}) after error failed: {}",
+                                    table.get_id(),
+                                    inner
+                                );
+                            }
+                            Err(e)
+                        }
+                    };
+                }
 
                 let res = self
                     .create_table(
                         schema_name.clone(),
                         table_name.clone(),
-                        &columns,
+                        convert_columns_type(&columns)?,
                         external,
+                        true,
                         locations,
                         Some(import_format),
                         indexes,
                         unique_key,
                         partitioned_index,
+                        ttl_seconds,
+                        parquet_settings,
                         &context.trace_obj,
                     )
                     .await?;
@@ -736,6 +1226,98 @@ impl SqlService for SqlServiceImpl {
                     .await?;
                 Ok(Arc::new(DataFrame::from(vec![res])))
             }
+            CubeStoreStatement::AlterTable { name, operation } => {
+                if name.0.len() != 2 {
+                    return Err(CubeError::user(format!(
+                        "Schema's name should be present in table name but found: {}",
+                        name
+                    )));
+                }
+                let schema_name = name.0[0].value.to_string();
+                let table_name = name.0[1].value.to_string();
+                let res = match operation {
+                    AlterTableOperation::AddColumn {
+                        column_def,
+                        default,
+                    } => {
+                        let column = convert_columns_type(&vec![column_def])?.remove(0);
+                        let default_value = match default {
+                            Some(default) => match parse_default_value(&default, &column)? {
+                                TableValue::Null => None,
+                                v => Some(v),
+                            },
+                            None => None,
+                        };
+                        vec![
+                            self.db
+                                .add_column(
+                                    schema_name,
+                                    table_name,
+                                    column.with_default_value(default_value),
+                                )
+                                .await?,
+                        ]
+                    }
+                    AlterTableOperation::RenameColumn {
+                        old_column_name,
+                        new_column_name,
+                    } => {
+                        vec![
+                            self.db
+                                .rename_column(
+                                    schema_name,
+                                    table_name,
+                                    old_column_name.value,
+                                    new_column_name.value,
+                                )
+                                .await?,
+                        ]
+                    }
+                    AlterTableOperation::RenameTable {
+                        table_name: new_table_name,
+                    } => {
+                        vec![
+                            self.db
+                                .rename_table(schema_name, table_name, new_table_name.value)
+                                .await?,
+                        ]
+                    }
+                    AlterTableOperation::SwapTable {
+                        table_name: other_table_name,
+                    } => {
+                        self.db
+                            .swap_tables(schema_name, table_name, other_table_name.value)
+                            .await?
+                    }
+                };
+                Ok(Arc::new(DataFrame::from(res)))
+            }
+            CubeStoreStatement::RenameTables { renames } => {
+                let renames = renames
+                    .into_iter()
+                    .map(|(name, new_name)| match (&name.0[..], &new_name.0[..]) {
+                        ([schema, table], [new_schema, new_table])
+                            if schema.value == new_schema.value =>
+                        {
+                            Ok((
+                                schema.value.to_string(),
+                                table.value.to_string(),
+                                new_table.value.to_string(),
+                            ))
+                        }
+                        ([_, _], [_, _]) => Err(CubeError::user(format!(
+                            "Tables can't be moved between schemas: {} to {}",
+                            name, new_name
+                        ))),
+                        _ => Err(CubeError::user(format!(
+                            "Schema's name should be present in table names but found: {} to {}",
+                            name, new_name
+                        ))),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let res = self.db.rename_tables(renames).await?;
+                Ok(Arc::new(DataFrame::from(res)))
+            }
             CubeStoreStatement::CreateSource {
                 name,
                 source_type,
@@ -784,6 +1366,67 @@ impl SqlService for SqlServiceImpl {
                                 ))?,
                             })
                         }
+                        "kafka" => {
+                            let option = |name: &str| {
+                                credentials
+                                    .iter()
+                                    .find(|o| o.name.value == name)
+                                    .and_then(|x| {
+                                        if let Value::SingleQuotedString(v) = &x.value {
+                                            Some(v.to_string())
+                                        } else {
+                                            None
+                                        }
+                                    })
+                            };
+                            let use_ssl = match credentials
+                                .iter()
+                                .find(|o| o.name.value == "use_ssl")
+                                .map(|x| &x.value)
+                            {
+                                None => false,
+                                Some(Value::Boolean(v)) => *v,
+                                Some(x) => {
+                                    return Err(CubeError::user(format!(
+                                        "use_ssl should be boolean but {} found",
+                                        x
+                                    )))
+                                }
+                            };
+                            let value_format = match option("format").as_deref() {
+                                None | Some("json") => KafkaValueFormat::Json,
+                                Some("avro") => {
+                                    let schema = option("avro_schema");
+                                    let schema_registry_url = option("schema_registry_url");
+                                    if schema.is_none() && schema_registry_url.is_none() {
+                                        return Err(CubeError::user(
+                                            "avro_schema or schema_registry_url is required for avro format"
+                                                .to_string(),
+                                        ));
+                                    }
+                                    KafkaValueFormat::Avro {
+                                        schema,
+                                        schema_registry_url,
+                                    }
+                                }
+                                Some(x) => {
+                                    return Err(CubeError::user(format!(
+                                        "Not supported kafka value format: {}",
+                                        x
+                                    )))
+                                }
+                            };
+                            Ok(SourceCredentials::Kafka {
+                                user: option("user"),
+                                password: option("password"),
+                                host: option("host").ok_or(CubeError::user(
+                                    "host is required as credential for kafka source".to_string(),
+                                ))?,
+                                use_ssl,
+                                group_id: option("group_id"),
+                                value_format,
+                            })
+                        }
                         x => Err(CubeError::user(format!("Not supported stream type: {}", x))),
                     };
                     let source = self
@@ -849,15 +1492,6 @@ impl SqlService for SqlServiceImpl {
                 source,
                 ..
             }) => {
-                let data = if let SetExpr::Values(Values(data_series)) = &source.body {
-                    data_series
-                } else {
-                    return Err(CubeError::user(format!(
-                        "Data should be present in query. Your query was '{}'",
-                        query
-                    )));
-                };
-
                 let nv = &table_name.0;
                 if nv.len() != 2 {
                     return Err(CubeError::user(format!("Schema's name should be present in query (boo.table1). Your query was '{}'", query)));
@@ -865,7 +1499,25 @@ impl SqlService for SqlServiceImpl {
                 let schema_name = &nv[0].value;
                 let table_name = &nv[1].value;
 
-                self.insert_data(schema_name.clone(), table_name.clone(), &columns, data)
+                if let SetExpr::Values(Values(data)) = &source.body {
+                    self.insert_data(schema_name.clone(), table_name.clone(), &columns, data)
+                        .await?;
+                } else {
+                    self.insert_select(schema_name.clone(), table_name.clone(), &columns, source)
+                        .await?;
+                }
+                Ok(Arc::new(DataFrame::new(vec![], vec![])))
+            }
+            CubeStoreStatement::Statement(Statement::Delete {
+                table_name,
+                selection,
+                ..
+            }) => {
+                let nv = &table_name.0;
+                if nv.len() != 2 {
+                    return Err(CubeError::user(format!("Schema's name should be present in query (boo.table1). Your query was '{}'", query)));
+                }
+                self.delete_rows(nv[0].value.clone(), nv[1].value.clone(), selection)
                     .await?;
                 Ok(Arc::new(DataFrame::new(vec![], vec![])))
             }
@@ -884,6 +1536,7 @@ impl SqlService for SqlServiceImpl {
                         app_metrics::DATA_QUERIES.increment();
                         let cluster = self.cluster.clone();
                         let executor = self.query_executor.clone();
+                        let source_tables = source_tables(&serialized.schema());
                         timeout(
                             self.query_timeout,
                             self.cache
@@ -904,7 +1557,7 @@ impl SqlService for SqlServiceImpl {
                                     Ok(cube_ext::spawn_blocking(
                                         move || -> Result<DataFrame, CubeError> {
                                             let df = batch_to_dataframe(&records)?;
-                                            Ok(df)
+                                            Ok(df.with_source_tables(source_tables))
                                         },
                                     )
                                     .await??)
@@ -921,6 +1574,62 @@ impl SqlService for SqlServiceImpl {
         }
     }
 
+    async fn exec_query_stream_with_context(
+        &self,
+        context: SqlQueryContext,
+        query: &str,
+    ) -> Result<QueryResultStream, CubeError> {
+        if SqlServiceImpl::handle_workbench_queries(query).is_none() {
+            let ast = CubeStoreParser::new(query).and_then(|mut p| p.parse_statement());
+            if let Ok(CubeStoreStatement::Statement(Statement::Query(q))) = ast {
+                let logical_plan = self
+                    .query_planner
+                    .logical_plan(DFStatement::Statement(Statement::Query(q)))
+                    .await?;
+                if let QueryPlan::Select(plan, workers) = logical_plan {
+                    app_metrics::DATA_QUERIES.increment();
+                    // Same time limit as for selects in [exec_query_with_context], but for the
+                    // whole stream.
+                    let deadline = Instant::now() + self.query_timeout;
+                    let span = tracing::span!(
+                        tracing::Level::TRACE,
+                        "select_stream",
+                        trace_obj = ?context.trace_obj
+                    );
+                    let batches =
+                        timeout_at(deadline, self.select_stream(plan, workers).instrument(span))
+                            .await??;
+                    let schema = batches.schema();
+                    let batches =
+                        futures::stream::unfold(Some(batches), move |batches| async move {
+                            let mut batches = batches?;
+                            match timeout_at(deadline, batches.next()).await {
+                                Ok(Some(b)) => Some((b.map_err(CubeError::from), Some(batches))),
+                                Ok(None) => None,
+                                Err(e) => Some((Err(e.into()), None)),
+                            }
+                        });
+                    return Ok(QueryResultStream {
+                        schema,
+                        batches: Box::pin(batches),
+                    });
+                }
+            }
+        }
+        // Results of other statements are small, convert them in one go.
+        let data_frame = self.exec_query_with_context(context, query).await?;
+        let columns = data_frame.get_columns();
+        let schema = Arc::new(ArrowSchema::new(columns.iter().map(|c| c.into()).collect()));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            data::rows_to_columns(columns, data_frame.get_rows()),
+        )?;
+        Ok(QueryResultStream {
+            schema,
+            batches: Box::pin(futures::stream::once(async move { Ok(batch) })),
+        })
+    }
+
     async fn plan_query(&self, q: &str) -> Result<QueryPlans, CubeError> {
         let ast = {
             let replaced_quote = q.replace("\\'", "''");
@@ -1006,6 +1715,49 @@ impl SqlService for SqlServiceImpl {
     }
 }
 
+/// Columns of the table created by CREATE TABLE AS SELECT.
+fn columns_from_schema(schema: &arrow::datatypes::Schema) -> Result<Vec<Column>, CubeError> {
+    let mut columns: Vec<Column> = Vec::with_capacity(schema.fields().len());
+    for (i, field) in schema.fields().iter().enumerate() {
+        let column_type = match field.data_type() {
+            ArrowDataType::Int64 => ColumnType::Int,
+            ArrowDataType::Int32 => ColumnType::Int32,
+            ArrowDataType::Int16 => ColumnType::Int16,
+            ArrowDataType::Utf8 => ColumnType::String,
+            ArrowDataType::Binary => ColumnType::Bytes,
+            ArrowDataType::Float64 => ColumnType::Float,
+            ArrowDataType::Float32 => ColumnType::Float32,
+            ArrowDataType::Int64Decimal(scale) => ColumnType::Decimal {
+                scale: *scale as i32,
+                precision: 18,
+            },
+            ArrowDataType::Boolean => ColumnType::Boolean,
+            ArrowDataType::Timestamp(TimeUnit::Microsecond, None) => ColumnType::Timestamp,
+            ArrowDataType::Date32 => ColumnType::Date,
+            t => {
+                return Err(CubeError::user(format!(
+                    "Can't create column '{}' of type {:?}",
+                    field.name(),
+                    t
+                )))
+            }
+        };
+        // Plain column references can be qualified by the table name.
+        let name = match field.name().rfind('.') {
+            Some(i) if !field.name().contains('(') => field.name()[i + 1..].to_string(),
+            _ => field.name().to_string(),
+        };
+        if columns.iter().any(|c| c.get_name() == &name) {
+            return Err(CubeError::user(format!(
+                "Duplicate column '{}' in CREATE TABLE AS SELECT, use aliases to rename it",
+                name
+            )));
+        }
+        columns.push(Column::new(name, column_type, i));
+    }
+    Ok(columns)
+}
+
 fn convert_columns_type(columns: &Vec<ColumnDef>) -> Result<Vec<Column>, CubeError> {
     let mut rolupdb_columns = Vec::new();
 
@@ -1013,8 +1765,7 @@ fn convert_columns_type(columns: &Vec<ColumnDef>) -> Result<Vec<Column>, CubeErr
         let cube_col = Column::new(
             col.name.value.clone(),
             match &col.data_type {
-                DataType::Date
-                | DataType::Time
+                DataType::Time
                 | DataType::Char(_)
                 | DataType::Varchar(_)
                 | DataType::Clob(_)
@@ -1024,8 +1775,9 @@ fn convert_columns_type(columns: &Vec<ColumnDef>) -> Result<Vec<Column>, CubeErr
                 | DataType::Binary(_)
                 | DataType::Varbinary(_)
                 | DataType::Blob(_)
-                | DataType::Bytea
-                | DataType::Array(_) => ColumnType::Bytes,
+                | DataType::Bytea => ColumnType::Bytes,
+                // There are no array column types yet, values are kept as opaque bytes.
+                DataType::Array(_) => ColumnType::Bytes,
                 DataType::Decimal(precision, scale) => {
                     let mut precision = precision.unwrap_or(18);
                     let mut scale = scale.unwrap_or(5);
@@ -1043,16 +1795,21 @@ fn convert_columns_type(columns: &Vec<ColumnDef>) -> Result<Vec<Column>, CubeErr
                         scale: scale as i32,
                     }
                 }
-                DataType::SmallInt | DataType::Int | DataType::BigInt | DataType::Interval => {
-                    ColumnType::Int
-                }
+                DataType::Int | DataType::BigInt | DataType::Interval => ColumnType::Int,
+                DataType::SmallInt => ColumnType::Int16,
                 DataType::Boolean => ColumnType::Boolean,
-                DataType::Float(_) | DataType::Real | DataType::Double => ColumnType::Float,
+                DataType::Float(_) | DataType::Double => ColumnType::Float,
+                DataType::Real => ColumnType::Float32,
                 DataType::Timestamp => ColumnType::Timestamp,
+                DataType::Date => ColumnType::Date,
                 DataType::Custom(custom) => {
                     let custom_type_name = custom.to_string().to_lowercase();
                     match custom_type_name.as_str() {
+                        // Existing schemas rely on mediumint columns being 64-bit.
                         "mediumint" => ColumnType::Int,
+                        "int4" | "int32" => ColumnType::Int32,
+                        "int2" | "int16" => ColumnType::Int16,
+                        "float4" | "float32" => ColumnType::Float32,
                         "bytes" => ColumnType::Bytes,
                         "varbinary" => ColumnType::Bytes,
                         "hyperloglog" => ColumnType::HyperLogLog(HllFlavour::Airlift),
@@ -1111,6 +1868,238 @@ fn parse_chunk(chunk: &[Vec<Expr>], column: &Vec<&Column>) -> Result<Vec<ArrayRe
     Ok(arrays)
 }
 
+/// Converts results of INSERT ... SELECT query into arrays of inserted columns ordered by their
+/// index in the table, like [parse_chunk] does for VALUES.
+fn select_batches_to_chunk(
+    batches: &[RecordBatch],
+    columns: &[Column],
+) -> Result<Vec<ArrayRef>, CubeError> {
+    let mut builders = columns
+        .iter()
+        .map(|c| create_array_builder(c.get_column_type()))
+        .collect_vec();
+    for batch in batches {
+        for (i, column) in columns.iter().enumerate() {
+            let array = batch.column(i);
+            let source_scale = match array.data_type() {
+                ArrowDataType::Int64Decimal(scale) => *scale as i32,
+                ArrowDataType::Int64
+                | ArrowDataType::Int32
+                | ArrowDataType::Int16
+                | ArrowDataType::Utf8
+                | ArrowDataType::Binary
+                | ArrowDataType::Float64
+                | ArrowDataType::Float32
+                | ArrowDataType::Date32
+                | ArrowDataType::Timestamp(TimeUnit::Microsecond, None)
+                | ArrowDataType::Boolean
+                | ArrowDataType::Null => 0,
+                t => {
+                    return Err(CubeError::user(format!(
+                        "Can't insert values of type {:?} into column '{}'",
+                        t,
+                        column.get_name()
+                    )))
+                }
+            };
+            for row in 0..batch.num_rows() {
+                let value = if let ArrowDataType::Null = array.data_type() {
+                    TableValue::Null
+                } else {
+                    TableValue::from_array(array.as_ref(), row)
+                };
+                let value = convert_select_value(value, source_scale, column)?;
+                data::append_value(builders[i].as_mut(), column.get_column_type(), &value);
+            }
+        }
+    }
+    let mut order = (0..columns.len()).collect_vec();
+    order.sort_unstable_by_key(|i| columns[*i].get_index());
+    Ok(order.into_iter().map(|i| builders[i].finish()).collect())
+}
+
+/// Turns rows of [table] without the deleted column, ordered by column index, into tombstones
+/// that are merged after the rows they copy. Sequence values of tombstones are at least
+/// [min_seq] and always above the ones of the copied rows, which are selected after [min_seq] was
+/// computed and may be newer.
+fn to_tombstones(
+    table: &Table,
+    mut rows: Vec<ArrayRef>,
+    min_seq: i64,
+) -> Result<Vec<ArrayRef>, CubeError> {
+    let (seq_column, deleted_column) = match (table.seq_column(), table.deleted_column()) {
+        (Some(seq_column), Some(deleted_column)) => (seq_column, deleted_column),
+        _ => {
+            return Err(CubeError::internal(format!(
+                "Seq and deleted columns are expected for tombstones of table '{}'",
+                table.get_table_name()
+            )))
+        }
+    };
+    let num_rows = rows.first().map(|a| a.len()).unwrap_or(0);
+    rows.insert(
+        deleted_column.get_index(),
+        Arc::new(BooleanArray::from(vec![true; num_rows])),
+    );
+    let seq = rows[seq_column.get_index()]
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .iter()
+        .map(|s| Some(s.map_or(min_seq, |s| min_seq.max(s + 1))))
+        .collect::<Int64Array>();
+    rows[seq_column.get_index()] = Arc::new(seq);
+    Ok(rows)
+}
+
+/// [source_scale] is the scale of decimal values.
+fn convert_select_value(
+    value: TableValue,
+    source_scale: i32,
+    column: &Column,
+) -> Result<TableValue, CubeError> {
+    let err = |value: &TableValue| {
+        CubeError::user(format!(
+            "Can't insert {:?} into {} column '{}'",
+            value,
+            column.get_column_type(),
+            column.get_name()
+        ))
+    };
+    let pow10 = |scale: i32| 10_i64.pow(scale as u32);
+    Ok(match (column.get_column_type(), value) {
+        (_, TableValue::Null) => TableValue::Null,
+        (ColumnType::String, v @ TableValue::String(_)) => v,
+        (ColumnType::String, TableValue::Int(v)) => TableValue::String(v.to_string()),
+        (ColumnType::String, TableValue::Float(v)) => TableValue::String(v.0.to_string()),
+        (ColumnType::String, TableValue::Boolean(v)) => TableValue::String(v.to_string()),
+        (ColumnType::String, TableValue::Decimal(v)) => {
+            TableValue::String(v.to_string(source_scale as u8))
+        }
+        (ColumnType::String, TableValue::Timestamp(v)) => TableValue::String(v.to_string()),
+        (t @ ColumnType::Int, v) | (t @ ColumnType::Int16, v) | (t @ ColumnType::Int32, v) => {
+            // Values that lose their fractional part are rejected rather than truncated.
+            let i = match &v {
+                TableValue::Int(i) => Some(*i),
+                TableValue::Decimal(d) if d.raw_value() % pow10(source_scale) == 0 => {
+                    Some(d.raw_value() / pow10(source_scale))
+                }
+                TableValue::Float(f) if f.0.fract() == 0.0 => float_to_i64(f.0),
+                _ => None,
+            }
+            .ok_or_else(|| err(&v))?;
+            let fits = match t {
+                ColumnType::Int16 => i16::try_from(i).is_ok(),
+                ColumnType::Int32 => i32::try_from(i).is_ok(),
+                _ => true,
+            };
+            if !fits {
+                return Err(err(&v));
+            }
+            TableValue::Int(i)
+        }
+        (t @ ColumnType::Decimal { .. }, v) => {
+            let target_scale = t.target_scale();
+            let raw = match &v {
+                TableValue::Decimal(d) if target_scale >= source_scale => d
+                    .raw_value()
+                    .checked_mul(pow10(target_scale - source_scale)),
+                // Narrowing the scale is only allowed when the dropped digits are zeros.
+                TableValue::Decimal(d)
+                    if d.raw_value() % pow10(source_scale - target_scale) == 0 =>
+                {
+                    Some(d.raw_value() / pow10(source_scale - target_scale))
+                }
+                TableValue::Int(i) => i.checked_mul(pow10(target_scale)),
+                TableValue::Float(f) => float_to_i64((f.0 * pow10(target_scale) as f64).round()),
+                _ => None,
+            };
+            TableValue::Decimal(Decimal::new(raw.ok_or_else(|| err(&v))?))
+        }
+        (ColumnType::Float, v) | (ColumnType::Float32, v) => match v {
+            v @ TableValue::Float(_) => v,
+            TableValue::Int(i) => TableValue::Float((i as f64).into()),
+            TableValue::Decimal(d) => {
+                TableValue::Float((d.raw_value() as f64 / pow10(source_scale) as f64).into())
+            }
+            v => return Err(err(&v)),
+        },
+        (ColumnType::Boolean, v @ TableValue::Boolean(_)) => v,
+        (ColumnType::Timestamp, v @ TableValue::Timestamp(_)) => v,
+        (ColumnType::Date, TableValue::Timestamp(v)) => {
+            TableValue::Timestamp(TimestampValue::from_days(v.days()))
+        }
+        (ColumnType::Timestamp, TableValue::String(v)) => {
+            TableValue::Timestamp(timestamp_from_string(&v)?)
+        }
+        (ColumnType::Date, TableValue::String(v)) => TableValue::Timestamp(date_from_string(&v)?),
+        (ColumnType::Bytes, v @ TableValue::Bytes(_)) => v,
+        (ColumnType::HyperLogLog(f), TableValue::Bytes(v)) => {
+            // Snowflake and Postgres sketches are stored in the Airlift format.
+            let storage = match f {
+                HllFlavour::ZetaSketch => HllFlavour::ZetaSketch,
+                _ => HllFlavour::Airlift,
+            };
+            is_valid_plain_binary_hll(&v, storage)?;
+            TableValue::Bytes(v)
+        }
+        (_, v) => return Err(err(&v)),
+    })
+}
+
+/// Fills [template], a query of a single table, with [table], [projection] and [selection].
+/// Expressions are put into the AST as is, they're never formatted and parsed again.
+fn select_query(
+    template: &str,
+    table: ObjectName,
+    projection: Vec<SelectItem>,
+    selection: Option<Expr>,
+) -> Result<Box<Query>, CubeError> {
+    let mut query = match CubeStoreParser::new(template)?.parse_statement()? {
+        CubeStoreStatement::Statement(Statement::Query(q)) => q,
+        s => {
+            return Err(CubeError::internal(format!(
+                "Unexpected statement for query template: {:?}",
+                s
+            )))
+        }
+    };
+    let select = match &mut query.body {
+        SetExpr::Select(select) => select,
+        b => {
+            return Err(CubeError::internal(format!(
+                "Unexpected query template body: {:?}",
+                b
+            )))
+        }
+    };
+    match select.from.as_mut_slice() {
+        [TableWithJoins {
+            relation: TableFactor::Table { name, .. },
+            ..
+        }] => *name = table,
+        f => {
+            return Err(CubeError::internal(format!(
+                "Unexpected query template tables: {:?}",
+                f
+            )))
+        }
+    }
+    select.projection = projection;
+    select.selection = selection;
+    Ok(query)
+}
+
+/// Unlike `as`, doesn't saturate values out of the `i64` range.
+fn float_to_i64(f: f64) -> Option<i64> {
+    // `i64::MAX as f64` is rounded up to 2^63, so it's out of range too.
+    if f.is_finite() && i64::MIN as f64 <= f && f < i64::MAX as f64 {
+        Some(f as i64)
+    } else {
+        None
+    }
+}
+
 fn decode_byte(s: &str) -> Option<u8> {
     let v = s.as_bytes();
     if v.len() != 2 {
@@ -1226,29 +2215,29 @@ fn extract_data<'a>(
                 builder.append_null()?;
                 return Ok(());
             }
-            let val_int = match cell {
-                Expr::Value(Value::Number(v, _)) | Expr::Value(Value::SingleQuotedString(v)) => {
-                    v.parse::<i64>()
-                }
-                Expr::UnaryOp {
-                    op: UnaryOperator::Minus,
-                    expr,
-                } => {
-                    if let Expr::Value(Value::Number(v, _)) = expr.as_ref() {
-                        v.parse::<i64>().map(|v| v * -1)
-                    } else {
-                        return Err(CubeError::user(format!("Can't parse int from, {:?}", cell)));
-                    }
-                }
-                _ => return Err(CubeError::user(format!("Can't parse int from, {:?}", cell))),
-            };
-            if let Err(e) = val_int {
-                return Err(CubeError::user(format!(
-                    "Can't parse int from, {:?}: {}",
-                    cell, e
-                )));
+            builder.append_value(parse_int(cell)?)?;
+        }
+        ColumnType::Int16 => {
+            let builder = builder.as_any_mut().downcast_mut::<Int16Builder>().unwrap();
+            if is_null {
+                builder.append_null()?;
+                return Ok(());
             }
-            builder.append_value(val_int.unwrap())?;
+            let v = i16::try_from(parse_int(cell)?).map_err(|e| {
+                CubeError::user(format!("Can't parse smallint from, {:?}: {}", cell, e))
+            })?;
+            builder.append_value(v)?;
+        }
+        ColumnType::Int32 => {
+            let builder = builder.as_any_mut().downcast_mut::<Int32Builder>().unwrap();
+            if is_null {
+                builder.append_null()?;
+                return Ok(());
+            }
+            let v = i32::try_from(parse_int(cell)?).map_err(|e| {
+                CubeError::user(format!("Can't parse int32 from, {:?}: {}", cell, e))
+            })?;
+            builder.append_value(v)?;
         }
         t @ ColumnType::Decimal { .. } => {
             let scale = u8::try_from(t.target_scale()).unwrap();
@@ -1388,10 +2377,95 @@ fn extract_data<'a>(
             let v = parse_float(cell)?;
             builder.append_value(v)?;
         }
+        ColumnType::Float32 => {
+            let builder = builder
+                .as_any_mut()
+                .downcast_mut::<Float32Builder>()
+                .unwrap();
+            if is_null {
+                builder.append_null()?;
+                return Ok(());
+            }
+            let v = parse_float(cell)?;
+            builder.append_value(v as f32)?;
+        }
+        ColumnType::Date => {
+            let builder = builder
+                .as_any_mut()
+                .downcast_mut::<Date32Builder>()
+                .unwrap();
+            if is_null {
+                builder.append_null()?;
+                return Ok(());
+            }
+            match cell {
+                Expr::Value(Value::SingleQuotedString(v)) => {
+                    builder.append_value(date_from_string(v)?.days())?;
+                }
+                x => return Err(CubeError::user(format!("Can't parse date from, {:?}", x))),
+            }
+        }
     }
     Ok(())
 }
 
+fn parse_default_value(cell: &Expr, column: &Column) -> Result<TableValue, CubeError> {
+    let mut buffer = Vec::new();
+    let mut builder = create_array_builder(column.get_column_type());
+    extract_data(cell, column, &mut buffer, builder.as_mut())?;
+    Ok(TableValue::from_array(builder.finish().as_ref(), 0))
+}
+
+/// Accepts a number of seconds or an interval like `'7 days'`.
+fn parse_ttl(value: &Value) -> Result<u64, CubeError> {
+    let bad_ttl = || CubeError::user(format!("Bad ttl {}", value));
+    let ttl = match value {
+        Value::Number(v, _) => v.parse::<u64>().map_err(|_| bad_ttl())?,
+        Value::SingleQuotedString(v) => {
+            let parts = v.split_whitespace().collect_vec();
+            let (amount, unit) = match parts.as_slice() {
+                [amount] => (*amount, "seconds"),
+                [amount, unit] => (*amount, *unit),
+                _ => return Err(bad_ttl()),
+            };
+            let amount = amount.parse::<u64>().map_err(|_| bad_ttl())?;
+            let multiplier = match unit.to_lowercase().trim_end_matches('s') {
+                "second" => 1,
+                "minute" => 60,
+                "hour" => 60 * 60,
+                "day" => 24 * 60 * 60,
+                "week" => 7 * 24 * 60 * 60,
+                _ => return Err(bad_ttl()),
+            };
+            amount.checked_mul(multiplier).ok_or_else(bad_ttl)?
+        }
+        _ => return Err(bad_ttl()),
+    };
+    if ttl == 0 {
+        return Err(bad_ttl());
+    }
+    Ok(ttl)
+}
+
+/// Reads `parquet_compression`, `parquet_dictionary` and `parquet_statistics` options.
+fn parse_parquet_settings(with_options: &[SqlOption]) -> Result<ParquetSettings, CubeError> {
+    let mut settings = ParquetSettings::default();
+    for option in with_options {
+        match (option.name.value.as_str(), &option.value) {
+            ("parquet_compression", Value::SingleQuotedString(v)) => {
+                settings.compression = Some(v.parse()?)
+            }
+            ("parquet_dictionary", Value::Boolean(v)) => settings.dictionary_enabled = Some(*v),
+            ("parquet_statistics", Value::Boolean(v)) => settings.statistics_enabled = Some(*v),
+            ("parquet_compression" | "parquet_dictionary" | "parquet_statistics", v) => {
+                return Err(CubeError::user(format!("Bad {} {}", option.name.value, v)))
+            }
+            _ => {}
+        }
+    }
+    Ok(settings)
+}
+
 pub fn timestamp_from_string(v: &str) -> Result<TimestampValue, CubeError> {
     let nanos;
     if v.ends_with("UTC") {
@@ -1411,12 +2485,45 @@ pub fn timestamp_from_string(v: &str) -> Result<TimestampValue, CubeError> {
     Ok(TimestampValue::new(nanos))
 }
 
+/// Accepts plain `YYYY-MM-DD` dates as well as any timestamp [timestamp_from_string] understands,
+/// the time part of the latter is truncated.
+pub fn date_from_string(v: &str) -> Result<TimestampValue, CubeError> {
+    if let Ok(d) = NaiveDate::parse_from_str(v, "%Y-%m-%d") {
+        let days = d.signed_duration_since(NaiveDate::from_ymd(1970, 1, 1));
+        return Ok(TimestampValue::from_days(days.num_days() as i32));
+    }
+    match timestamp_from_string(v) {
+        Ok(ts) => Ok(TimestampValue::from_days(ts.days())),
+        Err(_) => Err(CubeError::user(format!("Can't parse date: {}", v))),
+    }
+}
+
 fn parse_time(s: &str, format: &[chrono::format::Item]) -> ParseResult<Parsed> {
     let mut p = Parsed::new();
     chrono::format::parse(&mut p, s, format.into_iter())?;
     Ok(p)
 }
 
+fn parse_int(cell: &Expr) -> Result<i64, CubeError> {
+    let val_int = match cell {
+        Expr::Value(Value::Number(v, _)) | Expr::Value(Value::SingleQuotedString(v)) => {
+            v.parse::<i64>()
+        }
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => {
+            if let Expr::Value(Value::Number(v, _)) = expr.as_ref() {
+                v.parse::<i64>().map(|v| v * -1)
+            } else {
+                return Err(CubeError::user(format!("Can't parse int from, {:?}", cell)));
+            }
+        }
+        _ => return Err(CubeError::user(format!("Can't parse int from, {:?}", cell))),
+    };
+    val_int.map_err(|e| CubeError::user(format!("Can't parse int from, {:?}: {}", cell, e)))
+}
+
 fn parse_float(cell: &Expr) -> Result<f64, CubeError> {
     match cell {
         Expr::Value(Value::Number(v, _)) | Expr::Value(Value::SingleQuotedString(v)) => {
@@ -1478,10 +2585,67 @@ mod tests {
     use super::*;
     use crate::queryplanner::pretty_printers::pp_phys_plan;
     use crate::remotefs::queue::QueueRemoteFs;
-    use crate::scheduler::SchedulerImpl;
     use crate::table::data::{cmp_min_rows, cmp_row_key_heap};
     use regex::Regex;
 
+    fn test_scheduler(
+        meta_store: &Arc<RocksMetaStore>,
+        remote_fs: &Arc<LocalDirRemoteFs>,
+        config: &Config,
+    ) -> Arc<SchedulerImpl> {
+        Arc::new(SchedulerImpl::new(
+            meta_store.clone(),
+            Arc::new(MockCluster::new()),
+            remote_fs.clone(),
+            tokio::sync::broadcast::channel(1).1,
+            config.config_obj(),
+        ))
+    }
+
+    #[test]
+    fn convert_select_values() {
+        let decimal = Column::new(
+            "d".to_string(),
+            ColumnType::Decimal {
+                scale: 5,
+                precision: 18,
+            },
+            0,
+        );
+        assert_eq!(
+            convert_select_value(
+                TableValue::Decimal(Decimal::new(15_000_000_000)),
+                10,
+                &decimal
+            )
+            .unwrap(),
+            TableValue::Decimal(Decimal::new(150_000))
+        );
+        assert!(convert_select_value(
+            TableValue::Decimal(Decimal::new(15_000_000_001)),
+            10,
+            &decimal
+        )
+        .is_err());
+
+        let date = Column::new("date".to_string(), ColumnType::Date, 0);
+        let noon =
+            TimestampValue::new(TimestampValue::from_days(3).get_time_stamp() + 43_200_000_000_000);
+        assert_eq!(
+            convert_select_value(TableValue::Timestamp(noon), 0, &date).unwrap(),
+            TableValue::Timestamp(TimestampValue::from_days(3))
+        );
+
+        for f in [
+            HllFlavour::Airlift,
+            HllFlavour::Postgres,
+            HllFlavour::ZetaSketch,
+        ] {
+            let hll = Column::new("hll".to_string(), ColumnType::HyperLogLog(f), 0);
+            assert!(convert_select_value(TableValue::Bytes(vec![1, 2, 3]), 0, &hll).is_err());
+        }
+    }
+
     #[tokio::test]
     async fn create_schema_test() {
         let config = Config::test("create_schema_test");
@@ -1497,7 +2661,8 @@ mod tests {
                 Some(PathBuf::from(remote_store_path.clone())),
                 PathBuf::from(store_path.clone()),
             );
-            let meta_store = RocksMetaStore::new(path, remote_fs.clone(), config.config_obj());
+            let meta_store =
+                RocksMetaStore::new(path, remote_fs.clone(), config.config_obj()).unwrap();
             let rows_per_chunk = 10;
             let query_timeout = Duration::from_secs(30);
             let store = ChunkStore::new(
@@ -1509,7 +2674,7 @@ mod tests {
             );
             let limits = Arc::new(ConcurrencyLimits::new(4));
             let service = SqlServiceImpl::new(
-                meta_store,
+                meta_store.clone(),
                 store,
                 limits,
                 Arc::new(MockQueryPlanner::new()),
@@ -1518,6 +2683,7 @@ mod tests {
                 Arc::new(MockImportService::new()),
                 config.config_obj(),
                 remote_fs.clone(),
+                test_scheduler(&meta_store, &remote_fs, &config),
                 rows_per_chunk,
                 query_timeout,
                 query_timeout,
@@ -1551,7 +2717,8 @@ mod tests {
                 Some(PathBuf::from(remote_store_path.clone())),
                 PathBuf::from(store_path.clone()),
             );
-            let meta_store = RocksMetaStore::new(path, remote_fs.clone(), config.config_obj());
+            let meta_store =
+                RocksMetaStore::new(path, remote_fs.clone(), config.config_obj()).unwrap();
             let rows_per_chunk = 10;
             let query_timeout = Duration::from_secs(30);
             let chunk_store = ChunkStore::new(
@@ -1572,6 +2739,7 @@ mod tests {
                 Arc::new(MockImportService::new()),
                 config.config_obj(),
                 remote_fs.clone(),
+                test_scheduler(&meta_store, &remote_fs, &config),
                 rows_per_chunk,
                 query_timeout,
                 query_timeout,
@@ -1607,6 +2775,7 @@ mod tests {
                 TableValue::String("NULL".to_string()),
                 TableValue::String("NULL".to_string()),
                 TableValue::String("NULL".to_string()),
+                TableValue::String("NULL".to_string()),
             ]));
         }
         let _ = DB::destroy(&Options::default(), path);
@@ -2032,6 +3201,188 @@ mod tests {
             .await;
     }
 
+    #[tokio::test]
+    async fn cleanup_remote_files() {
+        let config = Config::test("cleanup_remote_files").update_config(|mut c| {
+            c.remote_files_cleanup_delay = 0;
+            c
+        });
+        let remote_dir = config.remote_dir().clone();
+        config
+            .start_test(async move |services| {
+                let service = services.sql_service;
+                service.exec_query("CREATE SCHEMA foo").await.unwrap();
+                service
+                    .exec_query("CREATE TABLE foo.numbers (num int)")
+                    .await
+                    .unwrap();
+                service
+                    .exec_query("INSERT INTO foo.numbers (num) VALUES (1), (2)")
+                    .await
+                    .unwrap();
+
+                let orphaned = vec!["1000000-abcdefgh.parquet", "1000001.chunk.parquet"];
+                for f in orphaned.iter().chain(["notes.txt"].iter()) {
+                    std::fs::write(remote_dir.join(f), "data").unwrap();
+                }
+                Delay::new(Duration::from_millis(10)).await;
+
+                let to_paths = |r: Arc<DataFrame>| {
+                    let mut paths = r
+                        .get_rows()
+                        .iter()
+                        .map(|r| match &r.values()[0] {
+                            TableValue::String(s) => s.clone(),
+                            v => panic!("unexpected value: {:?}", v),
+                        })
+                        .collect::<Vec<_>>();
+                    paths.sort();
+                    paths
+                };
+
+                let r = service
+                    .exec_query("SYS CLEANUP REMOTE FILES DRY RUN")
+                    .await
+                    .unwrap();
+                assert_eq!(to_paths(r), orphaned);
+                assert!(remote_dir.join(orphaned[0]).exists());
+
+                let r = service
+                    .exec_query("SYS CLEANUP REMOTE FILES")
+                    .await
+                    .unwrap();
+                assert_eq!(to_paths(r), orphaned);
+                assert!(!remote_dir.join(orphaned[0]).exists());
+                assert!(!remote_dir.join(orphaned[1]).exists());
+                assert!(remote_dir.join("notes.txt").exists());
+                std::fs::remove_file(remote_dir.join("notes.txt")).unwrap();
+
+                let r = service
+                    .exec_query("SELECT count(*) FROM foo.numbers")
+                    .await
+                    .unwrap();
+                assert_eq!(r.get_rows(), &vec![Row::new(vec![TableValue::Int(2)])]);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn cleanup_remote_files_grace_period() {
+        let config = Config::test("cleanup_remote_files_grace_period").update_config(|mut c| {
+            c.remote_files_cleanup_delay = 2;
+            c
+        });
+        let remote_dir = config.remote_dir().clone();
+        config
+            .start_test(async move |services| {
+                let service = services.sql_service;
+                let orphaned = "1000000-abcdefgh.parquet";
+                std::fs::write(remote_dir.join(orphaned), "data").unwrap();
+
+                let r = service
+                    .exec_query("SYS CLEANUP REMOTE FILES")
+                    .await
+                    .unwrap();
+                assert!(r.get_rows().is_empty());
+                assert!(remote_dir.join(orphaned).exists());
+
+                Delay::new(Duration::from_millis(3000)).await;
+                let r = service
+                    .exec_query("SYS CLEANUP REMOTE FILES")
+                    .await
+                    .unwrap();
+                assert_eq!(r.get_rows().len(), 1);
+                assert_eq!(
+                    r.get_rows()[0].values()[0],
+                    TableValue::String(orphaned.to_string())
+                );
+                assert!(!remote_dir.join(orphaned).exists());
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn cleanup_remote_files_pending_removal() {
+        Config::test("cleanup_remote_files_pending_removal")
+            .update_config(|mut c| {
+                c.remote_files_cleanup_delay = 0;
+                c
+            })
+            .start_test(async move |services| {
+                let service = services.sql_service;
+                service.exec_query("CREATE SCHEMA foo").await.unwrap();
+                service
+                    .exec_query("CREATE TABLE foo.numbers (num int)")
+                    .await
+                    .unwrap();
+                service
+                    .exec_query("INSERT INTO foo.numbers (num) VALUES (1), (2)")
+                    .await
+                    .unwrap();
+
+                let remote_fs = services.remote_fs.clone();
+                let data_files = || {
+                    let remote_fs = remote_fs.clone();
+                    async move {
+                        let mut files = remote_fs
+                            .list("")
+                            .await
+                            .unwrap()
+                            .into_iter()
+                            .filter(|f| f.ends_with(".parquet"))
+                            .collect::<Vec<_>>();
+                        files.sort();
+                        files
+                    }
+                };
+                let files = data_files().await;
+                assert!(!files.is_empty());
+
+                service.exec_query("DROP TABLE foo.numbers").await.unwrap();
+                // TODO API to wait for all events processed
+                Delay::new(Duration::from_millis(500)).await;
+
+                // Files of the dropped table are left to the GC that already scheduled them.
+                let r = service
+                    .exec_query("SYS CLEANUP REMOTE FILES")
+                    .await
+                    .unwrap();
+                assert!(r.get_rows().is_empty());
+                assert_eq!(data_files().await, files);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn drop_expired_tables() {
+        Config::test("drop_expired_tables")
+            .start_test(async move |services| {
+                let service = services.sql_service;
+                service.exec_query("CREATE SCHEMA foo").await.unwrap();
+                service
+                    .exec_query("CREATE TABLE foo.expiring (num int) WITH (ttl = 1)")
+                    .await
+                    .unwrap();
+                service
+                    .exec_query("CREATE TABLE foo.kept (num int) WITH (ttl = 3600)")
+                    .await
+                    .unwrap();
+                service
+                    .exec_query("INSERT INTO foo.expiring (num) VALUES (1)")
+                    .await
+                    .unwrap();
+
+                Delay::new(Duration::from_millis(1100)).await;
+                services.scheduler.reconcile().await.unwrap();
+                service
+                    .exec_query("SELECT * FROM foo.expiring")
+                    .await
+                    .unwrap_err();
+                service.exec_query("SELECT * FROM foo.kept").await.unwrap();
+            })
+            .await;
+    }
+
     #[tokio::test]
     async fn delete_middle_main() {
         Config::test("delete_middle_main")
@@ -2297,6 +3648,7 @@ mod tests {
                 c.compaction_chunks_count_threshold = 0;
                 c.not_used_timeout = 0;
                 c.meta_store_log_upload_interval = 1;
+                c.meta_store_checkpoint_retention = 0;
                 c.gc_loop_interval = 1;
                 c
             })
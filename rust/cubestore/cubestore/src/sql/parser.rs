@@ -1,5 +1,7 @@
+use hex::ToHex;
 use sqlparser::ast::{
-    HiveDistributionStyle, Ident, ObjectName, Query, SqlOption, Statement as SQLStatement, Value,
+    ColumnDef, Expr, HiveDistributionStyle, Ident, ObjectName, Query, SqlOption,
+    Statement as SQLStatement, Value,
 };
 use sqlparser::dialect::keywords::Keyword;
 use sqlparser::dialect::Dialect;
@@ -53,21 +55,121 @@ pub enum Statement {
         credentials: Vec<SqlOption>,
         or_update: bool,
     },
+    AlterTable {
+        name: ObjectName,
+        operation: AlterTableOperation,
+    },
+    RenameTables {
+        renames: Vec<(ObjectName, ObjectName)>,
+    },
     System(SystemCommand),
     Dump(Box<Query>),
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlterTableOperation {
+    AddColumn {
+        column_def: ColumnDef,
+        default: Option<Expr>,
+    },
+    RenameColumn {
+        old_column_name: Ident,
+        new_column_name: Ident,
+    },
+    RenameTable {
+        table_name: Ident,
+    },
+    SwapTable {
+        table_name: Ident,
+    },
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum SystemCommand {
     KillAllJobs,
     Repartition { partition_id: u64 },
     PanicWorker,
+    MetaStoreCheckpoints,
+    CleanupRemoteFiles { dry_run: bool },
 }
 
 pub struct CubeStoreParser<'a> {
     parser: Parser<'a>,
 }
 
+/// Value bound to a `?` placeholder of a [PreparedStatement].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaceholderValue {
+    Null,
+    Number(String),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+/// Statement with `?` placeholders that is executed with different parameter values.
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    tokens: Vec<Token>,
+    num_params: usize,
+}
+
+impl PreparedStatement {
+    /// Fails if [sql] is not a valid statement when NULLs are put in place of placeholders.
+    pub fn new(sql: &str) -> Result<Self, ParserError> {
+        let tokens = Tokenizer::new(&MySqlDialectWithBackTicks {}, sql).tokenize()?;
+        let num_params = tokens.iter().filter(|t| is_placeholder(t)).count();
+        let statement = PreparedStatement { tokens, num_params };
+        let nulls = vec![PlaceholderValue::Null; num_params];
+        CubeStoreParser::new(&statement.bind(&nulls)?)?.parse_statement()?;
+        Ok(statement)
+    }
+
+    pub fn num_params(&self) -> usize {
+        self.num_params
+    }
+
+    /// SQL of the statement with literals of [params] in place of placeholders.
+    pub fn bind(&self, params: &[PlaceholderValue]) -> Result<String, ParserError> {
+        if params.len() != self.num_params {
+            return Err(ParserError::ParserError(format!(
+                "Expected {} parameters but {} are provided",
+                self.num_params,
+                params.len()
+            )));
+        }
+        let mut params = params.iter();
+        let mut sql = String::new();
+        for t in &self.tokens {
+            if is_placeholder(t) {
+                match params.next().unwrap() {
+                    PlaceholderValue::Null => sql += "NULL",
+                    // Otherwise `-` before the placeholder would start a comment.
+                    PlaceholderValue::Number(n) if n.starts_with('-') => sql += &format!("({})", n),
+                    PlaceholderValue::Number(n) => sql += n,
+                    PlaceholderValue::String(s) => sql += &quote_string(s),
+                    PlaceholderValue::Bytes(b) => {
+                        sql += &format!("X'{}'", b.encode_hex_upper::<String>())
+                    }
+                }
+            } else if let Token::SingleQuotedString(s) = t {
+                // Tokenizer unescapes quotes, so they must be escaped again.
+                sql += &quote_string(s)
+            } else {
+                sql += &t.to_string()
+            }
+        }
+        Ok(sql)
+    }
+}
+
+fn is_placeholder(t: &Token) -> bool {
+    t == &Token::Char('?')
+}
+
+fn quote_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
 impl<'a> CubeStoreParser<'a> {
     pub fn new(sql: &str) -> Result<Self, ParserError> {
         let dialect = &MySqlDialectWithBackTicks {};
@@ -89,6 +191,21 @@ impl<'a> CubeStoreParser<'a> {
                     self.parser.next_token();
                     self.parse_create()
                 }
+                Keyword::ALTER => {
+                    self.parser.next_token();
+                    self.parser.expect_keyword(Keyword::TABLE)?;
+                    self.parse_alter_table()
+                }
+                Keyword::RENAME => {
+                    self.parser.next_token();
+                    self.parser.expect_keyword(Keyword::TABLE)?;
+                    let renames = self.parser.parse_comma_separated(|p| {
+                        let name = p.parse_object_name()?;
+                        p.expect_keyword(Keyword::TO)?;
+                        Ok((name, p.parse_object_name()?))
+                    })?;
+                    Ok(Statement::RenameTables { renames })
+                }
                 _ if w.value.eq_ignore_ascii_case("dump") => {
                     self.parser.next_token();
                     let s = self.parser.parse_statement()?;
@@ -122,6 +239,59 @@ impl<'a> CubeStoreParser<'a> {
         }
     }
 
+    /// `ADD INDEX` is the same as `CREATE INDEX` and is returned as such.
+    fn parse_alter_table(&mut self) -> Result<Statement, ParserError> {
+        let name = self.parser.parse_object_name()?;
+        if self.parser.parse_keywords(&[Keyword::ADD, Keyword::INDEX]) {
+            return Ok(Statement::Statement(self.parse_with_index(name)?));
+        }
+        let operation = if self.parser.parse_keyword(Keyword::ADD) {
+            let _ = self.parser.parse_keyword(Keyword::COLUMN);
+            let column_name = self.parser.parse_identifier()?;
+            let data_type = self.parser.parse_data_type()?;
+            let default = if self.parser.parse_keyword(Keyword::DEFAULT) {
+                Some(self.parser.parse_expr()?)
+            } else {
+                None
+            };
+            AlterTableOperation::AddColumn {
+                column_def: ColumnDef {
+                    name: column_name,
+                    data_type,
+                    collation: None,
+                    options: Vec::new(),
+                },
+                default,
+            }
+        } else if self.parser.parse_keyword(Keyword::RENAME) {
+            if self.parser.parse_keyword(Keyword::TO) {
+                AlterTableOperation::RenameTable {
+                    table_name: self.parser.parse_identifier()?,
+                }
+            } else {
+                let _ = self.parser.parse_keyword(Keyword::COLUMN);
+                let old_column_name = self.parser.parse_identifier()?;
+                self.parser.expect_keyword(Keyword::TO)?;
+                let new_column_name = self.parser.parse_identifier()?;
+                AlterTableOperation::RenameColumn {
+                    old_column_name,
+                    new_column_name,
+                }
+            }
+        } else if self.parse_custom_token("swap") {
+            self.parser.expect_keyword(Keyword::WITH)?;
+            AlterTableOperation::SwapTable {
+                table_name: self.parser.parse_identifier()?,
+            }
+        } else {
+            return Err(ParserError::ParserError(format!(
+                "Expected ADD, RENAME or SWAP after ALTER TABLE, found: {}",
+                self.parser.peek_token()
+            )));
+        };
+        Ok(Statement::AlterTable { name, operation })
+    }
+
     fn parse_system(&mut self) -> Result<Statement, ParserError> {
         if self.parse_custom_token("kill")
             && self.parser.parse_keywords(&[Keyword::ALL])
@@ -142,6 +312,16 @@ impl<'a> CubeStoreParser<'a> {
             }
         } else if self.parse_custom_token("panic") && self.parse_custom_token("worker") {
             Ok(Statement::System(SystemCommand::PanicWorker))
+        } else if self.parse_custom_token("metastore") && self.parse_custom_token("checkpoints") {
+            Ok(Statement::System(SystemCommand::MetaStoreCheckpoints))
+        } else if self.parse_custom_token("cleanup")
+            && self.parse_custom_token("remote")
+            && self.parse_custom_token("files")
+        {
+            let dry_run = self.parse_custom_token("dry") && self.parse_custom_token("run");
+            Ok(Statement::System(SystemCommand::CleanupRemoteFiles {
+                dry_run,
+            }))
         } else {
             Err(ParserError::ParserError(
                 "Unknown system command".to_string(),
@@ -224,6 +404,12 @@ impl<'a> CubeStoreParser<'a> {
                 None
             };
 
+            let query = if query.is_none() && self.parser.parse_keyword(Keyword::AS) {
+                Some(Box::new(self.parser.parse_query()?))
+            } else {
+                query
+            };
+
             Ok(Statement::CreateTable {
                 create_table: SQLStatement::CreateTable {
                     or_replace,
@@ -297,3 +483,46 @@ impl<'a> CubeStoreParser<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_prepared_statement() {
+        let s = PreparedStatement::new(
+            "SELECT a, '?', 'it''s' FROM s.t WHERE a = ? AND b IN (?, ?) AND `?` = ?",
+        )
+        .unwrap();
+        assert_eq!(s.num_params(), 4);
+        assert_eq!(
+            s.bind(&[
+                PlaceholderValue::Number("-1".to_string()),
+                PlaceholderValue::String("x'y".to_string()),
+                PlaceholderValue::Null,
+                PlaceholderValue::Number("2.5".to_string()),
+            ])
+            .unwrap(),
+            "SELECT a, '?', 'it''s' FROM s.t WHERE a = (-1) AND b IN ('x''y', NULL) AND `?` = 2.5"
+        );
+        assert!(s.bind(&[PlaceholderValue::Null]).is_err());
+
+        let s = PreparedStatement::new("SELECT 1-?, ? FROM s.t").unwrap();
+        let sql = s
+            .bind(&[
+                PlaceholderValue::Number("-1".to_string()),
+                PlaceholderValue::Bytes(vec![0xff, 0x00, b'\'']),
+            ])
+            .unwrap();
+        assert_eq!(sql, "SELECT 1-(-1), X'FF0027' FROM s.t");
+        CubeStoreParser::new(&sql)
+            .unwrap()
+            .parse_statement()
+            .unwrap();
+    }
+
+    #[test]
+    fn prepare_invalid_statement() {
+        assert!(PreparedStatement::new("SELECT FROM WHERE ?").is_err());
+    }
+}
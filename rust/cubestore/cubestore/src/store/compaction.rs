@@ -2,13 +2,14 @@ use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::metastore::multi_index::MultiPartition;
 use crate::metastore::partition::partition_file_name;
+use crate::metastore::table::ParquetSettings;
 use crate::metastore::{
-    deactivate_table_on_corrupt_data, Chunk, IdRow, MetaStore, Partition, PartitionData,
+    deactivate_table_on_corrupt_data, Chunk, IdRow, Index, MetaStore, Partition, PartitionData,
 };
-use crate::remotefs::{ensure_temp_file_is_dropped, RemoteFs};
+use crate::remotefs::{ensure_temp_file_is_dropped, PinnedLocalFiles, RemoteFs};
 use crate::store::{ChunkDataStore, ChunkStore, ROW_GROUP_SIZE};
 use crate::table::data::{cmp_min_rows, cmp_partition_key};
-use crate::table::parquet::{arrow_schema, ParquetTableStore};
+use crate::table::parquet::{arrow_schema, read_index_file, ParquetTableStore};
 use crate::table::redistribute::redistribute;
 use crate::table::{Row, TableValue};
 use crate::CubeError;
@@ -19,7 +20,8 @@ use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use datafusion::cube_ext;
 use datafusion::physical_plan::empty::EmptyExec;
-use datafusion::physical_plan::expressions::{Column, Count, Literal};
+use datafusion::physical_plan::expressions::{is_null, Column, Count, Literal};
+use datafusion::physical_plan::filter::FilterExec;
 use datafusion::physical_plan::hash_aggregate::{
     AggregateMode, AggregateStrategy, HashAggregateExec,
 };
@@ -166,6 +168,16 @@ impl CompactionService for CompactionServiceImpl {
             }
         }
 
+        // Partitions and chunks created after an alter are expected to have the new columns.
+        let current_index = self.meta_store.get_index(index.get_id()).await?;
+        if current_index.get_row().columns() != index.get_row().columns() {
+            log::debug!(
+                "Cancelled compaction of {}. It runs concurrently with alter table",
+                partition_id
+            );
+            return Ok(());
+        }
+
         let mut data = Vec::new();
         let num_columns = index.get_row().columns().len();
         for chunk in chunks.iter() {
@@ -180,12 +192,28 @@ impl CompactionService for CompactionServiceImpl {
                 data.push(b)
             }
         }
+        if data.is_empty() {
+            // Partitions of altered indexes are compacted without chunks to rewrite their files.
+            data.push(RecordBatch::new_empty(Arc::new(arrow_schema(
+                index.get_row(),
+            ))));
+        }
 
-        let store = ParquetTableStore::new(index.get_row().clone(), ROW_GROUP_SIZE);
+        let store = ParquetTableStore::new(index.get_row().clone(), ROW_GROUP_SIZE).with_settings(
+            table
+                .get_row()
+                .parquet_settings()
+                .or(self.config.parquet_settings()),
+        );
         let old_partition_remote = match &new_chunk {
             Some(_) => None,
             None => partition.get_row().get_full_name(partition.get_id()),
         };
+        // Keep the main table on local disk until it is merged.
+        let _pinned_files = PinnedLocalFiles::new(
+            self.remote_fs.clone(),
+            old_partition_remote.iter().cloned().collect(),
+        );
         let old_partition_local = if let Some(f) = old_partition_remote {
             let result = self
                 .remote_fs
@@ -248,14 +276,9 @@ impl CompactionService for CompactionServiceImpl {
         // Merge and write rows.
         let schema = Arc::new(arrow_schema(index.get_row()));
         let main_table: Arc<dyn ExecutionPlan> = match old_partition_local {
-            Some(file) => Arc::new(ParquetExec::try_from_path(
-                file.as_str(),
-                None,
-                None,
-                ROW_GROUP_SIZE,
-                1,
-                None,
-            )?),
+            Some(file) => {
+                read_index_file(file.as_str(), index.get_row(), None, None, ROW_GROUP_SIZE)?
+            }
             None => Arc::new(EmptyExec::new(false, schema.clone())),
         };
 
@@ -264,7 +287,14 @@ impl CompactionService for CompactionServiceImpl {
             .get_table_by_id(index.get_row().table_id())
             .await?;
         let unique_key = table.get_row().unique_key_columns();
-        let records = merge_chunks(key_size, main_table, new, unique_key).await?;
+        // Tombstones must outlive all older versions of their rows, so they are dropped only when
+        // every pending chunk is merged into the main table.
+        let deleted_column = if new_chunk.is_none() && chunks.len() == all_pending_chunks.len() {
+            table.get_row().deleted_column()
+        } else {
+            None
+        };
+        let records = merge_chunks(key_size, main_table, new, unique_key, deleted_column).await?;
         let count_and_min =
             write_to_files(records, total_rows as usize, store, new_local_files2).await?;
 
@@ -394,7 +424,8 @@ impl CompactionService for CompactionServiceImpl {
 
         // Find key ranges for new partitions.
         // TODO deactivate corrupt tables
-        let files = download_files(&partitions, self.remote_fs.clone()).await?;
+        // Files stay on local disk until all partitions are split.
+        let (files, _pinned_files) = download_files(&partitions, self.remote_fs.clone()).await?;
         let keys = find_partition_keys(
             keys_with_counts(&files, key_len).await?,
             key_len,
@@ -443,6 +474,7 @@ impl CompactionService for CompactionServiceImpl {
             key_len,
             multi_partition_id,
             mchildren,
+            self.config.parquet_settings().clone(),
         );
         for p in partitions {
             s.split_single_partition(p).await?;
@@ -485,6 +517,7 @@ impl CompactionService for CompactionServiceImpl {
             key_len,
             multi_partition_id,
             children,
+            self.config.parquet_settings().clone(),
         );
         s.split_single_partition(data).await?;
         s.finish(false).await
@@ -523,22 +556,33 @@ async fn find_partition_keys(
     Ok(points)
 }
 
+/// Pass [index] to read files of a single index with all of its columns.
 async fn read_files(
     files: &[String],
     key_len: usize,
     projection: Option<Vec<usize>>,
+    index: Option<&Index>,
 ) -> Result<Arc<dyn ExecutionPlan>, CubeError> {
     assert!(!files.is_empty());
     let mut inputs = Vec::<Arc<dyn ExecutionPlan>>::with_capacity(files.len());
     for f in files {
-        inputs.push(Arc::new(ParquetExec::try_from_files(
-            &[f.as_str()],
-            projection.clone(),
-            None,
-            ROW_GROUP_SIZE,
-            1,
-            None,
-        )?));
+        match index {
+            Some(index) => inputs.push(read_index_file(
+                f.as_str(),
+                index,
+                projection.clone(),
+                None,
+                ROW_GROUP_SIZE,
+            )?),
+            None => inputs.push(Arc::new(ParquetExec::try_from_files(
+                &[f.as_str()],
+                projection.clone(),
+                None,
+                ROW_GROUP_SIZE,
+                1,
+                None,
+            )?)),
+        }
     }
     let plan = Arc::new(UnionExec::new(inputs));
     let fields = plan.schema();
@@ -557,7 +601,7 @@ async fn keys_with_counts(
     key_len: usize,
 ) -> Result<HashAggregateExec, CubeError> {
     let projection = (0..key_len).collect_vec();
-    let plan = read_files(files, key_len, Some(projection.clone())).await?;
+    let plan = read_files(files, key_len, Some(projection.clone()), None).await?;
 
     let fields = plan.schema();
     let fields = fields.fields();
@@ -599,29 +643,33 @@ fn collect_remote_files(p: &PartitionData, out: &mut Vec<(String, Option<u64>)>)
     }
 }
 
+/// Returns local paths of the downloaded files. They are pinned until the guard is dropped.
 async fn download_files(
     ps: &[PartitionData],
     fs: Arc<dyn RemoteFs>,
-) -> Result<Vec<String>, CubeError> {
-    let mut tasks = Vec::new();
+) -> Result<(Vec<String>, PinnedLocalFiles), CubeError> {
     let mut remote_files = Vec::new();
     for p in ps {
         collect_remote_files(p, &mut remote_files);
-        for f in &mut remote_files {
-            let (f, size) = take(f);
-            let fs = fs.clone();
-            tasks.push(cube_ext::spawn(
-                async move { fs.download_file(&f, size).await },
-            ))
-        }
-        remote_files.clear();
+    }
+    let pinned = PinnedLocalFiles::new(
+        fs.clone(),
+        remote_files.iter().map(|(f, _)| f.clone()).collect(),
+    );
+
+    let mut tasks = Vec::new();
+    for (f, size) in remote_files {
+        let fs = fs.clone();
+        tasks.push(cube_ext::spawn(
+            async move { fs.download_file(&f, size).await },
+        ))
     }
 
     let mut results = Vec::new();
     for t in tasks {
         results.push(t.await??)
     }
-    Ok(results)
+    Ok((results, pinned))
 }
 
 /// Writes [records] into [files], trying to split into equally-sized rows, with an additional
@@ -832,6 +880,7 @@ async fn merge_chunks(
     l: Arc<dyn ExecutionPlan>,
     r: Vec<ArrayRef>,
     unique_key_columns: Option<Vec<&crate::metastore::Column>>,
+    deleted_column: Option<&crate::metastore::Column>,
 ) -> Result<SendableRecordBatchStream, CubeError> {
     let schema = l.schema();
     let r = RecordBatch::try_new(schema.clone(), r)?;
@@ -860,7 +909,18 @@ async fn merge_chunks(
                     )
                 })
                 .collect::<Result<Vec<_>, _>>()?,
-        )?)
+        )?);
+        if let Some(deleted_column) = deleted_column {
+            res = Arc::new(FilterExec::try_new(
+                is_null(Arc::new(
+                    datafusion::physical_plan::expressions::Column::new_with_schema(
+                        deleted_column.get_name().as_str(),
+                        &res.schema(),
+                    )?,
+                ))?,
+                res.clone(),
+            )?);
+        }
     }
 
     Ok(res.execute(0).await?)
@@ -898,6 +958,8 @@ mod tests {
                 true,
                 None,
                 None,
+                None,
+                ParquetSettings::default(),
             )
             .await
             .unwrap();
@@ -945,6 +1007,10 @@ mod tests {
             .expect_compaction_chunks_total_size_threshold()
             .returning(|| 30);
 
+        config
+            .expect_parquet_settings()
+            .return_const(ParquetSettings::default());
+
         let compaction_service = CompactionServiceImpl::new(
             metastore.clone(),
             Arc::new(chunk_store),
@@ -1078,6 +1144,8 @@ struct MultiSplit {
     new_partitions: Vec<IdRow<Partition>>,
     new_partition_rows: Vec<u64>,
     uploads: Vec<JoinHandle<Result<u64, CubeError>>>,
+    /// Server defaults, tables of split partitions may override them.
+    parquet_settings: ParquetSettings,
 }
 
 impl MultiSplit {
@@ -1088,6 +1156,7 @@ impl MultiSplit {
         key_len: usize,
         multi_partition_id: u64,
         new_multi_parts: Vec<IdRow<MultiPartition>>,
+        parquet_settings: ParquetSettings,
     ) -> MultiSplit {
         let new_multi_rows = vec![0; new_multi_parts.len()];
         MultiSplit {
@@ -1102,6 +1171,7 @@ impl MultiSplit {
             new_partitions: Vec::new(),
             new_partition_rows: Vec::new(),
             uploads: Vec::new(),
+            parquet_settings,
         }
     }
 
@@ -1144,12 +1214,23 @@ impl MultiSplit {
             }
         });
 
-        let store = ParquetTableStore::new(p.index.get_row().clone(), ROW_GROUP_SIZE);
+        let table = self
+            .meta
+            .get_table_by_id(p.index.get_row().table_id())
+            .await?;
+        let store = ParquetTableStore::new(p.index.get_row().clone(), ROW_GROUP_SIZE)
+            .with_settings(
+                table
+                    .get_row()
+                    .parquet_settings()
+                    .or(&self.parquet_settings),
+            );
         let records = if !in_files.is_empty() {
             read_files(
                 &in_files.into_iter().map(|(f, _)| f).collect::<Vec<_>>(),
                 self.key_len,
                 None,
+                Some(p.index.get_row()),
             )
             .await?
             .execute(0)
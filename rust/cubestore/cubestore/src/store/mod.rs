@@ -12,13 +12,13 @@ use crate::metastore::{
     deactivate_table_on_corrupt_data, table::Table, Chunk, Column, ColumnType, IdRow, Index,
     MetaStore, Partition, WAL,
 };
-use crate::remotefs::{ensure_temp_file_is_dropped, RemoteFs};
+use crate::remotefs::{ensure_temp_file_is_dropped, PinnedLocalFiles, RemoteFs};
 use crate::table::{Row, TableValue};
 use crate::CubeError;
-use arrow::datatypes::Schema;
+use arrow::datatypes::{DataType, Field, Schema};
 use std::{
     fs::File,
-    io::{BufReader, BufWriter, Write},
+    io::{BufReader, BufWriter, Cursor, Write},
     sync::Arc,
 };
 
@@ -27,8 +27,13 @@ use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::metastore::chunks::chunk_file_name;
 use crate::table::data::cmp_partition_key;
-use crate::table::parquet::{arrow_schema, ParquetTableStore};
-use arrow::array::{Array, ArrayRef, Int64Builder, StringBuilder, UInt64Array};
+use crate::table::parquet::{adapt_batch, arrow_schema, ParquetTableStore};
+use arrow::array::{
+    Array, ArrayRef, BooleanArray, DecimalBuilder, Int64Builder, Int64Decimal0Array,
+    Int64Decimal10Array, Int64Decimal1Array, Int64Decimal2Array, Int64Decimal3Array,
+    Int64Decimal4Array, Int64Decimal5Array, StringBuilder, UInt64Array,
+};
+use arrow::ipc::writer::MemStreamWriter;
 use arrow::record_batch::RecordBatch;
 use datafusion::cube_ext;
 use datafusion::cube_ext::util::lexcmp_array_rows;
@@ -47,11 +52,30 @@ pub const ROW_GROUP_SIZE: usize = 16384; // TODO config
 pub struct DataFrame {
     columns: Vec<Column>,
     data: Vec<Row>,
+    /// Tables the columns are read from, empty if unknown.
+    #[serde(default)]
+    source_tables: Vec<Option<String>>,
 }
 
 impl DataFrame {
     pub fn new(columns: Vec<Column>, data: Vec<Row>) -> DataFrame {
-        DataFrame { columns, data }
+        DataFrame {
+            columns,
+            data,
+            source_tables: Vec::new(),
+        }
+    }
+
+    pub fn with_source_tables(mut self, source_tables: Vec<Option<String>>) -> DataFrame {
+        self.source_tables = source_tables;
+        self
+    }
+
+    /// Name of the table [column] is read from. None for computed columns.
+    pub fn source_table(&self, column: usize) -> Option<&str> {
+        self.source_tables
+            .get(column)
+            .and_then(|t| t.as_ref().map(|t| t.as_str()))
     }
 
     pub fn len(&self) -> usize {
@@ -124,6 +148,71 @@ impl DataFrame {
     }
 }
 
+/// Serializes [batches] in the Arrow IPC stream format. Clients can't read the `Int64Decimal`
+/// extension of our Arrow fork, so decimals are sent as standard `Decimal(18, scale)` columns.
+pub fn batches_to_arrow_ipc(
+    schema: &Schema,
+    batches: &[RecordBatch],
+) -> Result<Vec<u8>, CubeError> {
+    let schema = Arc::new(Schema::new(
+        schema
+            .fields()
+            .iter()
+            .map(|f| match f.data_type() {
+                DataType::Int64Decimal(scale) => {
+                    Field::new(f.name(), DataType::Decimal(18, *scale), f.is_nullable())
+                }
+                _ => f.clone(),
+            })
+            .collect(),
+    ));
+    let mut writer = MemStreamWriter::try_new(Cursor::new(Vec::new()), &schema)?;
+    for batch in batches {
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|c| match c.data_type() {
+                DataType::Int64Decimal(scale) => int64_decimal_to_decimal(c, *scale),
+                _ => Ok(c.clone()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        writer.write(&RecordBatch::try_new(schema.clone(), columns)?)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+fn int64_decimal_to_decimal(array: &ArrayRef, scale: usize) -> Result<ArrayRef, CubeError> {
+    macro_rules! convert {
+        ($ARRAY_TYPE: ident) => {{
+            let a = array.as_any().downcast_ref::<$ARRAY_TYPE>().unwrap();
+            let mut builder = DecimalBuilder::new(a.len(), 18, scale);
+            for i in 0..a.len() {
+                if a.is_null(i) {
+                    builder.append_null()?;
+                } else {
+                    builder.append_value(a.value(i) as i128)?;
+                }
+            }
+            Arc::new(builder.finish())
+        }};
+    }
+    Ok(match scale {
+        0 => convert!(Int64Decimal0Array),
+        1 => convert!(Int64Decimal1Array),
+        2 => convert!(Int64Decimal2Array),
+        3 => convert!(Int64Decimal3Array),
+        4 => convert!(Int64Decimal4Array),
+        5 => convert!(Int64Decimal5Array),
+        10 => convert!(Int64Decimal10Array),
+        _ => {
+            return Err(CubeError::internal(format!(
+                "Unsupported decimal scale: {}",
+                scale
+            )))
+        }
+    })
+}
+
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
 pub struct ChunkData {
     data_frame: DataFrame,
@@ -201,6 +290,13 @@ pub trait ChunkDataStore: DIService + Send + Sync {
         columns: &[Column],
         in_memory: bool,
     ) -> Result<Vec<ChunkUploadJob>, CubeError>;
+    /// Same as [partition_data], but writes only into a single index. [rows] must be in the
+    /// order of index columns.
+    async fn partition_index_data(
+        &self,
+        index_id: u64,
+        rows: Vec<ArrayRef>,
+    ) -> Result<Vec<ChunkUploadJob>, CubeError>;
     async fn repartition(&self, partition_id: u64) -> Result<(), CubeError>;
     async fn repartition_chunk(&self, chunk_id: u64) -> Result<(), CubeError>;
     async fn get_chunk_columns(&self, chunk: IdRow<Chunk>) -> Result<Vec<RecordBatch>, CubeError>;
@@ -324,6 +420,14 @@ impl ChunkDataStore for ChunkStore {
             .await
     }
 
+    async fn partition_index_data(
+        &self,
+        index_id: u64,
+        rows: Vec<ArrayRef>,
+    ) -> Result<Vec<ChunkUploadJob>, CubeError> {
+        self.partition_rows(index_id, rows, false).await
+    }
+
     async fn partition(&self, _wal_id: u64) -> Result<(), CubeError> {
         panic!("not used");
     }
@@ -460,13 +564,21 @@ impl ChunkDataStore for ChunkStore {
                 .get_index(partition.get_row().get_index_id())
                 .await?;
             let memory_chunks = self.memory_chunks.read().await;
-            Ok(vec![memory_chunks
+            let batch = memory_chunks
                 .get(&chunk.get_id())
                 .map(|b| b.clone())
-                .unwrap_or(RecordBatch::new_empty(Arc::new(
-                    arrow_schema(&index.get_row()),
-                )))])
+                .unwrap_or(RecordBatch::new_empty(Arc::new(arrow_schema(
+                    &index.get_row(),
+                ))));
+            Ok(vec![adapt_batch(index.get_row(), batch)?])
         } else {
+            let _pinned_file = PinnedLocalFiles::new(
+                self.remote_fs.clone(),
+                vec![ChunkStore::chunk_remote_path(
+                    chunk.get_id(),
+                    chunk.get_row().suffix(),
+                )],
+            );
             let (local_file, index) = self.download_chunk(chunk).await?;
             Ok(cube_ext::spawn_blocking(move || -> Result<_, CubeError> {
                 let parquet = ParquetTableStore::new(index, ROW_GROUP_SIZE);
@@ -524,10 +636,16 @@ mod tests {
     use crate::assert_eq_columns;
     use crate::cluster::MockCluster;
     use crate::config::Config;
+    use crate::metastore::table::ParquetSettings;
     use crate::metastore::RocksMetaStore;
+    use crate::queryplanner::query_executor::batch_to_dataframe;
     use crate::remotefs::LocalDirRemoteFs;
     use crate::table::data::{concat_record_batches, rows_to_columns};
+    use crate::table::TimestampValue;
+    use crate::util::decimal::Decimal;
     use crate::{metastore::ColumnType, table::TableValue};
+    use arrow::array::DecimalArray;
+    use arrow::ipc::reader::StreamReader;
     use rocksdb::{Options, DB};
     use std::fs;
     use std::path::PathBuf;
@@ -548,7 +666,7 @@ mod tests {
                 PathBuf::from(store_path.clone()),
             );
             let store = WALStore::new(
-                RocksMetaStore::new(path, remote_fs.clone(), config.config_obj()),
+                RocksMetaStore::new(path, remote_fs.clone(), config.config_obj()).unwrap(),
                 remote_fs.clone(),
                 10,
             );
@@ -587,6 +705,8 @@ mod tests {
                     true,
                     None,
                     None,
+                    None,
+                    ParquetSettings::default(),
                 )
                 .await
                 .unwrap();
@@ -630,7 +750,8 @@ mod tests {
                 Some(PathBuf::from(chunk_remote_store_path.clone())),
                 PathBuf::from(chunk_store_path.clone()),
             );
-            let meta_store = RocksMetaStore::new(path, remote_fs.clone(), config.config_obj());
+            let meta_store =
+                RocksMetaStore::new(path, remote_fs.clone(), config.config_obj()).unwrap();
             let chunk_store = ChunkStore::new(
                 meta_store.clone(),
                 remote_fs.clone(),
@@ -670,6 +791,8 @@ mod tests {
                     true,
                     None,
                     None,
+                    None,
+                    ParquetSettings::default(),
                 )
                 .await
                 .unwrap();
@@ -704,6 +827,72 @@ mod tests {
         let _ = fs::remove_dir_all(chunk_store_path.clone());
         let _ = fs::remove_dir_all(chunk_remote_store_path.clone());
     }
+
+    #[test]
+    fn record_batches_to_arrow_ipc() {
+        let columns = vec![
+            Column::new("id".to_string(), ColumnType::Int, 0),
+            Column::new("name".to_string(), ColumnType::String, 1),
+            Column::new("ts".to_string(), ColumnType::Timestamp, 2),
+            Column::new(
+                "amount".to_string(),
+                ColumnType::Decimal {
+                    scale: 2,
+                    precision: 18,
+                },
+                3,
+            ),
+        ];
+        let rows = vec![
+            Row::new(vec![
+                TableValue::Int(1),
+                TableValue::String("a".to_string()),
+                TableValue::Timestamp(TimestampValue::new(1_000_000)),
+                TableValue::Decimal(Decimal::new(12345)),
+            ]),
+            Row::new(vec![
+                TableValue::Int(2),
+                TableValue::Null,
+                TableValue::Null,
+                TableValue::Null,
+            ]),
+        ];
+        let schema = Arc::new(Schema::new(columns.iter().map(|c| c.into()).collect()));
+        let batch = RecordBatch::try_new(schema.clone(), rows_to_columns(&columns, &rows)).unwrap();
+
+        let ipc = batches_to_arrow_ipc(&schema, &[batch.clone(), batch]).unwrap();
+        let reader = StreamReader::try_new(Cursor::new(ipc)).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(
+            batches[0].schema().field(3).data_type(),
+            &DataType::Decimal(18, 2)
+        );
+        let amounts = batches[1]
+            .column(3)
+            .as_any()
+            .downcast_ref::<DecimalArray>()
+            .unwrap();
+        assert_eq!(amounts.value(0), 12345);
+        assert!(amounts.is_null(1));
+
+        // Other columns are sent as is.
+        let other_columns = RecordBatch::try_new(
+            Arc::new(Schema::new(batches[1].schema().fields()[0..3].to_vec())),
+            batches[1].columns()[0..3].to_vec(),
+        )
+        .unwrap();
+        let other_rows = rows
+            .iter()
+            .map(|r| Row::new(r.values()[0..3].to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            batch_to_dataframe(&vec![other_columns])
+                .unwrap()
+                .into_rows(),
+            other_rows
+        );
+    }
 }
 
 pub type ChunkUploadJob = JoinHandle<Result<(IdRow<Chunk>, Option<u64>), CubeError>>;
@@ -791,6 +980,13 @@ impl ChunkStore {
             .meta_store
             .create_chunk(partition.get_id(), data[0].len(), in_memory)
             .await?;
+        // Chunks created after an alter are expected to have the new columns.
+        let current_index = self.meta_store.get_index(index.get_id()).await?;
+        if current_index.get_row().columns() != index.get_row().columns() {
+            return Err(CubeError::user(
+                "Table is altered during the write, please retry".to_string(),
+            ));
+        }
         if in_memory {
             trace!(
                 "New in memory chunk allocated during partitioning: {:?}",
@@ -881,6 +1077,21 @@ fn remap_columns(
     Ok(new)
 }
 
+/// Adds NULLs of the deleted column to [rows] written without it. [rows] must be ordered by
+/// column index.
+pub fn fill_deleted_column(table: &Table, mut rows: Vec<ArrayRef>) -> Vec<ArrayRef> {
+    if let Some(c) = table.deleted_column() {
+        if rows.len() + 1 == table.get_columns().len() {
+            let num_rows = rows.first().map(|a| a.len()).unwrap_or(0);
+            rows.insert(
+                c.get_index(),
+                Arc::new(BooleanArray::from(vec![None; num_rows])),
+            );
+        }
+    }
+    rows
+}
+
 /// A wrapper to workaround Rust compiler error when using Vec<ArrayRef> in function arguments.
 /// ``error[E0700]: hidden type for `impl Trait` captures lifetime that does not appear in bounds``
 pub struct VecArrayRef(Vec<ArrayRef>);
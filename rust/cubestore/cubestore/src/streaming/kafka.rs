@@ -0,0 +1,775 @@
+use crate::metastore::source::KafkaValueFormat;
+use crate::metastore::table::StreamOffset;
+use crate::metastore::{Column, ColumnType};
+use crate::sql::{date_from_string, timestamp_from_string};
+use crate::streaming::{json_value_to_table_value, StreamingSource};
+use crate::table::{Row, TableValue, TimestampValue};
+use crate::util::decimal::Decimal;
+use crate::CubeError;
+use async_trait::async_trait;
+use avro_rs::types::Value as AvroValue;
+use avro_rs::Schema;
+use datafusion::cube_ext;
+use datafusion::cube_ext::ordfloat::OrdF64;
+use futures::stream::StreamExt;
+use futures::Stream;
+use json::JsonValue;
+use log::info;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::Message;
+use rdkafka::{Offset, TopicPartitionList};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Consumes all partitions of a topic starting from offsets persisted in the metastore, so rows
+/// are neither lost nor duplicated on restarts. Offsets of messages are used as sequence values.
+/// The consumer group is only informed of them through [StreamingSource::commit] for monitoring.
+pub struct KafkaStreamingSource {
+    consumer: Arc<StreamConsumer>,
+    topic: String,
+    value_format: KafkaValueFormat,
+    /// Offsets of the next messages after the rows returned so far, by partition.
+    delivered_offsets: Arc<Mutex<HashMap<i32, i64>>>,
+}
+
+impl KafkaStreamingSource {
+    pub fn new(
+        user: Option<String>,
+        password: Option<String>,
+        host: String,
+        use_ssl: bool,
+        group_id: String,
+        topic: String,
+        value_format: KafkaValueFormat,
+    ) -> Result<Self, CubeError> {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &host)
+            .set("group.id", &group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest");
+        if let Some(user) = user {
+            config
+                .set(
+                    "security.protocol",
+                    if use_ssl {
+                        "SASL_SSL"
+                    } else {
+                        "SASL_PLAINTEXT"
+                    },
+                )
+                .set("sasl.mechanisms", "PLAIN")
+                .set("sasl.username", &user)
+                .set("sasl.password", password.as_deref().unwrap_or(""));
+        } else if use_ssl {
+            config.set("security.protocol", "SSL");
+        }
+        let consumer: StreamConsumer = config.create()?;
+        Ok(Self {
+            consumer: Arc::new(consumer),
+            topic,
+            value_format,
+            delivered_offsets: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Waits for the next message until [deadline], `None` is returned once it passes.
+    async fn next_row(
+        consumer: &StreamConsumer,
+        decoder: &mut ValueDecoder,
+        columns: &[Column],
+        seq_column: &Column,
+        deadline: Instant,
+    ) -> Result<Option<(i32, i64, Option<Row>)>, CubeError> {
+        // Only receiving is interrupted, a message is never dropped half way through decoding.
+        let message = match tokio::time::timeout_at(deadline, consumer.recv()).await {
+            Ok(message) => message?.detach(),
+            Err(_) => return Ok(None),
+        };
+        let row = match message.payload() {
+            // Tombstones don't carry rows.
+            None => None,
+            Some(payload) => match decoder
+                .decode(payload, columns, seq_column, message.offset())
+                .await
+            {
+                Ok(row) => Some(row),
+                // Skipping the message would silently lose a row, so the stream stops here and
+                // resumes from the same message after the source is fixed.
+                Err(e) => {
+                    return Err(CubeError::user(format!(
+                        "Can't decode message at offset {} of partition {} in topic '{}': {}",
+                        message.offset(),
+                        message.partition(),
+                        message.topic(),
+                        e.message
+                    )))
+                }
+            },
+        };
+        Ok(Some((message.partition(), message.offset(), row)))
+    }
+
+    async fn fetch_partitions(
+        consumer: Arc<StreamConsumer>,
+        topic: String,
+    ) -> Result<Vec<i32>, CubeError> {
+        cube_ext::spawn_blocking(move || -> Result<Vec<i32>, CubeError> {
+            let metadata = consumer.fetch_metadata(Some(&topic), Duration::from_secs(30))?;
+            let topic_metadata = metadata
+                .topics()
+                .iter()
+                .find(|t| t.name() == topic)
+                .ok_or_else(|| CubeError::user(format!("Topic '{}' not found", topic)))?;
+            if let Some(e) = topic_metadata.error() {
+                return Err(CubeError::user(format!(
+                    "Can't fetch metadata of topic '{}': {:?}",
+                    topic, e
+                )));
+            }
+            Ok(topic_metadata.partitions().iter().map(|p| p.id()).collect())
+        })
+        .await?
+    }
+
+    /// Assigns all partitions of the topic if some of them aren't assigned yet. Assigned partitions
+    /// continue from [positions], the new ones are consumed from the beginning.
+    async fn assign_partitions(
+        consumer: &Arc<StreamConsumer>,
+        topic: &str,
+        assigned: &mut Vec<i32>,
+        positions: &HashMap<i32, i64>,
+    ) -> Result<(), CubeError> {
+        let mut partitions = Self::fetch_partitions(consumer.clone(), topic.to_string()).await?;
+        partitions.sort();
+        if &partitions == assigned {
+            return Ok(());
+        }
+        let mut assignment = TopicPartitionList::new();
+        for partition in partitions.iter() {
+            let offset = positions
+                .get(partition)
+                .map_or(Offset::Beginning, |o| Offset::Offset(*o));
+            assignment.add_partition_offset(topic, *partition, offset)?;
+        }
+        consumer.assign(&assignment)?;
+        if !assigned.is_empty() {
+            info!(
+                "Partitions of topic '{}' changed from {:?} to {:?}",
+                topic, assigned, partitions
+            );
+        }
+        *assigned = partitions;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StreamingSource for KafkaStreamingSource {
+    async fn row_stream(
+        &self,
+        columns: Vec<Column>,
+        seq_column: Column,
+        initial_offsets: Vec<StreamOffset>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Row>, CubeError>> + Send>>, CubeError> {
+        let decoder = ValueDecoder::new(&self.value_format)?;
+        let positions = initial_offsets
+            .iter()
+            .map(|o| (o.partition as i32, o.offset as i64))
+            .collect::<HashMap<_, _>>();
+        let mut assigned = Vec::new();
+        Self::assign_partitions(&self.consumer, &self.topic, &mut assigned, &positions).await?;
+        *self.delivered_offsets.lock().unwrap() = positions.clone();
+
+        let delivered_offsets = self.delivered_offsets.clone();
+        let topic = self.topic.clone();
+        let columns = Arc::new(columns);
+        let state = StreamState {
+            consumer: self.consumer.clone(),
+            decoder,
+            assigned,
+            positions,
+            next_refresh: Instant::now() + PARTITIONS_REFRESH_INTERVAL,
+        };
+        Ok(Box::pin(
+            futures::stream::unfold(state, move |mut state| {
+                let columns = columns.clone();
+                let seq_column = seq_column.clone();
+                let topic = topic.clone();
+                async move {
+                    loop {
+                        if state.next_refresh <= Instant::now() {
+                            // Partitions added to the topic later aren't consumed otherwise.
+                            if let Err(e) = Self::assign_partitions(
+                                &state.consumer,
+                                &topic,
+                                &mut state.assigned,
+                                &state.positions,
+                            )
+                            .await
+                            {
+                                return Some((Err(e), state));
+                            }
+                            state.next_refresh = Instant::now() + PARTITIONS_REFRESH_INTERVAL;
+                        }
+                        let res = Self::next_row(
+                            &state.consumer,
+                            &mut state.decoder,
+                            &columns,
+                            &seq_column,
+                            state.next_refresh,
+                        )
+                        .await;
+                        match res {
+                            Ok(None) => continue,
+                            Ok(Some((partition, offset, row))) => {
+                                state.positions.insert(partition, offset + 1);
+                                return Some((Ok((partition, offset, row)), state));
+                            }
+                            Err(e) => return Some((Err(e), state)),
+                        }
+                    }
+                }
+            })
+            .ready_chunks(16384)
+            .map(move |chunk| -> Result<Vec<Row>, CubeError> {
+                let mut rows = Vec::new();
+                let mut offsets = delivered_offsets.lock().unwrap();
+                for res in chunk {
+                    let (partition, offset, row) = res?;
+                    let next_offset = offsets.entry(partition).or_insert(0);
+                    // Already persisted messages can be redelivered after seeks.
+                    if offset < *next_offset {
+                        continue;
+                    }
+                    *next_offset = offset + 1;
+                    if let Some(row) = row {
+                        rows.push(row);
+                    }
+                }
+                Ok(rows)
+            }),
+        ))
+    }
+
+    fn offsets(&self) -> Vec<StreamOffset> {
+        self.delivered_offsets
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(partition, offset)| StreamOffset {
+                partition: *partition as u64,
+                offset: *offset as u64,
+            })
+            .collect()
+    }
+
+    async fn commit(&self) -> Result<(), CubeError> {
+        let mut offsets = TopicPartitionList::new();
+        for (partition, offset) in self.delivered_offsets.lock().unwrap().iter() {
+            offsets.add_partition_offset(&self.topic, *partition, Offset::Offset(*offset))?;
+        }
+        if offsets.count() == 0 {
+            return Ok(());
+        }
+        let consumer = self.consumer.clone();
+        cube_ext::spawn_blocking(move || consumer.commit(&offsets, CommitMode::Sync)).await??;
+        Ok(())
+    }
+}
+
+/// How often the topic is checked for new partitions.
+const PARTITIONS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+struct StreamState {
+    consumer: Arc<StreamConsumer>,
+    decoder: ValueDecoder,
+    /// Sorted ids of assigned partitions.
+    assigned: Vec<i32>,
+    /// Offsets of the next messages to receive, by partition.
+    positions: HashMap<i32, i64>,
+    next_refresh: Instant,
+}
+
+struct ValueDecoder {
+    format: KafkaValueFormat,
+    schema: Option<Schema>,
+    registry_schemas: HashMap<u32, Schema>,
+}
+
+impl ValueDecoder {
+    fn new(format: &KafkaValueFormat) -> Result<ValueDecoder, CubeError> {
+        let schema = match format {
+            KafkaValueFormat::Avro {
+                schema: Some(schema),
+                schema_registry_url: None,
+            } => Some(Schema::parse_str(schema)?),
+            _ => None,
+        };
+        Ok(ValueDecoder {
+            format: format.clone(),
+            schema,
+            registry_schemas: HashMap::new(),
+        })
+    }
+
+    async fn decode(
+        &mut self,
+        payload: &[u8],
+        columns: &[Column],
+        seq_column: &Column,
+        seq_value: i64,
+    ) -> Result<Row, CubeError> {
+        let mut values = match &self.format {
+            KafkaValueFormat::Json => Self::decode_json(payload, columns)?,
+            KafkaValueFormat::Avro {
+                schema_registry_url: Some(url),
+                ..
+            } => {
+                // Confluent wire format: zero byte, big endian schema id and the datum itself.
+                if payload.len() < 5 || payload[0] != 0 {
+                    return Err(CubeError::user(
+                        "Avro value without schema id received".to_string(),
+                    ));
+                }
+                let id = u32::from_be_bytes([payload[1], payload[2], payload[3], payload[4]]);
+                if !self.registry_schemas.contains_key(&id) {
+                    let schema = Self::fetch_schema(url, id).await?;
+                    self.registry_schemas.insert(id, schema);
+                }
+                Self::decode_avro(&self.registry_schemas[&id], &payload[5..], columns)?
+            }
+            KafkaValueFormat::Avro { .. } => {
+                Self::decode_avro(self.schema.as_ref().unwrap(), payload, columns)?
+            }
+        };
+        Ok(Row::new(
+            columns
+                .iter()
+                .map(|c| {
+                    if c.get_name() == seq_column.get_name() {
+                        TableValue::Int(seq_value)
+                    } else {
+                        values.remove(c.get_name()).unwrap_or(TableValue::Null)
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn decode_json(
+        payload: &[u8],
+        columns: &[Column],
+    ) -> Result<HashMap<String, TableValue>, CubeError> {
+        let text = std::str::from_utf8(payload)
+            .map_err(|e| CubeError::user(format!("Invalid UTF-8 in JSON value: {}", e)))?;
+        let mut object = match json::parse(text)? {
+            JsonValue::Object(o) => o,
+            x => {
+                return Err(CubeError::user(format!(
+                    "kafka source returned {:?} but object was expected",
+                    x
+                )))
+            }
+        };
+        let mut values = HashMap::new();
+        for c in columns {
+            if let Some(v) = object.remove(c.get_name()) {
+                values.insert(
+                    c.get_name().to_string(),
+                    json_value_to_table_value("kafka", v, c)?,
+                );
+            }
+        }
+        Ok(values)
+    }
+
+    fn decode_avro(
+        schema: &Schema,
+        mut datum: &[u8],
+        columns: &[Column],
+    ) -> Result<HashMap<String, TableValue>, CubeError> {
+        let fields = match avro_rs::from_avro_datum(schema, &mut datum, None)? {
+            AvroValue::Record(fields) => fields,
+            x => {
+                return Err(CubeError::user(format!(
+                    "kafka source returned {:?} but record was expected",
+                    x
+                )))
+            }
+        };
+        let mut fields = fields.into_iter().collect::<HashMap<_, _>>();
+        let mut values = HashMap::new();
+        for c in columns {
+            if let Some(v) = fields.remove(c.get_name()) {
+                let avro_scale = avro_decimal_scale(schema, c.get_name());
+                values.insert(
+                    c.get_name().to_string(),
+                    avro_value_to_table_value(v, c, avro_scale)?,
+                );
+            }
+        }
+        Ok(values)
+    }
+
+    async fn fetch_schema(registry_url: &str, id: u32) -> Result<Schema, CubeError> {
+        let url = format!("{}/schemas/ids/{}", registry_url.trim_end_matches('/'), id);
+        let res = reqwest::get(&url).await?;
+        if !res.status().is_success() {
+            return Err(CubeError::user(format!(
+                "Schema registry returned {} for schema {}",
+                res.status(),
+                id
+            )));
+        }
+        let body = res.json::<serde_json::Value>().await?;
+        let schema = body["schema"].as_str().ok_or_else(|| {
+            CubeError::user(format!("Schema registry returned no schema for {}", id))
+        })?;
+        Ok(Schema::parse_str(schema)?)
+    }
+}
+
+/// Avro decimal values don't carry their scale, it's only defined by the schema of the field.
+fn avro_decimal_scale(schema: &Schema, field: &str) -> Option<usize> {
+    fn scale(schema: &Schema) -> Option<usize> {
+        match schema {
+            Schema::Decimal { scale, .. } => Some(*scale),
+            Schema::Union(union) => union.variants().iter().find_map(scale),
+            _ => None,
+        }
+    }
+    match schema {
+        Schema::Record { fields, .. } => fields
+            .iter()
+            .find(|f| f.name == field)
+            .and_then(|f| scale(&f.schema)),
+        _ => None,
+    }
+}
+
+fn avro_value_to_table_value(
+    value: AvroValue,
+    col: &Column,
+    avro_scale: Option<usize>,
+) -> Result<TableValue, CubeError> {
+    let value = match value {
+        AvroValue::Union(v) => *v,
+        v => v,
+    };
+    if let AvroValue::Null = value {
+        return Ok(TableValue::Null);
+    }
+    let unexpected = |v: AvroValue| {
+        Err(CubeError::user(format!(
+            "kafka source returned {:?} as value of {} column '{}'",
+            v,
+            col.get_column_type(),
+            col.get_name()
+        )))
+    };
+    let int = |v: i64, min: i64, max: i64| {
+        if v < min || max < v {
+            Err(CubeError::user(format!(
+                "Can't convert {} to {}",
+                v,
+                col.get_column_type()
+            )))
+        } else {
+            Ok(TableValue::Int(v))
+        }
+    };
+    match col.get_column_type() {
+        ColumnType::String => match value {
+            AvroValue::String(v) | AvroValue::Enum(_, v) => Ok(TableValue::String(v)),
+            AvroValue::Int(v) => Ok(TableValue::String(v.to_string())),
+            AvroValue::Long(v) => Ok(TableValue::String(v.to_string())),
+            AvroValue::Float(v) => Ok(TableValue::String(v.to_string())),
+            AvroValue::Double(v) => Ok(TableValue::String(v.to_string())),
+            AvroValue::Boolean(v) => Ok(TableValue::String(v.to_string())),
+            AvroValue::Uuid(v) => Ok(TableValue::String(v.to_string())),
+            x => unexpected(x),
+        },
+        ColumnType::Int | ColumnType::Int16 | ColumnType::Int32 => {
+            let (min, max) = match col.get_column_type() {
+                ColumnType::Int16 => (i16::MIN as i64, i16::MAX as i64),
+                ColumnType::Int32 => (i32::MIN as i64, i32::MAX as i64),
+                _ => (i64::MIN, i64::MAX),
+            };
+            match value {
+                AvroValue::Int(v) => int(v as i64, min, max),
+                AvroValue::Long(v) => int(v, min, max),
+                x => unexpected(x),
+            }
+        }
+        ColumnType::Float | ColumnType::Float32 => match value {
+            AvroValue::Float(v) => Ok(TableValue::Float(OrdF64(v as f64))),
+            AvroValue::Double(v) => Ok(TableValue::Float(OrdF64(v))),
+            AvroValue::Int(v) => Ok(TableValue::Float(OrdF64(v as f64))),
+            AvroValue::Long(v) => Ok(TableValue::Float(OrdF64(v as f64))),
+            x => unexpected(x),
+        },
+        ColumnType::Decimal { scale, .. } => {
+            let multiplier = 10_i64.pow(*scale as u32);
+            let overflow = || {
+                CubeError::user(format!(
+                    "Can't convert {:?} to {}",
+                    value,
+                    col.get_column_type()
+                ))
+            };
+            let int = |v: i64| v.checked_mul(multiplier).ok_or_else(overflow);
+            let float = |v: f64| {
+                let v = (v * multiplier as f64).round();
+                // `as` saturates, so out of range values have to be rejected explicitly.
+                if v.is_finite() && i64::MIN as f64 <= v && v < i64::MAX as f64 {
+                    Ok(v as i64)
+                } else {
+                    Err(overflow())
+                }
+            };
+            // Unscaled value in big endian two's complement, rescaled to the column scale.
+            let decimal = |v: &avro_rs::Decimal| -> Result<i64, CubeError> {
+                let bytes = Vec::<u8>::try_from(v)?;
+                if bytes.len() > 16 {
+                    return Err(overflow());
+                }
+                let negative = bytes.first().map_or(false, |b| b & 0x80 != 0);
+                let mut buf = [if negative { 0xFF } else { 0 }; 16];
+                buf[16 - bytes.len()..].copy_from_slice(&bytes);
+                let unscaled = i128::from_be_bytes(buf);
+                let avro_scale = avro_scale.ok_or_else(|| {
+                    CubeError::internal(format!(
+                        "Scale of decimal field '{}' is missing in schema",
+                        col.get_name()
+                    ))
+                })? as i64;
+                let diff = *scale as i64 - avro_scale;
+                let v = if diff >= 0 {
+                    10_i128
+                        .checked_pow(diff as u32)
+                        .and_then(|m| unscaled.checked_mul(m))
+                        .ok_or_else(overflow)?
+                } else {
+                    // Rounded half away from zero like floats are.
+                    let divisor = match 10_i128.checked_pow(-diff as u32) {
+                        Some(d) => d,
+                        None => return Ok(0),
+                    };
+                    let rounded = unscaled
+                        .checked_abs()
+                        .and_then(|v| v.checked_add(divisor / 2))
+                        .ok_or_else(overflow)?
+                        / divisor;
+                    if unscaled < 0 {
+                        -rounded
+                    } else {
+                        rounded
+                    }
+                };
+                i64::try_from(v).map_err(|_| overflow())
+            };
+            let v = match &value {
+                AvroValue::Int(v) => int(*v as i64)?,
+                AvroValue::Long(v) => int(*v)?,
+                AvroValue::Float(v) => float(*v as f64)?,
+                AvroValue::Double(v) => float(*v)?,
+                AvroValue::Decimal(v) => decimal(v)?,
+                _ => return unexpected(value.clone()),
+            };
+            Ok(TableValue::Decimal(Decimal::new(v)))
+        }
+        ColumnType::Boolean => match value {
+            AvroValue::Boolean(v) => Ok(TableValue::Boolean(v)),
+            x => unexpected(x),
+        },
+        ColumnType::Timestamp => match value {
+            AvroValue::TimestampMillis(v) | AvroValue::Long(v) => {
+                Ok(TableValue::Timestamp(TimestampValue::new(v * 1_000_000)))
+            }
+            AvroValue::TimestampMicros(v) => {
+                Ok(TableValue::Timestamp(TimestampValue::new(v * 1000)))
+            }
+            AvroValue::Date(v) => Ok(TableValue::Timestamp(TimestampValue::from_days(v))),
+            AvroValue::String(v) => Ok(TableValue::Timestamp(timestamp_from_string(&v)?)),
+            x => unexpected(x),
+        },
+        ColumnType::Date => match value {
+            AvroValue::Date(v) => Ok(TableValue::Timestamp(TimestampValue::from_days(v))),
+            AvroValue::TimestampMillis(v) => Ok(TableValue::Timestamp(TimestampValue::from_days(
+                v.div_euclid(86_400_000) as i32,
+            ))),
+            AvroValue::String(v) => Ok(TableValue::Timestamp(date_from_string(&v)?)),
+            x => unexpected(x),
+        },
+        ColumnType::Bytes => match value {
+            AvroValue::Bytes(v) | AvroValue::Fixed(_, v) => Ok(TableValue::Bytes(v)),
+            x => unexpected(x),
+        },
+        ColumnType::HyperLogLog(_) => Err(CubeError::user(
+            "kafka source HLL import isn't supported".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::new("name".to_string(), ColumnType::String, 0),
+            Column::new("count".to_string(), ColumnType::Int, 1),
+            Column::new("amount".to_string(), ColumnType::Float, 2),
+            Column::new("__seq".to_string(), ColumnType::Int, 3),
+        ]
+    }
+
+    #[tokio::test]
+    async fn decode_json() {
+        let columns = columns();
+        let mut decoder = ValueDecoder::new(&KafkaValueFormat::Json).unwrap();
+        let row = decoder
+            .decode(
+                br#"{"name": "a", "count": 5, "extra": true}"#,
+                &columns,
+                &columns[3],
+                42,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            row.values(),
+            &vec![
+                TableValue::String("a".to_string()),
+                TableValue::Int(5),
+                TableValue::Null,
+                TableValue::Int(42),
+            ]
+        );
+        assert!(decoder
+            .decode(b"[1, 2]", &columns, &columns[3], 43)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn decode_avro() {
+        let schema = r#"{
+            "type": "record",
+            "name": "event",
+            "fields": [
+                {"name": "name", "type": "string"},
+                {"name": "count", "type": ["null", "long"]},
+                {"name": "amount", "type": "double"}
+            ]
+        }"#;
+        let parsed = Schema::parse_str(schema).unwrap();
+        let datum = avro_rs::to_avro_datum(
+            &parsed,
+            AvroValue::Record(vec![
+                ("name".to_string(), AvroValue::String("b".to_string())),
+                (
+                    "count".to_string(),
+                    AvroValue::Union(Box::new(AvroValue::Long(7))),
+                ),
+                ("amount".to_string(), AvroValue::Double(1.5)),
+            ]),
+        )
+        .unwrap();
+
+        let columns = columns();
+        let mut decoder = ValueDecoder::new(&KafkaValueFormat::Avro {
+            schema: Some(schema.to_string()),
+            schema_registry_url: None,
+        })
+        .unwrap();
+        let row = decoder
+            .decode(&datum, &columns, &columns[3], 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            row.values(),
+            &vec![
+                TableValue::String("b".to_string()),
+                TableValue::Int(7),
+                TableValue::Float(OrdF64(1.5)),
+                TableValue::Int(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn avro_decimal_overflow() {
+        let col = Column::new(
+            "amount".to_string(),
+            ColumnType::Decimal {
+                scale: 5,
+                precision: 18,
+            },
+            0,
+        );
+        assert_eq!(
+            avro_value_to_table_value(AvroValue::Long(12), &col, None).unwrap(),
+            TableValue::Decimal(Decimal::new(1_200_000))
+        );
+        assert_eq!(
+            avro_value_to_table_value(AvroValue::Double(1.5), &col, None).unwrap(),
+            TableValue::Decimal(Decimal::new(150_000))
+        );
+        assert!(avro_value_to_table_value(AvroValue::Long(i64::MAX / 10), &col, None).is_err());
+        assert!(avro_value_to_table_value(AvroValue::Double(1e20), &col, None).is_err());
+        assert!(avro_value_to_table_value(AvroValue::Double(f64::NAN), &col, None).is_err());
+    }
+
+    #[test]
+    fn avro_decimal() {
+        let schema = Schema::parse_str(
+            r#"{
+                "type": "record",
+                "name": "event",
+                "fields": [
+                    {"name": "amount", "type": ["null", {
+                        "type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2
+                    }]},
+                    {"name": "count", "type": "long"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(avro_decimal_scale(&schema, "amount"), Some(2));
+        assert_eq!(avro_decimal_scale(&schema, "count"), None);
+
+        let col = |scale| {
+            Column::new(
+                "amount".to_string(),
+                ColumnType::Decimal {
+                    scale,
+                    precision: 18,
+                },
+                0,
+            )
+        };
+        let decimal = |v: i64| AvroValue::Decimal(avro_rs::Decimal::from(v.to_be_bytes()));
+        assert_eq!(
+            avro_value_to_table_value(decimal(1234), &col(5), Some(2)).unwrap(),
+            TableValue::Decimal(Decimal::new(1_234_000))
+        );
+        assert_eq!(
+            avro_value_to_table_value(decimal(-1234), &col(2), Some(2)).unwrap(),
+            TableValue::Decimal(Decimal::new(-1234))
+        );
+        assert_eq!(
+            avro_value_to_table_value(decimal(-1_234_567), &col(5), Some(6)).unwrap(),
+            TableValue::Decimal(Decimal::new(-123_457))
+        );
+        let wide = AvroValue::Decimal(avro_rs::Decimal::from([0x7F; 12]));
+        assert!(avro_value_to_table_value(wide, &col(2), Some(2)).is_err());
+        assert!(avro_value_to_table_value(decimal(i64::MAX), &col(5), Some(2)).is_err());
+    }
+}
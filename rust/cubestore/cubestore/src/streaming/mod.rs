@@ -1,10 +1,13 @@
+pub mod kafka;
+
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::metastore::source::SourceCredentials;
-use crate::metastore::table::Table;
+use crate::metastore::table::{StreamOffset, Table};
 use crate::metastore::{Column, ColumnType, IdRow, MetaStore};
-use crate::sql::timestamp_from_string;
-use crate::store::ChunkDataStore;
+use crate::sql::{date_from_string, timestamp_from_string};
+use crate::store::{fill_deleted_column, ChunkDataStore};
+use crate::streaming::kafka::KafkaStreamingSource;
 use crate::table::data::{append_row, create_array_builders};
 use crate::table::{Row, TableValue};
 use crate::util::decimal::Decimal;
@@ -20,9 +23,11 @@ use json::JsonValue;
 use log::debug;
 use reqwest::{Response, Url};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io::{Cursor, Write};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 use warp::hyper::body::Bytes;
 
@@ -52,7 +57,11 @@ impl StreamingServiceImpl {
         })
     }
 
-    async fn source_by(&self, location: &str) -> Result<Arc<dyn StreamingSource>, CubeError> {
+    async fn source_by(
+        &self,
+        table: &IdRow<Table>,
+        location: &str,
+    ) -> Result<Arc<dyn StreamingSource>, CubeError> {
         let location_url = Url::parse(location)?;
         if location_url.scheme() != "stream" {
             return Err(CubeError::internal(format!(
@@ -82,7 +91,28 @@ impl StreamingServiceImpl {
                 password: password.clone(),
                 table: location_url.path().to_string().replace("/", ""),
                 endpoint_url: url.to_string(),
+                delivered_offsets: Arc::new(Mutex::new(HashMap::new())),
             })),
+            SourceCredentials::Kafka {
+                user,
+                password,
+                host,
+                use_ssl,
+                group_id,
+                value_format,
+            } => Ok(Arc::new(KafkaStreamingSource::new(
+                user.clone(),
+                password.clone(),
+                host.clone(),
+                *use_ssl,
+                format!(
+                    "{}-{}",
+                    group_id.as_deref().unwrap_or("cubestore"),
+                    table.get_id()
+                ),
+                location_url.path().to_string().replace("/", ""),
+                value_format.clone(),
+            )?)),
         }
     }
 }
@@ -90,77 +120,135 @@ impl StreamingServiceImpl {
 #[async_trait]
 impl StreamingService for StreamingServiceImpl {
     async fn stream_table(&self, table: IdRow<Table>, location: &str) -> Result<(), CubeError> {
-        let source = self.source_by(location).await?;
+        let source = self.source_by(&table, location).await?;
+        // Offsets are updated with every batch, so the passed row may be stale.
+        let table = self.meta_store.get_table_by_id(table.get_id()).await?;
         let seq_column = table.get_row().seq_column().ok_or_else(|| {
             CubeError::internal(format!(
                 "Seq column is not defined for streaming table '{}'",
                 table.get_row().get_table_name()
             ))
         })?;
+        let table_cols = table.get_row().insertable_columns();
         let mut stream = source
             .row_stream(
-                table.get_row().get_columns().clone(),
+                table_cols.clone(),
                 seq_column.clone(),
-                (SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis()
-                    * 1000) as u64, // TODO store initial sequence number
+                table.get_row().stream_offsets().clone().unwrap_or_default(),
             )
             .await?;
 
-        let finish = |builders: Vec<Box<dyn ArrayBuilder>>| {
-            builders.into_iter().map(|mut b| b.finish()).collect_vec()
-        };
-
+        let in_memory = source.in_memory_chunks();
+        let stale_timeout = Duration::from_secs(self.config_obj.stale_stream_timeout());
+        let mut rows = Vec::new();
+        // Set while received rows wait for more to be uploaded together.
+        let mut flush_at = None;
         // TODO support sealing streaming tables through ALTER TABLE
-        while let Some(new_rows) = tokio::time::timeout(
-            Duration::from_secs(self.config_obj.stale_stream_timeout()),
-            stream.next(),
-        )
-        .await?
-        {
-            let rows = new_rows?;
-            debug!("Received {} rows for {}", rows.len(), location);
-            let table_cols = table.get_row().get_columns().as_slice();
-            let mut builders = create_array_builders(table_cols);
-            for row in rows {
-                append_row(&mut builders, table_cols, &row);
+        loop {
+            let next = match flush_at {
+                Some(deadline) => match tokio::time::timeout_at(deadline, stream.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        let batch = std::mem::take(&mut rows);
+                        self.write_rows(&table, &table_cols, source.as_ref(), batch, in_memory)
+                            .await?;
+                        flush_at = None;
+                        continue;
+                    }
+                },
+                None => tokio::time::timeout(stale_timeout, stream.next()).await?,
+            };
+            let new_rows = match next {
+                Some(new_rows) => new_rows?,
+                None => break,
+            };
+            debug!("Received {} rows for {}", new_rows.len(), location);
+            rows.extend(new_rows);
+            if in_memory || rows.len() >= PERSISTED_BATCH_ROWS {
+                let batch = std::mem::take(&mut rows);
+                self.write_rows(&table, &table_cols, source.as_ref(), batch, in_memory)
+                    .await?;
+                flush_at = None;
+            } else if flush_at.is_none() {
+                flush_at = Some(tokio::time::Instant::now() + PERSISTED_BATCH_TIMEOUT);
             }
-            let new_chunks = self
-                .chunk_store
-                .partition_data(
-                    table.get_id(),
-                    finish(builders),
-                    table.get_row().get_columns().as_slice(),
-                    true,
-                )
-                .await?;
-
-            let new_chunk_ids: Result<Vec<(u64, Option<u64>)>, CubeError> = join_all(new_chunks)
-                .await
-                .into_iter()
-                .map(|c| {
-                    let (c, file_size) = c??;
-                    Ok((c.get_id(), file_size))
-                })
-                .collect();
-            self.meta_store
-                .activate_chunks(table.get_id(), new_chunk_ids?)
+        }
+        if flush_at.is_some() {
+            self.write_rows(&table, &table_cols, source.as_ref(), rows, in_memory)
                 .await?;
         }
         Ok(())
     }
 }
 
+/// Rows of sources that upload chunks are collected into batches of up to this many rows or
+/// received within [PERSISTED_BATCH_TIMEOUT], so a file isn't uploaded for every received batch.
+const PERSISTED_BATCH_ROWS: usize = 262_144;
+const PERSISTED_BATCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl StreamingServiceImpl {
+    /// Activates chunks with [rows] together with offsets of [source] and commits them.
+    async fn write_rows(
+        &self,
+        table: &IdRow<Table>,
+        table_cols: &[Column],
+        source: &dyn StreamingSource,
+        rows: Vec<Row>,
+        in_memory: bool,
+    ) -> Result<(), CubeError> {
+        let mut builders = create_array_builders(table_cols);
+        for row in rows {
+            append_row(&mut builders, table_cols, &row);
+        }
+        let arrays = builders.into_iter().map(|mut b| b.finish()).collect_vec();
+        let new_chunks = self
+            .chunk_store
+            .partition_data(
+                table.get_id(),
+                fill_deleted_column(table.get_row(), arrays),
+                table.get_row().get_columns().as_slice(),
+                in_memory,
+            )
+            .await?;
+
+        let new_chunk_ids: Result<Vec<(u64, Option<u64>)>, CubeError> = join_all(new_chunks)
+            .await
+            .into_iter()
+            .map(|c| {
+                let (c, file_size) = c??;
+                Ok((c.get_id(), file_size))
+            })
+            .collect();
+        self.meta_store
+            .activate_chunks(table.get_id(), new_chunk_ids?, Some(source.offsets()))
+            .await?;
+        source.commit().await
+    }
+}
+
 #[async_trait]
 pub trait StreamingSource: Send + Sync {
+    /// Stream continues right after [initial_offsets] which are the last persisted ones.
     async fn row_stream(
         &self,
         columns: Vec<Column>,
         seq_column: Column,
-        initial_seq_value: u64,
+        initial_offsets: Vec<StreamOffset>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Row>, CubeError>> + Send>>, CubeError>;
+
+    /// Offsets following the rows returned by the stream so far.
+    fn offsets(&self) -> Vec<StreamOffset>;
+
+    /// Rows are written into in-memory chunks right away instead of uploaded batches. Such chunks
+    /// only survive restarts once compacted, while offsets are persisted with activation.
+    fn in_memory_chunks(&self) -> bool {
+        false
+    }
+
+    /// Acknowledges all rows returned by the stream so far as persisted.
+    async fn commit(&self) -> Result<(), CubeError> {
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -169,6 +257,9 @@ pub struct KSqlStreamingSource {
     password: Option<String>,
     table: String,
     endpoint_url: String,
+    /// Offsets of the next rows after the rows returned so far, by partition of the underlying
+    /// topic. Those are taken from `ROWPARTITION` and `ROWOFFSET` pseudo columns.
+    delivered_offsets: Arc<Mutex<HashMap<u64, u64>>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -179,6 +270,7 @@ pub struct KSqlError {
 #[derive(Serialize, Deserialize)]
 pub struct KSqlQuery {
     sql: String,
+    properties: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -198,7 +290,7 @@ impl KSqlStreamingSource {
         bytes: Result<Bytes, reqwest::Error>,
         columns: Vec<Column>,
         seq_column: Column,
-    ) -> Result<Vec<Row>, CubeError> {
+    ) -> Result<Vec<(StreamOffset, Row)>, CubeError> {
         let mut rows = Vec::new();
         let b = bytes?;
         let string = String::from_utf8_lossy(&b);
@@ -230,6 +322,7 @@ impl KSqlStreamingSource {
                     .iter()
                     .filter(|c| c.get_name() != seq_column.get_name())
                     .map(|c| c.get_name().to_string())
+                    .chain(vec!["ROWPARTITION".to_string(), "ROWOFFSET".to_string()])
                     .collect::<Vec<_>>();
                 let ksql_column_names = schema
                     .column_names
@@ -244,93 +337,35 @@ impl KSqlStreamingSource {
                 }
                 continue;
             }
-            let row_values = match res {
-                JsonValue::Array(values) => values
-                    .into_iter()
-                    .zip_longest(columns.iter())
-                    .map(|zip| {
-                        match zip {
+            let (offset, row_values) = match res {
+                JsonValue::Array(mut values) => {
+                    let offset = match (values.pop(), values.pop()) {
+                        (Some(offset), Some(partition)) => StreamOffset {
+                            partition: partition.as_u64().ok_or_else(|| {
+                                CubeError::internal(format!(
+                                    "ksql source returned {:?} as row partition",
+                                    partition
+                                ))
+                            })?,
+                            offset: offset.as_u64().ok_or_else(|| {
+                                CubeError::internal(format!(
+                                    "ksql source returned {:?} as row offset",
+                                    offset
+                                ))
+                            })?,
+                        },
+                        _ => {
+                            return Err(CubeError::internal(
+                                "ksql source returned row without partition and offset".to_string(),
+                            ))
+                        }
+                    };
+                    let values = values
+                        .into_iter()
+                        .zip_longest(columns.iter())
+                        .map(|zip| match zip {
                             EitherOrBoth::Both(value, col) => {
-                                match col.get_column_type() {
-                                    ColumnType::String => {
-                                        match value {
-                                            JsonValue::Short(v) => Ok(TableValue::String(v.to_string())),
-                                            JsonValue::String(v) => Ok(TableValue::String(v.to_string())),
-                                            JsonValue::Number(v) => Ok(TableValue::String(v.to_string())),
-                                            JsonValue::Boolean(v) => Ok(TableValue::String(v.to_string())),
-                                            JsonValue::Null => Ok(TableValue::Null),
-                                            x => Err(CubeError::internal(format!(
-                                                "ksql source returned {:?} as row value but only primitive values are supported",
-                                                x
-                                            ))),
-                                        }
-                                    }
-                                    ColumnType::Int => {
-                                        match value {
-                                            JsonValue::Number(v) => Ok(TableValue::Int(v.as_fixed_point_i64(0).ok_or(CubeError::user(format!("Can't convert {:?} to int", v)))?)),
-                                            JsonValue::Null => Ok(TableValue::Null),
-                                            x => Err(CubeError::internal(format!(
-                                                "ksql source returned {:?} as row value but int expected",
-                                                x
-                                            ))),
-                                        }
-                                    }
-                                    ColumnType::Bytes => {
-                                        match value {
-                                            _ => Err(CubeError::internal(format!(
-                                                "ksql source bytes import isn't supported"
-                                            ))),
-                                        }
-                                    }
-                                    ColumnType::HyperLogLog(_) => {
-                                        match value {
-                                            _ => Err(CubeError::internal(format!(
-                                                "ksql source HLL import isn't supported"
-                                            ))),
-                                        }
-                                    }
-                                    ColumnType::Timestamp => {
-                                        match value {
-                                            JsonValue::Short(v) => Ok(TableValue::Timestamp(timestamp_from_string(v.as_str())?)),
-                                            JsonValue::String(v) => Ok(TableValue::Timestamp(timestamp_from_string(v.as_str())?)),
-                                            JsonValue::Null => Ok(TableValue::Null),
-                                            x => Err(CubeError::internal(format!(
-                                                "ksql source returned {:?} as row value but only primitive values are supported",
-                                                x
-                                            ))),
-                                        }
-                                    }
-                                    ColumnType::Decimal { scale, .. } => {
-                                        match value {
-                                            JsonValue::Number(v) => Ok(TableValue::Decimal(Decimal::new(v.as_fixed_point_i64(*scale as u16).ok_or(CubeError::user(format!("Can't convert {:?} to decimal", v)))?))),
-                                            JsonValue::Null => Ok(TableValue::Null),
-                                            x => Err(CubeError::internal(format!(
-                                                "ksql source returned {:?} as row value but only number values are supported",
-                                                x
-                                            ))),
-                                        }
-                                    }
-                                    ColumnType::Float => {
-                                        match value {
-                                            JsonValue::Number(v) => Ok(TableValue::Float(OrdF64(v.into()))),
-                                            JsonValue::Null => Ok(TableValue::Null),
-                                            x => Err(CubeError::internal(format!(
-                                                "ksql source returned {:?} as row value but only number values are supported",
-                                                x
-                                            ))),
-                                        }
-                                    }
-                                    ColumnType::Boolean => {
-                                        match value {
-                                            JsonValue::Boolean(v) => Ok(TableValue::Boolean(v)),
-                                            JsonValue::Null => Ok(TableValue::Null),
-                                            x => Err(CubeError::internal(format!(
-                                                "ksql source returned {:?} as row value but only boolean values are supported",
-                                                x
-                                            ))),
-                                        }
-                                    }
-                                }
+                                json_value_to_table_value("ksql", value, col)
                             }
                             EitherOrBoth::Right(col) => {
                                 if col.get_name() == seq_column.get_name() {
@@ -344,42 +379,92 @@ impl KSqlStreamingSource {
                                     )))
                                 }
                             }
-                            EitherOrBoth::Left(v) => {
-                                Err(CubeError::internal(format!(
-                                    "ksql source returned value {:?} that doesn't match schema columns",
-                                    v
-                                )))
-                            }
-                        }
-
-                    })
-                    .collect::<Result<Vec<TableValue>, CubeError>>(),
-                x => Err(CubeError::internal(format!(
-                    "ksql source returned {:?} but array was expected",
-                    x
-                ))),
+                            EitherOrBoth::Left(v) => Err(CubeError::internal(format!(
+                                "ksql source returned value {:?} that doesn't match schema columns",
+                                v
+                            ))),
+                        })
+                        .collect::<Result<Vec<TableValue>, CubeError>>()?;
+                    (offset, values)
+                }
+                x => {
+                    return Err(CubeError::internal(format!(
+                        "ksql source returned {:?} but array was expected",
+                        x
+                    )))
+                }
             };
-            rows.push(Row::new(row_values?));
+            rows.push((offset, Row::new(row_values)));
         }
 
         Ok(rows)
     }
 
-    async fn post_req<T: Serialize + ?Sized>(
-        &self,
-        url: &str,
-        json: &T,
-    ) -> Result<Response, CubeError> {
+    /// `ROWPARTITION` and `ROWOFFSET` pseudo columns appeared in ksqlDB 0.24, which is shipped
+    /// with Confluent Platform 7.2.
+    fn check_version(version: &str) -> Result<(), CubeError> {
+        let parts = version
+            .split(|c: char| !c.is_ascii_digit())
+            .take(2)
+            .map(|p| p.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>();
+        let supported = match parts.as_ref().map(|p| p.as_slice()) {
+            Ok([0, minor]) => *minor >= 24,
+            Ok([major, minor]) => (*major, *minor) >= (7, 2),
+            _ => {
+                return Err(CubeError::user(format!(
+                    "Can't parse ksqlDB version '{}'",
+                    version
+                )))
+            }
+        };
+        if !supported {
+            return Err(CubeError::user(format!(
+                "ksqlDB 0.24 or later is required to track offsets of streams, but server version is {}",
+                version
+            )));
+        }
+        Ok(())
+    }
+
+    async fn server_version(&self) -> Result<String, CubeError> {
+        let res = self.request(reqwest::Method::GET, "/info").send().await?;
+        if res.status() != 200 {
+            return Err(CubeError::user(format!(
+                "ksql api error: /info returned {}",
+                res.status()
+            )));
+        }
+        let info = res.json::<serde_json::Value>().await?;
+        info["KsqlServerInfo"]["version"]
+            .as_str()
+            .map(|v| v.to_string())
+            .ok_or_else(|| CubeError::user(format!("ksql api returned no version: {}", info)))
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
         let client = reqwest::ClientBuilder::new()
             .use_rustls_tls()
             .user_agent("cubestore")
             .build()
             .unwrap();
-        let mut builder = client.post(format!("{}{}", self.endpoint_url, url));
+        let mut builder = client.request(method, format!("{}{}", self.endpoint_url, url));
         if let Some(user) = &self.user {
             builder = builder.basic_auth(user.to_string(), self.password.clone())
         }
-        let res = builder.json(&json).send().await?;
+        builder
+    }
+
+    async fn post_req<T: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        json: &T,
+    ) -> Result<Response, CubeError> {
+        let res = self
+            .request(reqwest::Method::POST, url)
+            .json(&json)
+            .send()
+            .await?;
         if res.status() != 200 {
             let error = res.json::<KSqlError>().await?;
             return Err(CubeError::user(format!(
@@ -391,66 +476,296 @@ impl KSqlStreamingSource {
     }
 }
 
+pub(crate) fn json_value_to_table_value(
+    source: &str,
+    value: JsonValue,
+    col: &Column,
+) -> Result<TableValue, CubeError> {
+    match col.get_column_type() {
+        ColumnType::String => match value {
+            JsonValue::Short(v) => Ok(TableValue::String(v.to_string())),
+            JsonValue::String(v) => Ok(TableValue::String(v.to_string())),
+            JsonValue::Number(v) => Ok(TableValue::String(v.to_string())),
+            JsonValue::Boolean(v) => Ok(TableValue::String(v.to_string())),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but only primitive values are supported",
+                source, x
+            ))),
+        },
+        ColumnType::Int => match value {
+            JsonValue::Number(v) => Ok(TableValue::Int(
+                v.as_fixed_point_i64(0)
+                    .ok_or(CubeError::user(format!("Can't convert {:?} to int", v)))?,
+            )),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but int expected",
+                source, x
+            ))),
+        },
+        ColumnType::Int16 => match value {
+            JsonValue::Number(v) => Ok(TableValue::Int(
+                v.as_fixed_point_i64(0)
+                    .and_then(|v| i16::try_from(v).ok())
+                    .ok_or(CubeError::user(format!(
+                        "Can't convert {:?} to smallint",
+                        v
+                    )))? as i64,
+            )),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but int expected",
+                source, x
+            ))),
+        },
+        ColumnType::Int32 => match value {
+            JsonValue::Number(v) => Ok(TableValue::Int(
+                v.as_fixed_point_i64(0)
+                    .and_then(|v| i32::try_from(v).ok())
+                    .ok_or(CubeError::user(format!("Can't convert {:?} to int32", v)))?
+                    as i64,
+            )),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but int expected",
+                source, x
+            ))),
+        },
+        ColumnType::Bytes => match value {
+            _ => Err(CubeError::internal(format!(
+                "{} source bytes import isn't supported",
+                source
+            ))),
+        },
+        ColumnType::HyperLogLog(_) => match value {
+            _ => Err(CubeError::internal(format!(
+                "{} source HLL import isn't supported",
+                source
+            ))),
+        },
+        ColumnType::Date => match value {
+            JsonValue::Short(v) => Ok(TableValue::Timestamp(date_from_string(v.as_str())?)),
+            JsonValue::String(v) => Ok(TableValue::Timestamp(date_from_string(v.as_str())?)),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but only primitive values are supported",
+                source, x
+            ))),
+        },
+        ColumnType::Timestamp => match value {
+            JsonValue::Short(v) => Ok(TableValue::Timestamp(timestamp_from_string(v.as_str())?)),
+            JsonValue::String(v) => Ok(TableValue::Timestamp(timestamp_from_string(v.as_str())?)),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but only primitive values are supported",
+                source, x
+            ))),
+        },
+        ColumnType::Decimal { scale, .. } => match value {
+            JsonValue::Number(v) => Ok(TableValue::Decimal(Decimal::new(
+                v.as_fixed_point_i64(*scale as u16)
+                    .ok_or(CubeError::user(format!("Can't convert {:?} to decimal", v)))?,
+            ))),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but only number values are supported",
+                source, x
+            ))),
+        },
+        ColumnType::Float | ColumnType::Float32 => match value {
+            JsonValue::Number(v) => Ok(TableValue::Float(OrdF64(v.into()))),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but only number values are supported",
+                source, x
+            ))),
+        },
+        ColumnType::Boolean => match value {
+            JsonValue::Boolean(v) => Ok(TableValue::Boolean(v)),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "{} source returned {:?} as row value but only boolean values are supported",
+                source, x
+            ))),
+        },
+    }
+}
+
 #[async_trait]
 impl StreamingSource for KSqlStreamingSource {
     async fn row_stream(
         &self,
         columns: Vec<Column>,
         seq_column: Column,
-        initial_seq_value: u64,
+        initial_offsets: Vec<StreamOffset>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Row>, CubeError>> + Send>>, CubeError> {
+        // Push queries can't be started from given offsets, so rows are re-read from the beginning
+        // of the topic and already persisted ones are skipped. Sequence values aren't persisted,
+        // so they're based on time to keep increasing across restarts.
+        Self::check_version(&self.server_version().await?)?;
+        let initial_seq_value = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+            * 1000;
+        *self.delivered_offsets.lock().unwrap() = initial_offsets
+            .iter()
+            .map(|o| (o.partition, o.offset))
+            .collect();
+        let delivered_offsets = self.delivered_offsets.clone();
         let res = self
             .post_req(
                 "/query-stream",
                 &KSqlQuery {
-                    sql: format!("SELECT * FROM `{}` EMIT CHANGES;", self.table),
+                    sql: format!(
+                        "SELECT *, ROWPARTITION, ROWOFFSET FROM `{}` EMIT CHANGES;",
+                        self.table
+                    ),
+                    properties: vec![("auto.offset.reset".to_string(), "earliest".to_string())]
+                        .into_iter()
+                        .collect(),
                 },
             )
             .await?;
         let column_to_move = columns.clone();
         let seq_column_to_move = seq_column.clone();
-        Ok(
-            Box::pin(
-                res.bytes_stream()
-                    .scan(
-                        (Bytes::new(), initial_seq_value),
-                        move |(tail_bytes, seq_value),
-                              bytes: Result<_, _>|
-                              -> futures_util::future::Ready<
-                            Option<Result<Vec<Row>, CubeError>>,
-                        > {
-                            let rows = Self::parse_lines(
-                                tail_bytes,
-                                seq_value,
-                                bytes,
-                                column_to_move.clone(),
-                                seq_column_to_move.clone(),
-                            )
-                            .map_err(|e| {
-                                CubeError::internal(format!(
-                                    "Error during parsing ksql response: {}",
-                                    e
-                                ))
-                            });
-                            futures_util::future::ready(Some(rows))
-                        },
-                    )
-                    .ready_chunks(16384)
-                    .map(move |chunks| -> Result<Vec<Row>, CubeError> {
-                        let mut rows = Vec::new();
-                        for chunk in chunks.into_iter() {
-                            match chunk {
-                                Ok(mut vec) => {
-                                    rows.append(&mut vec);
-                                }
-                                Err(e) => {
-                                    return Err(e);
-                                }
+        Ok(Box::pin(
+            res.bytes_stream()
+                .scan(
+                    (Bytes::new(), initial_seq_value),
+                    move |(tail_bytes, seq_value),
+                          bytes: Result<_, _>|
+                          -> futures_util::future::Ready<
+                        Option<Result<Vec<(StreamOffset, Row)>, CubeError>>,
+                    > {
+                        let rows = Self::parse_lines(
+                            tail_bytes,
+                            seq_value,
+                            bytes,
+                            column_to_move.clone(),
+                            seq_column_to_move.clone(),
+                        )
+                        .map_err(|e| {
+                            CubeError::internal(format!(
+                                "Error during parsing ksql response: {}",
+                                e
+                            ))
+                        });
+                        futures_util::future::ready(Some(rows))
+                    },
+                )
+                .ready_chunks(16384)
+                .map(move |chunks| -> Result<Vec<Row>, CubeError> {
+                    let mut rows = Vec::new();
+                    let mut offsets = delivered_offsets.lock().unwrap();
+                    for chunk in chunks.into_iter() {
+                        for (offset, row) in chunk? {
+                            let next_offset = offsets.entry(offset.partition).or_insert(0);
+                            if offset.offset < *next_offset {
+                                continue;
                             }
+                            *next_offset = offset.offset + 1;
+                            rows.push(row);
                         }
-                        Ok(rows)
-                    }),
-            ),
-        )
+                    }
+                    Ok(rows)
+                }),
+        ))
+    }
+
+    fn offsets(&self) -> Vec<StreamOffset> {
+        self.delivered_offsets
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(partition, offset)| StreamOffset {
+                partition: *partition,
+                offset: *offset,
+            })
+            .collect()
+    }
+
+    fn in_memory_chunks(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ksql_parse_lines() {
+        let columns = vec![
+            Column::new("EVENT".to_string(), ColumnType::String, 0),
+            Column::new("__seq".to_string(), ColumnType::Int, 1),
+        ];
+        let mut tail = Bytes::new();
+        let mut seq_value = 100;
+        let mut parse = |s: &str| {
+            KSqlStreamingSource::parse_lines(
+                &mut tail,
+                &mut seq_value,
+                Ok(Bytes::from(s.to_string())),
+                columns.clone(),
+                columns[1].clone(),
+            )
+        };
+        let rows = parse(concat!(
+            r#"{"queryId":"q","columnNames":["EVENT","ROWPARTITION","ROWOFFSET"],"#,
+            r#""columnTypes":["STRING","INTEGER","BIGINT"]}"#,
+            "\n",
+            r#"["a",1,5]"#,
+            "\n",
+            r#"["b",0,"#
+        ))
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![(
+                StreamOffset {
+                    partition: 1,
+                    offset: 5
+                },
+                Row::new(vec![
+                    TableValue::String("a".to_string()),
+                    TableValue::Int(100)
+                ])
+            )]
+        );
+        let rows = parse("7]\n").unwrap();
+        assert_eq!(
+            rows,
+            vec![(
+                StreamOffset {
+                    partition: 0,
+                    offset: 7
+                },
+                Row::new(vec![
+                    TableValue::String("b".to_string()),
+                    TableValue::Int(101)
+                ])
+            )]
+        );
+        assert!(parse("[\"c\"]\n").is_err());
+    }
+
+    #[test]
+    fn ksql_version() {
+        for version in ["0.24.0", "0.28.2-rc1", "7.2.0", "7.3.1-ce", "8.0.0"] {
+            assert!(
+                KSqlStreamingSource::check_version(version).is_ok(),
+                "{}",
+                version
+            );
+        }
+        for version in ["0.23.1", "0.15.0", "7.1.0", "6.2.4", "unknown"] {
+            assert!(
+                KSqlStreamingSource::check_version(version).is_err(),
+                "{}",
+                version
+            );
+        }
     }
 }
@@ -5,6 +5,7 @@ use arrow::array::{Array, ArrayBuilder, ArrayRef, StringArray};
 use arrow::record_batch::RecordBatch;
 use itertools::Itertools;
 use std::cmp::Ordering;
+use std::convert::TryFrom;
 
 use datafusion::cube_ext::ordfloat::OrdF64;
 use datafusion::physical_plan::memory::MemoryExec;
@@ -149,6 +150,10 @@ macro_rules! match_column_type {
                 n => panic!("unhandled target scale: {}", n),
             },
             ColumnType::Float => $matcher!(Float, Float64Builder, Float),
+            ColumnType::Int16 => $matcher!(Int16, Int16Builder, Int),
+            ColumnType::Int32 => $matcher!(Int32, Int32Builder, Int),
+            ColumnType::Float32 => $matcher!(Float32, Float32Builder, Float),
+            ColumnType::Date => $matcher!(Date, Date32Builder, Timestamp),
         }
     }};
 }
@@ -179,22 +184,34 @@ pub fn append_row(bs: &mut [Box<dyn ArrayBuilder>], cs: &[Column], r: &Row) {
 pub fn append_value(b: &mut dyn ArrayBuilder, c: &ColumnType, v: &TableValue) {
     let is_null = matches!(v, TableValue::Null);
     macro_rules! convert_value {
-        (Decimal, $v: expr) => {{
+        (Int16, $tv_enum: tt, $v: expr) => {{
+            i16::try_from(*$v).expect("smallint value out of range")
+        }};
+        (Int32, $tv_enum: tt, $v: expr) => {{
+            i32::try_from(*$v).expect("int32 value out of range")
+        }};
+        (Float32, $tv_enum: tt, $v: expr) => {{
+            $v.0 as f32
+        }};
+        (Date, $tv_enum: tt, $v: expr) => {{
+            $v.days()
+        }};
+        ($type: tt, Decimal, $v: expr) => {{
             $v.raw_value()
         }};
-        (Float, $v: expr) => {{
+        ($type: tt, Float, $v: expr) => {{
             $v.0
         }};
-        (Timestamp, $v: expr) => {{
+        ($type: tt, Timestamp, $v: expr) => {{
             $v.get_time_stamp() / 1000
         }}; // Nanoseconds to microseconds.
-        (String, $v: expr) => {{
+        ($type: tt, String, $v: expr) => {{
             $v.as_str()
         }};
-        (Bytes, $v: expr) => {{
+        ($type: tt, Bytes, $v: expr) => {{
             $v.as_slice()
         }};
-        ($tv_enum: tt, $v: expr) => {{
+        ($type: tt, $tv_enum: tt, $v: expr) => {{
             *$v
         }};
     }
@@ -206,7 +223,7 @@ pub fn append_value(b: &mut dyn ArrayBuilder, c: &ColumnType, v: &TableValue) {
                 return;
             }
             let v = match v {
-                TableValue::$tv_enum(v) => convert_value!($tv_enum, v),
+                TableValue::$tv_enum(v) => convert_value!($type, $tv_enum, v),
                 other => panic!("unexpected value {:?} for type {:?}", other, c),
             };
             b.append_value(v).unwrap();
@@ -1,9 +1,10 @@
 use crate::util::decimal::Decimal;
 
 use arrow::array::{
-    Array, ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array, Int64Decimal0Array,
-    Int64Decimal10Array, Int64Decimal1Array, Int64Decimal2Array, Int64Decimal3Array,
-    Int64Decimal4Array, Int64Decimal5Array, StringArray, TimestampMicrosecondArray,
+    Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, Float32Array, Float64Array,
+    Int16Array, Int32Array, Int64Array, Int64Decimal0Array, Int64Decimal10Array,
+    Int64Decimal1Array, Int64Decimal2Array, Int64Decimal3Array, Int64Decimal4Array,
+    Int64Decimal5Array, StringArray, TimestampMicrosecondArray,
 };
 use arrow::datatypes::{DataType, TimeUnit};
 
@@ -46,6 +47,12 @@ impl TableValue {
             DataType::Int64 => {
                 TableValue::Int(a.as_any().downcast_ref::<Int64Array>().unwrap().value(row))
             }
+            DataType::Int32 => {
+                TableValue::Int(a.as_any().downcast_ref::<Int32Array>().unwrap().value(row) as i64)
+            }
+            DataType::Int16 => {
+                TableValue::Int(a.as_any().downcast_ref::<Int16Array>().unwrap().value(row) as i64)
+            }
             DataType::Utf8 => TableValue::String(
                 a.as_any()
                     .downcast_ref::<StringArray>()
@@ -109,6 +116,16 @@ impl TableValue {
                     .value(row)
                     .into(),
             ),
+            DataType::Float32 => TableValue::Float(
+                (a.as_any()
+                    .downcast_ref::<Float32Array>()
+                    .unwrap()
+                    .value(row) as f64)
+                    .into(),
+            ),
+            DataType::Date32 => TableValue::Timestamp(TimestampValue::from_days(
+                a.as_any().downcast_ref::<Date32Array>().unwrap().value(row),
+            )),
             DataType::Timestamp(TimeUnit::Microsecond, None) => {
                 TableValue::Timestamp(TimestampValue::new(
                     1000 * a
@@ -148,8 +165,26 @@ impl TimestampValue {
     pub fn get_time_stamp(&self) -> i64 {
         self.unix_nano
     }
+
+    /// Dates are kept as timestamps at midnight UTC.
+    pub fn from_days(days: i32) -> TimestampValue {
+        TimestampValue::new(days as i64 * NANOS_IN_DAY)
+    }
+
+    /// Number of days since the epoch, time of day is truncated.
+    pub fn days(&self) -> i32 {
+        self.unix_nano.div_euclid(NANOS_IN_DAY) as i32
+    }
+
+    pub fn to_date_string(&self) -> String {
+        Utc.timestamp_nanos(self.unix_nano)
+            .format("%Y-%m-%d")
+            .to_string()
+    }
 }
 
+const NANOS_IN_DAY: i64 = 86_400_000_000_000;
+
 impl Debug for TimestampValue {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("TimestampValue")
@@ -237,4 +272,17 @@ mod tests {
             assert_eq!(v, &v2);
         }
     }
+
+    #[test]
+    fn dates() {
+        let d = TimestampValue::from_days(18932);
+        assert_eq!(d.to_date_string(), "2021-11-01");
+        assert_eq!(d.days(), 18932);
+        assert_eq!(
+            TimestampValue::new(d.get_time_stamp() + 1_000_000).days(),
+            18932
+        );
+        assert_eq!(TimestampValue::new(-1_000_000).days(), -1);
+        assert_eq!(TimestampValue::from_days(-1).to_date_string(), "1969-12-31");
+    }
 }
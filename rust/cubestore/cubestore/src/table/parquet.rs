@@ -1,9 +1,19 @@
-use crate::metastore::Index;
+use crate::metastore::table::{ParquetCompression, ParquetSettings};
+use crate::metastore::{Column, ColumnType, Index};
+use crate::table::data::{append_value, create_array_builder};
+use crate::table::TableValue;
 use crate::CubeError;
-use arrow::array::ArrayRef;
+use arrow::array::{ArrayBuilder, ArrayRef};
 use arrow::datatypes::Schema;
 use arrow::record_batch::RecordBatch;
+use datafusion::logical_plan::Expr;
+use datafusion::physical_plan::expressions::{Column as ColumnExpr, Literal};
+use datafusion::physical_plan::parquet::ParquetExec;
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::{ExecutionPlan, PhysicalExpr};
+use datafusion::scalar::ScalarValue;
 use parquet::arrow::{ArrowReader, ArrowWriter, ParquetFileArrowReader};
+use parquet::basic::Compression;
 use parquet::file::properties::{WriterProperties, WriterVersion};
 use parquet::file::reader::SerializedFileReader;
 use std::convert::TryFrom;
@@ -13,6 +23,7 @@ use std::sync::Arc;
 pub struct ParquetTableStore {
     table: Index,
     row_group_size: usize,
+    settings: ParquetSettings,
 }
 
 impl ParquetTableStore {
@@ -20,7 +31,7 @@ impl ParquetTableStore {
         let mut r = ParquetFileArrowReader::new(Arc::new(SerializedFileReader::try_from(file)?));
         let mut batches = Vec::new();
         for b in r.get_record_reader(self.row_group_size)? {
-            batches.push(b?)
+            batches.push(adapt_batch(&self.table, b?)?)
         }
         Ok(batches)
     }
@@ -31,9 +42,15 @@ impl ParquetTableStore {
         ParquetTableStore {
             table,
             row_group_size,
+            settings: ParquetSettings::default(),
         }
     }
 
+    /// Settings of written files, reads don't depend on them.
+    pub fn with_settings(self, settings: ParquetSettings) -> ParquetTableStore {
+        ParquetTableStore { settings, ..self }
+    }
+
     pub fn key_size(&self) -> u64 {
         self.table.sort_key_size()
     }
@@ -49,10 +66,25 @@ impl ParquetTableStore {
     }
 
     pub fn writer_props(&self) -> WriterProperties {
-        WriterProperties::builder()
+        let mut props = WriterProperties::builder()
             .set_max_row_group_size(self.row_group_size)
-            .set_writer_version(WriterVersion::PARQUET_2_0)
-            .build()
+            .set_writer_version(WriterVersion::PARQUET_2_0);
+        if let Some(compression) = self.settings.compression {
+            props = props.set_compression(match compression {
+                ParquetCompression::Uncompressed => Compression::UNCOMPRESSED,
+                ParquetCompression::Snappy => Compression::SNAPPY,
+                ParquetCompression::Gzip => Compression::GZIP,
+                ParquetCompression::Lz4 => Compression::LZ4,
+                ParquetCompression::Zstd => Compression::ZSTD,
+            });
+        }
+        if let Some(dictionary_enabled) = self.settings.dictionary_enabled {
+            props = props.set_dictionary_enabled(dictionary_enabled);
+        }
+        if let Some(statistics_enabled) = self.settings.statistics_enabled {
+            props = props.set_statistics_enabled(statistics_enabled);
+        }
+        props.build()
     }
 
     pub fn write_data(&self, dest_file: &str, columns: Vec<ArrayRef>) -> Result<(), CubeError> {
@@ -72,11 +104,117 @@ pub fn arrow_schema(i: &Index) -> Schema {
     Schema::new(i.columns().iter().map(|c| c.into()).collect())
 }
 
+/// Reads a data file of [index]. Files written before `ALTER TABLE` may lack added columns or
+/// have old column names, those are read with column defaults and current names.
+pub fn read_index_file(
+    file: &str,
+    index: &Index,
+    projection: Option<Vec<usize>>,
+    predicate: Option<Expr>,
+    batch_size: usize,
+) -> Result<Arc<dyn ExecutionPlan>, CubeError> {
+    if index.altered() {
+        let file_schema =
+            ParquetExec::try_from_path(file, None, None, batch_size, 1, None)?.schema();
+        let columns = index.columns();
+        let up_to_date = file_schema.fields().len() == columns.len()
+            && file_schema
+                .fields()
+                .iter()
+                .zip(columns.iter())
+                .all(|(f, c)| f.name() == c.get_name());
+        if !up_to_date {
+            let projection = projection.unwrap_or_else(|| (0..columns.len()).collect());
+            let file_projection = projection
+                .iter()
+                .cloned()
+                .filter(|i| *i < file_schema.fields().len())
+                .collect::<Vec<_>>();
+            // Predicate refers to current column names and can't be used for pruning here.
+            let input = Arc::new(ParquetExec::try_from_path(
+                file,
+                Some(file_projection.clone()),
+                None,
+                batch_size,
+                1,
+                None,
+            )?);
+            let mut exprs = Vec::<(Arc<dyn PhysicalExpr>, String)>::with_capacity(projection.len());
+            for i in projection {
+                let c = &columns[i];
+                let expr: Arc<dyn PhysicalExpr> = match file_projection.iter().position(|f| *f == i)
+                {
+                    Some(pos) => Arc::new(ColumnExpr::new(c.get_name(), pos)),
+                    None => Arc::new(Literal::new(default_scalar(c))),
+                };
+                exprs.push((expr, c.get_name().clone()));
+            }
+            return Ok(Arc::new(ProjectionExec::try_new(exprs, input)?));
+        }
+    }
+    Ok(Arc::new(ParquetExec::try_from_path(
+        file, projection, predicate, batch_size, 1, None,
+    )?))
+}
+
+/// Same as [read_index_file], but for batches of [index] which are already in memory.
+pub fn adapt_batch(index: &Index, batch: RecordBatch) -> Result<RecordBatch, CubeError> {
+    if !index.altered() {
+        return Ok(batch);
+    }
+    let mut columns = batch.columns().to_vec();
+    for c in &index.columns()[columns.len()..] {
+        let mut b = create_array_builder(c.get_column_type());
+        let v = c.default_value().unwrap_or(&TableValue::Null);
+        for _ in 0..batch.num_rows() {
+            append_value(b.as_mut(), c.get_column_type(), v);
+        }
+        columns.push(b.finish());
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(arrow_schema(index)),
+        columns,
+    )?)
+}
+
+fn default_scalar(c: &Column) -> ScalarValue {
+    let v = c.default_value().unwrap_or(&TableValue::Null);
+    macro_rules! value {
+        ($p: pat => $e: expr) => {
+            match v {
+                $p => Some($e),
+                _ => None,
+            }
+        };
+    }
+    match c.get_column_type() {
+        ColumnType::String => ScalarValue::Utf8(value!(TableValue::String(s) => s.clone())),
+        ColumnType::Int => ScalarValue::Int64(value!(TableValue::Int(i) => *i)),
+        ColumnType::Int16 => ScalarValue::Int16(value!(TableValue::Int(i) => *i as i16)),
+        ColumnType::Int32 => ScalarValue::Int32(value!(TableValue::Int(i) => *i as i32)),
+        t @ ColumnType::Decimal { .. } => ScalarValue::Int64Decimal(
+            value!(TableValue::Decimal(d) => d.raw_value()),
+            t.target_scale() as usize,
+        ),
+        ColumnType::Float => ScalarValue::Float64(value!(TableValue::Float(f) => f.0)),
+        ColumnType::Float32 => ScalarValue::Float32(value!(TableValue::Float(f) => f.0 as f32)),
+        ColumnType::Boolean => ScalarValue::Boolean(value!(TableValue::Boolean(b) => *b)),
+        ColumnType::Bytes | ColumnType::HyperLogLog(_) => {
+            ScalarValue::Binary(value!(TableValue::Bytes(b) => b.clone()))
+        }
+        ColumnType::Timestamp => ScalarValue::TimestampMicrosecond(
+            value!(TableValue::Timestamp(t) => t.get_time_stamp() / 1000),
+        ),
+        ColumnType::Date => ScalarValue::Date32(value!(TableValue::Timestamp(t) => t.days())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate test;
 
     use crate::assert_eq_columns;
+    use crate::metastore::table::{ParquetCompression, ParquetSettings};
     use crate::metastore::{Column, ColumnType, Index};
     use crate::store::{compaction, ROW_GROUP_SIZE};
     use crate::table::data::{cmp_row_key_heap, concat_record_batches, rows_to_columns, to_stream};
@@ -89,6 +227,7 @@ mod tests {
     };
     use arrow::record_batch::RecordBatch;
     use itertools::Itertools;
+    use parquet::basic::{Compression, Encoding};
     use parquet::data_type::DataType;
     use parquet::file::reader::FileReader;
     use parquet::file::reader::SerializedFileReader;
@@ -212,6 +351,7 @@ mod tests {
             )
             .unwrap(),
             row_group_size: 10,
+            settings: ParquetSettings::default(),
         };
         let file = NamedTempFile::new().unwrap();
         let file_name = file.path().to_str().unwrap();
@@ -384,6 +524,42 @@ mod tests {
         assert_eq_columns!(r.columns(), &data);
     }
 
+    #[test]
+    fn writer_settings() {
+        let index = Index::try_new(
+            "index".into(),
+            0,
+            vec![Column::new("str".into(), ColumnType::String, 0)],
+            1,
+            None,
+            None,
+        )
+        .unwrap();
+        let data: Vec<ArrayRef> = vec![Arc::new(StringArray::from(vec!["a", "b", "a", "b"]))];
+
+        let write = |settings: ParquetSettings| {
+            let file = NamedTempFile::new().unwrap();
+            ParquetTableStore::new(index.clone(), ROW_GROUP_SIZE)
+                .with_settings(settings)
+                .write_data(file.path().to_str().unwrap(), data.clone())
+                .unwrap();
+            let r = SerializedFileReader::new(file.into_file()).unwrap();
+            r.metadata().row_group(0).column(0).clone()
+        };
+
+        let c = write(ParquetSettings::default());
+        assert_eq!(c.compression(), Compression::UNCOMPRESSED);
+        assert!(c.encodings().contains(&Encoding::RLE_DICTIONARY));
+
+        let c = write(ParquetSettings {
+            compression: Some(ParquetCompression::Zstd),
+            dictionary_enabled: Some(false),
+            statistics_enabled: None,
+        });
+        assert_eq!(c.compression(), Compression::ZSTD);
+        assert!(!c.encodings().contains(&Encoding::RLE_DICTIONARY));
+    }
+
     fn print_min_max_typed<T: DataType>(s: &TypedStatistics<T>) -> String {
         format!("min: {}, max: {}", s.min(), s.max())
     }
@@ -0,0 +1,237 @@
+/*
+ * Copyright 2011 The Guava Authors
+ * Copyright 2021 Cube Dev, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+/// Port of Fingerprint2011 from Guava. ZetaSketch (and BigQuery) hash values with it before adding
+/// them to a sketch, so we must use exactly the same function to produce compatible sketches.
+///
+/// Note that Java performs all arithmetic on signed 64-bit integers, which is the same as wrapping
+/// arithmetic on unsigned ones.
+
+// Some primes between 2^63 and 2^64 for various uses.
+const K0: u64 = 0xa5b85c5e198ed849;
+const K1: u64 = 0x8d58ac26afe12e47;
+const K2: u64 = 0xc47b6e9e3a970ed3;
+const K3: u64 = 0xc6a4a7935bd1e995;
+
+pub fn fingerprint(bytes: &[u8]) -> u64 {
+    let length = bytes.len();
+    let result = if length <= 32 {
+        murmur_hash64_with_seed(bytes, K0 ^ K1 ^ K2)
+    } else if length <= 64 {
+        hash_length33_to64(bytes)
+    } else {
+        full_fingerprint(bytes)
+    };
+
+    let u = if length >= 8 { load64(bytes, 0) } else { K0 };
+    let v = if length >= 9 {
+        load64(bytes, length - 8)
+    } else {
+        K0
+    };
+    let result = hash128_to64(result.wrapping_add(v), u);
+    return if result == 0 || result == 1 {
+        result.wrapping_add(!1)
+    } else {
+        result
+    };
+}
+
+fn load64(bytes: &[u8], offset: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&bytes[offset..offset + 8]);
+    return u64::from_le_bytes(b);
+}
+
+/// Loads up to 8 bytes, the missing high bytes are zeros.
+fn load64_safely(bytes: &[u8], offset: usize, length: usize) -> u64 {
+    let mut b = [0u8; 8];
+    let length = length.min(8);
+    b[..length].copy_from_slice(&bytes[offset..offset + length]);
+    return u64::from_le_bytes(b);
+}
+
+fn shift_mix(val: u64) -> u64 {
+    return val ^ (val >> 47);
+}
+
+fn hash128_to64(high: u64, low: u64) -> u64 {
+    let mut a = (low ^ high).wrapping_mul(K3);
+    a ^= a >> 47;
+    let mut b = (high ^ a).wrapping_mul(K3);
+    b ^= b >> 47;
+    b = b.wrapping_mul(K3);
+    return b;
+}
+
+/// Computes intermediate hash of 32 bytes of byte array from the given offset.
+fn weak_hash_length32_with_seeds(
+    bytes: &[u8],
+    offset: usize,
+    mut seed_a: u64,
+    mut seed_b: u64,
+) -> (u64, u64) {
+    let part1 = load64(bytes, offset);
+    let part2 = load64(bytes, offset + 8);
+    let part3 = load64(bytes, offset + 16);
+    let part4 = load64(bytes, offset + 24);
+
+    seed_a = seed_a.wrapping_add(part1);
+    seed_b = seed_b
+        .wrapping_add(seed_a)
+        .wrapping_add(part4)
+        .rotate_right(51);
+    let c = seed_a;
+    seed_a = seed_a.wrapping_add(part2);
+    seed_a = seed_a.wrapping_add(part3);
+    seed_b = seed_b.wrapping_add(seed_a.rotate_right(23));
+
+    return (seed_a.wrapping_add(part4), seed_b.wrapping_add(c));
+}
+
+/// Computes an 8-byte hash of a byte array of length greater than 64 bytes.
+fn full_fingerprint(bytes: &[u8]) -> u64 {
+    let mut offset = 0;
+    let length = bytes.len();
+    // For lengths over 64 bytes we hash the end first, and then as we loop we keep 56 bytes of
+    // state: v, w, x, y, and z.
+    let mut x = load64(bytes, offset);
+    let mut y = load64(bytes, offset + length - 16) ^ K1;
+    let mut z = load64(bytes, offset + length - 56) ^ K0;
+    let mut v = weak_hash_length32_with_seeds(bytes, offset + length - 64, length as u64, y);
+    let mut w = weak_hash_length32_with_seeds(
+        bytes,
+        offset + length - 32,
+        (length as u64).wrapping_mul(K1),
+        K0,
+    );
+    z = z.wrapping_add(shift_mix(v.1).wrapping_mul(K1));
+    x = z.wrapping_add(x).rotate_right(39).wrapping_mul(K1);
+    y = y.rotate_right(33).wrapping_mul(K1);
+
+    // Decrease length to the nearest multiple of 64, and operate on 64-byte chunks.
+    let mut length = (length - 1) & !63;
+    loop {
+        x = x
+            .wrapping_add(y)
+            .wrapping_add(v.0)
+            .wrapping_add(load64(bytes, offset + 16))
+            .rotate_right(37)
+            .wrapping_mul(K1);
+        y = y
+            .wrapping_add(v.1)
+            .wrapping_add(load64(bytes, offset + 48))
+            .rotate_right(42)
+            .wrapping_mul(K1);
+        x ^= w.1;
+        y ^= v.0;
+        z = (z ^ w.0).rotate_right(33);
+        v = weak_hash_length32_with_seeds(bytes, offset, v.1.wrapping_mul(K1), x.wrapping_add(w.0));
+        w = weak_hash_length32_with_seeds(bytes, offset + 32, z.wrapping_add(w.1), y);
+        std::mem::swap(&mut z, &mut x);
+        offset += 64;
+        length -= 64;
+        if length == 0 {
+            break;
+        }
+    }
+    return hash128_to64(
+        hash128_to64(v.0, w.0)
+            .wrapping_add(shift_mix(y).wrapping_mul(K1))
+            .wrapping_add(z),
+        hash128_to64(v.1, w.1).wrapping_add(x),
+    );
+}
+
+fn hash_length33_to64(bytes: &[u8]) -> u64 {
+    let length = bytes.len();
+    let mut z = load64(bytes, 24);
+    let mut a = load64(bytes, 0).wrapping_add(
+        (length as u64)
+            .wrapping_add(load64(bytes, length - 16))
+            .wrapping_mul(K0),
+    );
+    let mut b = a.wrapping_add(z).rotate_right(52);
+    let mut c = a.rotate_right(37);
+    a = a.wrapping_add(load64(bytes, 8));
+    c = c.wrapping_add(a.rotate_right(7));
+    a = a.wrapping_add(load64(bytes, 16));
+    let vf = a.wrapping_add(z);
+    let vs = b.wrapping_add(a.rotate_right(31)).wrapping_add(c);
+    a = load64(bytes, 16).wrapping_add(load64(bytes, length - 32));
+    z = load64(bytes, length - 8);
+    b = a.wrapping_add(z).rotate_right(52);
+    c = a.rotate_right(37);
+    a = a.wrapping_add(load64(bytes, length - 24));
+    c = c.wrapping_add(a.rotate_right(7));
+    a = a.wrapping_add(load64(bytes, length - 16));
+    let wf = a.wrapping_add(z);
+    let ws = b.wrapping_add(a.rotate_right(31)).wrapping_add(c);
+    let r = shift_mix(
+        vf.wrapping_add(ws)
+            .wrapping_mul(K2)
+            .wrapping_add(wf.wrapping_add(vs).wrapping_mul(K0)),
+    );
+    return shift_mix(r.wrapping_mul(K0).wrapping_add(vs)).wrapping_mul(K2);
+}
+
+fn murmur_hash64_with_seed(bytes: &[u8], seed: u64) -> u64 {
+    let mul = K3;
+    let top_bit = 0x7;
+
+    let length = bytes.len();
+    let length_aligned = length & !top_bit;
+    let length_remainder = length & top_bit;
+    let mut hash = seed ^ (length as u64).wrapping_mul(mul);
+
+    for i in (0..length_aligned).step_by(8) {
+        let loaded = load64(bytes, i);
+        let data = shift_mix(loaded.wrapping_mul(mul)).wrapping_mul(mul);
+        hash ^= data;
+        hash = hash.wrapping_mul(mul);
+    }
+
+    if length_remainder != 0 {
+        let data = load64_safely(bytes, length_aligned, length_remainder);
+        hash ^= data;
+        hash = hash.wrapping_mul(mul);
+    }
+
+    hash = shift_mix(hash).wrapping_mul(mul);
+    hash = shift_mix(hash);
+    return hash;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_really_simple_fingerprints() {
+        // Same as `FingerprintTest.testReallySimpleFingerprints` in Guava.
+        assert_eq!(fingerprint(b"test") as i64, 8473225671271759044);
+        // 32 characters long.
+        assert_eq!(
+            fingerprint("test".repeat(8).as_bytes()) as i64,
+            7345148637025587076
+        );
+        // 256 characters long.
+        assert_eq!(
+            fingerprint("test".repeat(64).as_bytes()) as i64,
+            4904844928629814570
+        );
+    }
+}
@@ -17,6 +17,7 @@ mod data;
 mod difference_encoding;
 mod encoding;
 mod error;
+mod fingerprint;
 mod normal;
 mod sketch;
 mod sparse;
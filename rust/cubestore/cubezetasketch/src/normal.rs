@@ -116,6 +116,17 @@ impl NormalRepresentation {
         return (estimate - estimate_bias(estimate, state.precision)).round() as u64;
     }
 
+    pub fn add_hash(&mut self, state: &mut State, hash: u64) {
+        Self::ensure_data(state);
+        let data = state.data.as_mut().unwrap();
+
+        let idx = self.encoding.index(hash);
+        let rho_w = self.encoding.rho_w(hash);
+        if data[idx] < rho_w {
+            data[idx] = rho_w;
+        }
+    }
+
     pub fn merge_with_sparse(
         &mut self,
         state: &mut State,
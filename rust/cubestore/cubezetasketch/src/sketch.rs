@@ -29,6 +29,7 @@
 ///
 /// Note that this aggregator is *not* designed to be thread safe.
 use crate::error::Result;
+use crate::fingerprint::fingerprint;
 use crate::normal::NormalRepresentation;
 use crate::sparse::SparseRepresentation;
 use crate::state::aggregator_state_proto::AGGREGATOR_TYPE_HYPERLOGLOG_PLUS_UNIQUE;
@@ -102,6 +103,43 @@ impl HyperLogLogPlusPlus {
         return Self::for_coded_input(CodedInputStream::from_bytes(proto));
     }
 
+    /// Creates an empty aggregator with the given normal and sparse precisions.
+    ///
+    /// The value type is left unset, the sketch can be merged with sketches of any type.
+    pub fn new(precision: i32, sparse_precision: i32) -> Result<HyperLogLogPlusPlus> {
+        return Self::from_state(State {
+            type_: AGGREGATOR_TYPE_HYPERLOGLOG_PLUS_UNIQUE,
+            encoding_version: Self::ENCODING_VERSION,
+            precision,
+            sparse_precision,
+            ..State::default()
+        });
+    }
+
+    /// Adds the value to the sketch. Integers should be passed as 8 little-endian bytes and
+    /// strings as UTF-8, that is how ZetaSketch hashes them.
+    pub fn add_bytes(&mut self, value: &[u8]) -> Result<()> {
+        return self.add_hash(fingerprint(value));
+    }
+
+    fn add_hash(&mut self, hash: u64) -> Result<()> {
+        self.state.num_values += 1;
+
+        let new_repr: Option<NormalRepresentation>;
+        match &mut self.representation {
+            Representation::Sparse(r) => new_repr = r.add_hash(&mut self.state, hash)?,
+            Representation::Normal(r) => {
+                r.add_hash(&mut self.state, hash);
+                return Ok(());
+            }
+        }
+
+        if let Some(n) = new_repr {
+            self.representation = Representation::Normal(n)
+        }
+        return Ok(());
+    }
+
     pub fn write(&self) -> Vec<u8> {
         if let Representation::Sparse(r) = &self.representation {
             if r.requires_compaction() {
@@ -188,3 +226,41 @@ impl HyperLogLogPlusPlus {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_values() {
+        let mut s = HyperLogLogPlusPlus::new(15, 20).unwrap();
+        assert_eq!(s.cardinality(), 0);
+
+        // Stays sparse for small cardinalities.
+        for i in 0..100i64 {
+            s.add_bytes(&i.to_le_bytes()).unwrap();
+            s.add_bytes(&i.to_le_bytes()).unwrap();
+        }
+        assert!(matches!(s.representation, Representation::Sparse(_)));
+        assert_eq!(s.cardinality(), 100);
+
+        // Switches to normal representation for larger ones.
+        for i in 100..100000i64 {
+            s.add_bytes(&i.to_le_bytes()).unwrap();
+        }
+        assert!(matches!(s.representation, Representation::Normal(_)));
+        let c = s.cardinality();
+        assert!(99000 <= c && c <= 101000, "cardinality is {}", c);
+
+        // Survives the roundtrip and merges with sketches built separately.
+        let mut other = HyperLogLogPlusPlus::new(15, 20).unwrap();
+        for i in 50000..150000i64 {
+            other.add_bytes(&i.to_le_bytes()).unwrap();
+        }
+        let mut s = HyperLogLogPlusPlus::read(&s.write()).unwrap();
+        s.merge_with(&HyperLogLogPlusPlus::read(&other.write()).unwrap())
+            .unwrap();
+        let c = s.cardinality();
+        assert!(148500 <= c && c <= 151500, "cardinality is {}", c);
+    }
+}
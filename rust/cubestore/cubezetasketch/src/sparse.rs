@@ -166,6 +166,16 @@ impl SparseRepresentation {
         return estimate.round() as u64;
     }
 
+    /// Returns a new normal representation if this sparse representation has outgrown itself.
+    pub fn add_hash(
+        &mut self,
+        state: &mut State,
+        hash: u64,
+    ) -> Result<Option<NormalRepresentation>> {
+        self.buffer.insert(self.encoding.encode(hash));
+        return self.update_representation(state);
+    }
+
     /// `self` may end up be in the invalid state on error and must not be used further.
     pub fn merge_with_sparse(
         &mut self,